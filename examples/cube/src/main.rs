@@ -62,7 +62,11 @@ fn setup(
             style: outline_styles.add(OutlineStyle {
                 color: Color::hex("b4a2c8").unwrap(),
                 width: 33.0,
+                ..Default::default()
             }),
+            layers: Vec::new(),
+            groups: Default::default(),
+            occluded: None,
         });
 
     commands.spawn_bundle(PointLightBundle {
@@ -0,0 +1,388 @@
+use std::{
+    num::NonZeroU32,
+    sync::{
+        atomic::{AtomicU8, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use bevy::{
+    prelude::*,
+    render::{
+        render_asset::RenderAssets,
+        render_graph::{Node, NodeRunError, RenderGraph, RenderGraphContext},
+        render_resource::{
+            Buffer, BufferDescriptor, BufferUsages, Extent3d, ImageCopyBuffer, ImageDataLayout,
+            MapMode, TextureDimension, TextureFormat, TextureUsages,
+        },
+        renderer::{RenderContext, RenderDevice},
+        Extract, RenderApp, RenderStage,
+    },
+    utils::HashMap,
+};
+
+use crate::{
+    jfa_request::{jfa_pipelines, JfaRequestNode, JfaRequestResources},
+    readback::{decode_texel, texel_size},
+    textures::placeholder_image,
+    JfaRequest, JfaTextureFormat,
+};
+
+/// Plugin for baking images into signed distance fields.
+///
+/// Each entity with an [`SdfBake`] has the distance field of its source image
+/// computed by the jump flood passes and read back to the CPU, after which
+/// the baked field is stored in its output image and an [`SdfBaked`] event
+/// is sent. Baking takes a few frames.
+///
+/// This must be added after the [`JfaPlugin`](crate::JfaPlugin), which runs
+/// the jump flood passes.
+#[derive(Default)]
+pub struct SdfBakePlugin;
+
+impl Plugin for SdfBakePlugin {
+    fn build(&self, app: &mut App) {
+        let baked = BakedFields::default();
+
+        app.insert_resource(baked.clone())
+            .add_event::<SdfBaked>()
+            .add_system_to_stage(CoreStage::PreUpdate, finish_sdf_bakes)
+            .add_system_to_stage(CoreStage::PostUpdate, start_sdf_bakes);
+
+        let render_app = match app.get_sub_app_mut(RenderApp) {
+            Ok(r) => r,
+            Err(_) => return,
+        };
+
+        render_app
+            .insert_resource(baked)
+            .init_resource::<BakeBuffers>()
+            .add_system_to_stage(RenderStage::Extract, extract_sdf_bakes)
+            .add_system_to_stage(RenderStage::Prepare, prepare_bake_buffers)
+            .add_system_to_stage(RenderStage::Cleanup, map_bake_buffers);
+
+        let mut root_graph = render_app.world.resource_mut::<RenderGraph>();
+        root_graph.add_node(SdfBakeCopyNode::NAME, SdfBakeCopyNode);
+        root_graph
+            .add_node_edge(JfaRequestNode::NAME, SdfBakeCopyNode::NAME)
+            .unwrap();
+    }
+}
+
+/// Component requesting that an image be baked into a signed distance field.
+///
+/// The alpha component of `source` is its coverage, as for the seed of a
+/// [`JfaRequest`]. Once baked, `output` holds a `TextureFormat::R8Unorm` image
+/// of the same size, in which `0.5` lies on the edge of the covered region,
+/// values increase towards `1.0` at `max_dist` texels inside it and decrease
+/// towards `0.0` at `max_dist` texels outside it. This is the usual encoding
+/// for SDF glyphs and icons.
+///
+/// The output holds its data on the CPU, so it can be saved or cached like
+/// any other image asset. The component is removed once the bake finishes.
+#[derive(Clone, Debug, Component)]
+pub struct SdfBake {
+    /// The image to bake.
+    pub source: Handle<Image>,
+    /// The image receiving the baked distance field.
+    pub output: Handle<Image>,
+    /// The distance, in texels, covered by the output's range of values.
+    pub max_dist: u32,
+}
+
+impl SdfBake {
+    /// Creates a request to bake `source` into `output`, with distances of up
+    /// to `max_dist` texels.
+    pub fn new(source: Handle<Image>, output: Handle<Image>, max_dist: u32) -> Self {
+        SdfBake {
+            source,
+            output,
+            max_dist,
+        }
+    }
+}
+
+/// Event sent when an [`SdfBake`] finishes.
+#[derive(Clone, Debug)]
+pub struct SdfBaked {
+    /// The entity which had the `SdfBake`.
+    pub entity: Entity,
+    /// The image holding the baked distance field.
+    pub output: Handle<Image>,
+}
+
+/// Floods the sources of new bakes into intermediate images.
+fn start_sdf_bakes(
+    mut commands: Commands,
+    jfa_format: Res<JfaTextureFormat>,
+    mut images: ResMut<Assets<Image>>,
+    bakes: Query<(Entity, &SdfBake), Added<SdfBake>>,
+) {
+    for (entity, bake) in bakes.iter() {
+        // The flood output is copied into a readback buffer.
+        let mut intermediate = placeholder_image(jfa_format.texture_format());
+        intermediate.texture_descriptor.usage |= TextureUsages::COPY_SRC;
+
+        commands.entity(entity).insert(JfaRequest::new(
+            bake.source.clone(),
+            images.add(intermediate),
+            bake.max_dist,
+        ));
+    }
+}
+
+/// Stores the finished bakes in their output images.
+fn finish_sdf_bakes(
+    mut commands: Commands,
+    baked: Res<BakedFields>,
+    mut images: ResMut<Assets<Image>>,
+    mut events: EventWriter<SdfBaked>,
+    bakes: Query<&SdfBake>,
+) {
+    let mut baked = baked.0.lock().unwrap();
+    for (entity, field) in baked.drain(..) {
+        let bake = match bakes.get(entity) {
+            Ok(b) => b,
+            Err(_) => continue,
+        };
+
+        images.set_untracked(bake.output.clone(), field);
+        commands
+            .entity(entity)
+            .remove::<SdfBake>()
+            .remove::<JfaRequest>();
+        events.send(SdfBaked {
+            entity,
+            output: bake.output.clone(),
+        });
+    }
+}
+
+/// Fields baked in the render world, waiting to be stored by the main world.
+#[derive(Clone, Default)]
+struct BakedFields(Arc<Mutex<Vec<(Entity, Image)>>>);
+
+fn extract_sdf_bakes(
+    mut commands: Commands,
+    mut previous_len: Local<usize>,
+    bakes: Extract<Query<(Entity, &SdfBake)>>,
+) {
+    let mut batches = Vec::with_capacity(*previous_len);
+    batches.extend(bakes.iter().map(|(entity, bake)| (entity, (bake.clone(),))));
+    *previous_len = batches.len();
+    commands.insert_or_spawn_batch(batches);
+}
+
+// Readback states of a bake.
+const STATE_IDLE: u8 = 0;
+const STATE_COPIED: u8 = 1;
+const STATE_PENDING: u8 = 2;
+const STATE_MAPPED: u8 = 3;
+const STATE_DONE: u8 = 4;
+
+/// The buffer the flood output of a bake is read back into.
+struct BakeBuffer {
+    size: UVec2,
+    max_dist: u32,
+    // Rows of the buffer are padded to the copy alignment.
+    padded_bytes_per_row: u32,
+    buffer: Buffer,
+    // One of the `STATE_*` constants. Buffers are kept in `STATE_DONE` until
+    // the bake is removed, so that it isn't read back twice.
+    state: Arc<AtomicU8>,
+}
+
+#[derive(Default)]
+struct BakeBuffers {
+    bakes: HashMap<Entity, BakeBuffer>,
+}
+
+/// Publishes finished bakes and creates buffers for new ones.
+fn prepare_bake_buffers(
+    device: Res<RenderDevice>,
+    jfa_format: Res<JfaTextureFormat>,
+    baked: Res<BakedFields>,
+    images: Res<RenderAssets<Image>>,
+    mut buffers: ResMut<BakeBuffers>,
+    bakes: Query<(Entity, &SdfBake)>,
+) {
+    buffers.bakes.retain(|entity, _| bakes.get(*entity).is_ok());
+
+    // Mappings finish when a later frame's commands are submitted, which
+    // invokes their callbacks.
+    let format = *jfa_format;
+    let texel_bytes = texel_size(format);
+    let mut baked = baked.0.lock().unwrap();
+    for (&entity, bake) in buffers.bakes.iter() {
+        if bake.state.load(Ordering::Acquire) != STATE_MAPPED {
+            continue;
+        }
+
+        let field = {
+            let data = bake.buffer.slice(..).get_mapped_range();
+            let row_len = (bake.size.x * texel_bytes) as usize;
+            let texels: Vec<Vec4> = data
+                .chunks_exact(bake.padded_bytes_per_row as usize)
+                .flat_map(|row| row[..row_len].chunks_exact(texel_bytes as usize))
+                .map(|texel| decode_texel(texel, format))
+                .collect();
+            bake_field(&texels, bake.size, bake.max_dist)
+        };
+        bake.buffer.unmap();
+        bake.state.store(STATE_DONE, Ordering::Release);
+        baked.push((entity, field));
+    }
+
+    for (entity, bake) in bakes.iter() {
+        let size = match images.get(&bake.source) {
+            Some(source) => source.size.as_uvec2(),
+            None => continue,
+        };
+
+        // Sources may be modified before they are baked.
+        if buffers.bakes.get(&entity).is_some_and(|b| b.size == size) {
+            continue;
+        }
+
+        let padded_bytes_per_row =
+            RenderDevice::align_copy_bytes_per_row((size.x * texel_bytes) as usize) as u32;
+        buffers.bakes.insert(
+            entity,
+            BakeBuffer {
+                size,
+                max_dist: bake.max_dist,
+                padded_bytes_per_row,
+                buffer: device.create_buffer(&BufferDescriptor {
+                    label: Some("sdf_bake_readback"),
+                    size: (padded_bytes_per_row * size.y) as u64,
+                    usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                }),
+                state: Arc::new(AtomicU8::new(STATE_IDLE)),
+            },
+        );
+    }
+}
+
+/// Converts the texels of a flood output into a baked `R8Unorm` field.
+fn bake_field(texels: &[Vec4], size: UVec2, max_dist: u32) -> Image {
+    let max_dist = max_dist.max(1) as f32;
+    let texture_size = size.as_vec2();
+    let data = texels
+        .iter()
+        .enumerate()
+        .map(|(i, texel)| {
+            let pos = UVec2::new(i as u32 % size.x, i as u32 / size.x);
+            let center = pos.as_vec2() + 0.5;
+            // Texels with no seed in range are at least `max_dist` away.
+            let distance = |seed: Vec2| match seed.x < 0.0 {
+                true => max_dist,
+                false => (seed * texture_size - center).length(),
+            };
+
+            // The XY components hold the nearest covered texel, and the ZW
+            // components the nearest uncovered one.
+            let outside = distance(Vec2::new(texel.x, texel.y));
+            let inside = distance(Vec2::new(texel.z, texel.w));
+            let value = 0.5 + 0.5 * (inside - outside) / max_dist;
+            (value.clamp(0.0, 1.0) * u8::MAX as f32).round() as u8
+        })
+        .collect();
+
+    Image::new(
+        Extent3d {
+            width: size.x,
+            height: size.y,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::R8Unorm,
+    )
+}
+
+/// Starts mapping the readback buffers copied to this frame.
+///
+/// This runs after the frame's commands have been submitted.
+fn map_bake_buffers(buffers: Res<BakeBuffers>) {
+    for bake in buffers.bakes.values() {
+        if bake.state.load(Ordering::Acquire) != STATE_COPIED {
+            continue;
+        }
+
+        bake.state.store(STATE_PENDING, Ordering::Release);
+        let state = bake.state.clone();
+        bake.buffer
+            .slice(..)
+            .map_async(MapMode::Read, move |result| {
+                let new_state = match result {
+                    Ok(()) => STATE_MAPPED,
+                    // Copied again in the next frame.
+                    Err(_) => STATE_IDLE,
+                };
+                state.store(new_state, Ordering::Release);
+            });
+    }
+}
+
+/// Render graph node which copies the flood outputs of pending bakes into
+/// their readback buffers.
+///
+/// This runs after the [`JfaRequestNode`], once the outputs are complete.
+struct SdfBakeCopyNode;
+
+impl SdfBakeCopyNode {
+    const NAME: &'static str = "sdf_bake_copy";
+}
+
+impl Node for SdfBakeCopyNode {
+    fn run(
+        &self,
+        _graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        // Requests are only flooded once the pipelines are compiled.
+        if jfa_pipelines(world).is_none() {
+            return Ok(());
+        }
+
+        let images = world.resource::<RenderAssets<Image>>();
+        let buffers = world.resource::<BakeBuffers>();
+        for (&entity, bake) in buffers.bakes.iter() {
+            if bake.state.load(Ordering::Acquire) != STATE_IDLE
+                || world.get::<JfaRequestResources>(entity).is_none()
+            {
+                continue;
+            }
+
+            let output = match world
+                .get::<JfaRequest>(entity)
+                .and_then(|request| images.get(&request.output))
+            {
+                Some(o) if o.size.as_uvec2() == bake.size => o,
+                _ => continue,
+            };
+
+            render_context.command_encoder.copy_texture_to_buffer(
+                output.texture.as_image_copy(),
+                ImageCopyBuffer {
+                    buffer: &bake.buffer,
+                    layout: ImageDataLayout {
+                        offset: 0,
+                        bytes_per_row: NonZeroU32::new(bake.padded_bytes_per_row),
+                        rows_per_image: None,
+                    },
+                },
+                Extent3d {
+                    width: bake.size.x,
+                    height: bake.size.y,
+                    depth_or_array_layers: 1,
+                },
+            );
+            bake.state.store(STATE_COPIED, Ordering::Release);
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,227 @@
+use bevy::{
+    prelude::*,
+    render::{
+        camera::Viewport,
+        render_asset::RenderAssets,
+        render_resource::{
+            CachedRenderPipelineId, PipelineCache, SpecializedRenderPipelines, TextureUsages,
+        },
+        view::Msaa,
+        Extract,
+    },
+};
+
+use crate::{
+    mask::MaskColors,
+    outline::{OutlinePipeline, OutlinePipelineKey},
+    resources::ViewJfaFormat,
+    OutlineSettings,
+};
+
+/// Component for capturing a camera's outline layer into an image.
+///
+/// While this component is present on a camera with an outline, the outline
+/// pass is additionally rendered into `image` each frame, on a transparent
+/// background and without the rest of the scene. This happens entirely on the
+/// GPU, so the image can be displayed (e.g. in the UI) without a readback.
+///
+/// The outline is stretched to cover the whole image, so the image should
+/// have the same aspect ratio as the camera's target. The image's texture
+/// usages are extended to allow rendering to it if necessary.
+#[derive(Clone, Debug, Component)]
+pub struct OutlineCapture {
+    pub image: Handle<Image>,
+    /// The region of the image to render into, or `None` for the whole image.
+    ///
+    /// When set, the rest of the image is left untouched.
+    pub viewport: Option<Viewport>,
+    /// If `true`, render an opaque matte instead of the outline's colors.
+    ///
+    /// The matte is white where objects are outlined, blending partially
+    /// covered pixels at their edges with the outline, fades to black across
+    /// the outline following its alpha, and is black elsewhere. This is
+    /// intended for compositing the outline layer in external tools, e.g. as
+    /// a luma key in recording or streaming software.
+    pub matte: bool,
+}
+
+/// Component for capturing the outlines of several entities into the cells of
+/// an atlas image, e.g. to produce highlighted icons for an inventory UI.
+///
+/// While this component is present on a camera with an outline, one entity is
+/// captured per frame, in order. Only the entity being captured is visible
+/// during its frame; the others are hidden. Cells are filled left to right,
+/// then top to bottom, with `columns` cells per row. Once every entity has
+/// been captured, the entities' visibility is restored and the component is
+/// removed from the camera.
+///
+/// Every mesh visible to the camera contributes to the outline, so the camera
+/// should usually be dedicated to this purpose, e.g. using `RenderLayers`.
+/// The entities are captured as seen by the camera, so they are typically
+/// placed at the same spot in front of it.
+#[derive(Clone, Debug, Component)]
+pub struct OutlineAtlasCapture {
+    pub image: Handle<Image>,
+    pub entities: Vec<Entity>,
+    pub columns: u32,
+    next: usize,
+    restore_visibility: Vec<(Entity, bool)>,
+}
+
+impl OutlineAtlasCapture {
+    pub fn new(image: Handle<Image>, entities: Vec<Entity>, columns: u32) -> Self {
+        OutlineAtlasCapture {
+            image,
+            entities,
+            columns,
+            next: 0,
+            restore_visibility: Vec::new(),
+        }
+    }
+
+    /// Returns the cell of the atlas in which the entity at `index` is
+    /// captured, for an atlas of the given size.
+    pub fn cell(&self, index: usize, atlas_size: UVec2) -> Viewport {
+        let columns = self.columns.max(1);
+        let rows = (self.entities.len() as u32).div_ceil(columns);
+        let cell_size = atlas_size / UVec2::new(columns, rows.max(1));
+        let index = index as u32;
+
+        Viewport {
+            physical_position: UVec2::new(index % columns, index / columns) * cell_size,
+            physical_size: cell_size,
+            ..default()
+        }
+    }
+}
+
+/// Advances each `OutlineAtlasCapture` to its next entity.
+pub(crate) fn advance_outline_atlas_captures(
+    mut commands: Commands,
+    images: Res<Assets<Image>>,
+    mut cameras: Query<(Entity, &mut OutlineAtlasCapture)>,
+    mut visibility: Query<&mut Visibility>,
+) {
+    for (camera, mut atlas) in cameras.iter_mut() {
+        let atlas_size = match images.get(&atlas.image) {
+            Some(image) => image.size().as_uvec2(),
+            None => continue,
+        };
+
+        if atlas.next == 0 && atlas.restore_visibility.is_empty() {
+            atlas.restore_visibility = atlas
+                .entities
+                .iter()
+                .filter_map(|&e| Some((e, visibility.get(e).ok()?.is_visible)))
+                .collect();
+        }
+
+        if atlas.next >= atlas.entities.len() {
+            for &(entity, is_visible) in atlas.restore_visibility.iter() {
+                if let Ok(mut vis) = visibility.get_mut(entity) {
+                    vis.is_visible = is_visible;
+                }
+            }
+
+            commands
+                .entity(camera)
+                .remove::<OutlineAtlasCapture>()
+                .remove::<OutlineCapture>();
+            continue;
+        }
+
+        for (index, &entity) in atlas.entities.iter().enumerate() {
+            if let Ok(mut vis) = visibility.get_mut(entity) {
+                vis.is_visible = index == atlas.next;
+            }
+        }
+
+        commands.entity(camera).insert(OutlineCapture {
+            image: atlas.image.clone(),
+            viewport: Some(atlas.cell(atlas.next, atlas_size)),
+            matte: false,
+        });
+        atlas.next += 1;
+    }
+}
+
+/// The specialized pipeline used to render a view's `OutlineCapture`.
+#[derive(Component)]
+pub struct OutlineCapturePipeline {
+    pub(crate) pipeline_id: CachedRenderPipelineId,
+}
+
+pub(crate) fn enable_capture_image_rendering(
+    mut images: ResMut<Assets<Image>>,
+    captures: Query<&OutlineCapture>,
+) {
+    for capture in captures.iter() {
+        let needs_usage = images
+            .get(&capture.image)
+            .map(|image| {
+                !image
+                    .texture_descriptor
+                    .usage
+                    .contains(TextureUsages::RENDER_ATTACHMENT)
+            })
+            .unwrap_or(false);
+
+        // Only access the image mutably when necessary, as doing so causes it
+        // to be uploaded to the GPU again.
+        if needs_usage {
+            if let Some(image) = images.get_mut(&capture.image) {
+                image.texture_descriptor.usage |= TextureUsages::RENDER_ATTACHMENT;
+            }
+        }
+    }
+}
+
+pub(crate) fn extract_outline_captures(
+    mut commands: Commands,
+    mut previous_len: Local<usize>,
+    captures: Extract<Query<(Entity, &OutlineCapture), With<Camera>>>,
+) {
+    let mut batches = Vec::with_capacity(*previous_len);
+    batches.extend(
+        captures
+            .iter()
+            .map(|(entity, capture)| (entity, (capture.clone(),))),
+    );
+    *previous_len = batches.len();
+    commands.insert_or_spawn_batch(batches);
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn queue_outline_captures(
+    mut commands: Commands,
+    mut pipeline_cache: ResMut<PipelineCache>,
+    mut pipelines: ResMut<SpecializedRenderPipelines<OutlinePipeline>>,
+    outline_pipeline: Res<OutlinePipeline>,
+    settings: Res<OutlineSettings>,
+    msaa: Res<Msaa>,
+    images: Res<RenderAssets<Image>>,
+    captures: Query<(Entity, &OutlineCapture, &ViewJfaFormat, Option<&MaskColors>)>,
+) {
+    for (entity, capture, &ViewJfaFormat(jfa_format), mask_colors) in captures.iter() {
+        let image = match images.get(&capture.image) {
+            Some(i) => i,
+            None => continue,
+        };
+
+        let key = match OutlinePipelineKey::new(image.texture_format) {
+            Some(k) => k
+                .with_mask_samples(msaa.samples)
+                .with_mask_colors(mask_colors.is_some())
+                .with_jfa_format(jfa_format)
+                .with_upsample(settings.active_upsample(), settings.resolution)
+                .without_blending(),
+            None => continue,
+        };
+        let key = if capture.matte { key.with_matte() } else { key };
+
+        let pipeline_id = pipelines.specialize(&mut pipeline_cache, &outline_pipeline, key);
+        commands
+            .entity(entity)
+            .insert(OutlineCapturePipeline { pipeline_id });
+    }
+}
@@ -0,0 +1,194 @@
+use bevy::{prelude::*, render::primitives::Aabb};
+
+/// Estimates the fraction of a camera's viewport covered by outlines.
+///
+/// Each entity's bounding box is projected to the screen and grown by the
+/// outline width. The areas of the resulting rectangles are summed, so
+/// overlapping entities are counted more than once; the estimate is clamped to
+/// `1.0`. Bounding boxes are clipped to the camera's near plane, so entities
+/// which extend behind the camera only count the part in front of it, and
+/// entities entirely behind it are skipped.
+pub(crate) fn estimate_coverage<'a>(
+    camera: &Camera,
+    camera_transform: &GlobalTransform,
+    width: f32,
+    bounds: impl Iterator<Item = (&'a Aabb, &'a GlobalTransform)>,
+) -> Option<f32> {
+    let viewport_size = camera.physical_viewport_size()?.as_vec2();
+    if viewport_size.x == 0.0 || viewport_size.y == 0.0 {
+        return None;
+    }
+
+    let world_to_clip = camera.projection_matrix() * camera_transform.compute_matrix().inverse();
+    Some(projected_coverage(
+        world_to_clip,
+        viewport_size,
+        width,
+        bounds,
+    ))
+}
+
+/// Estimates the fraction of a viewport of `viewport_size` pixels covered by
+/// outlines `width` pixels wide around the given bounding boxes, as projected
+/// by `world_to_clip`.
+fn projected_coverage<'a>(
+    world_to_clip: Mat4,
+    viewport_size: Vec2,
+    width: f32,
+    bounds: impl Iterator<Item = (&'a Aabb, &'a GlobalTransform)>,
+) -> f32 {
+    let mut covered_area = 0.0;
+    for (aabb, transform) in bounds {
+        let local_to_clip = world_to_clip * transform.compute_matrix();
+        let (min, max) = match clipped_ndc_bounds(local_to_clip, aabb) {
+            Some(b) => b,
+            None => continue,
+        };
+
+        // Convert from NDC to pixels, grow by the outline width and clip to the
+        // viewport.
+        let pix_min = ((min + Vec2::ONE) * 0.5 * viewport_size - Vec2::splat(width))
+            .clamp(Vec2::ZERO, viewport_size);
+        let pix_max = ((max + Vec2::ONE) * 0.5 * viewport_size + Vec2::splat(width))
+            .clamp(Vec2::ZERO, viewport_size);
+        let size = (pix_max - pix_min).max(Vec2::ZERO);
+
+        covered_area += size.x * size.y;
+    }
+
+    (covered_area / (viewport_size.x * viewport_size.y)).min(1.0)
+}
+
+/// Returns the bounds in normalized device coordinates of the part of `aabb`
+/// in front of the near plane, given the transform from its local space to
+/// clip space, or `None` if it is entirely behind the near plane.
+fn clipped_ndc_bounds(local_to_clip: Mat4, aabb: &Aabb) -> Option<(Vec2, Vec2)> {
+    let center = Vec3::from(aabb.center);
+    let half_extents = Vec3::from(aabb.half_extents);
+    let corners: [Vec4; 8] = std::array::from_fn(|i| {
+        let sign = Vec3::new(
+            if i & 1 == 0 { -1.0 } else { 1.0 },
+            if i & 2 == 0 { -1.0 } else { 1.0 },
+            if i & 4 == 0 { -1.0 } else { 1.0 },
+        );
+        local_to_clip * (center + sign * half_extents).extend(1.0)
+    });
+
+    // Depth is reversed, so points in front of the near plane have a clip
+    // space depth of at most `w`.
+    let distance = |clip: Vec4| clip.w - clip.z;
+
+    let mut min = Vec2::splat(f32::INFINITY);
+    let mut max = Vec2::splat(f32::NEG_INFINITY);
+    let mut add = |clip: Vec4| {
+        if clip.w > 0.0 {
+            let ndc = clip.truncate().truncate() / clip.w;
+            min = min.min(ndc);
+            max = max.max(ndc);
+        }
+    };
+
+    for (i, &a) in corners.iter().enumerate() {
+        if distance(a) >= 0.0 {
+            add(a);
+        }
+
+        // Each edge of the box joins corners differing in a single axis, and
+        // is cut where it crosses the near plane.
+        for axis in [1, 2, 4] {
+            if i & axis != 0 {
+                continue;
+            }
+            let b = corners[i | axis];
+            let (da, db) = (distance(a), distance(b));
+            if (da < 0.0) != (db < 0.0) {
+                add(a + (b - a) * (da / (da - db)));
+            }
+        }
+    }
+
+    min.cmple(max).all().then_some((min, max))
+}
+
+/// Computes the alpha multiplier applied to an outline covering `coverage` of
+/// its viewport when limited to `max_coverage`.
+///
+/// Below the limit, the outline is drawn normally. Above it, the outline fades
+/// out linearly, disappearing entirely when it would cover the whole view.
+pub(crate) fn coverage_alpha(coverage: f32, max_coverage: f32) -> f32 {
+    if coverage <= max_coverage || max_coverage >= 1.0 {
+        return 1.0;
+    }
+
+    (1.0 - (coverage - max_coverage) / (1.0 - max_coverage)).clamp(0.0, 1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_PI_2;
+
+    use super::*;
+
+    // A camera at the origin looking down -Z, with a near plane at 0.1.
+    fn projection() -> Mat4 {
+        Mat4::perspective_infinite_reverse_rh(FRAC_PI_2, 1.0, 0.1)
+    }
+
+    fn aabb(center: Vec3) -> Aabb {
+        Aabb::from_min_max(center - Vec3::ONE, center + Vec3::ONE)
+    }
+
+    fn coverage(aabb: &Aabb, width: f32) -> f32 {
+        let transform = GlobalTransform::default();
+        projected_coverage(
+            projection(),
+            Vec2::splat(100.0),
+            width,
+            std::iter::once((aabb, &transform)),
+        )
+    }
+
+    #[test]
+    fn box_in_front_of_camera() {
+        // The near face is 4 units away, so it spans a quarter of the view.
+        let aabb = aabb(Vec3::new(0.0, 0.0, -5.0));
+        let (min, max) = clipped_ndc_bounds(projection(), &aabb).unwrap();
+        assert!(min.abs_diff_eq(Vec2::splat(-0.25), 1e-5));
+        assert!(max.abs_diff_eq(Vec2::splat(0.25), 1e-5));
+        assert!((coverage(&aabb, 0.0) - 0.0625).abs() < 1e-5);
+        // Grown by 5 pixels on each side of a 25 pixel square.
+        assert!((coverage(&aabb, 5.0) - 0.1225).abs() < 1e-5);
+    }
+
+    #[test]
+    fn box_straddling_near_plane() {
+        // Clipped at the near plane, the box covers the whole view.
+        let aabb = aabb(Vec3::ZERO);
+        let (min, max) = clipped_ndc_bounds(projection(), &aabb).unwrap();
+        assert!(min.cmple(Vec2::splat(-1.0)).all());
+        assert!(max.cmpge(Vec2::splat(1.0)).all());
+        assert_eq!(coverage(&aabb, 0.0), 1.0);
+
+        // The part behind the camera must not be projected through it onto
+        // the other side of the view.
+        let aabb = self::aabb(Vec3::new(3.0, 0.0, 0.0));
+        let (min, _) = clipped_ndc_bounds(projection(), &aabb).unwrap();
+        assert!(min.x >= 2.0 - 1e-5);
+        assert_eq!(coverage(&aabb, 0.0), 0.0);
+    }
+
+    #[test]
+    fn box_behind_camera() {
+        let aabb = aabb(Vec3::new(0.0, 0.0, 5.0));
+        assert_eq!(clipped_ndc_bounds(projection(), &aabb), None);
+        assert_eq!(coverage(&aabb, 10.0), 0.0);
+    }
+
+    #[test]
+    fn coverage_alpha_fades_above_limit() {
+        assert_eq!(coverage_alpha(0.25, 0.5), 1.0);
+        assert_eq!(coverage_alpha(0.75, 0.5), 0.5);
+        assert_eq!(coverage_alpha(1.0, 0.5), 0.0);
+        assert_eq!(coverage_alpha(1.0, 1.0), 1.0);
+    }
+}
@@ -0,0 +1,177 @@
+use bevy::{
+    core_pipeline::core_3d,
+    prelude::*,
+    render::{
+        render_asset::RenderAssets,
+        render_graph::{Node, NodeRunError, RenderGraph, RenderGraphContext, SlotInfo, SlotType},
+        renderer::RenderContext,
+        Extract, RenderApp, RenderStage,
+    },
+};
+
+use crate::{
+    graph, mask::MASK_COLOR_TEXTURE_FORMAT, resources::ViewOutlineResources, textures,
+    JfaTextureFormat, OutlineSettings,
+};
+
+/// Plugin for exposing the intermediate outline textures as images.
+///
+/// This adds an [`OutlineDebugImages`] resource whose images are overwritten
+/// each frame with the mask and distance field of the last outlined view to
+/// be rendered. As these are ordinary image assets, they can be shown in
+/// debug UIs, e.g. by registering them with `bevy_egui`'s
+/// `EguiContext::add_image`, which is more convenient than a fullscreen blit
+/// when tuning styles.
+///
+/// The images match the size of the intermediate textures of views covering
+/// the whole primary window, including the offscreen padding, and views of
+/// other sizes are not copied. The distance field is stored at full resolution even at
+/// reduced resolutions. To copy the textures of a particular camera instead,
+/// add an [`OutlineTextures`](crate::OutlineTextures) to it.
+///
+/// Masks normally only hold colors for views whose styles take their color
+/// from the covering entities. While this plugin is added, every view keeps
+/// the colors, so that its mask can be copied.
+///
+/// This must be added after the [`OutlinePlugin`](crate::OutlinePlugin) or
+/// [`MinimalOutlinePlugin`](crate::MinimalOutlinePlugin).
+#[derive(Default)]
+pub struct OutlineDebugImagesPlugin;
+
+impl Plugin for OutlineDebugImagesPlugin {
+    fn build(&self, app: &mut App) {
+        crate::init_jfa_texture_format(app);
+
+        let images = {
+            let jfa_format = app.world.resource::<JfaTextureFormat>().texture_format();
+            let mut assets = app.world.resource_mut::<Assets<Image>>();
+            OutlineDebugImages {
+                mask: assets.add(textures::placeholder_image(MASK_COLOR_TEXTURE_FORMAT)),
+                distance_field: assets.add(textures::placeholder_image(jfa_format)),
+            }
+        };
+
+        app.insert_resource(images)
+            .add_system_to_stage(CoreStage::PostUpdate, resize_outline_debug_images);
+
+        let render_app = match app.get_sub_app_mut(RenderApp) {
+            Ok(r) => r,
+            Err(_) => return,
+        };
+
+        render_app.add_system_to_stage(RenderStage::Extract, extract_outline_debug_images);
+
+        let mut root_graph = render_app.world.resource_mut::<RenderGraph>();
+        let outline_graph = root_graph
+            .get_sub_graph_mut(core_3d::graph::NAME)
+            .and_then(|draw_3d| draw_3d.get_sub_graph_mut(graph::outline::NAME))
+            .unwrap();
+        let input_node_id = outline_graph.input_node().unwrap().id;
+        outline_graph.add_node(OutlineDebugCopyNode::NAME, OutlineDebugCopyNode);
+        outline_graph
+            .add_slot_edge(
+                input_node_id,
+                graph::outline::input::VIEW_ENTITY,
+                OutlineDebugCopyNode::NAME,
+                OutlineDebugCopyNode::IN_VIEW,
+            )
+            .unwrap();
+        outline_graph
+            .add_node_edge(
+                graph::outline::node::OUTLINE_PASS,
+                OutlineDebugCopyNode::NAME,
+            )
+            .unwrap();
+    }
+}
+
+/// Images holding copies of the intermediate outline textures.
+///
+/// See [`OutlineDebugImagesPlugin`].
+#[derive(Clone, Debug)]
+pub struct OutlineDebugImages {
+    /// Copy of the mask.
+    ///
+    /// The RGB components hold the color of the covering entity, premultiplied
+    /// by the coverage stored in the alpha component.
+    pub mask: Handle<Image>,
+    /// Copy of the final jump flood output.
+    ///
+    /// The RG components hold the texture coordinates of the nearest seed
+    /// outside the silhouette, and the BA components those of the nearest
+    /// seed inside it. Texels without a seed hold `-1.0`.
+    pub distance_field: Handle<Image>,
+}
+
+/// Resizes the debug images to match the intermediate textures.
+fn resize_outline_debug_images(
+    windows: Res<Windows>,
+    settings: Res<OutlineSettings>,
+    debug_images: Res<OutlineDebugImages>,
+    jfa_format: Res<JfaTextureFormat>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    let primary = match windows.get_primary() {
+        Some(w) => w,
+        None => return,
+    };
+
+    let size = textures::intermediate_size(
+        &settings,
+        UVec2::new(primary.physical_width(), primary.physical_height()),
+    );
+    textures::fit_images(
+        &mut images,
+        *jfa_format,
+        &debug_images.mask,
+        &debug_images.distance_field,
+        size,
+    );
+}
+
+fn extract_outline_debug_images(mut commands: Commands, images: Extract<Res<OutlineDebugImages>>) {
+    commands.insert_resource(images.clone());
+}
+
+/// Render graph node which copies the intermediate outline textures into the
+/// [`OutlineDebugImages`].
+struct OutlineDebugCopyNode;
+
+impl OutlineDebugCopyNode {
+    const NAME: &'static str = "outline_debug_copy";
+    const IN_VIEW: &'static str = "view";
+}
+
+impl Node for OutlineDebugCopyNode {
+    fn input(&self) -> Vec<SlotInfo> {
+        vec![SlotInfo::new(Self::IN_VIEW, SlotType::Entity)]
+    }
+
+    fn run(
+        &self,
+        graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let view_ent = graph.get_input_entity(Self::IN_VIEW)?;
+        let res = match world.get::<ViewOutlineResources>(view_ent) {
+            Some(r) => r,
+            None => return Ok(()),
+        };
+        let gpu_images = world.resource::<RenderAssets<Image>>();
+        let debug_images = match world.get_resource::<OutlineDebugImages>() {
+            Some(d) => d,
+            None => return Ok(()),
+        };
+
+        textures::copy_outline_textures(
+            render_context,
+            gpu_images,
+            res,
+            &debug_images.mask,
+            &debug_images.distance_field,
+        );
+
+        Ok(())
+    }
+}
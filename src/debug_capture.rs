@@ -0,0 +1,414 @@
+use std::{
+    num::NonZeroU32,
+    sync::{
+        atomic::{AtomicU8, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use bevy::{
+    core_pipeline::{core_2d, core_3d},
+    prelude::*,
+    render::{
+        camera::ExtractedCamera,
+        render_graph::{Node, NodeRunError, RenderGraph, RenderGraphContext, SlotInfo, SlotType},
+        render_resource::{
+            Buffer, BufferDescriptor, BufferUsages, Extent3d, ImageCopyBuffer, ImageDataLayout,
+            MapMode, TextureDimension, TextureFormat,
+        },
+        renderer::{RenderContext, RenderDevice},
+        RenderApp, RenderStage,
+    },
+};
+
+use crate::{
+    graph,
+    mask::{self, MaskColors},
+    outline::OutlineViewUniform,
+    resources::{ViewJfaFormat, ViewOutlineResources},
+    textures::intermediate_size,
+    OutlineSettings,
+};
+
+bitflags::bitflags! {
+    /// Intermediate outline textures which can be captured with
+    /// [`OutlineDebug::capture_next_frame`].
+    #[derive(Default)]
+    pub struct OutlineBuffers: u32 {
+        /// The mask, in `TextureFormat::Rgba8Unorm` if any of the view's
+        /// styles takes its color from the covering entities.
+        ///
+        /// The RGB components hold the color of the covering entity,
+        /// premultiplied by the coverage stored in the alpha component. Other
+        /// views only store the coverage, in `TextureFormat::R8Unorm`.
+        const MASK = 1 << 0;
+        /// The output of the JFA initialization pass, before any jump flood
+        /// passes, in the format of the distance field. At reduced
+        /// resolutions, this is smaller than the other buffers.
+        const JFA_INIT = 1 << 1;
+        /// The final jump flood output, as described in
+        /// [`OutlineDebugImages`](crate::OutlineDebugImages).
+        const JFA_FINAL = 1 << 2;
+    }
+}
+
+/// Plugin for capturing intermediate outline textures to the CPU.
+///
+/// This adds an [`OutlineDebug`] resource for requesting captures, and sends
+/// an [`OutlineDebugCapture`] event holding an `Image` for each captured
+/// texture, which can be saved or inspected without a graphics debugger.
+/// Captures are asynchronous, so the events arrive a few frames after the
+/// request.
+///
+/// This must be added after the [`OutlinePlugin`](crate::OutlinePlugin) or
+/// [`MinimalOutlinePlugin`](crate::MinimalOutlinePlugin).
+#[derive(Default)]
+pub struct OutlineDebugCapturePlugin;
+
+impl Plugin for OutlineDebugCapturePlugin {
+    fn build(&self, app: &mut App) {
+        let debug = OutlineDebug::default();
+
+        app.insert_resource(debug.clone())
+            .add_event::<OutlineDebugCapture>()
+            .add_system_to_stage(CoreStage::PreUpdate, send_outline_debug_captures);
+
+        let render_app = match app.get_sub_app_mut(RenderApp) {
+            Ok(r) => r,
+            Err(_) => return,
+        };
+
+        render_app
+            .insert_resource(debug)
+            .init_resource::<CaptureBuffers>()
+            .add_system_to_stage(RenderStage::Prepare, prepare_capture_buffers)
+            .add_system_to_stage(RenderStage::Cleanup, map_capture_buffers);
+
+        let mut root_graph = render_app.world.resource_mut::<RenderGraph>();
+        for core_graph in [core_3d::graph::NAME, core_2d::graph::NAME] {
+            let outline_graph = match root_graph
+                .get_sub_graph_mut(core_graph)
+                .and_then(|core| core.get_sub_graph_mut(graph::outline::NAME))
+            {
+                Some(g) => g,
+                // 2D outlines require the `sprite` feature.
+                None => continue,
+            };
+
+            // The JFA initialization output is overwritten by the jump flood
+            // passes, so it's copied in between.
+            let input_node_id = outline_graph.input_node().unwrap().id;
+            let nodes = [
+                (
+                    OutlineDebugCaptureNode::INIT_NAME,
+                    OutlineBuffers::MASK | OutlineBuffers::JFA_INIT,
+                    graph::outline::node::JFA_INIT_PASS,
+                    Some(graph::outline::node::JFA_PASS),
+                ),
+                (
+                    OutlineDebugCaptureNode::FINAL_NAME,
+                    OutlineBuffers::JFA_FINAL,
+                    graph::outline::node::JFA_PASS,
+                    None,
+                ),
+            ];
+            for (name, buffers, after, before) in nodes {
+                outline_graph.add_node(name, OutlineDebugCaptureNode { buffers });
+                outline_graph
+                    .add_slot_edge(
+                        input_node_id,
+                        graph::outline::input::VIEW_ENTITY,
+                        name,
+                        OutlineDebugCaptureNode::IN_VIEW,
+                    )
+                    .unwrap();
+                outline_graph.add_node_edge(after, name).unwrap();
+                if let Some(before) = before {
+                    outline_graph.add_node_edge(name, before).unwrap();
+                }
+            }
+        }
+    }
+}
+
+/// Resource for requesting captures of intermediate outline textures.
+///
+/// See [`OutlineDebugCapturePlugin`].
+#[derive(Clone, Default)]
+pub struct OutlineDebug(Arc<Mutex<CaptureQueues>>);
+
+#[derive(Default)]
+struct CaptureQueues {
+    requested: OutlineBuffers,
+    finished: Vec<OutlineDebugCapture>,
+}
+
+impl OutlineDebug {
+    /// Requests captures of `buffers` for every outlined view rendered in the
+    /// next frame.
+    pub fn capture_next_frame(&self, buffers: OutlineBuffers) {
+        self.0.lock().unwrap().requested |= buffers;
+    }
+
+    /// Returns whether captures have been requested for the next frame.
+    pub(crate) fn is_capture_requested(&self) -> bool {
+        !self.0.lock().unwrap().requested.is_empty()
+    }
+}
+
+/// Event carrying a captured intermediate outline texture.
+#[derive(Clone, Debug)]
+pub struct OutlineDebugCapture {
+    pub camera: Entity,
+    /// The captured texture, which is one of the [`OutlineBuffers`].
+    pub buffer: OutlineBuffers,
+    /// The contents of the texture, in its own format.
+    pub image: Image,
+}
+
+fn send_outline_debug_captures(
+    debug: Res<OutlineDebug>,
+    mut events: EventWriter<OutlineDebugCapture>,
+) {
+    let mut queues = debug.0.lock().unwrap();
+    events.send_batch(queues.finished.drain(..));
+}
+
+// Capture states of a buffer.
+const STATE_IDLE: u8 = 0;
+const STATE_COPIED: u8 = 1;
+const STATE_PENDING: u8 = 2;
+const STATE_MAPPED: u8 = 3;
+
+/// A capture which has been assigned a buffer.
+struct PendingCapture {
+    camera: Entity,
+    buffer: OutlineBuffers,
+    format: TextureFormat,
+    size: UVec2,
+    // The size of the view's distance field, for matching the capture to the
+    // view's textures when it's drawn.
+    view_size: UVec2,
+    // Rows of the buffer are padded to the copy alignment.
+    padded_bytes_per_row: u32,
+    gpu_buffer: Buffer,
+    // One of the `STATE_*` constants.
+    state: Arc<AtomicU8>,
+}
+
+#[derive(Default)]
+struct CaptureBuffers {
+    pending: Vec<PendingCapture>,
+}
+
+/// Publishes finished captures and creates buffers for new requests.
+fn prepare_capture_buffers(
+    settings: Res<OutlineSettings>,
+    device: Res<RenderDevice>,
+    debug: Res<OutlineDebug>,
+    mut buffers: ResMut<CaptureBuffers>,
+    views: Query<
+        (
+            Entity,
+            &ExtractedCamera,
+            &ViewJfaFormat,
+            Option<&MaskColors>,
+        ),
+        With<OutlineViewUniform>,
+    >,
+) {
+    let mut queues = debug.0.lock().unwrap();
+    let CaptureQueues {
+        requested,
+        finished,
+    } = &mut *queues;
+
+    // Mappings finish when a later frame's commands are submitted, which
+    // invokes their callbacks. Captures which weren't copied in the previous
+    // frame, as their view wasn't drawn, are dropped.
+    buffers.pending.retain(|capture| {
+        match capture.state.load(Ordering::Acquire) {
+            STATE_IDLE => return false,
+            STATE_MAPPED => {}
+            _ => return true,
+        }
+
+        let data = {
+            let mapped = capture.gpu_buffer.slice(..).get_mapped_range();
+            let row_len = (capture.size.x * texel_size(capture.format)) as usize;
+            mapped
+                .chunks_exact(capture.padded_bytes_per_row as usize)
+                .flat_map(|row| row[..row_len].iter().copied())
+                .collect()
+        };
+        capture.gpu_buffer.unmap();
+
+        let size = Extent3d {
+            width: capture.size.x,
+            height: capture.size.y,
+            depth_or_array_layers: 1,
+        };
+        finished.push(OutlineDebugCapture {
+            camera: capture.camera,
+            buffer: capture.buffer,
+            image: Image::new(size, TextureDimension::D2, data, capture.format),
+        });
+        false
+    });
+
+    let requested = std::mem::take(requested);
+    if requested.is_empty() {
+        return;
+    }
+
+    for (camera, extracted, &ViewJfaFormat(jfa_format), mask_colors) in views.iter() {
+        let viewport_size = match extracted.physical_viewport_size {
+            Some(s) => s,
+            None => continue,
+        };
+
+        let view_size = intermediate_size(&settings, viewport_size);
+        let view_size = UVec2::new(view_size.width, view_size.height);
+        let jfa_format = jfa_format.texture_format();
+        let captures = [
+            (
+                OutlineBuffers::MASK,
+                mask::mask_texture_format(mask_colors.is_some()),
+                view_size,
+            ),
+            (
+                OutlineBuffers::JFA_INIT,
+                jfa_format,
+                (view_size / settings.resolution().divisor()).max(UVec2::ONE),
+            ),
+            (OutlineBuffers::JFA_FINAL, jfa_format, view_size),
+        ];
+
+        for (buffer, format, size) in captures {
+            if !requested.contains(buffer) || size.x == 0 || size.y == 0 {
+                continue;
+            }
+
+            let padded_bytes_per_row =
+                RenderDevice::align_copy_bytes_per_row((size.x * texel_size(format)) as usize)
+                    as u32;
+            buffers.pending.push(PendingCapture {
+                camera,
+                buffer,
+                format,
+                size,
+                view_size,
+                padded_bytes_per_row,
+                gpu_buffer: device.create_buffer(&BufferDescriptor {
+                    label: Some("outline_debug_capture"),
+                    size: (padded_bytes_per_row * size.y) as u64,
+                    usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                }),
+                state: Arc::new(AtomicU8::new(STATE_IDLE)),
+            });
+        }
+    }
+}
+
+/// Returns the size of a texel of `format` in bytes.
+fn texel_size(format: TextureFormat) -> u32 {
+    format.describe().block_size as u32
+}
+
+/// Starts mapping the capture buffers copied to this frame.
+///
+/// This runs after the frame's commands have been submitted.
+fn map_capture_buffers(buffers: Res<CaptureBuffers>) {
+    for capture in buffers.pending.iter() {
+        if capture.state.load(Ordering::Acquire) != STATE_COPIED {
+            continue;
+        }
+
+        capture.state.store(STATE_PENDING, Ordering::Release);
+        let state = capture.state.clone();
+        capture
+            .gpu_buffer
+            .slice(..)
+            .map_async(MapMode::Read, move |result| {
+                let new_state = match result {
+                    Ok(()) => STATE_MAPPED,
+                    // Dropped in the next frame.
+                    Err(_) => STATE_IDLE,
+                };
+                state.store(new_state, Ordering::Release);
+            });
+    }
+}
+
+/// Render graph node which copies some of a view's intermediate textures into
+/// capture buffers.
+struct OutlineDebugCaptureNode {
+    // The textures copied by this node.
+    buffers: OutlineBuffers,
+}
+
+impl OutlineDebugCaptureNode {
+    const INIT_NAME: &'static str = "outline_debug_capture_init";
+    const FINAL_NAME: &'static str = "outline_debug_capture_final";
+    const IN_VIEW: &'static str = "view";
+}
+
+impl Node for OutlineDebugCaptureNode {
+    fn input(&self) -> Vec<SlotInfo> {
+        vec![SlotInfo::new(Self::IN_VIEW, SlotType::Entity)]
+    }
+
+    fn run(
+        &self,
+        graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let view_ent = graph.get_input_entity(Self::IN_VIEW)?;
+        let res = match world.get::<ViewOutlineResources>(view_ent) {
+            Some(r) => r,
+            None => return Ok(()),
+        };
+        let dims = res.dimensions_buffer.get().size();
+
+        let buffers = world.resource::<CaptureBuffers>();
+        for capture in buffers.pending.iter() {
+            if capture.camera != view_ent
+                || !self.buffers.contains(capture.buffer)
+                || capture.view_size != dims
+                || capture.state.load(Ordering::Acquire) != STATE_IDLE
+            {
+                continue;
+            }
+
+            let texture = if capture.buffer == OutlineBuffers::MASK {
+                &res.mask_output.texture
+            } else if capture.buffer == OutlineBuffers::JFA_INIT {
+                &res.jfa_primary_output.texture
+            } else {
+                &res.jfa_final_output.texture
+            };
+
+            render_context.command_encoder.copy_texture_to_buffer(
+                texture.as_image_copy(),
+                ImageCopyBuffer {
+                    buffer: &capture.gpu_buffer,
+                    layout: ImageDataLayout {
+                        offset: 0,
+                        bytes_per_row: NonZeroU32::new(capture.padded_bytes_per_row),
+                        rows_per_image: None,
+                    },
+                },
+                Extent3d {
+                    width: capture.size.x,
+                    height: capture.size.y,
+                    depth_or_array_layers: 1,
+                },
+            );
+            capture.state.store(STATE_COPIED, Ordering::Release);
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,131 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use bevy::{
+    diagnostic::{Diagnostic, DiagnosticId, Diagnostics},
+    prelude::*,
+    render::{RenderApp, RenderStage},
+};
+
+use crate::outline::{OutlineViewBindGroup, PreparedStyleCache, PREPARED_STYLE_CACHE_SIZE};
+
+/// Plugin for reporting diagnostics about outline rendering.
+///
+/// This adds diagnostics for the number of prepared outline styles and the
+/// number of styles and bind groups created each frame. If styles are evicted
+/// from the prepared style cache, a warning is logged suggesting that fewer
+/// distinct styles be used, as each eviction causes a style to be uploaded to
+/// the GPU again when it is next used.
+///
+/// This must be added after the [`OutlinePlugin`](crate::OutlinePlugin) or
+/// [`MinimalOutlinePlugin`](crate::MinimalOutlinePlugin).
+#[derive(Default)]
+pub struct OutlineDiagnosticsPlugin;
+
+impl OutlineDiagnosticsPlugin {
+    pub const PREPARED_STYLES: DiagnosticId =
+        DiagnosticId::from_u128(203871936478106384021364858413718920117);
+    pub const STYLE_UPLOADS: DiagnosticId =
+        DiagnosticId::from_u128(100523874165740287391512905187339473605);
+    pub const VIEW_BIND_GROUPS: DiagnosticId =
+        DiagnosticId::from_u128(317254108766431958103462357018346290881);
+}
+
+impl Plugin for OutlineDiagnosticsPlugin {
+    fn build(&self, app: &mut App) {
+        let stats = OutlineStats::default();
+
+        app.insert_resource(stats.clone())
+            .add_startup_system(setup_outline_diagnostics)
+            .add_system(outline_diagnostics_system);
+
+        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app
+                .insert_resource(stats)
+                .add_system_to_stage(RenderStage::PhaseSort, record_outline_stats);
+        }
+    }
+}
+
+/// Counts recorded by the render world, shared with the main world.
+#[derive(Clone, Default)]
+struct OutlineStats(Arc<OutlineStatsInner>);
+
+#[derive(Default)]
+struct OutlineStatsInner {
+    prepared_styles: AtomicUsize,
+    style_uploads: AtomicUsize,
+    style_evictions: AtomicUsize,
+    view_bind_groups: AtomicUsize,
+}
+
+fn setup_outline_diagnostics(mut diagnostics: ResMut<Diagnostics>) {
+    diagnostics.add(Diagnostic::new(
+        OutlineDiagnosticsPlugin::PREPARED_STYLES,
+        "outline_prepared_styles",
+        20,
+    ));
+    diagnostics.add(Diagnostic::new(
+        OutlineDiagnosticsPlugin::STYLE_UPLOADS,
+        "outline_style_uploads",
+        20,
+    ));
+    diagnostics.add(Diagnostic::new(
+        OutlineDiagnosticsPlugin::VIEW_BIND_GROUPS,
+        "outline_view_bind_groups",
+        20,
+    ));
+}
+
+fn record_outline_stats(
+    stats: Res<OutlineStats>,
+    mut cache: ResMut<PreparedStyleCache>,
+    views: Query<&OutlineViewBindGroup>,
+) {
+    let (uploads, evictions) = cache.take_counts();
+
+    stats
+        .0
+        .prepared_styles
+        .store(cache.len(), Ordering::Relaxed);
+    stats.0.style_uploads.store(uploads, Ordering::Relaxed);
+    stats
+        .0
+        .style_evictions
+        .fetch_add(evictions, Ordering::Relaxed);
+    stats
+        .0
+        .view_bind_groups
+        .store(views.iter().count(), Ordering::Relaxed);
+}
+
+fn outline_diagnostics_system(
+    mut diagnostics: ResMut<Diagnostics>,
+    mut warned: Local<bool>,
+    stats: Res<OutlineStats>,
+) {
+    let load = |count: &AtomicUsize| count.load(Ordering::Relaxed) as f64;
+
+    diagnostics.add_measurement(OutlineDiagnosticsPlugin::PREPARED_STYLES, || {
+        load(&stats.0.prepared_styles)
+    });
+    diagnostics.add_measurement(OutlineDiagnosticsPlugin::STYLE_UPLOADS, || {
+        load(&stats.0.style_uploads)
+    });
+    diagnostics.add_measurement(OutlineDiagnosticsPlugin::VIEW_BIND_GROUPS, || {
+        load(&stats.0.view_bind_groups)
+    });
+
+    let evictions = stats.0.style_evictions.swap(0, Ordering::Relaxed);
+    if evictions > 0 && !*warned {
+        warn!(
+            "More than {} distinct outline styles are in use, so prepared styles are being \
+             evicted and uploaded again. Consider sharing `OutlineStyle` handles or reducing \
+             the number of distinct styles.",
+            PREPARED_STYLE_CACHE_SIZE,
+        );
+        *warned = true;
+    }
+}
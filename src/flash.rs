@@ -0,0 +1,94 @@
+use bevy::{prelude::*, reflect::FromReflect};
+
+use crate::{CameraOutline, OutlineStyle};
+
+/// Component for briefly flashing a camera's outline, e.g. as feedback when
+/// the player takes damage or an item is pinged.
+///
+/// While this component is present, the camera's outline is drawn even if its
+/// [`CameraOutline`] is disabled. The outline starts out in `color` and fades
+/// back to the camera's style over `duration` seconds, after which this
+/// component is removed. If the outline wasn't otherwise enabled, it fades
+/// out instead. Adding a new flash restarts it.
+///
+/// The flashed style is updated in place on the GPU each frame, so flashing
+/// doesn't allocate new assets or bind groups.
+#[derive(Clone, Debug, Default, Component, Reflect, FromReflect)]
+#[reflect(Component, Default)]
+pub struct OutlineFlash {
+    pub color: Color,
+    /// Duration of the flash in seconds.
+    pub duration: f32,
+    elapsed: f32,
+}
+
+impl OutlineFlash {
+    pub fn new(color: Color, duration: f32) -> OutlineFlash {
+        OutlineFlash {
+            color,
+            duration,
+            elapsed: 0.0,
+        }
+    }
+
+    /// Returns the fraction of the flash which has elapsed.
+    pub fn progress(&self) -> f32 {
+        if self.duration <= 0.0 {
+            return 1.0;
+        }
+
+        (self.elapsed / self.duration).clamp(0.0, 1.0)
+    }
+
+    /// Returns the style drawn by a camera during the flash, given its
+    /// `CameraOutline` and `OutlineStyle` components, or `None` if the camera
+    /// has no style to flash.
+    pub(crate) fn flashed_style(
+        &self,
+        cam_outline: Option<&CameraOutline>,
+        cam_style: Option<&OutlineStyle>,
+        styles: Option<&Assets<OutlineStyle>>,
+    ) -> Option<OutlineStyle> {
+        let asset_style = cam_outline.and_then(|outline| styles?.get(&outline.style));
+        let asset_enabled = cam_outline.is_some_and(|outline| outline.enabled);
+
+        // Flash the style the camera would otherwise draw, if any.
+        let (mut style, enabled) = match (asset_style, cam_style) {
+            (Some(asset), _) if asset_enabled => (asset.clone(), true),
+            (_, Some(component)) => (component.clone(), true),
+            (Some(asset), None) => (asset.clone(), false),
+            (None, None) => return None,
+        };
+
+        let mut to = style.color;
+        if !enabled {
+            to.set_a(0.0);
+        }
+
+        // Ease out, so the flash is most noticeable at the start.
+        let t = self.progress();
+        let t = t * (2.0 - t);
+
+        let from = Vec4::from(self.color.as_rgba_f32());
+        let c = from.lerp(Vec4::from(to.as_rgba_f32()), t);
+        style.color = Color::rgba(c.x, c.y, c.z, c.w);
+
+        Some(style)
+    }
+}
+
+/// Advances each `OutlineFlash`, removing those which have finished.
+pub(crate) fn advance_outline_flashes(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut flashes: Query<(Entity, &mut OutlineFlash)>,
+) {
+    for (entity, mut flash) in flashes.iter_mut() {
+        if flash.progress() >= 1.0 {
+            commands.entity(entity).remove::<OutlineFlash>();
+            continue;
+        }
+
+        flash.elapsed += time.delta_seconds();
+    }
+}
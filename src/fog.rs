@@ -0,0 +1,615 @@
+use bevy::{
+    core_pipeline::{core_2d, core_3d},
+    prelude::*,
+    reflect::TypeUuid,
+    render::{
+        camera::{ExtractedCamera, RenderTarget},
+        render_asset::RenderAssets,
+        render_graph::{Node, NodeRunError, RenderGraph, RenderGraphContext, SlotInfo, SlotType},
+        render_phase::TrackedRenderPass,
+        render_resource::{
+            BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
+            BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource, BindingType,
+            BlendState, BufferBindingType, CachedRenderPipelineId, ColorTargetState, ColorWrites,
+            DynamicUniformBuffer, Extent3d, FragmentState, LoadOp, MultisampleState, Operations,
+            PipelineCache, RenderPassColorAttachment, RenderPassDescriptor,
+            RenderPipelineDescriptor, SamplerBindingType, ShaderStages, ShaderType,
+            SpecializedRenderPipeline, SpecializedRenderPipelines, TextureFormat,
+            TextureSampleType, TextureViewDimension, VertexState,
+        },
+        renderer::{RenderContext, RenderDevice, RenderQueue},
+        texture::BevyDefault,
+        view::{ExtractedView, ExtractedWindows},
+        Extract, RenderApp, RenderStage,
+    },
+    transform::TransformSystem,
+};
+
+use crate::{
+    graph::OutlineDriverNode, resources::OutlineResources, textures::placeholder_image, JfaNoSeed,
+    JfaRequest, JfaTextureFormat, FULLSCREEN_PRIMITIVE_STATE, FULLSCREEN_SHADER_HANDLE,
+};
+
+const FOG_SHADER_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 9087261547820193413);
+
+/// Plugin for fog of war, revealed around [`FogRevealer`]s.
+///
+/// Each frame, the revealers are drawn into the seed mask of the
+/// [`FogOfWar`] map, which is flooded by a [`JfaRequest`] to find the distance
+/// from each texel of the map to the revealed area. Cameras with a
+/// [`FogOfWarCamera`] are darkened by a fullscreen pass, with the fog
+/// thickening over [`FogOfWar::fade`] world units outside the revealed area.
+/// The pass runs after the main pass and before outlines are drawn.
+///
+/// This must be added after the [`JfaPlugin`](crate::JfaPlugin), which runs
+/// the jump flood passes.
+#[derive(Default)]
+pub struct FogOfWarPlugin;
+
+impl Plugin for FogOfWarPlugin {
+    fn build(&self, app: &mut App) {
+        crate::init_jfa_texture_format(app);
+
+        let mut shaders = app.world.resource_mut::<Assets<Shader>>();
+        shaders.set_untracked(
+            FOG_SHADER_HANDLE,
+            Shader::from_wgsl(include_str!("shaders/fog.wgsl")),
+        );
+
+        app.init_resource::<FogOfWar>().add_system_to_stage(
+            CoreStage::PostUpdate,
+            draw_fog_mask.after(TransformSystem::TransformPropagate),
+        );
+
+        let render_app = match app.get_sub_app_mut(RenderApp) {
+            Ok(r) => r,
+            Err(_) => return,
+        };
+
+        render_app
+            .init_resource::<FogPipeline>()
+            .init_resource::<SpecializedRenderPipelines<FogPipeline>>()
+            .init_resource::<FogUniforms>()
+            .add_system_to_stage(RenderStage::Extract, extract_fog_of_war)
+            .add_system_to_stage(RenderStage::Prepare, prepare_fog_uniforms)
+            .add_system_to_stage(RenderStage::Queue, queue_fog_bind_groups)
+            .add_system_to_stage(RenderStage::Queue, queue_fog_pipelines);
+
+        let mut root_graph = render_app.world.resource_mut::<RenderGraph>();
+        for (core_graph, input, main_pass) in [
+            (
+                core_3d::graph::NAME,
+                core_3d::graph::input::VIEW_ENTITY,
+                core_3d::graph::node::MAIN_PASS,
+            ),
+            (
+                core_2d::graph::NAME,
+                core_2d::graph::input::VIEW_ENTITY,
+                core_2d::graph::node::MAIN_PASS,
+            ),
+        ] {
+            let core_graph = match root_graph.get_sub_graph_mut(core_graph) {
+                Some(g) => g,
+                None => continue,
+            };
+
+            let input_node_id = core_graph.input_node().unwrap().id;
+            core_graph.add_node(FogNode::NAME, FogNode);
+            core_graph
+                .add_slot_edge(input_node_id, input, FogNode::NAME, FogNode::IN_VIEW)
+                .unwrap();
+            core_graph.add_node_edge(main_pass, FogNode::NAME).unwrap();
+            // 2D outlines require the `sprite` feature.
+            if core_graph.get_node_id(OutlineDriverNode::NAME).is_ok() {
+                core_graph
+                    .add_node_edge(FogNode::NAME, OutlineDriverNode::NAME)
+                    .unwrap();
+            }
+        }
+    }
+}
+
+/// The plane on which a [`FogOfWar`] map lies.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FogPlane {
+    /// The horizontal plane at the given height, for 3D scenes. The map's X
+    /// axis follows the world X axis, and its Y axis the world Z axis.
+    Xz { height: f32 },
+    /// The XY plane, for 2D scenes.
+    Xy,
+}
+
+impl Default for FogPlane {
+    fn default() -> Self {
+        FogPlane::Xz { height: 0.0 }
+    }
+}
+
+/// Resource describing the fog of war map.
+///
+/// The map covers a rectangle of `size` world units centered on `center`,
+/// in the coordinates of its `plane`.
+#[derive(Clone, Debug)]
+pub struct FogOfWar {
+    /// Whether fog is drawn.
+    pub enabled: bool,
+    pub plane: FogPlane,
+    pub center: Vec2,
+    pub size: Vec2,
+    /// The size of the map's textures, in texels.
+    pub resolution: UVec2,
+    /// The distance, in world units, over which the fog thickens outside the
+    /// revealed area.
+    pub fade: f32,
+    /// The color of the fog. Fully fogged areas are covered by this color,
+    /// blended by its alpha.
+    pub color: Color,
+    mask: Handle<Image>,
+    distance_field: Handle<Image>,
+    // The entity holding the `JfaRequest` for the map.
+    request: Entity,
+}
+
+impl FromWorld for FogOfWar {
+    fn from_world(world: &mut World) -> Self {
+        let jfa_format = world.resource::<JfaTextureFormat>().texture_format();
+        let mut images = world.resource_mut::<Assets<Image>>();
+        let mask = images.add(placeholder_image(TextureFormat::Rgba8Unorm));
+        let distance_field = images.add(placeholder_image(jfa_format));
+        let request = world
+            .spawn()
+            .insert(JfaRequest::new(mask.clone(), distance_field.clone(), 1))
+            .id();
+
+        FogOfWar {
+            enabled: true,
+            plane: FogPlane::default(),
+            center: Vec2::ZERO,
+            size: Vec2::splat(100.0),
+            resolution: UVec2::splat(256),
+            fade: 5.0,
+            color: Color::rgba(0.0, 0.0, 0.0, 0.85),
+            mask,
+            distance_field,
+            request,
+        }
+    }
+}
+
+impl FogOfWar {
+    /// The seed mask, in `TextureFormat::Rgba8Unorm`, whose alpha holds the
+    /// coverage of the revealers.
+    pub fn mask(&self) -> &Handle<Image> {
+        &self.mask
+    }
+
+    /// The distance field of the mask, as described in [`JfaRequest`].
+    ///
+    /// Texels of the revealed area are their own nearest seeds, so the
+    /// `sdf_sample_distance` function of the `outline::sdf` shader import
+    /// gives the distance in texels from any point to the revealed area.
+    pub fn distance_field(&self) -> &Handle<Image> {
+        &self.distance_field
+    }
+
+    /// Returns the matrix transforming world space into map space, in which
+    /// the map covers `[0, 1]` on the X and Y axes, and the map plane lies at
+    /// Z = 0.
+    pub fn world_to_map(&self) -> Mat4 {
+        let min = self.center - 0.5 * self.size;
+        let (y_axis, z_axis, offset) = match self.plane {
+            FogPlane::Xz { height } => (Vec4::Z, Vec4::Y / self.size.y, -height),
+            FogPlane::Xy => (Vec4::Y / self.size.y, Vec4::Z, 0.0),
+        };
+        Mat4::from_cols(
+            Vec4::X / self.size.x,
+            y_axis,
+            z_axis,
+            Vec4::new(-min.x / self.size.x, -min.y / self.size.y, offset, 1.0),
+        )
+    }
+}
+
+/// Component for entities which reveal the fog around them.
+///
+/// The revealed area is a circle of `radius` world units around the entity's
+/// position, projected onto the map plane.
+#[derive(Clone, Copy, Debug, PartialEq, Component)]
+pub struct FogRevealer {
+    pub radius: f32,
+}
+
+impl Default for FogRevealer {
+    fn default() -> Self {
+        FogRevealer { radius: 10.0 }
+    }
+}
+
+/// Marker component for cameras which draw the fog of war.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Component)]
+pub struct FogOfWarCamera;
+
+/// Draws the revealers into the seed mask, and updates the map's
+/// `JfaRequest`.
+fn draw_fog_mask(
+    fog: Res<FogOfWar>,
+    mut images: ResMut<Assets<Image>>,
+    mut requests: Query<&mut JfaRequest>,
+    revealers: Query<(&GlobalTransform, &FogRevealer)>,
+) {
+    let resolution = fog.resolution.max(UVec2::ONE);
+    let texels_per_unit = resolution.as_vec2() / fog.size;
+
+    // The flood must reach as far as the fog fades.
+    if let Ok(mut request) = requests.get_mut(fog.request) {
+        let max_dist = (fog.fade * texels_per_unit.max_element()).ceil() as u32;
+        if request.max_dist != max_dist {
+            request.max_dist = max_dist;
+        }
+    }
+
+    let world_to_map = fog.world_to_map();
+    let mut data = vec![0; (resolution.x * resolution.y * 4) as usize];
+    for (transform, revealer) in revealers.iter() {
+        let center = world_to_map.transform_point3(transform.translation());
+        let center = Vec2::new(center.x, center.y) * resolution.as_vec2();
+        let radius = revealer.radius * texels_per_unit;
+
+        let min = (center - radius).floor().max(Vec2::ZERO).as_uvec2();
+        let max = (center + radius).ceil().as_uvec2().min(resolution);
+        for y in min.y..max.y {
+            for x in min.x..max.x {
+                // Antialias the edge over one texel, measured in units of the
+                // radius to allow for non-square texels.
+                let offset = (Vec2::new(x as f32, y as f32) + 0.5 - center) / radius;
+                let edge = (1.0 - offset.length()) * radius.min_element();
+                let coverage = (edge + 0.5).clamp(0.0, 1.0);
+
+                let texel = ((y * resolution.x + x) * 4) as usize;
+                let alpha = (coverage * u8::MAX as f32).round() as u8;
+                data[texel..texel + 3].fill(u8::MAX);
+                data[texel + 3] = data[texel + 3].max(alpha);
+            }
+        }
+    }
+
+    // Only access the image mutably when necessary, as doing so causes it to
+    // be uploaded to the GPU again.
+    let size = Extent3d {
+        width: resolution.x,
+        height: resolution.y,
+        depth_or_array_layers: 1,
+    };
+    let up_to_date = images
+        .get(&fog.mask)
+        .is_some_and(|image| image.texture_descriptor.size == size && image.data == data);
+    if !up_to_date {
+        if let Some(image) = images.get_mut(&fog.mask) {
+            image.texture_descriptor.size = size;
+            image.data = data;
+        }
+    }
+}
+
+/// The fog of war map, extracted into the render world.
+struct ExtractedFogOfWar {
+    world_to_map: Mat4,
+    color: Color,
+    // The fade distance in distance field texels.
+    fade: f32,
+    distance_field: Handle<Image>,
+}
+
+/// Cameras drawing the fog of war.
+type FogOfWarCameraFilter = (With<Camera>, With<FogOfWarCamera>);
+
+fn extract_fog_of_war(
+    mut commands: Commands,
+    fog: Extract<Res<FogOfWar>>,
+    cameras: Extract<Query<Entity, FogOfWarCameraFilter>>,
+) {
+    if !fog.enabled {
+        commands.remove_resource::<ExtractedFogOfWar>();
+        return;
+    }
+
+    let texels_per_unit = fog.resolution.as_vec2() / fog.size;
+    commands.insert_resource(ExtractedFogOfWar {
+        world_to_map: fog.world_to_map(),
+        color: fog.color,
+        fade: fog.fade * texels_per_unit.max_element(),
+        distance_field: fog.distance_field.clone(),
+    });
+
+    for entity in cameras.iter() {
+        commands.get_or_spawn(entity).insert(FogOfWarCamera);
+    }
+}
+
+#[derive(Clone, ShaderType)]
+struct FogUniform {
+    inverse_view_proj: Mat4,
+    world_to_map: Mat4,
+    color: Vec4,
+    fade: f32,
+}
+
+#[derive(Default)]
+struct FogUniforms {
+    uniforms: DynamicUniformBuffer<FogUniform>,
+    bind_group: Option<BindGroup>,
+    distance_field_bind_group: Option<BindGroup>,
+}
+
+#[derive(Component)]
+struct FogUniformOffset {
+    offset: u32,
+}
+
+fn prepare_fog_uniforms(
+    mut commands: Commands,
+    device: Res<RenderDevice>,
+    queue: Res<RenderQueue>,
+    fog: Option<Res<ExtractedFogOfWar>>,
+    mut fog_uniforms: ResMut<FogUniforms>,
+    views: Query<(Entity, &ExtractedView), With<FogOfWarCamera>>,
+) {
+    fog_uniforms.uniforms.clear();
+    let fog = match fog {
+        Some(f) => f,
+        None => return,
+    };
+
+    for (entity, view) in views.iter() {
+        let inverse_view_proj = view.transform.compute_matrix() * view.projection.inverse();
+        let offset = fog_uniforms.uniforms.push(FogUniform {
+            inverse_view_proj,
+            world_to_map: fog.world_to_map,
+            color: fog.color.as_linear_rgba_f32().into(),
+            fade: fog.fade,
+        });
+        commands.entity(entity).insert(FogUniformOffset { offset });
+    }
+    fog_uniforms.uniforms.write_buffer(&device, &queue);
+}
+
+fn queue_fog_bind_groups(
+    device: Res<RenderDevice>,
+    res: Res<OutlineResources>,
+    pipeline: Res<FogPipeline>,
+    images: Res<RenderAssets<Image>>,
+    fog: Option<Res<ExtractedFogOfWar>>,
+    mut fog_uniforms: ResMut<FogUniforms>,
+) {
+    let fog_uniforms = &mut *fog_uniforms;
+    fog_uniforms.bind_group = fog_uniforms.uniforms.binding().map(|binding| {
+        device.create_bind_group(&BindGroupDescriptor {
+            label: Some("fog_uniform_bind_group"),
+            layout: &pipeline.uniform_layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: binding,
+            }],
+        })
+    });
+
+    let distance_field = fog.and_then(|fog| images.get(&fog.distance_field));
+    fog_uniforms.distance_field_bind_group = distance_field.map(|distance_field| {
+        device.create_bind_group(&BindGroupDescriptor {
+            label: Some("fog_distance_field_bind_group"),
+            layout: &pipeline.distance_field_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(&distance_field.texture_view),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::Sampler(&res.sampler),
+                },
+            ],
+        })
+    });
+}
+
+struct FogPipeline {
+    uniform_layout: BindGroupLayout,
+    distance_field_layout: BindGroupLayout,
+    jfa_format: JfaTextureFormat,
+    jfa_no_seed: JfaNoSeed,
+}
+
+impl FromWorld for FogPipeline {
+    fn from_world(world: &mut World) -> Self {
+        let device = world.resource::<RenderDevice>();
+
+        let uniform_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("fog_uniform_bind_group_layout"),
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: true,
+                    min_binding_size: Some(FogUniform::min_size()),
+                },
+                count: None,
+            }],
+        });
+
+        let distance_field_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("fog_distance_field_bind_group_layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: false },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(SamplerBindingType::NonFiltering),
+                    count: None,
+                },
+            ],
+        });
+
+        FogPipeline {
+            uniform_layout,
+            distance_field_layout,
+            jfa_format: *world.resource::<JfaTextureFormat>(),
+            jfa_no_seed: *world.resource::<JfaNoSeed>(),
+        }
+    }
+}
+
+impl SpecializedRenderPipeline for FogPipeline {
+    // The format of the camera target.
+    type Key = TextureFormat;
+
+    fn specialize(&self, key: Self::Key) -> RenderPipelineDescriptor {
+        RenderPipelineDescriptor {
+            label: Some("fog_pipeline".into()),
+            layout: Some(vec![
+                self.uniform_layout.clone(),
+                self.distance_field_layout.clone(),
+            ]),
+            vertex: VertexState {
+                shader: FULLSCREEN_SHADER_HANDLE.typed::<Shader>(),
+                shader_defs: vec![],
+                entry_point: "vertex".into(),
+                buffers: vec![],
+            },
+            fragment: Some(FragmentState {
+                shader: FOG_SHADER_HANDLE.typed::<Shader>(),
+                shader_defs: [
+                    self.jfa_format.shader_defs(),
+                    self.jfa_no_seed.shader_defs(),
+                ]
+                .concat(),
+                entry_point: "fragment".into(),
+                targets: vec![Some(ColorTargetState {
+                    format: key,
+                    blend: Some(BlendState::ALPHA_BLENDING),
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+            primitive: FULLSCREEN_PRIMITIVE_STATE,
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+        }
+    }
+}
+
+#[derive(Component)]
+struct FogViewPipeline {
+    pipeline_id: CachedRenderPipelineId,
+}
+
+fn queue_fog_pipelines(
+    mut commands: Commands,
+    mut pipeline_cache: ResMut<PipelineCache>,
+    mut pipelines: ResMut<SpecializedRenderPipelines<FogPipeline>>,
+    fog_pipeline: Res<FogPipeline>,
+    images: Res<RenderAssets<Image>>,
+    views: Query<(Entity, &ExtractedCamera), With<FogUniformOffset>>,
+) {
+    for (entity, camera) in views.iter() {
+        let format = match &camera.target {
+            RenderTarget::Window(_) => TextureFormat::bevy_default(),
+            RenderTarget::Image(handle) => match images.get(handle) {
+                Some(image) => image.texture_format,
+                None => continue,
+            },
+        };
+
+        let pipeline_id = pipelines.specialize(&mut pipeline_cache, &fog_pipeline, format);
+        commands
+            .entity(entity)
+            .insert(FogViewPipeline { pipeline_id });
+    }
+}
+
+/// Render graph node which darkens a view by the fog of war.
+struct FogNode;
+
+impl FogNode {
+    const NAME: &'static str = "fog_of_war";
+    const IN_VIEW: &'static str = "view";
+}
+
+impl Node for FogNode {
+    fn input(&self) -> Vec<SlotInfo> {
+        vec![SlotInfo::new(Self::IN_VIEW, SlotType::Entity)]
+    }
+
+    fn run(
+        &self,
+        graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let view_ent = graph.get_input_entity(Self::IN_VIEW)?;
+        let (camera, offset, view_pipeline) = match (
+            world.get::<ExtractedCamera>(view_ent),
+            world.get::<FogUniformOffset>(view_ent),
+            world.get::<FogViewPipeline>(view_ent),
+        ) {
+            (Some(c), Some(o), Some(p)) => (c, o, p),
+            _ => return Ok(()),
+        };
+
+        let fog_uniforms = world.resource::<FogUniforms>();
+        let (bind_group, distance_field_bind_group) = match (
+            &fog_uniforms.bind_group,
+            &fog_uniforms.distance_field_bind_group,
+        ) {
+            (Some(b), Some(d)) => (b, d),
+            _ => return Ok(()),
+        };
+
+        let pipeline_cache = world.resource::<PipelineCache>();
+        let pipeline = match pipeline_cache.get_render_pipeline(view_pipeline.pipeline_id) {
+            Some(p) => p,
+            None => return Ok(()),
+        };
+
+        let windows = world.resource::<ExtractedWindows>();
+        let images = world.resource::<RenderAssets<Image>>();
+        let target_view = match camera.target.get_texture_view(windows, images) {
+            Some(v) => v,
+            None => return Ok(()),
+        };
+
+        let render_pass = render_context
+            .command_encoder
+            .begin_render_pass(&RenderPassDescriptor {
+                label: Some("fog_of_war"),
+                color_attachments: &[Some(RenderPassColorAttachment {
+                    view: target_view,
+                    resolve_target: None,
+                    ops: Operations {
+                        load: LoadOp::Load,
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+        let mut tracked_pass = TrackedRenderPass::new(render_pass);
+        if let Some(viewport) = &camera.viewport {
+            tracked_pass.set_camera_viewport(viewport);
+        }
+        tracked_pass.set_render_pipeline(pipeline);
+        tracked_pass.set_bind_group(0, bind_group, &[offset.offset]);
+        tracked_pass.set_bind_group(1, distance_field_bind_group, &[]);
+        tracked_pass.draw(0..3, 0..1);
+
+        Ok(())
+    }
+}
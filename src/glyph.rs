@@ -0,0 +1,105 @@
+use bevy::{asset::HandleId, prelude::*, sprite::TextureAtlas, text::FontAtlasSet, utils::HashMap};
+
+use crate::{textures::placeholder_image, JfaRequest, JfaTextureFormat};
+
+/// Plugin for computing the distance fields of glyph atlases.
+///
+/// Each texture atlas holding rasterized glyphs, for UI or 2D text, is
+/// flooded by a [`JfaRequest`] every frame, so that glyphs added to the atlas
+/// are picked up. The resulting distance fields are listed in the
+/// [`GlyphSdfs`] resource, for custom text rendering to draw scalable
+/// outlines and glows around glyphs.
+///
+/// This must be added after the [`JfaPlugin`](crate::JfaPlugin) and Bevy's
+/// `TextPlugin`.
+#[derive(Default)]
+pub struct GlyphSdfPlugin;
+
+impl Plugin for GlyphSdfPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GlyphSdfs>()
+            .add_system_to_stage(CoreStage::PostUpdate, update_glyph_sdfs);
+    }
+}
+
+/// The distance fields of glyph atlases.
+///
+/// Each distance field is a JFA texture of the same size as its atlas, as
+/// described in [`JfaRequest`]. Glyphs are packed closely in their atlases,
+/// so a glyph's distances are only accurate up to about half the spacing
+/// between glyphs, beyond which they may refer to a neighbouring glyph.
+pub struct GlyphSdfs {
+    /// The distance, in texels, up to which seeds are found in atlases added
+    /// from now on.
+    pub max_dist: u32,
+    atlases: HashMap<HandleId, GlyphSdf>,
+}
+
+struct GlyphSdf {
+    // The entity holding the `JfaRequest`.
+    entity: Entity,
+    output: Handle<Image>,
+}
+
+impl Default for GlyphSdfs {
+    fn default() -> Self {
+        GlyphSdfs {
+            max_dist: 8,
+            atlases: HashMap::default(),
+        }
+    }
+}
+
+impl GlyphSdfs {
+    /// Returns the distance field of the glyph atlas texture `texture`.
+    ///
+    /// A glyph's atlas is found through the `texture_atlas` of its
+    /// `PositionedGlyph`, whose `texture` is the atlas texture.
+    pub fn get(&self, texture: &Handle<Image>) -> Option<&Handle<Image>> {
+        self.atlases.get(&texture.id).map(|sdf| &sdf.output)
+    }
+}
+
+/// Starts flooding new glyph atlases, and stops flooding removed ones.
+fn update_glyph_sdfs(
+    mut commands: Commands,
+    mut sdfs: ResMut<GlyphSdfs>,
+    jfa_format: Res<JfaTextureFormat>,
+    mut images: ResMut<Assets<Image>>,
+    font_atlas_sets: Res<Assets<FontAtlasSet>>,
+    texture_atlases: Res<Assets<TextureAtlas>>,
+) {
+    let textures: Vec<&Handle<Image>> = font_atlas_sets
+        .iter()
+        .flat_map(|(_, set)| set.iter())
+        .flat_map(|(_, atlases)| atlases.iter())
+        .filter_map(|atlas| texture_atlases.get(&atlas.texture_atlas))
+        .map(|atlas| &atlas.texture)
+        .collect();
+
+    let sdfs = &mut *sdfs;
+    sdfs.atlases.retain(|id, sdf| {
+        let present = textures.iter().any(|texture| texture.id == *id);
+        if !present {
+            commands.entity(sdf.entity).despawn();
+        }
+        present
+    });
+
+    for texture in textures {
+        if sdfs.atlases.contains_key(&texture.id) {
+            continue;
+        }
+
+        let output = images.add(placeholder_image(jfa_format.texture_format()));
+        let entity = commands
+            .spawn()
+            .insert(JfaRequest::new(
+                texture.clone(),
+                output.clone(),
+                sdfs.max_dist,
+            ))
+            .id();
+        sdfs.atlases.insert(texture.id, GlyphSdf { entity, output });
+    }
+}
@@ -1,19 +1,52 @@
+//! Render graph labels for ordering outlines relative to other passes.
+//!
+//! Outlines are drawn by the [`OutlineDriverNode`], which the plugin adds to
+//! the core 3D graph after [`core_3d::graph::node::MAIN_PASS`]. Other
+//! post-processing passes which draw to the camera target should be ordered
+//! relative to this node, e.g. using [`run_before_outline`] or
+//! [`run_after_outline`], so that the order in which the effects are applied
+//! doesn't depend on graph traversal order.
+//!
+//! # Custom graphs
+//!
+//! The passes making up the outline sub-graph are public, so that render
+//! graphs other than the core 3D graph, such as deferred pipelines or custom
+//! post-processing stacks, can run them. [`build_outline_graph`] builds the
+//! whole sub-graph, to be run by an [`OutlineDriverNode`] or directly with
+//! the view entity as input. The [`MeshMaskNode`], [`JfaInitNode`],
+//! [`JfaNode`] and [`OutlineNode`] can also be wired individually, connecting
+//! the slots named by their associated constants as
+//! [`build_outline_graph`] does. Without a [`MaskBoundsNode`] following the
+//! mask pass, scissor rectangles only cover the `Aabb`s of outlined entities,
+//! as described in [`OutlineSettings::set_scissor`](crate::OutlineSettings::set_scissor).
+//!
+//! The nodes draw using the outline resources of the view, so they only draw
+//! views with a [`CameraOutline`](crate::CameraOutline), and the
+//! [`OutlinePlugin`](crate::OutlinePlugin) must still be added to prepare
+//! those resources.
+
 use bevy::{
+    core_pipeline::core_3d,
     prelude::*,
     render::{
         render_graph::{
-            Node, NodeRunError, RenderGraph, RenderGraphContext, RenderGraphError, SlotInfo,
-            SlotType,
+            Node, NodeLabel, NodeRunError, RenderGraph, RenderGraphContext, RenderGraphError,
+            SlotInfo, SlotType,
         },
-        render_resource::TextureFormat,
         renderer::RenderContext,
-        texture::BevyDefault,
     },
 };
 
-use crate::{jfa::JfaNode, jfa_init::JfaInitNode, mask::MeshMaskNode, outline::OutlineNode};
+use crate::{resources::ViewOutlineResources, textures::OutlineTexturesCopyNode};
+
+pub use crate::{
+    jfa::JfaNode, jfa_init::JfaInitNode, mask::MeshMaskNode, mask_bounds::MaskBoundsNode,
+    outline::OutlineNode,
+};
 
-pub(crate) mod outline {
+/// Labels for the outline sub-graph, which is run once per outlined view by
+/// the [`OutlineDriverNode`].
+pub mod outline {
     pub const NAME: &str = "outline_graph";
 
     pub mod input {
@@ -22,12 +55,18 @@ pub(crate) mod outline {
 
     pub mod node {
         pub const MASK_PASS: &str = "mask_pass";
+        pub const MASK_BOUNDS: &str = "mask_bounds";
         pub const JFA_INIT_PASS: &str = "jfa_init_pass";
         pub const JFA_PASS: &str = "jfa_pass";
         pub const OUTLINE_PASS: &str = "outline_pass";
+        pub const TEXTURES_COPY: &str = "textures_copy";
     }
 }
 
+/// Render graph node which draws outlines for a view.
+///
+/// This node is added to the core 3D graph under [`OutlineDriverNode::NAME`],
+/// and to the core 2D graph when the `sprite` feature is enabled.
 pub struct OutlineDriverNode;
 
 impl OutlineDriverNode {
@@ -40,13 +79,9 @@ impl Node for OutlineDriverNode {
         &self,
         graph: &mut RenderGraphContext,
         _render_context: &mut RenderContext,
-        _world: &World,
+        world: &World,
     ) -> Result<(), NodeRunError> {
-        let view_ent = graph.get_input_entity(Self::INPUT_VIEW)?;
-
-        graph.run_sub_graph(outline::NAME, vec![view_ent.into()])?;
-
-        Ok(())
+        run_outline_graph(graph, world, Self::INPUT_VIEW, outline::NAME)
     }
 
     fn input(&self) -> Vec<SlotInfo> {
@@ -57,8 +92,70 @@ impl Node for OutlineDriverNode {
     }
 }
 
-/// Builds the render graph for applying the JFA outline.
-pub fn outline(render_app: &mut App) -> Result<RenderGraph, RenderGraphError> {
+/// Runs the outline sub-graph `graph_name` for the view in the input slot
+/// `input_view`, if the view has outline resources.
+pub(crate) fn run_outline_graph(
+    graph: &mut RenderGraphContext,
+    world: &World,
+    input_view: &'static str,
+    graph_name: &'static str,
+) -> Result<(), NodeRunError> {
+    let view_ent = graph.get_input_entity(input_view)?;
+
+    // Views without outlines have no intermediate textures to draw into.
+    if world.get::<ViewOutlineResources>(view_ent).is_none() {
+        return Ok(());
+    }
+
+    graph.run_sub_graph(graph_name, vec![view_ent.into()])?;
+
+    Ok(())
+}
+
+/// Orders `node` in the core 3D graph to run before outlines are drawn.
+///
+/// Both the outline plugin and `node` must already have been added to the
+/// graph.
+pub fn run_before_outline(
+    graph: &mut RenderGraph,
+    node: impl Into<NodeLabel>,
+) -> Result<(), RenderGraphError> {
+    graph
+        .get_sub_graph_mut(core_3d::graph::NAME)
+        .ok_or_else(|| RenderGraphError::InvalidNode(core_3d::graph::NAME.into()))?
+        .add_node_edge(node, OutlineDriverNode::NAME)
+}
+
+/// Orders `node` in the core 3D graph to run after outlines are drawn.
+///
+/// Both the outline plugin and `node` must already have been added to the
+/// graph.
+pub fn run_after_outline(
+    graph: &mut RenderGraph,
+    node: impl Into<NodeLabel>,
+) -> Result<(), RenderGraphError> {
+    graph
+        .get_sub_graph_mut(core_3d::graph::NAME)
+        .ok_or_else(|| RenderGraphError::InvalidNode(core_3d::graph::NAME.into()))?
+        .add_node_edge(OutlineDriverNode::NAME, node)
+}
+
+/// Builds the outline sub-graph, whose nodes are named in [`outline::node`].
+///
+/// `world` is the render world. The graph takes the view entity as its only
+/// input, named [`outline::input::VIEW_ENTITY`].
+pub fn build_outline_graph(world: &mut World) -> Result<RenderGraph, RenderGraphError> {
+    let mask_node = MeshMaskNode::new(world);
+    build_outline_graph_with_mask(world, mask_node)
+}
+
+/// Builds the outline sub-graph, using `mask_node` to draw the mask.
+///
+/// The mask node must have the same slots as the [`MeshMaskNode`].
+pub fn build_outline_graph_with_mask(
+    world: &mut World,
+    mask_node: impl Node,
+) -> Result<RenderGraph, RenderGraphError> {
     let mut graph = RenderGraph::default();
 
     let input_node_id = graph.set_input(vec![SlotInfo {
@@ -67,22 +164,19 @@ pub fn outline(render_app: &mut App) -> Result<RenderGraph, RenderGraphError> {
     }]);
 
     // Graph order:
-    // 1. Mask
+    // 1. Mask, and the reduction of its bounds
     // 2. JFA Init
     // 3. JFA
-    // 4. Outline
+    // 4. Outline, and copies of the textures
 
-    let mask_node = MeshMaskNode::new(&mut render_app.world);
-    let jfa_node = JfaNode::from_world(&mut render_app.world);
-    // TODO: BevyDefault for surface texture format is an anti-pattern;
-    // the target texture format should be queried from the window when
-    // Bevy exposes that functionality.
-    let outline_node = OutlineNode::new(&mut render_app.world, TextureFormat::bevy_default());
+    let outline_node = OutlineNode::new(world);
 
     graph.add_node(outline::node::MASK_PASS, mask_node);
-    graph.add_node(outline::node::JFA_INIT_PASS, JfaInitNode);
-    graph.add_node(outline::node::JFA_PASS, jfa_node);
+    graph.add_node(outline::node::MASK_BOUNDS, MaskBoundsNode);
+    graph.add_node(outline::node::JFA_INIT_PASS, JfaInitNode::new(world));
+    graph.add_node(outline::node::JFA_PASS, JfaNode::new(world));
     graph.add_node(outline::node::OUTLINE_PASS, outline_node);
+    graph.add_node(outline::node::TEXTURES_COPY, OutlineTexturesCopyNode);
 
     // Input -> Mask
     graph.add_slot_edge(
@@ -92,6 +186,25 @@ pub fn outline(render_app: &mut App) -> Result<RenderGraph, RenderGraphError> {
         MeshMaskNode::IN_VIEW,
     )?;
 
+    // Input -> Mask bounds
+    graph.add_slot_edge(
+        input_node_id,
+        outline::input::VIEW_ENTITY,
+        outline::node::MASK_BOUNDS,
+        MaskBoundsNode::IN_VIEW,
+    )?;
+
+    // Mask -> Mask bounds
+    graph.add_node_edge(outline::node::MASK_PASS, outline::node::MASK_BOUNDS)?;
+
+    // Input -> JFA Init
+    graph.add_slot_edge(
+        input_node_id,
+        outline::input::VIEW_ENTITY,
+        outline::node::JFA_INIT_PASS,
+        JfaInitNode::IN_VIEW,
+    )?;
+
     // Mask -> JFA Init
     graph.add_slot_edge(
         outline::node::MASK_PASS,
@@ -132,5 +245,16 @@ pub fn outline(render_app: &mut App) -> Result<RenderGraph, RenderGraphError> {
         OutlineNode::IN_JFA,
     )?;
 
+    // Input -> Textures copy
+    graph.add_slot_edge(
+        input_node_id,
+        outline::input::VIEW_ENTITY,
+        outline::node::TEXTURES_COPY,
+        OutlineTexturesCopyNode::IN_VIEW,
+    )?;
+
+    // JFA -> Textures copy
+    graph.add_node_edge(outline::node::JFA_PASS, outline::node::TEXTURES_COPY)?;
+
     Ok(graph)
 }
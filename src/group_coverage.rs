@@ -0,0 +1,379 @@
+use std::sync::{
+    atomic::{AtomicU32, AtomicU8, Ordering},
+    Arc, Mutex,
+};
+
+use bevy::{
+    core_pipeline::core_3d,
+    prelude::*,
+    render::{
+        render_graph::{Node, NodeRunError, RenderGraph, RenderGraphContext, SlotInfo, SlotType},
+        render_resource::{
+            BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor,
+            BindGroupLayoutEntry, BindingResource, BindingType, Buffer, BufferBindingType,
+            BufferDescriptor, BufferUsages, CachedComputePipelineId, ComputePassDescriptor,
+            ComputePipelineDescriptor, MapMode, PipelineCache, ShaderStages, TextureSampleType,
+            TextureViewDimension,
+        },
+        renderer::{RenderContext, RenderDevice},
+        RenderApp, RenderStage,
+    },
+    utils::HashMap,
+};
+
+use crate::{
+    graph, outline::OutlineViewUniform, resources::ViewOutlineResources,
+    GROUP_COVERAGE_SHADER_HANDLE,
+};
+
+// One counter per possible `OutlineGroup`.
+const GROUP_COUNT: usize = 256;
+const COUNTS_SIZE: u64 = (GROUP_COUNT * std::mem::size_of::<u32>()) as u64;
+
+/// Plugin for measuring how much of each camera's view is covered by each
+/// [`OutlineGroup`](crate::OutlineGroup).
+///
+/// This adds an [`OutlineGroupCoverage`] resource which is updated with the
+/// number of pixels covered by each group, counted on the GPU from the mask
+/// of each outlined view. This can drive gameplay logic, such as zooming the
+/// camera until a highlighted objective is prominent, or scaling UI elements.
+///
+/// The counts are read back asynchronously, so they lag a few frames behind
+/// the rendered image. Only entities which are visible to the camera and
+/// drawn into its mask are counted; entities without an `OutlineGroup` are
+/// counted in group 0.
+///
+/// This must be added after the [`OutlinePlugin`](crate::OutlinePlugin) or
+/// [`MinimalOutlinePlugin`](crate::MinimalOutlinePlugin).
+#[derive(Default)]
+pub struct OutlineGroupCoveragePlugin;
+
+impl Plugin for OutlineGroupCoveragePlugin {
+    fn build(&self, app: &mut App) {
+        let coverage = OutlineGroupCoverage::default();
+
+        let mut shaders = app.world.resource_mut::<Assets<Shader>>();
+        shaders.set_untracked(
+            GROUP_COVERAGE_SHADER_HANDLE,
+            Shader::from_wgsl(include_str!("shaders/group_coverage.wgsl")),
+        );
+
+        app.insert_resource(coverage.clone());
+
+        let render_app = match app.get_sub_app_mut(RenderApp) {
+            Ok(r) => r,
+            Err(_) => return,
+        };
+
+        render_app
+            .insert_resource(coverage)
+            .init_resource::<GroupCoveragePipeline>()
+            .init_resource::<GroupCoverageBuffers>()
+            .add_system_to_stage(RenderStage::Prepare, prepare_group_coverage_buffers)
+            .add_system_to_stage(RenderStage::Cleanup, map_group_coverage_buffers);
+
+        let mut root_graph = render_app.world.resource_mut::<RenderGraph>();
+        let outline_graph = root_graph
+            .get_sub_graph_mut(core_3d::graph::NAME)
+            .and_then(|draw_3d| draw_3d.get_sub_graph_mut(graph::outline::NAME))
+            .unwrap();
+        let input_node_id = outline_graph.input_node().unwrap().id;
+        outline_graph.add_node(GroupCoverageNode::NAME, GroupCoverageNode);
+        outline_graph
+            .add_slot_edge(
+                input_node_id,
+                graph::outline::input::VIEW_ENTITY,
+                GroupCoverageNode::NAME,
+                GroupCoverageNode::IN_VIEW,
+            )
+            .unwrap();
+        outline_graph
+            .add_node_edge(graph::outline::node::MASK_PASS, GroupCoverageNode::NAME)
+            .unwrap();
+    }
+}
+
+/// The screen coverage of each outline group, per camera.
+///
+/// See [`OutlineGroupCoveragePlugin`].
+#[derive(Clone, Default)]
+pub struct OutlineGroupCoverage(Arc<Mutex<HashMap<Entity, GroupCounts>>>);
+
+struct GroupCounts {
+    samples: Vec<u32>,
+    screen_pixels: u32,
+    // Mask samples per pixel.
+    samples_per_pixel: u32,
+}
+
+impl OutlineGroupCoverage {
+    /// Returns the number of pixels of `camera`'s view covered by entities in
+    /// `group`, or `None` if no measurement is available for the camera.
+    ///
+    /// Partially covered pixels contribute a fraction of a pixel.
+    pub fn pixels(&self, camera: Entity, group: u8) -> Option<f32> {
+        let views = self.0.lock().unwrap();
+        let counts = views.get(&camera)?;
+        Some(counts.samples[group as usize] as f32 / counts.samples_per_pixel as f32)
+    }
+
+    /// Returns the fraction of `camera`'s view covered by entities in `group`,
+    /// or `None` if no measurement is available for the camera.
+    pub fn fraction(&self, camera: Entity, group: u8) -> Option<f32> {
+        let views = self.0.lock().unwrap();
+        let counts = views.get(&camera)?;
+        let screen_samples = counts.screen_pixels.max(1) * counts.samples_per_pixel;
+        Some(counts.samples[group as usize] as f32 / screen_samples as f32)
+    }
+}
+
+// Indexed by whether the mask is multisampled.
+struct GroupCoveragePipeline {
+    layouts: [BindGroupLayout; 2],
+    pipeline_ids: [CachedComputePipelineId; 2],
+}
+
+impl FromWorld for GroupCoveragePipeline {
+    fn from_world(world: &mut World) -> Self {
+        let device = world.resource::<RenderDevice>();
+        let layouts = [false, true].map(|multisampled| {
+            device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+                label: Some("outline_group_coverage_bind_group_layout"),
+                entries: &[
+                    BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: ShaderStages::COMPUTE,
+                        ty: BindingType::Texture {
+                            sample_type: TextureSampleType::Uint,
+                            view_dimension: TextureViewDimension::D2,
+                            multisampled,
+                        },
+                        count: None,
+                    },
+                    BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: ShaderStages::COMPUTE,
+                        ty: BindingType::Buffer {
+                            ty: BufferBindingType::Storage { read_only: false },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+            })
+        });
+
+        let mut pipeline_cache = world.resource_mut::<PipelineCache>();
+        let pipeline_ids = [false, true].map(|multisampled| {
+            let shader_defs = match multisampled {
+                true => vec!["MASK_GROUP_MULTISAMPLED".into()],
+                false => vec![],
+            };
+            pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
+                label: Some("outline_group_coverage_pipeline".into()),
+                layout: Some(vec![layouts[multisampled as usize].clone()]),
+                shader: GROUP_COVERAGE_SHADER_HANDLE.typed::<Shader>(),
+                shader_defs,
+                entry_point: "count_groups".into(),
+            })
+        });
+
+        GroupCoveragePipeline {
+            layouts,
+            pipeline_ids,
+        }
+    }
+}
+
+// Readback states of a view's counts.
+const STATE_IDLE: u8 = 0;
+const STATE_COPIED: u8 = 1;
+const STATE_PENDING: u8 = 2;
+const STATE_MAPPED: u8 = 3;
+
+/// The buffers used to count a view's group coverage and read it back.
+struct ViewCountBuffers {
+    counts: Buffer,
+    readback: Buffer,
+    // One of the `STATE_*` constants. A new count is only started once the
+    // previous one has been read back, as the readback buffer can't be
+    // written while it is mapped.
+    state: Arc<AtomicU8>,
+    screen_pixels: AtomicU32,
+    samples_per_pixel: AtomicU32,
+}
+
+#[derive(Default)]
+struct GroupCoverageBuffers {
+    views: HashMap<Entity, ViewCountBuffers>,
+}
+
+/// Publishes finished readbacks and creates buffers for new views.
+fn prepare_group_coverage_buffers(
+    device: Res<RenderDevice>,
+    coverage: Res<OutlineGroupCoverage>,
+    mut buffers: ResMut<GroupCoverageBuffers>,
+    views: Query<Entity, With<OutlineViewUniform>>,
+) {
+    let mut published = coverage.0.lock().unwrap();
+    published.retain(|entity, _| views.get(*entity).is_ok());
+    buffers.views.retain(|entity, _| views.get(*entity).is_ok());
+
+    // Mappings finish when a later frame's commands are submitted, which
+    // invokes their callbacks.
+    for (&entity, view) in buffers.views.iter() {
+        if view.state.load(Ordering::Acquire) != STATE_MAPPED {
+            continue;
+        }
+
+        let samples = {
+            let data = view.readback.slice(..).get_mapped_range();
+            data.chunks_exact(4)
+                .map(|bytes| u32::from_ne_bytes(bytes.try_into().unwrap()))
+                .collect()
+        };
+        view.readback.unmap();
+        view.state.store(STATE_IDLE, Ordering::Release);
+
+        published.insert(
+            entity,
+            GroupCounts {
+                samples,
+                screen_pixels: view.screen_pixels.load(Ordering::Relaxed),
+                samples_per_pixel: view.samples_per_pixel.load(Ordering::Relaxed),
+            },
+        );
+    }
+
+    for entity in views.iter() {
+        buffers
+            .views
+            .entry(entity)
+            .or_insert_with(|| ViewCountBuffers {
+                counts: device.create_buffer(&BufferDescriptor {
+                    label: Some("outline_group_coverage_counts"),
+                    size: COUNTS_SIZE,
+                    usage: BufferUsages::STORAGE | BufferUsages::COPY_SRC | BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                }),
+                readback: device.create_buffer(&BufferDescriptor {
+                    label: Some("outline_group_coverage_readback"),
+                    size: COUNTS_SIZE,
+                    usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                }),
+                state: Arc::new(AtomicU8::new(STATE_IDLE)),
+                screen_pixels: AtomicU32::new(0),
+                samples_per_pixel: AtomicU32::new(1),
+            });
+    }
+}
+
+/// Starts mapping the readback buffers copied to this frame.
+///
+/// This runs after the frame's commands have been submitted.
+fn map_group_coverage_buffers(buffers: Res<GroupCoverageBuffers>) {
+    for view in buffers.views.values() {
+        if view.state.load(Ordering::Acquire) != STATE_COPIED {
+            continue;
+        }
+
+        view.state.store(STATE_PENDING, Ordering::Release);
+        let state = view.state.clone();
+        view.readback
+            .slice(..)
+            .map_async(MapMode::Read, move |result| {
+                let new_state = match result {
+                    Ok(()) => STATE_MAPPED,
+                    Err(_) => STATE_IDLE,
+                };
+                state.store(new_state, Ordering::Release);
+            });
+    }
+}
+
+/// Render graph node which counts the mask samples covered by each group.
+struct GroupCoverageNode;
+
+impl GroupCoverageNode {
+    const NAME: &'static str = "outline_group_coverage";
+    const IN_VIEW: &'static str = "view";
+}
+
+impl Node for GroupCoverageNode {
+    fn input(&self) -> Vec<SlotInfo> {
+        vec![SlotInfo::new(Self::IN_VIEW, SlotType::Entity)]
+    }
+
+    fn run(
+        &self,
+        graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let view_ent = graph.get_input_entity(Self::IN_VIEW)?;
+        let res = match world.get::<ViewOutlineResources>(view_ent) {
+            Some(r) => r,
+            None => return Ok(()),
+        };
+        let pipeline = world.resource::<GroupCoveragePipeline>();
+        let buffers = world.resource::<GroupCoverageBuffers>();
+
+        let view = match buffers.views.get(&view_ent) {
+            Some(v) if v.state.load(Ordering::Acquire) == STATE_IDLE => v,
+            _ => return Ok(()),
+        };
+
+        let pipeline_cache = world.resource::<PipelineCache>();
+        let multisampled = (res.mask_samples > 1) as usize;
+        let cached_pipeline =
+            match pipeline_cache.get_compute_pipeline(pipeline.pipeline_ids[multisampled]) {
+                Some(c) => c,
+                // Still queued.
+                None => return Ok(()),
+            };
+
+        let bind_group = render_context
+            .render_device
+            .create_bind_group(&BindGroupDescriptor {
+                label: Some("outline_group_coverage_bind_group"),
+                layout: &pipeline.layouts[multisampled],
+                entries: &[
+                    BindGroupEntry {
+                        binding: 0,
+                        resource: BindingResource::TextureView(
+                            &res.mask_group_multisample.default_view,
+                        ),
+                    },
+                    BindGroupEntry {
+                        binding: 1,
+                        resource: view.counts.as_entire_binding(),
+                    },
+                ],
+            });
+
+        let dims = res.dimensions_buffer.get();
+        let size = dims.size();
+        let screen_size = dims.screen_size();
+        view.screen_pixels
+            .store(screen_size.x * screen_size.y, Ordering::Relaxed);
+        view.samples_per_pixel
+            .store(res.mask_samples, Ordering::Relaxed);
+
+        let encoder = &mut render_context.command_encoder;
+        encoder.clear_buffer(&view.counts, 0, None);
+        {
+            let mut pass = encoder.begin_compute_pass(&ComputePassDescriptor {
+                label: Some("outline_group_coverage"),
+            });
+            pass.set_pipeline(cached_pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.dispatch_workgroups(size.x.div_ceil(8), size.y.div_ceil(8), 1);
+        }
+        encoder.copy_buffer_to_buffer(&view.counts, 0, &view.readback, 0, COUNTS_SIZE);
+        view.state.store(STATE_COPIED, Ordering::Release);
+
+        Ok(())
+    }
+}
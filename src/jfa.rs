@@ -1,60 +1,123 @@
 use bevy::{
     prelude::*,
     render::{
-        render_asset::RenderAssets,
         render_graph::{Node, NodeRunError, RenderGraphContext, SlotInfo, SlotType},
         render_phase::TrackedRenderPass,
         render_resource::{
-            BindGroup, CachedRenderPipelineId, ColorTargetState, ColorWrites, FragmentState,
-            LoadOp, MultisampleState, Operations, PipelineCache, RenderPassColorAttachment,
-            RenderPassDescriptor, RenderPipelineDescriptor, ShaderType, TextureView, VertexState,
+            BindGroup, BindGroupLayout, CachedRenderPipelineId, ColorTargetState, ColorWrites,
+            FragmentState, LoadOp, MultisampleState, Operations, PipelineCache,
+            RenderPassColorAttachment, RenderPassDescriptor, RenderPipeline,
+            RenderPipelineDescriptor, ShaderType, SpecializedRenderPipeline,
+            SpecializedRenderPipelines, TextureView, VertexState,
         },
         renderer::RenderContext,
     },
 };
 
+#[cfg(feature = "wgpu-profiler")]
+use crate::timings::OutlineProfiler;
 use crate::{
-    resources::OutlineResources, CameraOutline, OutlineStyle, FULLSCREEN_PRIMITIVE_STATE,
-    JFA_SHADER_HANDLE, JFA_TEXTURE_FORMAT,
+    jfa_init::{JfaInitPipeline, JfaInitPipelineKey},
+    mask::MaskColors,
+    outline::{view_pixel_extent, view_style},
+    resources::{OutlineResources, ViewOutlineResources},
+    reuse,
+    scissor::{self, ScissorRect},
+    JfaNoSeed, JfaTextureFormat, FULLSCREEN_PRIMITIVE_STATE, JFA_SHADER_HANDLE,
 };
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, ShaderType)]
 pub struct JumpDist {
     pub dist: u32,
+    // One of the `JFA_METRIC_*` constants in `jfa_utils.wgsl`.
+    pub metric: u32,
 }
 
+/// Framebuffer dimensions, as bound by the `outline::dimensions` shader
+/// import.
 #[derive(Copy, Clone, Debug, PartialEq, ShaderType)]
 pub struct Dimensions {
     width: f32,
     height: f32,
     inv_width: f32,
     inv_height: f32,
+    padding: f32,
 }
 
 impl Dimensions {
-    pub fn new(width: u32, height: u32) -> Dimensions {
+    /// Creates dimensions for a `width` by `height` framebuffer which is
+    /// extended by `padding` pixels on each side.
+    pub fn new(width: u32, height: u32, padding: u32) -> Dimensions {
+        let width = width + 2 * padding;
+        let height = height + 2 * padding;
+
         Dimensions {
             width: width as f32,
             height: height as f32,
             inv_width: 1.0 / width as f32,
             inv_height: 1.0 / height as f32,
+            padding: padding as f32,
         }
     }
+
+    /// Returns the size of the framebuffer, including padding.
+    pub fn size(&self) -> UVec2 {
+        UVec2::new(self.width as u32, self.height as u32)
+    }
+
+    /// Returns the size of the framebuffer, excluding padding.
+    pub fn screen_size(&self) -> UVec2 {
+        self.size() - UVec2::splat(2 * self.padding as u32)
+    }
 }
 
+/// The pipeline of the jump flood passes, specialized with a
+/// [`JfaPipelineKey`].
 pub struct JfaPipeline {
-    cached: CachedRenderPipelineId,
+    dimensions_layout: BindGroupLayout,
+    jfa_layout: BindGroupLayout,
+    no_seed: JfaNoSeed,
+    /// The pipeline for JFA textures in the app's format, as used by
+    /// `JfaRequest`s.
+    pub(crate) request_cached: CachedRenderPipelineId,
 }
 
 impl FromWorld for JfaPipeline {
     fn from_world(world: &mut World) -> Self {
         let res = world.get_resource::<OutlineResources>().unwrap();
-        let dimensions_bind_group_layout = res.dimensions_bind_group_layout.clone();
-        let jfa_bind_group_layout = res.jfa_bind_group_layout.clone();
+        let mut pipeline = JfaPipeline {
+            dimensions_layout: res.dimensions_bind_group_layout.clone(),
+            jfa_layout: res.jfa_bind_group_layout.clone(),
+            no_seed: res.jfa_no_seed,
+            request_cached: CachedRenderPipelineId::INVALID,
+        };
+
+        let descriptor = pipeline.specialize(JfaPipelineKey {
+            format: res.jfa_format,
+        });
         let mut pipeline_cache = world.get_resource_mut::<PipelineCache>().unwrap();
-        let cached = pipeline_cache.queue_render_pipeline(RenderPipelineDescriptor {
+        pipeline.request_cached = pipeline_cache.queue_render_pipeline(descriptor);
+        pipeline
+    }
+}
+
+/// Key for specializing the [`JfaPipeline`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct JfaPipelineKey {
+    /// The format of the JFA textures.
+    pub(crate) format: JfaTextureFormat,
+}
+
+impl SpecializedRenderPipeline for JfaPipeline {
+    type Key = JfaPipelineKey;
+
+    fn specialize(&self, key: Self::Key) -> RenderPipelineDescriptor {
+        RenderPipelineDescriptor {
             label: Some("outline_jfa_pipeline".into()),
-            layout: Some(vec![dimensions_bind_group_layout, jfa_bind_group_layout]),
+            layout: Some(vec![
+                self.dimensions_layout.clone(),
+                self.jfa_layout.clone(),
+            ]),
             vertex: VertexState {
                 shader: JFA_SHADER_HANDLE.typed::<Shader>(),
                 shader_defs: vec![],
@@ -63,10 +126,10 @@ impl FromWorld for JfaPipeline {
             },
             fragment: Some(FragmentState {
                 shader: JFA_SHADER_HANDLE.typed::<Shader>(),
-                shader_defs: vec![],
+                shader_defs: [key.format.shader_defs(), self.no_seed.shader_defs()].concat(),
                 entry_point: "fragment".into(),
                 targets: vec![Some(ColorTargetState {
-                    format: JFA_TEXTURE_FORMAT,
+                    format: key.format.texture_format(),
                     blend: None,
                     write_mask: ColorWrites::ALL,
                 })],
@@ -74,28 +137,73 @@ impl FromWorld for JfaPipeline {
             primitive: FULLSCREEN_PRIMITIVE_STATE,
             depth_stencil: None,
             multisample: MultisampleState::default(),
-        });
-
-        JfaPipeline { cached }
+        }
     }
 }
 
-pub struct JfaNode {
-    query: QueryState<&'static CameraOutline>,
+/// The JFA pipelines specialized for a view.
+#[derive(Component)]
+pub struct ViewJfaPipelines {
+    pub(crate) init: CachedRenderPipelineId,
+    pub(crate) jfa: CachedRenderPipelineId,
 }
 
-impl FromWorld for JfaNode {
-    fn from_world(world: &mut World) -> Self {
-        JfaNode {
-            query: QueryState::from_world(world),
-        }
+/// Specializes the JFA pipelines for the mask and JFA format of each outlined
+/// view.
+pub(crate) fn queue_view_jfa_pipelines(
+    mut commands: Commands,
+    mut pipeline_cache: ResMut<PipelineCache>,
+    mut init_pipelines: ResMut<SpecializedRenderPipelines<JfaInitPipeline>>,
+    mut jfa_pipelines: ResMut<SpecializedRenderPipelines<JfaPipeline>>,
+    init_pipeline: Res<JfaInitPipeline>,
+    jfa_pipeline: Res<JfaPipeline>,
+    views: Query<(Entity, &ViewOutlineResources, Option<&MaskColors>)>,
+) {
+    for (entity, res, mask_colors) in views.iter() {
+        let init = init_pipelines.specialize(
+            &mut pipeline_cache,
+            &init_pipeline,
+            JfaInitPipelineKey {
+                mask_colors: mask_colors.is_some(),
+                format: res.jfa_format,
+            },
+        );
+        let jfa = jfa_pipelines.specialize(
+            &mut pipeline_cache,
+            &jfa_pipeline,
+            JfaPipelineKey {
+                format: res.jfa_format,
+            },
+        );
+        commands
+            .entity(entity)
+            .insert(ViewJfaPipelines { init, jfa });
     }
 }
 
+/// Render graph node for the jump flood passes.
+#[non_exhaustive]
+pub struct JfaNode;
+
 impl JfaNode {
+    /// The view entity.
     pub const IN_VIEW: &'static str = "in_view";
+
+    /// The initialized JFA buffer, as produced by
+    /// [`JfaInitNode::OUT_JFA_INIT`](crate::graph::JfaInitNode::OUT_JFA_INIT).
     pub const IN_BASE: &'static str = "in_base";
+
+    /// The flooded JFA buffer.
+    ///
+    /// This has the same format as the initialized buffer. Each texel holds
+    /// the coordinates of the nearest seed, up to the extent of the widest
+    /// outline drawn in the view.
     pub const OUT_JUMP: &'static str = "out_jump";
+
+    /// Creates the node. `world` is the render world.
+    pub fn new(_world: &mut World) -> JfaNode {
+        JfaNode
+    }
 }
 
 impl Node for JfaNode {
@@ -110,38 +218,43 @@ impl Node for JfaNode {
         vec![SlotInfo::new(Self::OUT_JUMP, SlotType::TextureView)]
     }
 
-    fn update(&mut self, world: &mut World) {
-        self.query.update_archetypes(world);
-    }
-
     fn run(
         &self,
         graph: &mut RenderGraphContext,
         render_context: &mut RenderContext,
         world: &World,
     ) -> Result<(), NodeRunError> {
+        let view_ent = graph.get_input_entity(Self::IN_VIEW)?;
         let res = world.resource::<OutlineResources>();
+        let view_res = world.get::<ViewOutlineResources>(view_ent).unwrap();
         graph
-            .set_output(Self::OUT_JUMP, res.jfa_final_output.default_view.clone())
+            .set_output(
+                Self::OUT_JUMP,
+                view_res.jfa_final_output.default_view.clone(),
+            )
             .unwrap();
 
-        let styles = world.resource::<RenderAssets<OutlineStyle>>();
-        let width = match self
-            .query
-            .get_manual(world, graph.get_input_entity(Self::IN_VIEW)?)
-        {
-            Ok(outline) => {
-                let dims = res.dimensions_buffer.get();
-                dims.width
-                    .max(dims.height)
-                    .min(styles.get(&outline.style).unwrap().params.weight.ceil())
-            }
-            Err(_) => return Ok(()),
-        };
+        // The distance field of the previous frame is still valid.
+        if reuse::is_reused(world, view_ent) {
+            return Ok(());
+        }
+
+        // The flood must reach far enough for the widest layer.
+        let (extent, metric) =
+            match view_style(world, view_ent).zip(view_pixel_extent(world, view_ent)) {
+                Some((style, extent)) => (extent, style.params.metric),
+                None => return Ok(()),
+            };
+        let dims = view_res.dimensions_buffer.get();
+        let width = dims.width.max(dims.height).min(extent.ceil());
+        let region = scissor::view_outline_region(world, view_ent);
 
-        let pipeline = world.get_resource::<JfaPipeline>().unwrap();
+        let pipelines = match world.get::<ViewJfaPipelines>(view_ent) {
+            Some(p) => p,
+            None => return Ok(()),
+        };
         let pipeline_cache = world.get_resource::<PipelineCache>().unwrap();
-        let cached_pipeline = match pipeline_cache.get_render_pipeline(pipeline.cached) {
+        let cached_pipeline = match pipeline_cache.get_render_pipeline(pipelines.jfa) {
             Some(c) => c,
             // Still queued.
             None => {
@@ -149,69 +262,147 @@ impl Node for JfaNode {
             }
         };
 
-        // The half-width of the JFA region is 2^(max_exp + 1) - 1.
-        //
-        // weight < 2^(max_exp + 1) - 1
-        // weight + 1 < 2^(max_exp + 1)
-        // log2(weight + 1) < max_exp + 1
-        // max_exp > log2(weight + 1) - 1
+        let targets = JfaTargets {
+            format: view_res.jfa_format,
+            dimensions_bind_group: &view_res.dimensions_bind_group,
+            primary: &view_res.jfa_primary_output.default_view,
+            from_primary: &view_res.jfa_from_primary_bind_group,
+            secondary: &view_res.jfa_secondary_output.default_view,
+            from_secondary: &view_res.jfa_from_secondary_bind_group,
+            output: &view_res.jfa_final_output.default_view,
+            scissor: region.map(|region| {
+                (
+                    scissor::framebuffer_scissor(view_res, region, view_res.jfa_size),
+                    scissor::framebuffer_scissor(view_res, region, dims.size()),
+                )
+            }),
+            #[cfg(feature = "wgpu-profiler")]
+            profiler: world
+                .get_resource::<OutlineProfiler>()
+                .map(|profiler| (profiler, view_ent)),
+        };
+        run_jfa_passes(
+            render_context,
+            res,
+            cached_pipeline,
+            &targets,
+            width,
+            metric,
+        );
 
-        let max_exp = width.log2() as usize;
-        //let max_exp = width.log2().ceil() as usize;
-        for it in 0..=max_exp {
-            let exp = max_exp - it;
+        Ok(())
+    }
+}
 
-            let target: &TextureView;
-            let src: &BindGroup;
+/// The textures and bind groups used by the jump flood passes.
+pub(crate) struct JfaTargets<'a> {
+    // The format of the JFA textures.
+    pub(crate) format: JfaTextureFormat,
+    pub(crate) dimensions_bind_group: &'a BindGroup,
+    // The initialized JFA texture, which is overwritten by later passes.
+    pub(crate) primary: &'a TextureView,
+    // Bind group sampling `primary`.
+    pub(crate) from_primary: &'a BindGroup,
+    pub(crate) secondary: &'a TextureView,
+    // Bind group sampling `secondary`.
+    pub(crate) from_secondary: &'a BindGroup,
+    // Target of the final pass.
+    pub(crate) output: &'a TextureView,
+    // Scissor rectangles of `primary` and `secondary`, and of `output`, if
+    // the passes are restricted to part of the texture.
+    pub(crate) scissor: Option<(ScissorRect, ScissorRect)>,
+    // The profiler timing each pass, and the view timed, if the
+    // `OutlineTimingsPlugin` is added.
+    #[cfg(feature = "wgpu-profiler")]
+    pub(crate) profiler: Option<(&'a OutlineProfiler, Entity)>,
+}
 
-            if it % 2 == 1 {
-                if it == max_exp {
-                    target = &res.jfa_final_output.default_view;
-                } else {
-                    target = &res.jfa_primary_output.default_view;
-                }
-                src = &res.jfa_from_secondary_bind_group;
+/// Runs the jump flood passes on the initialized texture in `targets`, such
+/// that seeds are found up to `width` pixels away.
+pub(crate) fn run_jfa_passes(
+    render_context: &mut RenderContext,
+    res: &OutlineResources,
+    pipeline: &RenderPipeline,
+    targets: &JfaTargets,
+    width: f32,
+    metric: u32,
+) {
+    // The half-width of the JFA region is 2^(max_exp + 1) - 1.
+    //
+    // weight < 2^(max_exp + 1) - 1
+    // weight + 1 < 2^(max_exp + 1)
+    // log2(weight + 1) < max_exp + 1
+    // max_exp > log2(weight + 1) - 1
+
+    let max_exp = width.log2() as usize;
+    //let max_exp = width.log2().ceil() as usize;
+    for it in 0..=max_exp {
+        let exp = max_exp - it;
+
+        let target: &TextureView;
+        let src: &BindGroup;
+
+        if it % 2 == 1 {
+            if it == max_exp {
+                target = targets.output;
+            } else {
+                target = targets.primary;
+            }
+            src = targets.from_secondary;
+        } else {
+            if it == max_exp {
+                target = targets.output;
             } else {
+                target = targets.secondary;
+            }
+            src = targets.from_primary;
+        }
+        let scissor = targets.scissor.map(
+            |(intermediate, output)| {
                 if it == max_exp {
-                    target = &res.jfa_final_output.default_view;
+                    output
                 } else {
-                    target = &res.jfa_secondary_output.default_view;
+                    intermediate
                 }
-                src = &res.jfa_from_primary_bind_group;
-            }
+            },
+        );
 
-            let attachment = RenderPassColorAttachment {
-                view: target,
-                resolve_target: None,
-                ops: Operations {
-                    // TODO: ideally, this would be the equivalent of DONT_CARE, but wgpu doesn't expose that.
-                    load: LoadOp::Clear(
-                        Color::RgbaLinear {
-                            red: -1.0,
-                            green: -1.0,
-                            blue: 0.0,
-                            alpha: 0.0,
-                        }
-                        .into(),
-                    ),
-                    store: true,
-                },
-            };
-            let render_pass =
-                render_context
-                    .command_encoder
-                    .begin_render_pass(&RenderPassDescriptor {
-                        label: Some("outline_jfa"),
-                        color_attachments: &[Some(attachment)],
-                        depth_stencil_attachment: None,
-                    });
-            let mut tracked_pass = TrackedRenderPass::new(render_pass);
-            tracked_pass.set_render_pipeline(cached_pipeline);
-            tracked_pass.set_bind_group(0, &res.dimensions_bind_group, &[]);
-            tracked_pass.set_bind_group(1, src, &[res.jfa_distance_offsets[exp]]);
-            tracked_pass.draw(0..3, 0..1);
+        #[cfg(feature = "wgpu-profiler")]
+        if let Some((profiler, view)) = targets.profiler {
+            profiler.begin_jfa_pass(view, &mut render_context.command_encoder);
         }
 
-        Ok(())
+        let attachment = RenderPassColorAttachment {
+            view: target,
+            resolve_target: None,
+            ops: Operations {
+                // TODO: ideally, this would be the equivalent of DONT_CARE, but wgpu doesn't expose that.
+                load: LoadOp::Clear(targets.format.clear_color(res.jfa_no_seed).into()),
+                store: true,
+            },
+        };
+        let render_pass = render_context
+            .command_encoder
+            .begin_render_pass(&RenderPassDescriptor {
+                label: Some("outline_jfa"),
+                color_attachments: &[Some(attachment)],
+                depth_stencil_attachment: None,
+            });
+        let mut tracked_pass = TrackedRenderPass::new(render_pass);
+        // Texels outside of the scissor rectangle are cleared to no seed,
+        // which is correct as they lie beyond the flood distance.
+        if let Some(scissor) = scissor {
+            scissor.apply(&mut tracked_pass);
+        }
+        tracked_pass.set_render_pipeline(pipeline);
+        tracked_pass.set_bind_group(0, targets.dimensions_bind_group, &[]);
+        tracked_pass.set_bind_group(1, src, &[res.jfa_distance_offset(metric, exp)]);
+        tracked_pass.draw(0..3, 0..1);
+
+        #[cfg(feature = "wgpu-profiler")]
+        if let Some((profiler, _)) = targets.profiler {
+            drop(tracked_pass);
+            profiler.end_jfa_pass(&mut render_context.command_encoder);
+        }
     }
 }
@@ -4,31 +4,75 @@ use bevy::{
         render_graph::{Node, NodeRunError, RenderGraphContext, SlotInfo, SlotType},
         render_phase::TrackedRenderPass,
         render_resource::{
-            CachedRenderPipelineId, ColorTargetState, ColorWrites, Face, FragmentState, FrontFace,
-            LoadOp, MultisampleState, Operations, PipelineCache, PolygonMode, PrimitiveState,
-            PrimitiveTopology, RenderPassColorAttachment, RenderPassDescriptor,
-            RenderPipelineDescriptor, VertexState,
+            BindGroup, BindGroupLayout, CachedRenderPipelineId, ColorTargetState, ColorWrites,
+            Face, FragmentState, FrontFace, LoadOp, MultisampleState, Operations, PipelineCache,
+            PolygonMode, PrimitiveState, PrimitiveTopology, RenderPassColorAttachment,
+            RenderPassDescriptor, RenderPipeline, RenderPipelineDescriptor,
+            SpecializedRenderPipeline, TextureView, VertexState,
         },
         renderer::RenderContext,
     },
 };
 
-use crate::{resources::OutlineResources, JFA_INIT_SHADER_HANDLE, JFA_TEXTURE_FORMAT};
+use crate::{
+    jfa, mask,
+    resources::{OutlineResources, ViewOutlineResources},
+    reuse,
+    scissor::{self, ScissorRect},
+    JfaNoSeed, JfaTextureFormat, JFA_INIT_SHADER_HANDLE,
+};
 
+/// The pipeline of the JFA initialization pass, specialized with a
+/// [`JfaInitPipelineKey`].
 pub struct JfaInitPipeline {
-    cached: CachedRenderPipelineId,
+    dims_layout: BindGroupLayout,
+    init_layout: BindGroupLayout,
+    no_seed: JfaNoSeed,
+    /// The pipeline for seed textures holding their coverage in alpha and
+    /// JFA textures in the app's format, as used by `JfaRequest`s.
+    pub(crate) request_cached: CachedRenderPipelineId,
 }
 
 impl FromWorld for JfaInitPipeline {
     fn from_world(world: &mut World) -> Self {
         let res = world.resource::<OutlineResources>();
-        let dims_layout = res.dimensions_bind_group_layout.clone();
-        let init_layout = res.jfa_init_bind_group_layout.clone();
+        let mut pipeline = JfaInitPipeline {
+            dims_layout: res.dimensions_bind_group_layout.clone(),
+            init_layout: res.jfa_init_bind_group_layout.clone(),
+            no_seed: res.jfa_no_seed,
+            request_cached: CachedRenderPipelineId::INVALID,
+        };
 
+        let descriptor = pipeline.specialize(JfaInitPipelineKey {
+            mask_colors: true,
+            format: res.jfa_format,
+        });
         let mut pipeline_cache = world.get_resource_mut::<PipelineCache>().unwrap();
-        let cached = pipeline_cache.queue_render_pipeline(RenderPipelineDescriptor {
+        pipeline.request_cached = pipeline_cache.queue_render_pipeline(descriptor);
+        pipeline
+    }
+}
+
+/// Key for specializing the [`JfaInitPipeline`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct JfaInitPipelineKey {
+    /// Whether the mask holds colors, with the coverage in alpha, rather than
+    /// only the coverage in the R component.
+    pub(crate) mask_colors: bool,
+    /// The format of the JFA texture.
+    pub(crate) format: JfaTextureFormat,
+}
+
+impl SpecializedRenderPipeline for JfaInitPipeline {
+    type Key = JfaInitPipelineKey;
+
+    fn specialize(&self, key: Self::Key) -> RenderPipelineDescriptor {
+        let mut shader_defs = [key.format.shader_defs(), self.no_seed.shader_defs()].concat();
+        shader_defs.extend(mask::mask_shader_defs(key.mask_colors));
+
+        RenderPipelineDescriptor {
             label: Some("outline_jfa_init_pipeline".into()),
-            layout: Some(vec![dims_layout, init_layout]),
+            layout: Some(vec![self.dims_layout.clone(), self.init_layout.clone()]),
             vertex: VertexState {
                 shader: JFA_INIT_SHADER_HANDLE.typed::<Shader>(),
                 shader_defs: vec![],
@@ -48,43 +92,54 @@ impl FromWorld for JfaInitPipeline {
             multisample: MultisampleState::default(),
             fragment: Some(FragmentState {
                 shader: JFA_INIT_SHADER_HANDLE.typed::<Shader>(),
-                shader_defs: vec![],
+                shader_defs,
                 entry_point: "fragment".into(),
                 targets: vec![Some(ColorTargetState {
-                    format: JFA_TEXTURE_FORMAT,
+                    format: key.format.texture_format(),
                     blend: None,
                     write_mask: ColorWrites::ALL,
                 })],
             }),
-        });
-
-        JfaInitPipeline { cached }
+        }
     }
 }
 
 /// Render graph node for the JFA initialization pass.
+#[non_exhaustive]
 pub struct JfaInitNode;
 
 impl JfaInitNode {
-    /// The input stencil buffer.
+    /// The view entity.
+    pub const IN_VIEW: &'static str = "in_view";
+
+    /// The mask, as produced by [`MeshMaskNode::OUT_MASK`].
+    ///
+    /// The mask is read from the view's outline resources, so this slot only
+    /// orders the pass after the mask is drawn.
     ///
-    /// This should have the format `TextureFormat::Depth24PlusStencil8`.
-    /// Fragments in the JFA initialization pass will pass the stencil test if
-    /// the corresponding stencil buffer value is 255, and fail otherwise.
-    /// The depth aspect is ignored.
+    /// [`MeshMaskNode::OUT_MASK`]: crate::graph::MeshMaskNode::OUT_MASK
     pub const IN_MASK: &'static str = "in_stencil";
 
     /// The produced initialized JFA buffer.
     ///
-    /// This has the format `bevy_jfa::JFA_TEXTURE_FORMAT`. Fragments that pass
-    /// the stencil test are assigned their framebuffer coordinates. Fragments
-    /// that fail the stencil test are assigned a value of (-1, -1).
+    /// This has the app's [`JfaTextureFormat`](crate::JfaTextureFormat).
+    /// Fragments that pass the stencil test are assigned their framebuffer
+    /// coordinates. Fragments that fail the stencil test are assigned the
+    /// app's `bevy_jfa::JfaNoSeed` sentinel.
     pub const OUT_JFA_INIT: &'static str = "out_jfa_init";
+
+    /// Creates the node. `world` is the render world.
+    pub fn new(_world: &mut World) -> JfaInitNode {
+        JfaInitNode
+    }
 }
 
 impl Node for JfaInitNode {
     fn input(&self) -> Vec<SlotInfo> {
-        vec![SlotInfo::new(Self::IN_MASK, SlotType::TextureView)]
+        vec![
+            SlotInfo::new(Self::IN_VIEW, SlotType::Entity),
+            SlotInfo::new(Self::IN_MASK, SlotType::TextureView),
+        ]
     }
 
     fn output(&self) -> Vec<SlotInfo> {
@@ -97,7 +152,8 @@ impl Node for JfaInitNode {
         render_context: &mut RenderContext,
         world: &World,
     ) -> Result<(), NodeRunError> {
-        let res = world.get_resource::<OutlineResources>().unwrap();
+        let view_ent = graph.get_input_entity(Self::IN_VIEW)?;
+        let res = world.get::<ViewOutlineResources>(view_ent).unwrap();
         graph
             .set_output(
                 Self::OUT_JFA_INIT,
@@ -105,9 +161,16 @@ impl Node for JfaInitNode {
             )
             .unwrap();
 
-        let pipeline = world.get_resource::<JfaInitPipeline>().unwrap();
+        if reuse::is_reused(world, view_ent) {
+            return Ok(());
+        }
+
+        let pipelines = match world.get::<jfa::ViewJfaPipelines>(view_ent) {
+            Some(p) => p,
+            None => return Ok(()),
+        };
         let pipeline_cache = world.get_resource::<PipelineCache>().unwrap();
-        let cached_pipeline = match pipeline_cache.get_render_pipeline(pipeline.cached) {
+        let cached_pipeline = match pipeline_cache.get_render_pipeline(pipelines.init) {
             Some(c) => c,
             // Still queued.
             None => {
@@ -115,34 +178,58 @@ impl Node for JfaInitNode {
             }
         };
 
-        let render_pass = render_context
-            .command_encoder
-            .begin_render_pass(&RenderPassDescriptor {
-                label: Some("outline_jfa_init"),
-                color_attachments: &[Some(RenderPassColorAttachment {
-                    view: &res.jfa_primary_output.default_view,
-                    resolve_target: None,
-                    ops: Operations {
-                        load: LoadOp::Clear(
-                            Color::RgbaLinear {
-                                red: -1.0,
-                                green: -1.0,
-                                blue: 0.0,
-                                alpha: 0.0,
-                            }
-                            .into(),
-                        ),
-                        store: true,
-                    },
-                })],
-                depth_stencil_attachment: None,
-            });
-        let mut tracked_pass = TrackedRenderPass::new(render_pass);
-        tracked_pass.set_render_pipeline(cached_pipeline);
-        tracked_pass.set_bind_group(0, &res.dimensions_bind_group, &[]);
-        tracked_pass.set_bind_group(1, &res.jfa_init_bind_group, &[]);
-        tracked_pass.draw(0..3, 0..1);
+        let scissor = scissor::view_outline_region(world, view_ent)
+            .map(|region| scissor::framebuffer_scissor(res, region, res.jfa_size));
+        run_jfa_init_pass(
+            render_context,
+            world.resource::<OutlineResources>(),
+            res.jfa_format,
+            cached_pipeline,
+            &res.dimensions_bind_group,
+            &res.jfa_init_bind_group,
+            &res.jfa_primary_output.default_view,
+            scissor,
+        );
 
         Ok(())
     }
 }
+
+/// Initializes the JFA texture `target`, in `format`, from the mask bound by
+/// `init_bind_group`, only within `scissor` if given.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn run_jfa_init_pass(
+    render_context: &mut RenderContext,
+    res: &OutlineResources,
+    format: JfaTextureFormat,
+    pipeline: &RenderPipeline,
+    dimensions_bind_group: &BindGroup,
+    init_bind_group: &BindGroup,
+    target: &TextureView,
+    scissor: Option<ScissorRect>,
+) {
+    let render_pass = render_context
+        .command_encoder
+        .begin_render_pass(&RenderPassDescriptor {
+            label: Some("outline_jfa_init"),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: Operations {
+                    load: LoadOp::Clear(format.clear_color(res.jfa_no_seed).into()),
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+    let mut tracked_pass = TrackedRenderPass::new(render_pass);
+    // The target is cleared in full, so texels outside of the scissor
+    // rectangle have no seed.
+    if let Some(scissor) = scissor {
+        scissor.apply(&mut tracked_pass);
+    }
+    tracked_pass.set_render_pipeline(pipeline);
+    tracked_pass.set_bind_group(0, dimensions_bind_group, &[]);
+    tracked_pass.set_bind_group(1, init_bind_group, &[]);
+    tracked_pass.draw(0..3, 0..1);
+}
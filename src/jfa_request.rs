@@ -0,0 +1,338 @@
+use bevy::{
+    prelude::*,
+    render::{
+        main_graph,
+        render_asset::RenderAssets,
+        render_graph::{Node, NodeRunError, RenderGraph, RenderGraphContext},
+        render_resource::{
+            BindGroup, BindGroupDescriptor, BindGroupEntry, BindingResource, Extent3d,
+            PipelineCache, RenderPipeline, TextureDescriptor, TextureDimension, TextureUsages,
+            UniformBuffer,
+        },
+        renderer::{RenderContext, RenderDevice, RenderQueue},
+        texture::{CachedTexture, TextureCache},
+        Extract, RenderApp, RenderStage,
+    },
+};
+
+use crate::{
+    jfa::{self, JfaPipeline, JfaTargets},
+    jfa_init::{self, JfaInitPipeline},
+    resources::{OutlineResources, MAX_JFA_EXTENT},
+    DistanceMetric, JfaTextureFormat,
+};
+
+/// Plugin for running the jump flood algorithm on arbitrary seed textures.
+///
+/// Each entity with a [`JfaRequest`] has the distance field of its seed
+/// texture computed into its output image every frame, before any camera is
+/// rendered. This makes the passes used for outlines available for other
+/// effects, such as fog of war or glyph rendering, without a camera.
+///
+/// This must be added after the [`OutlinePlugin`](crate::OutlinePlugin) or
+/// [`MinimalOutlinePlugin`](crate::MinimalOutlinePlugin), which provide the
+/// shaders and pipelines.
+#[derive(Default)]
+pub struct JfaPlugin;
+
+impl Plugin for JfaPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_to_stage(CoreStage::PostUpdate, resize_jfa_outputs);
+
+        let render_app = match app.get_sub_app_mut(RenderApp) {
+            Ok(r) => r,
+            Err(_) => return,
+        };
+
+        render_app
+            .add_system_to_stage(RenderStage::Extract, extract_jfa_requests)
+            .add_system_to_stage(RenderStage::Queue, queue_jfa_requests);
+
+        let node = JfaRequestNode::new(&mut render_app.world);
+        let mut root_graph = render_app.world.resource_mut::<RenderGraph>();
+        root_graph.add_node(JfaRequestNode::NAME, node);
+        root_graph
+            .add_node_edge(JfaRequestNode::NAME, main_graph::node::CAMERA_DRIVER)
+            .unwrap();
+    }
+}
+
+/// Component requesting the distance field of a seed texture.
+///
+/// The alpha component of `seed` is its coverage: texels with an alpha above
+/// `0.99` are inside the seed, those below `0.01` are outside it, and edges
+/// are located to within a texel from the alpha in between, as for outline
+/// masks.
+///
+/// Once the seed is loaded, `output` is resized to match it and filled with
+/// the jump flood output each frame while this component is present. The RG
+/// components of each texel hold the texture coordinates of the nearest seed
+/// outside the covered region, and the BA components those of the nearest
+/// seed inside it. Texels with no seed within `max_dist` pixels hold the
+/// app's [`JfaNoSeed`](crate::JfaNoSeed) sentinel. The texels are stored in
+/// the app's [`JfaTextureFormat`], and the `outline::sdf` shader import
+/// decodes them into distances.
+#[derive(Clone, Debug, Component)]
+pub struct JfaRequest {
+    /// The texture whose alpha defines the seeds.
+    pub seed: Handle<Image>,
+    /// The image receiving the distance field.
+    pub output: Handle<Image>,
+    /// The distance, in pixels, up to which seeds are found.
+    pub max_dist: u32,
+    /// The metric used to measure distances.
+    pub metric: DistanceMetric,
+}
+
+impl JfaRequest {
+    /// Creates a request for the Euclidean distance field of `seed` into
+    /// `output`, up to `max_dist` pixels from the seeds.
+    pub fn new(seed: Handle<Image>, output: Handle<Image>, max_dist: u32) -> Self {
+        JfaRequest {
+            seed,
+            output,
+            max_dist,
+            metric: DistanceMetric::Euclidean,
+        }
+    }
+}
+
+fn resize_jfa_outputs(
+    jfa_format: Res<JfaTextureFormat>,
+    mut images: ResMut<Assets<Image>>,
+    requests: Query<&JfaRequest>,
+) {
+    let format = jfa_format.texture_format();
+    let usage = TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING;
+
+    for request in requests.iter() {
+        let size = match images.get(&request.seed) {
+            Some(seed) => seed.texture_descriptor.size,
+            None => continue,
+        };
+
+        let up_to_date = images.get(&request.output).is_some_and(|image| {
+            let desc = &image.texture_descriptor;
+            desc.size == size && desc.format == format && desc.usage.contains(usage)
+        });
+
+        // Only access the image mutably when necessary, as doing so causes it
+        // to be uploaded to the GPU again.
+        if !up_to_date {
+            if let Some(image) = images.get_mut(&request.output) {
+                image.texture_descriptor.format = format;
+                image.texture_descriptor.usage |= usage;
+                image.data.clear();
+                image.resize(size);
+            }
+        }
+    }
+}
+
+fn extract_jfa_requests(
+    mut commands: Commands,
+    mut previous_len: Local<usize>,
+    requests: Extract<Query<(Entity, &JfaRequest)>>,
+) {
+    let mut batches = Vec::with_capacity(*previous_len);
+    batches.extend(
+        requests
+            .iter()
+            .map(|(entity, request)| (entity, (request.clone(),))),
+    );
+    *previous_len = batches.len();
+    commands.insert_or_spawn_batch(batches);
+}
+
+/// Textures and bind groups for flooding the seed of a [`JfaRequest`].
+#[derive(Component)]
+pub(crate) struct JfaRequestResources {
+    dimensions_bind_group: BindGroup,
+    init_bind_group: BindGroup,
+    primary: CachedTexture,
+    from_primary_bind_group: BindGroup,
+    secondary: CachedTexture,
+    from_secondary_bind_group: BindGroup,
+}
+
+fn queue_jfa_requests(
+    mut commands: Commands,
+    outline: Res<OutlineResources>,
+    device: Res<RenderDevice>,
+    queue: Res<RenderQueue>,
+    images: Res<RenderAssets<Image>>,
+    mut textures: ResMut<TextureCache>,
+    requests: Query<(Entity, &JfaRequest)>,
+) {
+    for (entity, request) in requests.iter() {
+        let (seed, output) = match (images.get(&request.seed), images.get(&request.output)) {
+            (Some(s), Some(o)) => (s, o),
+            _ => continue,
+        };
+
+        // The output is resized in the main world, and may not match yet.
+        if output.size != seed.size {
+            continue;
+        }
+
+        let size = seed.size.as_uvec2();
+        let mut dimensions_buffer = UniformBuffer::from(jfa::Dimensions::new(size.x, size.y, 0));
+        dimensions_buffer.write_buffer(&device, &queue);
+        let dimensions_bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("jfa_request_dimensions_bind_group"),
+            layout: &outline.dimensions_bind_group_layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: dimensions_buffer.binding().unwrap(),
+            }],
+        });
+
+        let init_bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("jfa_request_init_bind_group"),
+            layout: &outline.jfa_init_bind_group_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(&seed.texture_view),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::Sampler(&outline.sampler),
+                },
+            ],
+        });
+
+        let [primary, secondary] = ["jfa_request_primary", "jfa_request_secondary"].map(|label| {
+            textures.get(
+                &device,
+                TextureDescriptor {
+                    label: Some(label),
+                    size: Extent3d {
+                        width: size.x,
+                        height: size.y,
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: TextureDimension::D2,
+                    format: outline.jfa_format.texture_format(),
+                    usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+                },
+            )
+        });
+        let from_primary_bind_group = outline.create_jfa_bind_group(
+            &device,
+            "jfa_request_from_primary_bind_group",
+            &primary.default_view,
+        );
+        let from_secondary_bind_group = outline.create_jfa_bind_group(
+            &device,
+            "jfa_request_from_secondary_bind_group",
+            &secondary.default_view,
+        );
+
+        commands.entity(entity).insert(JfaRequestResources {
+            dimensions_bind_group,
+            init_bind_group,
+            primary,
+            from_primary_bind_group,
+            secondary,
+            from_secondary_bind_group,
+        });
+    }
+}
+
+/// Returns the JFA init and iteration pipelines, once they are compiled.
+///
+/// Requests with [`JfaRequestResources`] are flooded in any frame where this
+/// returns `Some`.
+pub(crate) fn jfa_pipelines(world: &World) -> Option<(&RenderPipeline, &RenderPipeline)> {
+    let pipeline_cache = world.resource::<PipelineCache>();
+    let init =
+        pipeline_cache.get_render_pipeline(world.resource::<JfaInitPipeline>().request_cached)?;
+    let jfa = pipeline_cache.get_render_pipeline(world.resource::<JfaPipeline>().request_cached)?;
+    Some((init, jfa))
+}
+
+/// Render graph node which floods the seed textures of all [`JfaRequest`]s.
+///
+/// This runs in the main render graph, before the camera driver, so that the
+/// outputs can be sampled by any camera in the same frame.
+pub(crate) struct JfaRequestNode {
+    query: QueryState<(&'static JfaRequest, &'static JfaRequestResources)>,
+}
+
+impl JfaRequestNode {
+    pub(crate) const NAME: &'static str = "jfa_requests";
+
+    fn new(world: &mut World) -> Self {
+        JfaRequestNode {
+            query: QueryState::new(world),
+        }
+    }
+}
+
+impl Node for JfaRequestNode {
+    fn update(&mut self, world: &mut World) {
+        self.query.update_archetypes(world);
+    }
+
+    fn run(
+        &self,
+        _graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let res = world.resource::<OutlineResources>();
+        let (init_pipeline, jfa_pipeline) = match jfa_pipelines(world) {
+            Some(p) => p,
+            // Still queued.
+            None => return Ok(()),
+        };
+
+        let images = world.resource::<RenderAssets<Image>>();
+        for (request, request_res) in self.query.iter_manual(world) {
+            let output = match images.get(&request.output) {
+                Some(o) => o,
+                None => continue,
+            };
+
+            jfa_init::run_jfa_init_pass(
+                render_context,
+                res,
+                res.jfa_format,
+                init_pipeline,
+                &request_res.dimensions_bind_group,
+                &request_res.init_bind_group,
+                &request_res.primary.default_view,
+                None,
+            );
+
+            let targets = JfaTargets {
+                format: res.jfa_format,
+                dimensions_bind_group: &request_res.dimensions_bind_group,
+                primary: &request_res.primary.default_view,
+                from_primary: &request_res.from_primary_bind_group,
+                secondary: &request_res.secondary.default_view,
+                from_secondary: &request_res.from_secondary_bind_group,
+                output: &output.texture_view,
+                scissor: None,
+                #[cfg(feature = "wgpu-profiler")]
+                profiler: None,
+            };
+            let width = (request.max_dist as f32)
+                .min(output.size.max_element())
+                .min(MAX_JFA_EXTENT);
+            jfa::run_jfa_passes(
+                render_context,
+                res,
+                jfa_pipeline,
+                &targets,
+                width,
+                request.metric as u32,
+            );
+        }
+
+        Ok(())
+    }
+}
@@ -1,8 +1,9 @@
 //! A Bevy library for computing the Jump Flooding Algorithm.
 //!
 //! The **jump flooding algorithm** (JFA) is a fast screen-space algorithm for
-//! computing distance fields. Currently, this crate provides a plugin for
-//! adding outlines to arbitrary meshes.
+//! computing distance fields. This crate provides a plugin for adding outlines
+//! to arbitrary meshes, and a [`JfaPlugin`] for computing the distance fields
+//! of arbitrary seed textures.
 //!
 //! Outlines adapted from ["The Quest for Very Wide Outlines" by Ben Golus][0].
 //!
@@ -18,19 +19,180 @@
 //!    camera which should render the outline.  Currently, outline styling is
 //!    tied to the camera rather than the mesh.
 //! 4. Add an [`Outline`] component to the mesh with `enabled: true`.
+//!
+//! For simple cases, the [`MinimalOutlinePlugin`] can be used instead. It
+//! does not register `OutlineStyle` as an asset; instead, an `OutlineStyle`
+//! is added to the camera directly as a component.
+//!
+//! Systems which react to highlights, e.g. to play a sound, can read the
+//! [`OutlineShown`] and [`OutlineHidden`] events, which are sent when an
+//! outlined entity comes into or leaves the view of an outlined camera. For
+//! drag selection, the [`OutlineMarqueePlugin`] outlines the entities inside
+//! a rectangle on screen.
+//!
+//! # Render graph
+//!
+//! Outlines are drawn directly to the camera target after the main pass. To
+//! combine them deterministically with other post-processing effects, order
+//! those effects relative to the outline using the labels and helpers in the
+//! [`graph`] module.
+//!
+//! The [`prelude`] re-exports the plugins, components and style types most
+//! apps need, along with the render graph nodes and labels.
+//!
+//! # Shader imports
+//!
+//! The plugin registers shader imports for working with JFA textures in user
+//! shaders:
+//!
+//! - `outline::jfa_utils` defines [`JFA_NO_SEED`] and `jfa_has_seed`, for
+//!   identifying texels with no nearest seed, and `jfa_decode`, for reading
+//!   texels stored in the app's [`JfaTextureFormat`].
+//! - `outline::sdf` defines `sdf_sample_distance` and `sdf_seed_distance`, for
+//!   converting the contents of a JFA texture into distances in texels.
+//!
+//! Shaders using either import must be compiled with the
+//! [`JfaTextureFormat::shader_defs`] and [`JfaNoSeed::shader_defs`] of the
+//! app.
+//!
+//! A camera's mask and distance field can be bound by user shaders by adding
+//! an [`OutlineTextures`] component to the camera, which publishes copies of
+//! them as images. The distance field can also be read back to the CPU, e.g.
+//! for gameplay logic, with the [`OutlineReadbackPlugin`]. Building on it, the
+//! [`OutlinePickingPlugin`] finds the outlined entity nearest to the cursor.
+//! For debugging, the [`OutlineDebugCapturePlugin`] captures any of the
+//! intermediate textures of a frame into an `Image`, e.g. to save to a file,
+//! and [`OutlineSettings::set_debug_view`] draws them on screen in place of
+//! the outline.
+//!
+//! It also registers the imports used by its own screen-space passes, which
+//! are equally usable by dependent crates:
+//!
+//! - `outline::fullscreen` defines a `vertex` entry point which draws a single
+//!   triangle covering the screen, passing the texcoord to the fragment stage
+//!   at `@location(0)`. Draw it with 3 vertices, no vertex buffers and
+//!   [`FULLSCREEN_PRIMITIVE_STATE`].
+//! - `outline::dimensions` defines the `Dimensions` struct, matching
+//!   [`Dimensions`], and binds it as `dims` at `@group(0) @binding(0)`. It
+//!   also defines `screen_to_framebuffer` for converting screen texcoords
+//!   into texcoords of a framebuffer padded by `Dimensions::new`.
+//! - `outline::mask_vertex` binds the mesh, mask dimensions and outline
+//!   parameters for vertex shaders drawing into the mask, as described in
+//!   [`OutlineMaterial`].
+//!
+//! These imports are part of the public API, and breaking changes to them
+//! follow the same versioning rules as the Rust API.
+//!
+//! # Scenes
+//!
+//! With the `scene` feature enabled, an `OutlineScene` component can be added
+//! to a scene root to outline every mesh in the scene once it has spawned.
+//!
+//! # Custom materials
+//!
+//! Meshes are drawn into the mask with their undeformed vertices, whatever
+//! their material. Materials which displace vertices can implement
+//! [`OutlineMaterial`] to supply a vertex shader for the mask, which is used
+//! once an [`OutlineMaterialPlugin`] is added for the material.
+//!
+//! Geometry which isn't drawn as one mesh per entity, such as instanced
+//! meshes, can be drawn into the mask by custom render app systems. Adding a
+//! [`CustomMeshMask`] to such an entity keeps the plugin from drawing it, and
+//! the systems queue [`MeshMask`] items for it with their own draw functions.
+//!
+//! # Sprites
+//!
+//! With the `sprite` feature enabled, `Sprite`, `TextureAtlasSprite` and 2D
+//! mesh entities with an [`Outline`] are outlined by 2D cameras with a
+//! [`CameraOutline`]. Sprite outlines follow the texels of the sprite with an
+//! alpha of at least one half, while 2D mesh outlines follow the mesh, taking
+//! their color from its `ColorMaterial`. Sprites are drawn into the mask after
+//! 2D meshes, so they take precedence where the two overlap. Occlusion,
+//! whether by other outlined entities or by the view depth buffer, is not
+//! supported for 2D cameras.
+//!
+//! As in the main pass, meshes and sprites are only drawn into the mask of
+//! cameras whose `RenderLayers` intersect their own.
+//!
+//! # UI
+//!
+//! With the `ui` feature enabled, which implies `sprite`, UI nodes with an
+//! [`Outline`] are outlined on top of the UI of cameras with a
+//! [`CameraOutline`]. Text is not outlined. The plugin must be added after
+//! `UiPlugin`, which `DefaultPlugins` includes.
+//!
+//! # Fog of war
+//!
+//! The [`FogOfWarPlugin`] builds fog of war on the [`JfaPlugin`]: entities
+//! with a [`FogRevealer`] reveal a map, whose distance field darkens cameras
+//! with a [`FogOfWarCamera`] smoothly outside the revealed area.
+//!
+//! # 2D shadows
+//!
+//! The [`Shadow2dPlugin`] casts soft shadows from entities with a
+//! [`ShadowOccluder2d`], by tracing each [`ShadowLight2d`] through the
+//! distance field of a [`ShadowMap2d`] into the light's shadow image.
+//!
+//! The [`LightFalloffPlugin`] draws cheap 2D area lights: an entity with both
+//! a [`JfaRequest`] and a [`LightFalloff2d`] lights a texture around the
+//! request's seed, fading out smoothly with the distance from it.
+//!
+//! # Text
+//!
+//! Text isn't outlined directly. With the `text` feature enabled, which implies
+//! `sprite`, the `GlyphSdfPlugin` computes the distance fields of Bevy's glyph
+//! atlases with the [`JfaPlugin`], for custom text rendering to draw outlines
+//! and glows from.
+//!
+//! # WebGL2
+//!
+//! Outlines can be drawn on WebGL2 and other downlevel backends, which can't
+//! render to 16-bit normalized textures, by storing the distance field in a
+//! half-float format instead. Multisampled textures can't be sampled there,
+//! so MSAA must be disabled with `Msaa { samples: 1 }`. The
+//! [`OutlineGroupCoveragePlugin`] uses a compute shader, and is not supported.
+//!
+//! # Baking
+//!
+//! With the `bake` feature enabled, the `SdfBakePlugin` bakes images, such as
+//! icons or font atlases, into signed distance fields with the same passes as
+//! the [`JfaPlugin`]. Adding an `SdfBake` to an entity produces an `R8Unorm`
+//! image holding the field, which can be saved or kept for use at runtime.
+//!
+//! # Timings
+//!
+//! With the `wgpu-profiler` feature enabled, which is the default, the
+//! `OutlineTimingsPlugin` measures the GPU time of each outline pass with
+//! timestamp queries, for comparing the cost of resolutions and outline widths.
+//!
+//! # Style files
+//!
+//! With the `ron` feature enabled, outline styles can be defined in
+//! `.outline.ron` files and loaded with the `AssetServer`, e.g.
+//! `asset_server.load("styles/selected.outline.ron")`. See
+//! `OutlineStyleLoader` for the file format.
 
 use bevy::{
     app::prelude::*,
     asset::{Assets, Handle, HandleUntyped},
     core_pipeline::core_3d,
-    ecs::{prelude::*, system::SystemParamItem},
-    pbr::{DrawMesh, MeshPipelineKey, MeshUniform, SetMeshBindGroup, SetMeshViewBindGroup},
+    ecs::{
+        entity::{EntityMap, MapEntities, MapEntitiesError},
+        prelude::*,
+        reflect::ReflectMapEntities,
+        system::SystemParamItem,
+    },
+    log::{error, warn},
+    math::{UVec2, Vec2},
+    pbr::{DrawMesh, MeshUniform, SetMeshBindGroup, SetMeshViewBindGroup},
     prelude::{AddAsset, Camera3d},
-    reflect::TypeUuid,
+    reflect::{std_traits::ReflectDefault, FromReflect, Reflect, TypeUuid},
     render::{
+        camera::CameraUpdateSystem,
+        extract_component::UniformComponentPlugin,
         extract_resource::ExtractResource,
         prelude::*,
-        render_asset::{PrepareAssetError, RenderAsset, RenderAssetPlugin, RenderAssets},
+        render_asset::{PrepareAssetError, RenderAsset, RenderAssets},
         render_graph::RenderGraph,
         render_phase::{
             AddRenderCommand, CachedRenderPipelinePhaseItem, DrawFunctionId, DrawFunctions,
@@ -38,28 +200,411 @@ use bevy::{
         },
         render_resource::*,
         renderer::{RenderDevice, RenderQueue},
-        view::{ExtractedView, VisibleEntities},
+        view::{ExtractedView, VisibilitySystems, VisibleEntities},
         Extract, RenderApp, RenderStage,
     },
-    utils::FloatOrd,
+    utils::{FloatOrd, HashMap},
 };
 
+#[cfg(feature = "ron")]
+use bevy::reflect::{ReflectDeserialize, ReflectSerialize};
+
 use crate::{
     graph::OutlineDriverNode,
-    mask::MeshMaskPipeline,
-    outline::{GpuOutlineParams, OutlineParams},
+    mask::SetMaskAlphaBindGroup,
+    outline::{ExtractedOutlineStyle, GpuOutlineParams, PreparedStyleCache},
     resources::OutlineResources,
 };
 
-mod graph;
+#[cfg(feature = "bake")]
+mod bake;
+mod capture;
+mod coverage;
+mod debug;
+mod debug_capture;
+mod diagnostics;
+mod flash;
+mod fog;
+#[cfg(feature = "text")]
+mod glyph;
+pub mod graph;
+mod group_coverage;
 mod jfa;
 mod jfa_init;
+mod jfa_request;
+mod light;
+#[cfg(feature = "ron")]
+mod loader;
+mod marquee;
 mod mask;
+mod mask_bounds;
+mod matching;
+mod material;
+#[cfg(feature = "sprite")]
+mod mesh2d;
 mod outline;
+mod picking;
+mod readback;
 mod resources;
+mod reuse;
+#[cfg(feature = "scene")]
+mod scene;
+mod scissor;
+mod shadow;
+#[cfg(feature = "sprite")]
+mod sprite;
+mod textures;
+#[cfg(feature = "wgpu-profiler")]
+mod timings;
+mod tween;
+#[cfg(feature = "ui")]
+mod ui;
+mod visibility;
+
+#[cfg(feature = "bake")]
+pub use bake::{SdfBake, SdfBakePlugin, SdfBaked};
+pub use capture::{OutlineAtlasCapture, OutlineCapture};
+pub use debug::{OutlineDebugImages, OutlineDebugImagesPlugin};
+pub use debug_capture::{
+    OutlineBuffers, OutlineDebug, OutlineDebugCapture, OutlineDebugCapturePlugin,
+};
+pub use diagnostics::OutlineDiagnosticsPlugin;
+pub use flash::OutlineFlash;
+pub use fog::{FogOfWar, FogOfWarCamera, FogOfWarPlugin, FogPlane, FogRevealer};
+#[cfg(feature = "text")]
+pub use glyph::{GlyphSdfPlugin, GlyphSdfs};
+pub use group_coverage::{OutlineGroupCoverage, OutlineGroupCoveragePlugin};
+pub use jfa::Dimensions;
+pub use jfa_request::{JfaPlugin, JfaRequest};
+pub use light::{LightFalloff2d, LightFalloffPlugin};
+#[cfg(feature = "ron")]
+pub use loader::OutlineStyleLoader;
+pub use marquee::{
+    MarqueeSelectable, OutlineMarquee, OutlineMarqueeBegin, OutlineMarqueeEnd, OutlineMarqueePlugin,
+};
+pub use mask::{
+    CustomMeshMask, MeshMaskPipeline, MeshMaskPipelineKey, MeshMaskViewKey, MeshMaskViewKeys,
+    SetMaskViewBindGroup,
+};
+pub use matching::{OutlineForMarker, OutlineMatchingPlugin};
+pub use material::{OutlineMaterial, OutlineMaterialPlugin};
+pub use picking::{OutlinePicking, OutlinePickingPlugin};
+pub use readback::{OutlineReadback, OutlineReadbackPlugin, OutlineReadbacks};
+#[cfg(feature = "scene")]
+pub use scene::OutlineScene;
+pub use shadow::{OccluderShape, Shadow2dPlugin, ShadowLight2d, ShadowMap2d, ShadowOccluder2d};
+pub use textures::OutlineTextures;
+#[cfg(feature = "wgpu-profiler")]
+pub use timings::{OutlineTimings, OutlineTimingsPlugin, OutlineViewTimings};
+pub use tween::{OutlineEasing, OutlineStyleTween};
+pub use visibility::{OutlineHidden, OutlineShown};
+
+/// Commonly used types, for glob importing with `use bevy_jfa::prelude::*`.
+pub mod prelude {
+    pub use crate::{
+        graph::{
+            outline as outline_graph, run_after_outline, run_before_outline, JfaInitNode, JfaNode,
+            MeshMaskNode, OutlineDriverNode, OutlineNode,
+        },
+        CameraOutline, DistanceMetric, InnerOutline, JfaPlugin, JfaRequest, MinimalOutlinePlugin,
+        Outline, OutlineColorSource, OutlineDepthOcclusion, OutlineFalloff, OutlineFill,
+        OutlineFlash, OutlineGroup, OutlineJitter, OutlineOcclusion, OutlineOnly, OutlineOverlay,
+        OutlinePlugin, OutlinePulseTarget, OutlineScreenMask, OutlineSettings, OutlineStyle,
+        OutlineUnits,
+    };
+}
+
+/// The value stored in JFA texels for which no seed has been found, with the
+/// default [`JfaNoSeed`].
+///
+/// Valid texels hold framebuffer-space coordinates in the range `[0, 1]`, so
+/// any negative value unambiguously marks an empty texel. Shaders can use the
+/// `outline::jfa_utils` import to test for this value.
+///
+/// Texels read back to the CPU hold this value whichever sentinel is used on
+/// the GPU.
+pub const JFA_NO_SEED: [f32; 2] = [-1.0, -1.0];
+
+/// The value stored in JFA texels for which no seed has been found, with
+/// [`JfaNoSeed::Far`].
+pub const JFA_FAR_NO_SEED: [f32; 2] = [1024.0, 1024.0];
+
+/// The sentinel value stored in JFA texels for which no seed has been found.
+///
+/// This is a build setting, like the [`JfaTextureFormat`]: insert it as a
+/// resource before adding [`OutlinePlugin`] or [`MinimalOutlinePlugin`].
+/// Sentinels which the format can't hold are replaced with
+/// [`JfaNoSeed::Negative`], so the resource holds the sentinel in use
+/// afterwards.
+///
+/// User shaders sampling JFA textures through the `outline::jfa_utils` or
+/// `outline::sdf` imports must be compiled with the
+/// [`shader_defs`](Self::shader_defs) of the sentinel as well as those of the
+/// format.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum JfaNoSeed {
+    /// [`JFA_NO_SEED`]. Shaders must test texels with `jfa_has_seed` before
+    /// measuring the distance to their seeds.
+    #[default]
+    Negative,
+    /// [`JFA_FAR_NO_SEED`], which is farther from every texel than any seed
+    /// in the texture, so the distance to an empty texel's seed is simply
+    /// larger than any real one, and shaders can take the nearest of several
+    /// seeds without testing for empty texels. This can't be held by
+    /// [`JfaTextureFormat::Rgba16Snorm`] or [`JfaTextureFormat::Rg16Snorm`]
+    /// textures.
+    Far,
+}
+
+impl JfaNoSeed {
+    /// Returns the coordinates stored in texels with no seed.
+    pub fn value(self) -> [f32; 2] {
+        match self {
+            JfaNoSeed::Negative => JFA_NO_SEED,
+            JfaNoSeed::Far => JFA_FAR_NO_SEED,
+        }
+    }
+
+    /// Returns the shader defs with which the `outline::jfa_utils` import
+    /// uses this sentinel.
+    pub fn shader_defs(self) -> Vec<String> {
+        match self {
+            JfaNoSeed::Negative => vec![],
+            JfaNoSeed::Far => vec!["JFA_NO_SEED_FAR".into()],
+        }
+    }
+
+    /// Returns this sentinel if textures in `format` can hold it, and
+    /// [`JfaNoSeed::Negative`] otherwise.
+    fn validate(self, format: JfaTextureFormat) -> JfaNoSeed {
+        if self == JfaNoSeed::Far && format.is_normalized() {
+            warn!(
+                "JFA no-seed sentinel {:?} can't be held by {:?} textures, using {:?}",
+                self,
+                format,
+                JfaNoSeed::Negative
+            );
+            return JfaNoSeed::Negative;
+        }
+        self
+    }
+}
+
+/// The format of the jump flood textures.
+///
+/// This is a build setting: insert it as a resource before adding
+/// [`OutlinePlugin`] or [`MinimalOutlinePlugin`], as with `WgpuSettings`. It
+/// is validated against the features of the render device when the plugin is
+/// built, and formats the device doesn't support are replaced with
+/// [`JfaTextureFormat::Rgba16Float`], or [`JfaTextureFormat::Rg16Float`] for
+/// two-channel formats, so the resource holds the format in use afterwards.
+///
+/// Four-channel formats hold the nearest seeds both outside and inside the
+/// silhouette. Two-channel formats only hold the nearest seeds outside it,
+/// halving memory and bandwidth, but [`InnerOutline`]s aren't drawn with
+/// them, and the ZW components of texels read back from them are always
+/// [`JFA_NO_SEED`]. Views which don't draw an `InnerOutline` flood their
+/// distance field in the two-channel counterpart of the app's format, given
+/// by [`outer_seeds_only`](Self::outer_seeds_only), unless it's copied into
+/// an [`OutlineTextures`] or [`OutlineDebugImages`] image. Cameras can select
+/// a format of their own with an [`OutlineJfaFormat`].
+///
+/// User shaders sampling JFA textures through the `outline::jfa_utils` or
+/// `outline::sdf` imports must be compiled with the
+/// [`shader_defs`](Self::shader_defs) of the format.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Reflect, FromReflect)]
+#[cfg_attr(feature = "ron", derive(serde::Serialize, serde::Deserialize))]
+#[reflect_value(Debug, PartialEq, Hash, Default)]
+#[cfg_attr(
+    feature = "ron",
+    reflect_value(Debug, PartialEq, Hash, Default, Serialize, Deserialize)
+)]
+pub enum JfaTextureFormat {
+    /// Both seeds as 16-bit normalized coordinates. This requires
+    /// `WgpuFeatures::TEXTURE_FORMAT_16BIT_NORM`, which is unavailable on some
+    /// backends, notably WebGL2.
+    #[default]
+    Rgba16Snorm,
+    /// Both seeds as half-precision float coordinates. This loses some
+    /// precision for coordinates near 1.0, by up to a pixel in masks more than
+    /// 2048 pixels across.
+    Rgba16Float,
+    /// The outer seed as 16-bit normalized coordinates. This requires the
+    /// same features as `Rgba16Snorm`.
+    Rg16Snorm,
+    /// The outer seed as half-precision float coordinates.
+    Rg16Float,
+    /// The outer seed as 16-bit fixed-point coordinates, each split across two
+    /// channels of an `Rgba8Unorm` texture. This is as precise as
+    /// `Rgba16Snorm` and renderable everywhere, at the cost of packing and
+    /// unpacking texels in every pass. JFA textures in this format must be
+    /// sampled with a non-filtering sampler.
+    Rgba8Packed,
+}
+
+impl JfaTextureFormat {
+    /// Returns the texture format of JFA textures.
+    pub fn texture_format(self) -> TextureFormat {
+        match self {
+            JfaTextureFormat::Rgba16Snorm => TextureFormat::Rgba16Snorm,
+            JfaTextureFormat::Rgba16Float => TextureFormat::Rgba16Float,
+            JfaTextureFormat::Rg16Snorm => TextureFormat::Rg16Snorm,
+            JfaTextureFormat::Rg16Float => TextureFormat::Rg16Float,
+            JfaTextureFormat::Rgba8Packed => TextureFormat::Rgba8Unorm,
+        }
+    }
+
+    /// Returns whether texels hold the nearest seed inside the silhouette as
+    /// well as outside it.
+    pub fn has_inner_seeds(self) -> bool {
+        matches!(
+            self,
+            JfaTextureFormat::Rgba16Snorm | JfaTextureFormat::Rgba16Float
+        )
+    }
+
+    /// Returns the format storing the outer seeds in the same way as this one,
+    /// without the inner seeds.
+    pub fn outer_seeds_only(self) -> JfaTextureFormat {
+        match self {
+            JfaTextureFormat::Rgba16Snorm => JfaTextureFormat::Rg16Snorm,
+            JfaTextureFormat::Rgba16Float => JfaTextureFormat::Rg16Float,
+            _ => self,
+        }
+    }
+
+    /// Returns whether coordinates are stored as normalized integers, which
+    /// can't exceed 1.0.
+    fn is_normalized(self) -> bool {
+        matches!(
+            self,
+            JfaTextureFormat::Rgba16Snorm | JfaTextureFormat::Rg16Snorm
+        )
+    }
 
-const JFA_TEXTURE_FORMAT: TextureFormat = TextureFormat::Rg16Snorm;
-const FULLSCREEN_PRIMITIVE_STATE: PrimitiveState = PrimitiveState {
+    /// Returns the shader defs with which shaders using the
+    /// `outline::jfa_utils` import decode texels of this format.
+    pub fn shader_defs(self) -> Vec<String> {
+        let mut defs = vec![];
+        if !self.has_inner_seeds() {
+            defs.push("JFA_OUTER_SEEDS_ONLY".into());
+        }
+        if self == JfaTextureFormat::Rgba8Packed {
+            defs.push("JFA_PACKED".into());
+        }
+        defs
+    }
+
+    /// Returns the color to which JFA textures are cleared, which decodes to
+    /// the value of `no_seed`.
+    pub(crate) fn clear_color(self, no_seed: JfaNoSeed) -> Color {
+        let [x, y] = no_seed.value();
+        match self {
+            // The largest packed value is reserved for texels with no seed.
+            JfaTextureFormat::Rgba8Packed => Color::RgbaLinear {
+                red: 1.0,
+                green: 1.0,
+                blue: 1.0,
+                alpha: 1.0,
+            },
+            _ => Color::RgbaLinear {
+                red: x,
+                green: y,
+                blue: x,
+                alpha: y,
+            },
+        }
+    }
+
+    /// Returns the half-precision float format holding the same seeds as this
+    /// one, which is renderable everywhere and can hold any [`JfaNoSeed`].
+    fn fallback(self) -> JfaTextureFormat {
+        if self.has_inner_seeds() {
+            JfaTextureFormat::Rgba16Float
+        } else {
+            JfaTextureFormat::Rg16Float
+        }
+    }
+
+    /// Returns this format if `device` supports it, and a fallback
+    /// otherwise.
+    fn validate(self, device: &RenderDevice) -> JfaTextureFormat {
+        let required = self.texture_format().describe().required_features;
+        if device.features().contains(required) {
+            return self;
+        }
+
+        let fallback = self.fallback();
+        // The default is only available on some backends, so falling back
+        // from it is expected.
+        if self != JfaTextureFormat::default() {
+            warn!(
+                "JFA texture format {:?} requires unsupported features {:?}, using {:?}",
+                self,
+                required - device.features(),
+                fallback
+            );
+        }
+        fallback
+    }
+}
+
+/// Validates the [`JfaTextureFormat`] and [`JfaNoSeed`] requested by the app,
+/// inserting the format and sentinel in use into the app and render worlds.
+///
+/// Plugins which use the format while building call this first, so that they
+/// can be added in any order.
+pub(crate) fn init_jfa_texture_format(app: &mut App) {
+    let requested = app
+        .world
+        .get_resource::<JfaTextureFormat>()
+        .copied()
+        .unwrap_or_default();
+    let format = requested.validate(app.world.resource::<RenderDevice>());
+    let no_seed = app
+        .world
+        .get_resource::<JfaNoSeed>()
+        .copied()
+        .unwrap_or_default()
+        .validate(format);
+    app.insert_resource(format).insert_resource(no_seed);
+
+    if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+        render_app.insert_resource(format).insert_resource(no_seed);
+    }
+}
+
+/// Replaces the formats of [`OutlineJfaFormat`]s which the render device
+/// doesn't support, or which can't hold the app's [`JfaNoSeed`], with a
+/// fallback.
+pub(crate) fn validate_outline_jfa_formats(
+    device: Res<RenderDevice>,
+    no_seed: Res<JfaNoSeed>,
+    mut formats: Query<&mut OutlineJfaFormat, Changed<OutlineJfaFormat>>,
+) {
+    for mut format in formats.iter_mut() {
+        let mut validated = format.0.validate(&device);
+        if *no_seed == JfaNoSeed::Far && validated.is_normalized() {
+            warn!(
+                "JFA texture format {:?} can't hold the no-seed sentinel {:?}, using {:?}",
+                validated,
+                *no_seed,
+                validated.fallback()
+            );
+            validated = validated.fallback();
+        }
+
+        // Only access the component mutably when necessary, so that it isn't
+        // validated again.
+        if validated != format.0 {
+            format.0 = validated;
+        }
+    }
+}
+
+/// The primitive state for drawing with the `outline::fullscreen` shader
+/// import.
+pub const FULLSCREEN_PRIMITIVE_STATE: PrimitiveState = PrimitiveState {
     topology: PrimitiveTopology::TriangleList,
     strip_index_format: None,
     front_face: FrontFace::Ccw,
@@ -73,32 +618,353 @@ const FULLSCREEN_PRIMITIVE_STATE: PrimitiveState = PrimitiveState {
 #[derive(Default)]
 pub struct OutlinePlugin;
 
+/// Plugin for enabling outlines without the asset pipeline.
+///
+/// Rather than referencing an `OutlineStyle` asset through a
+/// [`CameraOutline`], outlines are enabled on a camera by adding an
+/// [`OutlineStyle`] to it directly as a component. [`OutlinePlugin`] includes
+/// this functionality, so this plugin should not be added alongside it.
+#[derive(Default)]
+pub struct MinimalOutlinePlugin;
+
 /// Performance and visual quality settings for JFA-based outlines.
-#[derive(Clone, ExtractResource)]
+#[derive(Clone, ExtractResource, Reflect)]
+#[reflect(Resource, Default)]
 pub struct OutlineSettings {
-    pub(crate) half_resolution: bool,
+    pub(crate) resolution: OutlineResolution,
+    pub(crate) upsample: OutlineUpsample,
+    pub(crate) offscreen_padding: u32,
+    pub(crate) deterministic: Option<UVec2>,
+    pub(crate) alpha_mask: bool,
+    pub(crate) alpha_to_coverage: bool,
+    pub(crate) debug_view: OutlineDebugView,
+    pub(crate) scissor: bool,
+    pub(crate) temporal_reuse: bool,
 }
 
 impl OutlineSettings {
+    /// Returns the resolution of the intermediate jump flood textures.
+    pub fn resolution(&self) -> OutlineResolution {
+        self.resolution
+    }
+
+    /// Sets the resolution of the intermediate jump flood textures.
+    ///
+    /// Lower resolutions save fill rate, which is mostly spent on the jump
+    /// flood passes over wide outlines. Outline widths are unaffected, as the
+    /// distance field stores positions in full-resolution pixels, so this can
+    /// be changed at runtime without the outline visibly changing thickness.
+    /// The [`edge_width`](OutlineStyle::edge_width) of the outline is
+    /// multiplied by the resolution's divisor to hide the coarser distance
+    /// field, unless an [`upsample`](Self::set_upsample) filter is set.
+    pub fn set_resolution(&mut self, resolution: OutlineResolution) {
+        self.resolution = resolution;
+    }
+
     /// Returns whether the half-resolution setting is enabled.
     pub fn half_resolution(&self) -> bool {
-        self.half_resolution
+        self.resolution == OutlineResolution::Half
     }
 
-    /// Sets whether the half-resolution setting is enabled.
+    /// Sets whether the half-resolution setting is enabled, switching between
+    /// [`OutlineResolution::Half`] and [`OutlineResolution::Full`].
+    ///
+    /// See [`set_resolution`](Self::set_resolution) for details.
     pub fn set_half_resolution(&mut self, value: bool) {
-        self.half_resolution = value;
+        self.resolution = if value {
+            OutlineResolution::Half
+        } else {
+            OutlineResolution::Full
+        };
+    }
+
+    /// Returns the filter used to upsample the distance field at reduced
+    /// resolutions.
+    pub fn upsample(&self) -> OutlineUpsample {
+        self.upsample
+    }
+
+    /// Sets the filter used to upsample the distance field at reduced
+    /// resolutions.
+    ///
+    /// The distance field only locates the silhouette to within a texel, so
+    /// without filtering, outlines drawn from it at half or quarter
+    /// resolution are visibly jagged. See [`OutlineUpsample`] for the
+    /// available filters. This has no effect at full resolution.
+    pub fn set_upsample(&mut self, filter: OutlineUpsample) {
+        self.upsample = filter;
+    }
+
+    /// Returns the upsampling filter in effect, which is always
+    /// [`OutlineUpsample::Nearest`] at full resolution.
+    pub(crate) fn active_upsample(&self) -> OutlineUpsample {
+        if self.resolution != OutlineResolution::Full {
+            self.upsample
+        } else {
+            OutlineUpsample::Nearest
+        }
+    }
+
+    /// Returns the number of pixels by which the mask extends past each edge
+    /// of the screen.
+    pub fn offscreen_padding(&self) -> u32 {
+        self.offscreen_padding
+    }
+
+    /// Sets the number of pixels by which the mask extends past each edge of
+    /// the screen.
+    ///
+    /// Without padding, the parts of an outlined entity which lie off-screen
+    /// are not rendered into the mask, so they can't contribute outline to the
+    /// visible area. Padding the mask by the outline width avoids this, at the
+    /// cost of larger intermediate textures. Entities which are entirely
+    /// outside the camera's view are culled and never contribute.
+    pub fn set_offscreen_padding(&mut self, value: u32) {
+        self.offscreen_padding = value;
+    }
+
+    /// Returns the internal resolution used in deterministic mode, if it is
+    /// enabled.
+    pub fn deterministic(&self) -> Option<UVec2> {
+        self.deterministic
+    }
+
+    /// Enables deterministic mode with the given internal resolution, or
+    /// disables it if `None`.
+    ///
+    /// In deterministic mode, the outline output depends only on the scene and
+    /// the camera, so automated visual tests and replay-based capture tools
+    /// produce identical output across runs on the same hardware:
+    ///
+    /// - The mask and distance field are rendered at the given resolution
+    ///   (plus padding) regardless of the viewport size, and outline widths are
+    ///   measured in pixels of this resolution.
+    /// - Time-based animation, such as scrolling dashes and pulses, is frozen
+    ///   at time zero.
+    ///
+    /// Entities are always drawn into the mask in a deterministic order.
+    pub fn set_deterministic(&mut self, resolution: Option<UVec2>) {
+        self.deterministic = resolution;
+    }
+
+    /// Returns whether alpha-masked materials are cut out of the mask.
+    pub fn alpha_mask(&self) -> bool {
+        self.alpha_mask
+    }
+
+    /// Sets whether alpha-masked materials are cut out of the mask.
+    ///
+    /// By default, every fragment of an outlined mesh covers the mask, so
+    /// foliage and fences using `AlphaMode::Mask` are outlined along the
+    /// edges of their geometry. When enabled, the mask samples the base color
+    /// texture of such materials and discards fragments below the alpha
+    /// cutoff, so the outline follows the cutout instead. Meshes without UVs
+    /// are unaffected.
+    pub fn set_alpha_mask(&mut self, value: bool) {
+        self.alpha_mask = value;
+    }
+
+    /// Returns whether alpha-masked materials are cut out of the mask using
+    /// alpha to coverage.
+    pub fn alpha_to_coverage(&self) -> bool {
+        self.alpha_to_coverage
+    }
+
+    /// Sets whether alpha-masked materials are cut out of the mask using
+    /// alpha to coverage.
+    ///
+    /// With [`alpha_mask`](Self::set_alpha_mask) enabled, fragments below the
+    /// alpha cutoff are discarded outright, so the cutout has hard, aliased
+    /// edges. When this is also enabled, each fragment instead covers a share
+    /// of the mask's samples which grows smoothly across the cutoff, giving
+    /// foliage and fences smoother silhouettes. This only takes effect while
+    /// [`Msaa`] uses more than one sample.
+    pub fn set_alpha_to_coverage(&mut self, value: bool) {
+        self.alpha_to_coverage = value;
+    }
+
+    /// Returns the intermediate buffer drawn in place of the outline.
+    pub fn debug_view(&self) -> OutlineDebugView {
+        self.debug_view
+    }
+
+    /// Sets the intermediate buffer drawn in place of the outline, for
+    /// diagnosing outline widths and missing outlines.
+    ///
+    /// Every outlined view then draws the buffer opaquely over its entire
+    /// target, ignoring its styles apart from their widths, units and
+    /// distance metrics. Offscreen padding is not shown.
+    pub fn set_debug_view(&mut self, view: OutlineDebugView) {
+        self.debug_view = view;
+    }
+
+    /// Returns whether outline passes are restricted to the screen bounds of
+    /// outlined entities.
+    pub fn scissor(&self) -> bool {
+        self.scissor
+    }
+
+    /// Sets whether outline passes are restricted to the screen bounds of
+    /// outlined entities.
+    ///
+    /// When enabled, the JFA initialization, jump flood and outline passes of
+    /// each view only draw within the union of the screen-space `Aabb`s of
+    /// its visible outlined entities, expanded by the widest outline it
+    /// draws. This saves most of their cost when outlined entities cover a
+    /// small part of the screen. Views are drawn in full while any visible
+    /// outlined entity has no `Aabb`, such as a sprite, or lies partly behind
+    /// the camera, and while any UI node is outlined.
+    ///
+    /// The bounds of each view's mask are also reduced in a compute pass and
+    /// read back, and the rectangle is enlarged to cover those of the most
+    /// recent mask read back, which is usually two frames old. This covers
+    /// geometry drawn outside of its `Aabb`, such as skinned meshes posed
+    /// beyond their bind pose or [`OutlineMaterial`]s displacing vertices,
+    /// unless it moves beyond both its `Aabb` and the stale mask bounds, in
+    /// which case its outline may be cut off. Backends without compute
+    /// shaders, such as WebGL2, only use the `Aabb`s.
+    pub fn set_scissor(&mut self, value: bool) {
+        self.scissor = value;
+    }
+
+    /// Returns whether the distance field of a view is reused while nothing
+    /// outlined by it changes.
+    pub fn temporal_reuse(&self) -> bool {
+        self.temporal_reuse
+    }
+
+    /// Sets whether the distance field of a view is reused while nothing
+    /// outlined by it changes.
+    ///
+    /// When enabled, the mask and distance field of each view are kept from
+    /// one frame to the next, and the mask and jump flood passes are skipped
+    /// while neither the camera nor any of its visible outlined entities has
+    /// moved or changed, so that only the outline itself is drawn. Entities
+    /// are tracked through their `Outline`, `OutlineGroup`, `GlobalTransform`,
+    /// mesh and `StandardMaterial`, including changes to those assets, and a
+    /// view is redrawn whenever these settings, the `Msaa` setting or the
+    /// extent, metric or color source of its styles change.
+    ///
+    /// Views are drawn in full each frame while they outline skinned meshes,
+    /// entities with a [`CustomMeshMask`], sprites or 2D meshes, while they
+    /// draw a style depending on occlusion, and while any UI node is
+    /// outlined. Vertex animation which changes none of the above, such as in
+    /// a custom vertex shader, is not detected.
+    pub fn set_temporal_reuse(&mut self, value: bool) {
+        self.temporal_reuse = value;
+    }
+
+    /// Returns the size of the screen area covered by the mask, excluding
+    /// padding, for a viewport of the given size.
+    pub(crate) fn internal_size(&self, viewport_size: UVec2) -> UVec2 {
+        self.deterministic.unwrap_or(viewport_size).max(UVec2::ONE)
     }
 }
 
 impl Default for OutlineSettings {
     fn default() -> Self {
         Self {
-            half_resolution: false,
+            resolution: OutlineResolution::Full,
+            upsample: OutlineUpsample::Nearest,
+            offscreen_padding: 0,
+            deterministic: None,
+            alpha_mask: false,
+            alpha_to_coverage: false,
+            debug_view: OutlineDebugView::None,
+            scissor: false,
+            temporal_reuse: false,
+        }
+    }
+}
+
+/// The resolution of the intermediate jump flood textures, as set by
+/// [`OutlineSettings::set_resolution`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Reflect, FromReflect)]
+#[cfg_attr(feature = "ron", derive(serde::Serialize, serde::Deserialize))]
+#[reflect_value(Debug, PartialEq, Hash, Default)]
+#[cfg_attr(
+    feature = "ron",
+    reflect_value(Debug, PartialEq, Hash, Default, Serialize, Deserialize)
+)]
+pub enum OutlineResolution {
+    /// The resolution of the mask.
+    #[default]
+    Full,
+    /// Half the resolution of the mask along each axis.
+    Half,
+    /// A quarter of the resolution of the mask along each axis, for very wide
+    /// glows on low-end hardware. Silhouette features narrower than a few
+    /// pixels may be missed.
+    Quarter,
+}
+
+impl OutlineResolution {
+    /// Returns the number of mask texels along each axis per texel of the
+    /// jump flood textures.
+    pub(crate) fn divisor(self) -> u32 {
+        match self {
+            OutlineResolution::Full => 1,
+            OutlineResolution::Half => 2,
+            OutlineResolution::Quarter => 4,
         }
     }
 }
 
+/// A filter for upsampling the reduced-resolution distance field, as set by
+/// [`OutlineSettings::set_upsample`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Reflect, FromReflect)]
+#[cfg_attr(feature = "ron", derive(serde::Serialize, serde::Deserialize))]
+#[reflect_value(Debug, PartialEq, Hash, Default)]
+#[cfg_attr(
+    feature = "ron",
+    reflect_value(Debug, PartialEq, Hash, Default, Serialize, Deserialize)
+)]
+pub enum OutlineUpsample {
+    /// Measure distances to the nearest seed as found at half resolution,
+    /// softening the outer edge of the outline over two pixels to hide the
+    /// stair-stepping. This is the cheapest filter.
+    #[default]
+    Nearest,
+    /// Interpolate distances bilinearly between the texels of the
+    /// reduced-resolution field. This smooths wide, soft outlines such as
+    /// glows at the cost of four extra texture samples per fragment, but
+    /// slightly rounds sharp corners of the silhouette.
+    Bilinear,
+    /// Refine the nearest seed against the full-resolution mask, searching
+    /// the block of mask texels around it. This keeps thin outlines as sharp
+    /// as at full resolution, at the cost of sixteen extra texture loads per
+    /// fragment at half resolution, or sixty-four at quarter resolution.
+    EdgeAware,
+}
+
+/// An intermediate buffer to draw in place of the outline, as set by
+/// [`OutlineSettings::set_debug_view`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Reflect, FromReflect)]
+#[cfg_attr(feature = "ron", derive(serde::Serialize, serde::Deserialize))]
+#[reflect_value(Debug, PartialEq, Hash, Default)]
+#[cfg_attr(
+    feature = "ron",
+    reflect_value(Debug, PartialEq, Hash, Default, Serialize, Deserialize)
+)]
+pub enum OutlineDebugView {
+    /// Draw the outline as usual.
+    #[default]
+    None,
+    /// The coverage of the mask, in grayscale.
+    Mask,
+    /// The position of each texel's nearest seed, with the X and Y
+    /// coordinates in the red and green channels. Seeds themselves, which lie
+    /// on the edges of silhouettes, are white.
+    Seeds,
+    /// The distance of each texel from the nearest silhouette, from blue at
+    /// the silhouette to red at twice the width of the view's style, with a
+    /// white contour at the width itself.
+    DistanceHeatmap,
+    /// The Voronoi regions of the seeds, i.e. the texels sharing each
+    /// nearest seed, in arbitrary colors.
+    Voronoi,
+}
+
 const MASK_SHADER_HANDLE: HandleUntyped =
     HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 10400755559809425757);
 const JFA_INIT_SHADER_HANDLE: HandleUntyped =
@@ -111,14 +977,152 @@ const OUTLINE_SHADER_HANDLE: HandleUntyped =
     HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 11094028876979933159);
 const DIMENSIONS_SHADER_HANDLE: HandleUntyped =
     HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 11721531257850828867);
+const JFA_UTILS_SHADER_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 6296427946355926104);
+const SDF_SHADER_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 15863702174316227093);
+const GROUP_COVERAGE_SHADER_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 11819159927653576608);
+const MASK_VERTEX_SHADER_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 3470169227383902446);
+const MASK_BOUNDS_SHADER_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 9842361509286310847);
 
 use crate::graph::outline as outline_graph;
 
+/// Handle to a built-in style for highlighting selected entities: a thin,
+/// solid orange outline.
+///
+/// Built-in styles are registered by the [`OutlinePlugin`], and can be used
+/// with `OUTLINE_STYLE_SELECTION.typed()` without creating any assets.
+pub const OUTLINE_STYLE_SELECTION: HandleUntyped =
+    HandleUntyped::weak_from_u64(OutlineStyle::TYPE_UUID, 4382905672193847620);
+
+/// Handle to a built-in style for highlighting hovered entities: a very thin
+/// white outline.
+///
+/// See [`OUTLINE_STYLE_SELECTION`] for details on built-in styles.
+pub const OUTLINE_STYLE_HOVER: HandleUntyped =
+    HandleUntyped::weak_from_u64(OutlineStyle::TYPE_UUID, 11953360184722104839);
+
+/// Handle to a built-in style for a soft glow around entities.
+///
+/// See [`OUTLINE_STYLE_SELECTION`] for details on built-in styles.
+pub const OUTLINE_STYLE_GLOW: HandleUntyped =
+    HandleUntyped::weak_from_u64(OutlineStyle::TYPE_UUID, 9217408251638790113);
+
 impl Plugin for OutlinePlugin {
     fn build(&self, app: &mut App) {
-        app.add_plugin(RenderAssetPlugin::<OutlineStyle>::default())
-            .add_asset::<OutlineStyle>()
-            .init_resource::<OutlineSettings>();
+        app.add_asset::<OutlineStyle>().add_system_to_stage(
+            CoreStage::PostUpdate,
+            outline::update_styles_with_modified_textures,
+        );
+
+        #[cfg(feature = "ron")]
+        app.init_asset_loader::<loader::OutlineStyleLoader>();
+
+        let mut styles = app.world.resource_mut::<Assets<OutlineStyle>>();
+        styles.set_untracked(
+            OUTLINE_STYLE_SELECTION,
+            OutlineStyle {
+                color: Color::rgb(1.0, 0.6, 0.1),
+                width: 4.0,
+                ..Default::default()
+            },
+        );
+        styles.set_untracked(
+            OUTLINE_STYLE_HOVER,
+            OutlineStyle {
+                color: Color::WHITE,
+                width: 2.0,
+                ..Default::default()
+            },
+        );
+        styles.set_untracked(
+            OUTLINE_STYLE_GLOW,
+            OutlineStyle {
+                color: Color::rgb(0.5, 0.8, 1.0),
+                fill: OutlineFill::Glow {
+                    radius: 24.0,
+                    exponent: 2.0,
+                },
+                ..Default::default()
+            },
+        );
+
+        MinimalOutlinePlugin.build(app);
+
+        let render_app = match app.get_sub_app_mut(RenderApp) {
+            Ok(r) => r,
+            Err(_) => return,
+        };
+
+        render_app
+            .init_resource::<outline::ExtractedStyleAssets>()
+            .init_resource::<RenderAssets<OutlineStyle>>()
+            .add_system_to_stage(RenderStage::Extract, outline::extract_outline_style_assets)
+            .add_system_to_stage(RenderStage::Prepare, outline::prepare_outline_style_assets);
+    }
+}
+
+impl Plugin for MinimalOutlinePlugin {
+    fn build(&self, app: &mut App) {
+        init_jfa_texture_format(app);
+        app.init_resource::<OutlineSettings>()
+            .register_type::<OutlineSettings>()
+            .register_type::<OutlineDebugView>()
+            .register_type::<OutlineResolution>()
+            .register_type::<OutlineUpsample>()
+            .register_type::<Outline>()
+            .register_type::<CameraOutline>()
+            .register_type::<OutlineStyle>()
+            .register_type::<InnerOutline>()
+            .register_type::<OutlineUnits>()
+            .register_type::<OutlineFalloff>()
+            .register_type::<DistanceMetric>()
+            .register_type::<OutlineColorSource>()
+            .register_type::<OutlineOcclusion>()
+            .register_type::<OutlinePulseTarget>()
+            .register_type::<OutlineFill>()
+            .register_type::<OutlineGroup>()
+            .register_type::<OutlineOnly>()
+            .register_type::<OutlineDepthOcclusion>()
+            .register_type::<OutlineJitter>()
+            .register_type::<JfaTextureFormat>()
+            .register_type::<OutlineJfaFormat>()
+            .register_type::<OutlineOverlay>()
+            .register_type::<OutlineScreenMask>()
+            .register_type::<OutlineStyleTween>()
+            .register_type::<OutlineEasing>()
+            .register_type::<OutlineFlash>()
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                capture::enable_capture_image_rendering,
+            )
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                capture::advance_outline_atlas_captures.before(VisibilitySystems::CheckVisibility),
+            )
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                validate_outline_jfa_formats.before(textures::resize_outline_textures),
+            )
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                textures::resize_outline_textures.after(CameraUpdateSystem),
+            )
+            .add_event::<OutlineShown>()
+            .add_event::<OutlineHidden>()
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                visibility::send_outline_visibility_events
+                    .after(VisibilitySystems::CheckVisibility),
+            )
+            .add_system(tween::tween_outline_styles)
+            .add_system(flash::advance_outline_flashes);
+
+        #[cfg(feature = "scene")]
+        app.add_system(scene::outline_scene_instances);
 
         let mut shaders = app.world.get_resource_mut::<Assets<Shader>>().unwrap();
 
@@ -130,6 +1134,13 @@ impl Plugin for OutlinePlugin {
         let outline_shader = Shader::from_wgsl(include_str!("shaders/outline.wgsl"));
         let dimensions_shader = Shader::from_wgsl(include_str!("shaders/dimensions.wgsl"))
             .with_import_path("outline::dimensions");
+        let jfa_utils_shader = Shader::from_wgsl(include_str!("shaders/jfa_utils.wgsl"))
+            .with_import_path("outline::jfa_utils");
+        let sdf_shader =
+            Shader::from_wgsl(include_str!("shaders/sdf.wgsl")).with_import_path("outline::sdf");
+        let mask_vertex_shader = Shader::from_wgsl(include_str!("shaders/mask_vertex.wgsl"))
+            .with_import_path("outline::mask_vertex");
+        let mask_bounds_shader = Shader::from_wgsl(include_str!("shaders/mask_bounds.wgsl"));
 
         shaders.set_untracked(MASK_SHADER_HANDLE, mask_shader);
         shaders.set_untracked(JFA_INIT_SHADER_HANDLE, jfa_init_shader);
@@ -137,6 +1148,12 @@ impl Plugin for OutlinePlugin {
         shaders.set_untracked(FULLSCREEN_SHADER_HANDLE, fullscreen_shader);
         shaders.set_untracked(OUTLINE_SHADER_HANDLE, outline_shader);
         shaders.set_untracked(DIMENSIONS_SHADER_HANDLE, dimensions_shader);
+        shaders.set_untracked(JFA_UTILS_SHADER_HANDLE, jfa_utils_shader);
+        shaders.set_untracked(SDF_SHADER_HANDLE, sdf_shader);
+        shaders.set_untracked(MASK_VERTEX_SHADER_HANDLE, mask_vertex_shader);
+        shaders.set_untracked(MASK_BOUNDS_SHADER_HANDLE, mask_bounds_shader);
+
+        app.add_plugin(UniformComponentPlugin::<mask::MaskUniform>::default());
 
         let render_app = match app.get_sub_app_mut(RenderApp) {
             Ok(r) => r,
@@ -150,17 +1167,71 @@ impl Plugin for OutlinePlugin {
             .init_resource::<resources::OutlineResources>()
             .init_resource::<mask::MeshMaskPipeline>()
             .init_resource::<SpecializedMeshPipelines<mask::MeshMaskPipeline>>()
+            .init_resource::<mask::MeshMaskViewKeys>()
+            .init_resource::<mask_bounds::MaskBoundsPipeline>()
+            .init_resource::<mask_bounds::MaskBoundsBuffers>()
             .init_resource::<jfa_init::JfaInitPipeline>()
+            .init_resource::<SpecializedRenderPipelines<jfa_init::JfaInitPipeline>>()
             .init_resource::<jfa::JfaPipeline>()
+            .init_resource::<SpecializedRenderPipelines<jfa::JfaPipeline>>()
             .init_resource::<outline::OutlinePipeline>()
             .init_resource::<SpecializedRenderPipelines<outline::OutlinePipeline>>()
+            .init_resource::<outline::PreparedStyleCache>()
+            .init_resource::<outline::ViewOutlineStyles>()
+            .init_resource::<outline::ResolvedViewStyles>()
+            .init_resource::<outline::OutlineViewUniforms>()
+            .init_resource::<reuse::RetainedOutlineTextures>()
             .add_system_to_stage(RenderStage::Extract, extract_outline_settings)
             .add_system_to_stage(RenderStage::Extract, extract_camera_outlines)
+            .add_system_to_stage(RenderStage::Extract, outline::extract_camera_outline_styles)
+            .add_system_to_stage(RenderStage::Extract, outline::extract_outline_view_uniforms)
+            .add_system_to_stage(RenderStage::Extract, outline::extract_outline_time)
             .add_system_to_stage(RenderStage::Extract, extract_mask_camera_phase)
-            .add_system_to_stage(RenderStage::Prepare, resources::recreate_outline_resources)
-            .add_system_to_stage(RenderStage::Queue, queue_mesh_masks);
+            .add_system_to_stage(
+                RenderStage::Extract,
+                mask_bounds::read_mask_bounds.before(scissor::extract_silhouette_bounds),
+            )
+            .add_system_to_stage(RenderStage::Extract, scissor::extract_silhouette_bounds)
+            .add_system_to_stage(RenderStage::Extract, reuse::extract_static_outline_views)
+            .add_system_to_stage(RenderStage::Extract, capture::extract_outline_captures)
+            .add_system_to_stage(RenderStage::Extract, textures::extract_outline_textures)
+            .add_system_to_stage(RenderStage::Extract, mask::extract_mask_uniforms)
+            .add_system_to_stage(RenderStage::Extract, mask::extract_custom_mesh_masks)
+            .add_system_to_stage(
+                RenderStage::Prepare,
+                resources::prepare_view_outline_resources,
+            )
+            .add_system_to_stage(RenderStage::Prepare, outline::prepare_view_outline_styles)
+            .add_system_to_stage(
+                RenderStage::Prepare,
+                mask_bounds::prepare_mask_bounds_buffers,
+            )
+            .add_system_to_stage(RenderStage::Prepare, outline::prepare_outline_view_uniforms)
+            .add_system_to_stage(
+                RenderStage::Prepare,
+                mask::prepare_outline_depth_textures.after(core_3d::prepare_core_3d_depth_textures),
+            )
+            .add_system_to_stage(RenderStage::Queue, outline::resolve_view_styles)
+            .add_system_to_stage(
+                RenderStage::Queue,
+                mask::queue_mesh_mask_view_keys
+                    .label(OutlineRenderSystem::QueueMaskViewKeys)
+                    .after(outline::resolve_view_styles),
+            )
+            .add_system_to_stage(
+                RenderStage::Queue,
+                queue_mesh_masks.after(OutlineRenderSystem::QueueMaskViewKeys),
+            )
+            .add_system_to_stage(RenderStage::Queue, mask::queue_mask_view_bind_groups)
+            .add_system_to_stage(RenderStage::Queue, mask::queue_mask_alpha_bind_groups)
+            .add_system_to_stage(RenderStage::Queue, outline::queue_outline_view_bind_groups)
+            .add_system_to_stage(RenderStage::Queue, outline::queue_outline_view_pipelines)
+            .add_system_to_stage(RenderStage::Queue, jfa::queue_view_jfa_pipelines)
+            .add_system_to_stage(RenderStage::Queue, capture::queue_outline_captures)
+            .add_system_to_stage(RenderStage::PhaseSort, reuse::resolve_outline_reuse)
+            .add_system_to_stage(RenderStage::Cleanup, mask_bounds::map_mask_bounds_buffers);
 
-        let outline_graph = graph::outline(render_app).unwrap();
+        let outline_graph = graph::build_outline_graph(&mut render_app.world).unwrap();
 
         let mut root_graph = render_app.world.resource_mut::<RenderGraph>();
         let draw_3d_graph = root_graph.get_sub_graph_mut(core_3d::graph::NAME).unwrap();
@@ -179,21 +1250,54 @@ impl Plugin for OutlinePlugin {
         draw_3d_graph
             .add_node_edge(core_3d::graph::node::MAIN_PASS, outline_driver)
             .unwrap();
+
+        #[cfg(feature = "sprite")]
+        sprite::add_sprite_outlines(app);
+        #[cfg(feature = "sprite")]
+        mesh2d::add_mesh2d_outlines(app);
+        // Must follow sprites, as sprite outlines are ordered before the UI.
+        #[cfg(feature = "ui")]
+        ui::add_ui_outlines(app);
     }
 }
 
-struct MeshMask {
-    distance: f32,
-    pipeline: CachedRenderPipelineId,
-    entity: Entity,
-    draw_function: DrawFunctionId,
+/// Phase item for drawing an entity into the mask of a 3D view.
+///
+/// Items are added to each view's `RenderPhase<MeshMask>` in
+/// `RenderStage::Queue`, after [`OutlineRenderSystem::QueueMaskViewKeys`]. Mesh
+/// entities are queued by the plugin unless they have a [`CustomMeshMask`], in
+/// which case custom systems can queue them with their own draw functions,
+/// such as for instanced geometry.
+///
+/// Draw functions bind the view at group 0, the mesh at group 1 and the
+/// [`SetMaskViewBindGroup`] at group 2, matching the layout of pipelines
+/// specialized from [`MeshMaskPipeline`]. Vertex shaders can use the
+/// `outline::mask_vertex` shader import.
+pub struct MeshMask {
+    /// The view space depth of the entity. Items are drawn from back to front,
+    /// so nearer entities overwrite the colors and groups of farther ones.
+    pub distance: f32,
+    pub pipeline: CachedRenderPipelineId,
+    pub entity: Entity,
+    pub draw_function: DrawFunctionId,
+}
+
+/// Labels for outline systems in the render app, for ordering custom systems
+/// relative to them.
+#[derive(Clone, Debug, Hash, PartialEq, Eq, SystemLabel)]
+pub enum OutlineRenderSystem {
+    /// Fills [`MeshMaskViewKeys`] in `RenderStage::Queue`. Systems queuing
+    /// [`MeshMask`] items must run after this.
+    QueueMaskViewKeys,
 }
 
 impl PhaseItem for MeshMask {
-    type SortKey = FloatOrd;
+    type SortKey = (FloatOrd, Entity);
 
     fn sort_key(&self) -> Self::SortKey {
-        FloatOrd(self.distance)
+        // Ties are broken by entity, as later entities overwrite the colors
+        // and groups of earlier ones in the mask.
+        (FloatOrd(self.distance), self.entity)
     }
 
     fn draw_function(&self) -> DrawFunctionId {
@@ -217,67 +1321,736 @@ type DrawMeshMask = (
     SetItemPipeline,
     SetMeshViewBindGroup<0>,
     SetMeshBindGroup<1>,
+    SetMaskViewBindGroup<2>,
+    SetMaskAlphaBindGroup<3>,
     DrawMesh,
 );
 
 /// Visual style for an outline.
-#[derive(Clone, Debug, PartialEq, TypeUuid)]
+///
+/// This is usually added as an asset and referenced by a [`CameraOutline`],
+/// but may also be added to a camera directly as a component.
+#[derive(Clone, Debug, PartialEq, Component, Reflect, FromReflect, TypeUuid)]
+#[reflect(Component, Default)]
 #[uuid = "256fd556-e497-4df2-8d9c-9bdb1419ee90"]
+#[cfg_attr(feature = "ron", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "ron", serde(default))]
 pub struct OutlineStyle {
+    /// Outline color.
+    ///
+    /// Color components are not clamped by the outline pass, so values above
+    /// `1.0` are preserved when the outline is drawn to a floating-point
+    /// target, such as an [`OutlineCapture`] image with an HDR format. Camera
+    /// targets in Bevy 0.8 always use an LDR format, which clamps them.
     pub color: Color,
+    /// Outline width in the [`units`](OutlineStyle::units) of the style,
+    /// which are physical pixels of the render target by default.
+    ///
+    /// Distances are always measured at the full resolution of the target,
+    /// so the outline keeps its thickness when
+    /// [`OutlineSettings::set_resolution`] is changed.
     pub width: f32,
+    /// Whether to discard outline fragments covered by the outlined object.
+    ///
+    /// By default, the outline is blended over the edges of the object to
+    /// antialias the silhouette. When this is `true`, any fragment with
+    /// nonzero mask coverage is left untouched instead.
+    pub stencil_interior: bool,
+    /// The maximum fraction of the screen the outline may cover, if any.
+    ///
+    /// Coverage is estimated from the screen-space bounding boxes of outlined
+    /// entities, grown by the outline width. When the estimate exceeds this
+    /// value, the outline fades out, disappearing entirely once it would
+    /// cover the whole screen. This prevents huge outlines when an outlined
+    /// entity comes very close to the camera.
+    pub max_coverage: Option<f32>,
+    /// Distance from the camera, in world units, at which the outline of an
+    /// entity starts to fade out.
+    ///
+    /// The outline fades out smoothly between `fade_start` and `fade_end`, so
+    /// that distant entities don't pop in and out of being outlined. Each
+    /// part of the outline fades according to the distance of the nearest
+    /// outlined surface. Fading is disabled unless `fade_end` is greater than
+    /// `fade_start`.
+    pub fade_start: f32,
+    /// Distance from the camera, in world units, beyond which the outline of
+    /// an entity is invisible.
+    ///
+    /// See [`fade_start`](OutlineStyle::fade_start).
+    pub fade_end: f32,
+    /// Scale applied to the outline width along each screen axis.
+    ///
+    /// With a scale of `Vec2::new(2.0, 1.0)`, the outline extends twice as far
+    /// horizontally as it does vertically. This is useful for stylized
+    /// outlines and for targets whose pixels are not square. Distances are
+    /// still flooded using the unscaled metric, so very uneven scales may
+    /// produce slightly irregular outlines.
+    pub axis_scale: Vec2,
+    /// Length in pixels of the dashes in the outline, or `0.0` for a solid
+    /// outline.
+    ///
+    /// Dashes are drawn as diagonal stripes across the outline, separated by
+    /// gaps of the same length.
+    pub dash_length: f32,
+    /// Speed in pixels per second at which the dashes scroll along the
+    /// outline.
+    ///
+    /// A nonzero speed produces a "marching ants" effect, as commonly used to
+    /// indicate a selection.
+    pub dash_speed: f32,
+    /// Fraction by which the outline shrinks or fades at the trough of each
+    /// pulse, from `0.0` for a static outline to `1.0`.
+    ///
+    /// Pulses are animated in the outline shader, so they don't require the
+    /// style to be modified each frame. The outline is at its full width and
+    /// alpha at the peak of each pulse.
+    pub pulse_amplitude: f32,
+    /// Number of pulses per second.
+    pub pulse_frequency: f32,
+    /// Offset of the pulses, as a fraction of their period.
+    ///
+    /// Styles with different phases pulse out of step with each other. With
+    /// a phase of `0.0`, the outline is at its peak at time zero.
+    pub pulse_phase: f32,
+    /// Which properties of the outline pulse.
+    pub pulse_target: OutlinePulseTarget,
+    /// How the area around the silhouette is filled.
+    pub fill: OutlineFill,
+    /// How the alpha of a solid outline decays across its width.
+    pub falloff: OutlineFalloff,
+    /// Width in physical pixels over which the outer edge of a solid outline,
+    /// and of the inner outline, fades out.
+    ///
+    /// Wider edges look softer and shimmer less as the outline moves, while
+    /// narrower ones look crisper. The edge is widened further while
+    /// [`OutlineSettings::set_resolution`] reduces the resolution of the
+    /// distance field without bilinear upsampling, to hide its coarser steps.
+    /// Must be greater than zero.
+    pub edge_width: f32,
+    /// The metric used to measure distances from the silhouette.
+    pub metric: DistanceMetric,
+    /// Where the outline takes its color from.
+    ///
+    /// With a source other than [`OutlineColorSource::Style`], each entity's
+    /// outline is drawn in the color of its [`StandardMaterial`], replacing
+    /// the RGB components of `color` and of the inner outline's color.
+    pub color_source: OutlineColorSource,
+    /// An additional band drawn inside the silhouette, if any.
+    ///
+    /// This is drawn in the same pass as the outer outline, so an entity can
+    /// have both an inner and an outer outline with independent colors and
+    /// widths. Set `width` to `0.0` to draw only the inner outline.
+    pub inner: Option<InnerOutline>,
+    /// Texture modulating the color and alpha of the outline, if any.
+    ///
+    /// The texture is mapped to the screen at its native resolution and
+    /// repeated, so it can be used for hatched, striped or noisy outlines.
+    /// The image should use a filterable color format. In `.outline.ron`
+    /// files, this is given as an asset path, e.g.
+    /// `texture: Some("textures/hatching.png")`.
+    #[cfg_attr(feature = "ron", serde(skip))]
+    pub texture: Option<Handle<Image>>,
+    /// Order in which this style is composited relative to other styles drawn
+    /// by the same camera.
+    ///
+    /// Styles with a lower order are drawn first, beneath styles with a higher
+    /// order; styles with equal order are drawn in an unspecified order. For
+    /// example, a glow can be given a lower order than a crisp line so that
+    /// the line is always drawn on top.
+    pub order: i32,
+    /// Whether the outline is drawn where the outlined entity is visible,
+    /// hidden behind other geometry, or both.
+    pub occlusion: OutlineOcclusion,
+    /// The units of the width and other lengths of the outline.
+    pub units: OutlineUnits,
+}
+
+/// The units in which the lengths of an [`OutlineStyle`] are given.
+///
+/// This applies to the width, the inner width, the glow radius, the band
+/// falloff distances and the dash length and speed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Reflect, FromReflect)]
+#[cfg_attr(feature = "ron", derive(serde::Serialize, serde::Deserialize))]
+#[reflect_value(Debug, PartialEq, Default)]
+#[cfg_attr(
+    feature = "ron",
+    reflect_value(Debug, PartialEq, Default, Serialize, Deserialize)
+)]
+pub enum OutlineUnits {
+    /// Physical pixels of the render target.
+    #[default]
+    Pixels,
+    /// Logical pixels of the render target, converted to physical pixels
+    /// using the scale factor of the target's window.
+    ///
+    /// The outline then has the same apparent thickness on standard and
+    /// high-DPI displays. Image targets have a scale factor of one, as does
+    /// every target in deterministic mode.
+    LogicalPixels,
+    /// World units, converted to pixels using the scale of the camera's
+    /// orthographic projection.
+    ///
+    /// The outline then keeps its thickness relative to the scene as an
+    /// orthographic camera zooms in and out. Cameras with a perspective
+    /// projection have no single scale, and treat world units as pixels.
+    World,
+}
+
+/// How the alpha of a solid outline decays with distance from the silhouette.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Reflect, FromReflect)]
+#[cfg_attr(feature = "ron", derive(serde::Serialize, serde::Deserialize))]
+#[reflect_value(Debug, PartialEq, Default)]
+#[cfg_attr(
+    feature = "ron",
+    reflect_value(Debug, PartialEq, Default, Serialize, Deserialize)
+)]
+pub enum OutlineFalloff {
+    /// Fully opaque across the whole width, with an antialiased outer edge.
+    #[default]
+    Hard,
+    /// Fades linearly to transparent at the outer edge.
+    Linear,
+    /// Fades to transparent at the outer edge along a smoothstep curve.
+    Smoothstep,
+    /// Fades exponentially, with alpha `exp(-k * d / width)` at distance `d`.
+    Exponential { k: f32 },
+    /// Fully opaque up to `start` pixels from the silhouette, then fades to
+    /// transparent at `end` pixels along `easing`.
+    ///
+    /// The band replaces the style's `width`, which is ignored.
+    Band {
+        start: f32,
+        end: f32,
+        easing: OutlineEasing,
+    },
+}
+
+/// The metric used to measure distances from an outlined silhouette.
+///
+/// This determines the shape of the outline around corners: Euclidean
+/// distance produces rounded corners, Chebyshev distance square corners and
+/// Manhattan distance diamond-shaped corners.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Reflect, FromReflect)]
+#[cfg_attr(feature = "ron", derive(serde::Serialize, serde::Deserialize))]
+#[reflect_value(Debug, PartialEq, Default)]
+#[cfg_attr(
+    feature = "ron",
+    reflect_value(Debug, PartialEq, Default, Serialize, Deserialize)
+)]
+pub enum DistanceMetric {
+    #[default]
+    Euclidean = 0,
+    Chebyshev = 1,
+    Manhattan = 2,
+}
+
+/// Where an outline takes its color from.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Reflect, FromReflect)]
+#[cfg_attr(feature = "ron", derive(serde::Serialize, serde::Deserialize))]
+#[reflect_value(Debug, PartialEq, Default)]
+#[cfg_attr(
+    feature = "ron",
+    reflect_value(Debug, PartialEq, Default, Serialize, Deserialize)
+)]
+pub enum OutlineColorSource {
+    /// The color given by the style.
+    #[default]
+    Style = 0,
+    /// The `base_color` of the entity's `StandardMaterial`.
+    ///
+    /// The material's base color texture is not taken into account.
+    BaseColor = 1,
+    /// The `emissive` color of the entity's `StandardMaterial`.
+    Emissive = 2,
+}
+
+/// Where an outline is drawn, depending on whether the outlined entity is
+/// hidden behind other geometry.
+///
+/// Each pixel of the outline takes the visibility of the nearest entity at
+/// that point, as determined by comparing the mask against the main pass
+/// depth. Views only perform this test while they draw a style which needs
+/// it.
+///
+/// The discriminants must be kept in sync with the `OUTLINE_OCCLUSION_*`
+/// constants in `outline.wgsl`.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Reflect, FromReflect,
+)]
+#[cfg_attr(feature = "ron", derive(serde::Serialize, serde::Deserialize))]
+#[reflect_value(Debug, PartialEq, Default)]
+#[cfg_attr(
+    feature = "ron",
+    reflect_value(Debug, PartialEq, Default, Serialize, Deserialize)
+)]
+pub enum OutlineOcclusion {
+    /// Drawn regardless of occlusion.
+    #[default]
+    Always = 0,
+    /// Drawn only where the entity is visible.
+    Visible = 1,
+    /// Drawn only where the entity is hidden, e.g. to show a character
+    /// through walls.
+    Occluded = 2,
+}
+
+/// A band drawn along the inside edge of an outlined silhouette.
+#[derive(Clone, Debug, PartialEq, Reflect, FromReflect)]
+#[cfg_attr(feature = "ron", derive(serde::Serialize, serde::Deserialize))]
+pub struct InnerOutline {
+    pub color: Color,
+    /// Width of the band, in the [`units`](OutlineStyle::units) of its style.
+    pub width: f32,
+}
+
+/// The properties of an outline animated by the pulses of its
+/// [`OutlineStyle`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Reflect, FromReflect)]
+#[cfg_attr(feature = "ron", derive(serde::Serialize, serde::Deserialize))]
+#[reflect_value(Debug, PartialEq, Default)]
+#[cfg_attr(
+    feature = "ron",
+    reflect_value(Debug, PartialEq, Default, Serialize, Deserialize)
+)]
+pub enum OutlinePulseTarget {
+    /// The width of the outline, the inner outline and the glow radius.
+    Width,
+    /// The alpha of the outline.
+    #[default]
+    Alpha,
+    /// Both the width and the alpha of the outline.
+    Both,
+}
+
+/// How the area around an outlined silhouette is filled.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Reflect, FromReflect)]
+#[cfg_attr(feature = "ron", derive(serde::Serialize, serde::Deserialize))]
+#[reflect_value(Debug, PartialEq, Default)]
+#[cfg_attr(
+    feature = "ron",
+    reflect_value(Debug, PartialEq, Default, Serialize, Deserialize)
+)]
+pub enum OutlineFill {
+    /// A solid band of the outline color, [`OutlineStyle::width`] pixels wide.
+    #[default]
+    Solid,
+    /// A soft glow which fades out with distance from the silhouette.
+    ///
+    /// The alpha at distance `d` from the silhouette is
+    /// `(1 - d / radius).powf(exponent)`, so higher exponents produce a
+    /// tighter glow. The radius must be greater than zero.
+    /// [`OutlineStyle::width`] is ignored.
+    Glow { radius: f32, exponent: f32 },
+}
+
+impl Default for OutlineStyle {
+    fn default() -> Self {
+        Self {
+            color: Color::hex("b4a2c8").unwrap(),
+            width: 32.0,
+            stencil_interior: false,
+            max_coverage: None,
+            fade_start: 0.0,
+            fade_end: 0.0,
+            axis_scale: Vec2::ONE,
+            dash_length: 0.0,
+            dash_speed: 0.0,
+            pulse_amplitude: 0.0,
+            pulse_frequency: 1.0,
+            pulse_phase: 0.0,
+            pulse_target: OutlinePulseTarget::Alpha,
+            fill: OutlineFill::Solid,
+            falloff: OutlineFalloff::Hard,
+            edge_width: 1.0,
+            metric: DistanceMetric::Euclidean,
+            color_source: OutlineColorSource::Style,
+            inner: None,
+            texture: None,
+            order: 0,
+            occlusion: OutlineOcclusion::Always,
+            units: OutlineUnits::Pixels,
+        }
+    }
+}
+
+impl OutlineStyle {
+    /// Checks the style for values which would render incorrectly with the
+    /// given settings.
+    ///
+    /// This is intended for editors and loaders to surface authoring errors
+    /// early. Invalid styles can still be used, but may render incorrectly or
+    /// not at all. The first problem found is returned.
+    pub fn validate(&self, settings: &OutlineSettings) -> Result<(), OutlineStyleError> {
+        use OutlineStyleError::*;
+
+        let finite = |field, value: f32| value.is_finite().then_some(()).ok_or(NonFinite { field });
+        let non_negative = |field, value: f32| {
+            finite(field, value)?;
+            (value >= 0.0).then_some(()).ok_or(Negative { field })
+        };
+        let color = |field, color: Color| {
+            let rgba = color.as_rgba_f32();
+            if rgba.iter().any(|c| !c.is_finite()) {
+                return Err(NonFinite { field });
+            }
+            if rgba[..3].iter().any(|&c| c > 1.0) {
+                return Err(HdrColor { field });
+            }
+            Ok(())
+        };
+
+        color("color", self.color)?;
+        non_negative("width", self.width)?;
+        if let Some(max_coverage) = self.max_coverage {
+            finite("max_coverage", max_coverage)?;
+            if !(0.0..=1.0).contains(&max_coverage) {
+                return Err(OutOfRange {
+                    field: "max_coverage",
+                });
+            }
+        }
+        non_negative("fade_start", self.fade_start)?;
+        non_negative("fade_end", self.fade_end)?;
+        finite("axis_scale", self.axis_scale.x)?;
+        finite("axis_scale", self.axis_scale.y)?;
+        if self.axis_scale.min_element() <= 0.0 {
+            return Err(OutOfRange {
+                field: "axis_scale",
+            });
+        }
+        non_negative("dash_length", self.dash_length)?;
+        finite("dash_speed", self.dash_speed)?;
+        finite("pulse_amplitude", self.pulse_amplitude)?;
+        if !(0.0..=1.0).contains(&self.pulse_amplitude) {
+            return Err(OutOfRange {
+                field: "pulse_amplitude",
+            });
+        }
+        non_negative("pulse_frequency", self.pulse_frequency)?;
+        finite("pulse_phase", self.pulse_phase)?;
+        finite("edge_width", self.edge_width)?;
+        if self.edge_width <= 0.0 {
+            return Err(OutOfRange {
+                field: "edge_width",
+            });
+        }
+
+        if let OutlineFill::Glow { radius, exponent } = self.fill {
+            finite("fill.radius", radius)?;
+            if radius <= 0.0 {
+                return Err(OutOfRange {
+                    field: "fill.radius",
+                });
+            }
+            non_negative("fill.exponent", exponent)?;
+        }
+
+        match self.falloff {
+            OutlineFalloff::Exponential { k } => finite("falloff.k", k)?,
+            OutlineFalloff::Band { start, end, .. } => {
+                non_negative("falloff.start", start)?;
+                non_negative("falloff.end", end)?;
+                if start > end {
+                    return Err(OutOfRange {
+                        field: "falloff.start",
+                    });
+                }
+            }
+            _ => (),
+        }
+
+        if let Some(inner) = &self.inner {
+            color("inner.color", inner.color)?;
+            non_negative("inner.width", inner.width)?;
+        }
+
+        // The flood can't reach further than the furthest jump, nor beyond
+        // the framebuffer if its size is fixed. Lengths in other units depend
+        // on the camera and its target, so they can't be checked here.
+        if self.units != OutlineUnits::Pixels {
+            return Ok(());
+        }
+        let mut max = resources::MAX_JFA_EXTENT;
+        if let Some(size) = settings.deterministic {
+            max = max.min((size.max_element() + 2 * settings.offscreen_padding) as f32);
+        }
+        let extent = outline::OutlineParams::from_style(self).extent();
+        if extent > max {
+            return Err(ExtentTooLarge { extent, max });
+        }
+
+        Ok(())
+    }
+}
+
+/// A problem with an [`OutlineStyle`], as found by
+/// [`OutlineStyle::validate`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum OutlineStyleError {
+    /// A field is NaN or infinite.
+    NonFinite { field: &'static str },
+    /// A width, distance or similar field is negative.
+    Negative { field: &'static str },
+    /// A field is outside of its valid range.
+    OutOfRange { field: &'static str },
+    /// A color has components above `1.0`.
+    ///
+    /// Such components are clamped when drawn to a camera target, as these
+    /// always use an LDR format in Bevy 0.8. They are only preserved by an
+    /// [`OutlineCapture`] into an image with a floating-point format.
+    HdrColor { field: &'static str },
+    /// The outline extends further from the silhouette, in pixels, than the
+    /// distance field can reach.
+    ExtentTooLarge { extent: f32, max: f32 },
 }
 
+impl std::fmt::Display for OutlineStyleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OutlineStyleError::NonFinite { field } => write!(f, "`{field}` is not finite"),
+            OutlineStyleError::Negative { field } => write!(f, "`{field}` is negative"),
+            OutlineStyleError::OutOfRange { field } => write!(f, "`{field}` is out of range"),
+            OutlineStyleError::HdrColor { field } => write!(
+                f,
+                "`{field}` has components above 1.0, which are clamped on camera targets"
+            ),
+            OutlineStyleError::ExtentTooLarge { extent, max } => write!(
+                f,
+                "the outline extends {extent} pixels from the silhouette, but at most {max} \
+                 are supported"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for OutlineStyleError {}
+
 impl RenderAsset for OutlineStyle {
-    type ExtractedAsset = OutlineParams;
+    type ExtractedAsset = ExtractedOutlineStyle;
     type PreparedAsset = GpuOutlineParams;
     type Param = (
         Res<'static, RenderDevice>,
         Res<'static, RenderQueue>,
         Res<'static, OutlineResources>,
+        Res<'static, RenderAssets<Image>>,
+        ResMut<'static, PreparedStyleCache>,
     );
 
     fn extract_asset(&self) -> Self::ExtractedAsset {
-        OutlineParams::new(self.color, self.width)
+        ExtractedOutlineStyle::from_style(self)
     }
 
+    // Assets are prepared by `prepare_outline_style_assets` instead, which
+    // also updates modified styles in place.
     fn prepare_asset(
         extracted_asset: Self::ExtractedAsset,
-        (device, queue, outline_res): &mut SystemParamItem<Self::Param>,
+        (device, queue, outline_res, images, cache): &mut SystemParamItem<Self::Param>,
     ) -> Result<Self::PreparedAsset, PrepareAssetError<Self::ExtractedAsset>> {
-        let mut buffer = UniformBuffer::from(extracted_asset.clone());
-        buffer.write_buffer(device, queue);
-
-        let bind_group = device.create_bind_group(&BindGroupDescriptor {
-            label: None,
-            layout: &outline_res.outline_params_bind_group_layout,
-            entries: &[BindGroupEntry {
-                binding: 0,
-                resource: buffer.buffer().unwrap().as_entire_binding(),
-            }],
-        });
-
-        Ok(GpuOutlineParams {
-            params: extracted_asset,
-            _buffer: buffer,
-            bind_group,
-        })
+        cache
+            .get_or_prepare(extracted_asset.clone(), images, device, queue, outline_res)
+            .ok_or(PrepareAssetError::RetryNextUpdate(extracted_asset))
     }
 }
 
 /// Component for enabling outlines when rendering with a given camera.
-#[derive(Clone, Debug, PartialEq, Component)]
+///
+/// Cameras with a `viewport` are outlined within their viewport only, so
+/// several cameras can share a window, e.g. the top, front, side and
+/// perspective views of an editor. Each camera's mask and distance field are
+/// sized to its viewport, so outlines are measured in physical pixels
+/// regardless of the size of the viewport, and cameras can share style
+/// handles.
+///
+/// Cameras rendering to an [`Image`] are outlined as well, using the image's
+/// texture format, e.g. for minimaps or portals.
+#[derive(Clone, Debug, PartialEq, Component, Reflect, FromReflect)]
+#[reflect(Component, Default)]
 pub struct CameraOutline {
     pub enabled: bool,
+    /// The primary outline style.
+    ///
+    /// This style determines the distance metric, color source and maximum
+    /// coverage of the outline, and is the only style drawn into an
+    /// [`OutlineCapture`].
     pub style: Handle<OutlineStyle>,
+    /// Additional styles drawn over the same distance field, e.g. a wide faint
+    /// glow beneath a thin crisp line.
+    ///
+    /// These are drawn together with `style`, from lowest to highest
+    /// [`OutlineStyle::order`]. Layers which haven't been prepared yet are
+    /// skipped.
+    pub layers: Vec<Handle<OutlineStyle>>,
+    /// Styles for entities in specific [`OutlineGroup`]s.
+    ///
+    /// Entities in a group with a style here are outlined with that style
+    /// instead of `style` and `layers`. Where the outlines of different groups
+    /// meet, each pixel takes the style of the nearest entity's group. Group
+    /// styles are drawn together with the other layers in order of
+    /// [`OutlineStyle::order`], and are skipped until they have been
+    /// prepared.
+    pub groups: HashMap<u8, Handle<OutlineStyle>>,
+    /// Style drawn in place of `style` where the outlined entity is hidden
+    /// behind other geometry, e.g. a dimmed dashed line.
+    ///
+    /// Each pixel of the outline takes the visibility of the nearest entity
+    /// at that point, as determined by comparing the mask against the main
+    /// pass depth. This overrides the [`OutlineStyle::occlusion`] of both
+    /// styles, while `layers` and `groups` keep their own.
+    pub occluded: Option<Handle<OutlineStyle>>,
+}
+
+impl Default for CameraOutline {
+    /// Outlines the camera with the built-in [`OUTLINE_STYLE_SELECTION`].
+    fn default() -> Self {
+        CameraOutline {
+            enabled: true,
+            style: OUTLINE_STYLE_SELECTION.typed(),
+            layers: Vec::new(),
+            groups: HashMap::default(),
+            occluded: None,
+        }
+    }
+}
+
+/// Component assigning an outlined entity to a group, e.g. to outline allies
+/// and enemies in different colors.
+///
+/// Groups are styled using [`CameraOutline::groups`]. Entities without this
+/// component are in group `0`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Component, Reflect, FromReflect)]
+#[reflect(Component, Default)]
+pub struct OutlineGroup(pub u8);
+
+/// Component for cameras which should render only outlines.
+///
+/// This is useful for overlay cameras which highlight entities without drawing
+/// the rest of the scene. By default, the outline is drawn over the existing
+/// contents of the camera's target, so an outline-only camera layered over a
+/// window another camera already drew keeps that camera's image. Such cameras
+/// should use `ClearColorConfig::None` and see nothing else in their main
+/// pass, e.g. using `RenderLayers`.
+#[derive(Clone, Debug, Default, PartialEq, Component, Reflect, FromReflect)]
+#[reflect(Component, Default)]
+pub struct OutlineOnly {
+    /// The color the camera's whole target is cleared to before the outline
+    /// is drawn, if any, hiding everything drawn to it so far.
+    pub clear_color: Option<Color>,
+    /// Whether outlined entities are filled with the outline color.
+    pub fill_silhouettes: bool,
+}
+
+/// Component for drawing a camera's outline onto another camera's target.
+///
+/// The outline is computed from the entities seen by the camera with this
+/// component, but composited onto the target of `camera`, stretched to fit its
+/// viewport. This allows a dedicated overlay camera (typically orthographic)
+/// to draw markers or other UI on top of the outlines, or to show them in a
+/// picture-in-picture view, reusing the outlined camera's distance field.
+///
+/// The outline is composited while the outlined camera renders, so the
+/// overlay camera should have a higher priority and use
+/// `ClearColorConfig::None` to preserve it. Its target must have the same
+/// texture format as the outlined camera's, and its viewport the same aspect
+/// ratio.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Component, Reflect, FromReflect)]
+#[reflect(Component, MapEntities)]
+pub struct OutlineOverlay {
+    pub camera: Entity,
+}
+
+// Reflected components must be constructible from the world, but an overlay
+// is only meaningful with a real camera, so the placeholder is never valid.
+impl FromWorld for OutlineOverlay {
+    fn from_world(_world: &mut World) -> Self {
+        OutlineOverlay {
+            camera: Entity::from_raw(u32::MAX),
+        }
+    }
+}
+
+impl MapEntities for OutlineOverlay {
+    fn map_entities(&mut self, entity_map: &EntityMap) -> Result<(), MapEntitiesError> {
+        self.camera = entity_map.get(self.camera)?;
+        Ok(())
+    }
+}
+
+/// Component for hiding a camera's outlines behind nearer geometry.
+///
+/// By default, outlines are drawn over the whole scene, so they show through
+/// walls and other objects in front of the outlined entity. With this
+/// component, each pixel of the outline is compared against the camera's
+/// depth buffer and discarded if the geometry there is nearer than the
+/// outlined entity the pixel belongs to. As a result, outlines are also cut
+/// off where they overlap a surface in front of the entity, such as the floor
+/// it stands on. An [`OutlineCapture`] is not occluded.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Component, Reflect, FromReflect)]
+#[reflect(Component, Default)]
+pub struct OutlineDepthOcclusion;
+
+/// Component for cameras whose projection is jittered, e.g. for temporal
+/// anti-aliasing.
+///
+/// A jittered projection moves the mask by a different subpixel offset each
+/// frame, making the outline shimmer. The mask is drawn with the offset
+/// removed, so the outline stays stable. Whatever applies the jitter should
+/// update this component in the same frame.
+///
+/// The outline is drawn to the camera target after the main pass, so a
+/// temporal resolve ordered after it with [`graph::run_after_outline`] also
+/// resolves the outline, while one ordered before it with
+/// [`graph::run_before_outline`] leaves the outline unaffected.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Component, Reflect, FromReflect)]
+#[reflect(Component, Default)]
+pub struct OutlineJitter {
+    /// The offset by which the jittered projection moves the image, in
+    /// physical pixels of the viewport with +Y pointing down.
+    pub offset: Vec2,
+}
+
+/// Component for cameras flooding their distance field in a format other than
+/// the app's [`JfaTextureFormat`].
+///
+/// This trades the precision and memory of a camera's outline against those
+/// of other cameras, e.g. to use [`JfaTextureFormat::Rgba8Packed`] for a
+/// minimap while the main camera keeps 16-bit normalized seeds. The format is
+/// validated like the app's, and formats the device doesn't support, or which
+/// can't hold the app's [`JfaNoSeed`], are replaced with their half-precision
+/// float counterpart when the component is added or changed.
+///
+/// [`InnerOutline`]s aren't drawn with two-channel formats. The distance field
+/// of a camera with this component is only copied into the
+/// [`OutlineDebugImages`] if the formats match, while its [`OutlineTextures`]
+/// take on the format.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Component, Reflect, FromReflect)]
+#[reflect(Component, Default)]
+pub struct OutlineJfaFormat(pub JfaTextureFormat);
+
+/// Component for masking a camera's outlines with an image.
+///
+/// The red channel of `image` is stretched over the camera's target and
+/// multiplies the alpha of the outline. This can be used to suppress outlines
+/// behind HUD elements, or to restrict them to a region of the screen such as
+/// a magnifier lens.
+#[derive(Clone, Debug, Default, PartialEq, Component, Reflect, FromReflect)]
+#[reflect(Component, Default)]
+pub struct OutlineScreenMask {
+    pub image: Handle<Image>,
 }
 
 /// Component for entities that should be outlined.
-#[derive(Clone, Debug, PartialEq, Component)]
+#[derive(Clone, Debug, PartialEq, Component, Reflect, FromReflect)]
+#[reflect(Component, Default)]
 pub struct Outline {
     pub enabled: bool,
 }
 
+impl Default for Outline {
+    fn default() -> Self {
+        Outline { enabled: true }
+    }
+}
+
 fn extract_outline_settings(mut commands: Commands, settings: Extract<Res<OutlineSettings>>) {
     commands.insert_resource(settings.clone());
 }
@@ -297,9 +2070,15 @@ fn extract_camera_outlines(
     commands.insert_or_spawn_batch(batches);
 }
 
+/// 3D cameras drawing an outline.
+type OutlinedCamera3dFilter = (
+    With<Camera3d>,
+    Or<(With<CameraOutline>, With<OutlineStyle>, With<OutlineFlash>)>,
+);
+
 fn extract_mask_camera_phase(
     mut commands: Commands,
-    cameras: Extract<Query<Entity, (With<Camera3d>, With<CameraOutline>)>>,
+    cameras: Extract<Query<Entity, OutlinedCamera3dFilter>>,
 ) {
     for entity in cameras.iter() {
         commands
@@ -308,16 +2087,27 @@ fn extract_mask_camera_phase(
     }
 }
 
+/// An outlined mesh drawn into the mask by the built-in mask pipeline.
+type OutlinedMesh = (
+    Entity,
+    &'static Handle<Mesh>,
+    &'static MeshUniform,
+    Option<&'static mask::MaskAlphaTexture>,
+);
+
+#[allow(clippy::too_many_arguments)]
 fn queue_mesh_masks(
     mesh_mask_draw_functions: Res<DrawFunctions<MeshMask>>,
     mesh_mask_pipeline: Res<MeshMaskPipeline>,
     mut pipelines: ResMut<SpecializedMeshPipelines<MeshMaskPipeline>>,
     mut pipeline_cache: ResMut<PipelineCache>,
     render_meshes: Res<RenderAssets<Mesh>>,
-    outline_meshes: Query<(Entity, &Handle<Mesh>, &MeshUniform)>,
+    view_keys: Res<MeshMaskViewKeys>,
+    outline_meshes: Query<OutlinedMesh, (With<Outline>, Without<CustomMeshMask>)>,
     mut views: Query<(
+        Entity,
         &ExtractedView,
-        &mut VisibleEntities,
+        &VisibleEntities,
         &mut RenderPhase<MeshMask>,
     )>,
 ) {
@@ -326,26 +2116,44 @@ fn queue_mesh_masks(
         .get_id::<DrawMeshMask>()
         .unwrap();
 
-    for (view, visible_entities, mut mesh_mask_phase) in views.iter_mut() {
+    for (view_entity, view, visible_entities, mut mesh_mask_phase) in views.iter_mut() {
+        let view_key = match view_keys.get(view_entity) {
+            Some(k) => k,
+            None => continue,
+        };
+
         let view_matrix = view.transform.compute_matrix();
         let inv_view_row_2 = view_matrix.inverse().row(2);
 
         for visible_entity in visible_entities.entities.iter().copied() {
-            let (entity, mesh_handle, mesh_uniform) = match outline_meshes.get(visible_entity) {
-                Ok(m) => m,
-                Err(_) => continue,
-            };
+            let (entity, mesh_handle, mesh_uniform, alpha_texture) =
+                match outline_meshes.get(visible_entity) {
+                    Ok(m) => m,
+                    Err(_) => continue,
+                };
 
             let mesh = match render_meshes.get(mesh_handle) {
                 Some(m) => m,
                 None => continue,
             };
 
-            let key = MeshPipelineKey::from_primitive_topology(mesh.primitive_topology);
+            let key = MeshMaskPipelineKey {
+                alpha_mask: alpha_texture.is_some(),
+                ..view_key.pipeline_key(mesh.primitive_topology)
+            };
 
-            let pipeline = pipelines
-                .specialize(&mut pipeline_cache, &mesh_mask_pipeline, key, &mesh.layout)
-                .unwrap();
+            let pipeline = match pipelines.specialize(
+                &mut pipeline_cache,
+                &mesh_mask_pipeline,
+                key,
+                &mesh.layout,
+            ) {
+                Ok(p) => p,
+                Err(e) => {
+                    error!("failed to specialize outline mask pipeline: {}", e);
+                    continue;
+                }
+            };
 
             mesh_mask_phase.add(MeshMask {
                 entity,
@@ -356,3 +2164,123 @@ fn queue_mesh_masks(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn validate(style: OutlineStyle) -> Result<(), OutlineStyleError> {
+        style.validate(&OutlineSettings::default())
+    }
+
+    #[test]
+    fn default_style_is_valid() {
+        assert_eq!(validate(OutlineStyle::default()), Ok(()));
+    }
+
+    #[test]
+    fn validate_rejects_invalid_fields() {
+        assert_eq!(
+            validate(OutlineStyle {
+                width: f32::NAN,
+                ..Default::default()
+            }),
+            Err(OutlineStyleError::NonFinite { field: "width" })
+        );
+        assert_eq!(
+            validate(OutlineStyle {
+                width: -1.0,
+                ..Default::default()
+            }),
+            Err(OutlineStyleError::Negative { field: "width" })
+        );
+        assert_eq!(
+            validate(OutlineStyle {
+                max_coverage: Some(1.5),
+                ..Default::default()
+            }),
+            Err(OutlineStyleError::OutOfRange {
+                field: "max_coverage"
+            })
+        );
+        assert_eq!(
+            validate(OutlineStyle {
+                edge_width: 0.0,
+                ..Default::default()
+            }),
+            Err(OutlineStyleError::OutOfRange {
+                field: "edge_width"
+            })
+        );
+        assert_eq!(
+            validate(OutlineStyle {
+                color: Color::rgb(2.0, 0.0, 0.0),
+                ..Default::default()
+            }),
+            Err(OutlineStyleError::HdrColor { field: "color" })
+        );
+        assert_eq!(
+            validate(OutlineStyle {
+                falloff: OutlineFalloff::Band {
+                    start: 8.0,
+                    end: 4.0,
+                    easing: OutlineEasing::Linear,
+                },
+                ..Default::default()
+            }),
+            Err(OutlineStyleError::OutOfRange {
+                field: "falloff.start"
+            })
+        );
+        assert_eq!(
+            validate(OutlineStyle {
+                pulse_amplitude: 1.5,
+                ..Default::default()
+            }),
+            Err(OutlineStyleError::OutOfRange {
+                field: "pulse_amplitude"
+            })
+        );
+    }
+
+    #[test]
+    fn validate_rejects_zero_glow_radius() {
+        assert_eq!(
+            validate(OutlineStyle {
+                fill: OutlineFill::Glow {
+                    radius: 0.0,
+                    exponent: 1.0,
+                },
+                ..Default::default()
+            }),
+            Err(OutlineStyleError::OutOfRange {
+                field: "fill.radius"
+            })
+        );
+    }
+
+    #[test]
+    fn validate_rejects_excessive_extent() {
+        let width = resources::MAX_JFA_EXTENT + 1.0;
+        assert_eq!(
+            validate(OutlineStyle {
+                width,
+                ..Default::default()
+            }),
+            Err(OutlineStyleError::ExtentTooLarge {
+                extent: width,
+                max: resources::MAX_JFA_EXTENT,
+            })
+        );
+
+        // Lengths in other units can't be checked.
+        assert_eq!(
+            validate(OutlineStyle {
+                width,
+                units: OutlineUnits::World,
+                ..Default::default()
+            }),
+            Ok(())
+        );
+    }
+}
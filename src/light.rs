@@ -0,0 +1,433 @@
+use bevy::{
+    asset::HandleId,
+    prelude::*,
+    reflect::TypeUuid,
+    render::{
+        main_graph,
+        render_asset::RenderAssets,
+        render_graph::{Node, NodeRunError, RenderGraph, RenderGraphContext},
+        render_phase::TrackedRenderPass,
+        render_resource::{
+            BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
+            BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource, BindingType,
+            BlendComponent, BlendFactor, BlendOperation, BlendState, BufferBindingType,
+            CachedRenderPipelineId, ColorTargetState, ColorWrites, DynamicUniformBuffer,
+            FragmentState, LoadOp, MultisampleState, Operations, PipelineCache,
+            RenderPassColorAttachment, RenderPassDescriptor, RenderPipelineDescriptor,
+            SamplerBindingType, ShaderStages, ShaderType, TextureFormat, TextureSampleType,
+            TextureUsages, TextureViewDimension, VertexState,
+        },
+        renderer::{RenderContext, RenderDevice, RenderQueue},
+        Extract, RenderApp, RenderStage,
+    },
+    utils::HashSet,
+};
+
+use crate::{
+    jfa_request::JfaRequestNode, resources::OutlineResources, JfaNoSeed, JfaRequest,
+    JfaTextureFormat, FULLSCREEN_PRIMITIVE_STATE, FULLSCREEN_SHADER_HANDLE,
+};
+
+const LIGHT_FALLOFF_SHADER_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 6650170852383142296);
+
+const LIGHT_TEXTURE_FORMAT: TextureFormat = TextureFormat::Rgba8Unorm;
+
+/// Plugin for 2D area lights whose falloff follows a distance field.
+///
+/// Each entity with both a [`JfaRequest`] and a [`LightFalloff2d`] lights the
+/// `target` of its [`LightFalloff2d`] around the request's seed, attenuated
+/// by the distance to the nearest seed. The lights are drawn after the jump
+/// flood requests, and before any camera is rendered.
+///
+/// This must be added after the [`JfaPlugin`](crate::JfaPlugin), which runs
+/// the jump flood passes.
+#[derive(Default)]
+pub struct LightFalloffPlugin;
+
+impl Plugin for LightFalloffPlugin {
+    fn build(&self, app: &mut App) {
+        crate::init_jfa_texture_format(app);
+
+        let mut shaders = app.world.resource_mut::<Assets<Shader>>();
+        shaders.set_untracked(
+            LIGHT_FALLOFF_SHADER_HANDLE,
+            Shader::from_wgsl(include_str!("shaders/light_falloff.wgsl")),
+        );
+
+        app.add_system_to_stage(CoreStage::PostUpdate, resize_light_targets);
+
+        let render_app = match app.get_sub_app_mut(RenderApp) {
+            Ok(r) => r,
+            Err(_) => return,
+        };
+
+        render_app
+            .init_resource::<LightFalloffPipeline>()
+            .init_resource::<LightFalloffUniforms>()
+            .add_system_to_stage(RenderStage::Extract, extract_light_falloffs)
+            .add_system_to_stage(RenderStage::Prepare, prepare_light_falloff_uniforms)
+            .add_system_to_stage(RenderStage::Queue, queue_light_falloff_bind_groups);
+
+        let light_node = LightFalloffNode::new(&mut render_app.world);
+        let mut root_graph = render_app.world.resource_mut::<RenderGraph>();
+        root_graph.add_node(LightFalloffNode::NAME, light_node);
+        root_graph
+            .add_node_edge(JfaRequestNode::NAME, LightFalloffNode::NAME)
+            .unwrap();
+        root_graph
+            .add_node_edge(LightFalloffNode::NAME, main_graph::node::CAMERA_DRIVER)
+            .unwrap();
+    }
+}
+
+/// Component for 2D area lights shaped by the seed of a [`JfaRequest`].
+///
+/// This must be added to an entity with a [`JfaRequest`]. Each frame, `target`
+/// is resized to match the request's seed and cleared, then every light
+/// drawing into it adds its `color` around its seed, in
+/// `TextureFormat::Rgba8Unorm`. Texels covered by the seed are fully lit, and
+/// the light eases out to zero at `radius` pixels from the seed. The
+/// request's `max_dist` should be at least `radius`, as the light is cut off
+/// where no seed is found.
+#[derive(Clone, Debug, Component)]
+pub struct LightFalloff2d {
+    pub color: Color,
+    /// The distance, in pixels, at which the light reaches zero.
+    pub radius: f32,
+    /// The image receiving the light.
+    pub target: Handle<Image>,
+}
+
+impl LightFalloff2d {
+    /// Creates a white light reaching `radius` pixels from the seed, drawing
+    /// into `target`.
+    pub fn new(target: Handle<Image>, radius: f32) -> Self {
+        LightFalloff2d {
+            color: Color::WHITE,
+            radius,
+            target,
+        }
+    }
+}
+
+/// Resizes the targets of lights to match the seeds of their requests.
+fn resize_light_targets(
+    mut images: ResMut<Assets<Image>>,
+    lights: Query<(&JfaRequest, &LightFalloff2d)>,
+) {
+    let usage = TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING;
+
+    for (request, light) in lights.iter() {
+        let size = match images.get(&request.seed) {
+            Some(seed) => seed.texture_descriptor.size,
+            None => continue,
+        };
+
+        let up_to_date = images.get(&light.target).is_some_and(|image| {
+            let desc = &image.texture_descriptor;
+            desc.size == size && desc.format == LIGHT_TEXTURE_FORMAT && desc.usage.contains(usage)
+        });
+
+        // Only access the image mutably when necessary, as doing so causes it
+        // to be uploaded to the GPU again.
+        if !up_to_date {
+            if let Some(image) = images.get_mut(&light.target) {
+                image.texture_descriptor.format = LIGHT_TEXTURE_FORMAT;
+                image.texture_descriptor.usage |= usage;
+                image.data.clear();
+                image.resize(size);
+            }
+        }
+    }
+}
+
+#[derive(Component)]
+struct ExtractedLightFalloff {
+    uniform: LightFalloffUniform,
+    distance_field: Handle<Image>,
+    target: Handle<Image>,
+}
+
+fn extract_light_falloffs(
+    mut commands: Commands,
+    mut previous_len: Local<usize>,
+    lights: Extract<Query<(Entity, &JfaRequest, &LightFalloff2d)>>,
+) {
+    let mut batches = Vec::with_capacity(*previous_len);
+    for (entity, request, light) in lights.iter() {
+        let uniform = LightFalloffUniform {
+            color: light.color.as_linear_rgba_f32().into(),
+            radius: light.radius,
+        };
+        batches.push((
+            entity,
+            (ExtractedLightFalloff {
+                uniform,
+                distance_field: request.output.clone(),
+                target: light.target.clone(),
+            },),
+        ));
+    }
+    *previous_len = batches.len();
+    commands.insert_or_spawn_batch(batches);
+}
+
+#[derive(Clone, ShaderType)]
+struct LightFalloffUniform {
+    color: Vec4,
+    radius: f32,
+}
+
+#[derive(Default)]
+struct LightFalloffUniforms {
+    uniforms: DynamicUniformBuffer<LightFalloffUniform>,
+    bind_group: Option<BindGroup>,
+}
+
+#[derive(Component)]
+struct LightFalloffUniformOffset {
+    offset: u32,
+}
+
+#[derive(Component)]
+struct LightFalloffBindGroup {
+    distance_field: BindGroup,
+}
+
+fn prepare_light_falloff_uniforms(
+    mut commands: Commands,
+    device: Res<RenderDevice>,
+    queue: Res<RenderQueue>,
+    mut light_uniforms: ResMut<LightFalloffUniforms>,
+    lights: Query<(Entity, &ExtractedLightFalloff)>,
+) {
+    light_uniforms.uniforms.clear();
+    for (entity, light) in lights.iter() {
+        let offset = light_uniforms.uniforms.push(light.uniform.clone());
+        commands
+            .entity(entity)
+            .insert(LightFalloffUniformOffset { offset });
+    }
+    light_uniforms.uniforms.write_buffer(&device, &queue);
+}
+
+fn queue_light_falloff_bind_groups(
+    mut commands: Commands,
+    device: Res<RenderDevice>,
+    res: Res<OutlineResources>,
+    pipeline: Res<LightFalloffPipeline>,
+    images: Res<RenderAssets<Image>>,
+    mut light_uniforms: ResMut<LightFalloffUniforms>,
+    lights: Query<(Entity, &ExtractedLightFalloff)>,
+) {
+    light_uniforms.bind_group = light_uniforms.uniforms.binding().map(|binding| {
+        device.create_bind_group(&BindGroupDescriptor {
+            label: Some("light_falloff_uniform_bind_group"),
+            layout: &pipeline.uniform_layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: binding,
+            }],
+        })
+    });
+
+    for (entity, light) in lights.iter() {
+        let distance_field = match images.get(&light.distance_field) {
+            Some(d) => d,
+            None => continue,
+        };
+
+        let distance_field = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("light_falloff_distance_field_bind_group"),
+            layout: &pipeline.distance_field_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(&distance_field.texture_view),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::Sampler(&res.sampler),
+                },
+            ],
+        });
+        commands
+            .entity(entity)
+            .insert(LightFalloffBindGroup { distance_field });
+    }
+}
+
+struct LightFalloffPipeline {
+    uniform_layout: BindGroupLayout,
+    distance_field_layout: BindGroupLayout,
+    pipeline_id: CachedRenderPipelineId,
+}
+
+impl FromWorld for LightFalloffPipeline {
+    fn from_world(world: &mut World) -> Self {
+        let device = world.resource::<RenderDevice>();
+
+        let uniform_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("light_falloff_uniform_bind_group_layout"),
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: true,
+                    min_binding_size: Some(LightFalloffUniform::min_size()),
+                },
+                count: None,
+            }],
+        });
+
+        let distance_field_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("light_falloff_distance_field_bind_group_layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: false },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(SamplerBindingType::NonFiltering),
+                    count: None,
+                },
+            ],
+        });
+
+        // Lights sharing a target add up.
+        let additive = BlendComponent {
+            src_factor: BlendFactor::One,
+            dst_factor: BlendFactor::One,
+            operation: BlendOperation::Add,
+        };
+
+        let jfa_format = *world.resource::<JfaTextureFormat>();
+        let jfa_no_seed = *world.resource::<JfaNoSeed>();
+        let mut pipeline_cache = world.resource_mut::<PipelineCache>();
+        let pipeline_id = pipeline_cache.queue_render_pipeline(RenderPipelineDescriptor {
+            label: Some("light_falloff_pipeline".into()),
+            layout: Some(vec![uniform_layout.clone(), distance_field_layout.clone()]),
+            vertex: VertexState {
+                shader: FULLSCREEN_SHADER_HANDLE.typed::<Shader>(),
+                shader_defs: vec![],
+                entry_point: "vertex".into(),
+                buffers: vec![],
+            },
+            fragment: Some(FragmentState {
+                shader: LIGHT_FALLOFF_SHADER_HANDLE.typed::<Shader>(),
+                shader_defs: [jfa_format.shader_defs(), jfa_no_seed.shader_defs()].concat(),
+                entry_point: "fragment".into(),
+                targets: vec![Some(ColorTargetState {
+                    format: LIGHT_TEXTURE_FORMAT,
+                    blend: Some(BlendState {
+                        color: additive,
+                        alpha: additive,
+                    }),
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+            primitive: FULLSCREEN_PRIMITIVE_STATE,
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+        });
+
+        LightFalloffPipeline {
+            uniform_layout,
+            distance_field_layout,
+            pipeline_id,
+        }
+    }
+}
+
+/// Render graph node which draws all [`LightFalloff2d`]s into their targets.
+///
+/// This runs in the main render graph, after the jump flood requests and
+/// before the camera driver.
+struct LightFalloffNode {
+    query: QueryState<(
+        &'static ExtractedLightFalloff,
+        &'static LightFalloffUniformOffset,
+        &'static LightFalloffBindGroup,
+    )>,
+}
+
+impl LightFalloffNode {
+    const NAME: &'static str = "light_falloff";
+
+    fn new(world: &mut World) -> Self {
+        LightFalloffNode {
+            query: QueryState::new(world),
+        }
+    }
+}
+
+impl Node for LightFalloffNode {
+    fn update(&mut self, world: &mut World) {
+        self.query.update_archetypes(world);
+    }
+
+    fn run(
+        &self,
+        _graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let bind_group = match &world.resource::<LightFalloffUniforms>().bind_group {
+            Some(b) => b,
+            None => return Ok(()),
+        };
+
+        let pipeline_cache = world.resource::<PipelineCache>();
+        let pipeline_id = world.resource::<LightFalloffPipeline>().pipeline_id;
+        let pipeline = match pipeline_cache.get_render_pipeline(pipeline_id) {
+            Some(p) => p,
+            None => return Ok(()),
+        };
+
+        let images = world.resource::<RenderAssets<Image>>();
+        let mut cleared: HashSet<HandleId> = HashSet::default();
+        for (light, offset, light_bind_group) in self.query.iter_manual(world) {
+            let target = match images.get(&light.target) {
+                Some(t) if t.texture_format == LIGHT_TEXTURE_FORMAT => t,
+                _ => continue,
+            };
+
+            // The first light drawn into each target clears it.
+            let load = if cleared.insert(light.target.id) {
+                LoadOp::Clear(Color::NONE.into())
+            } else {
+                LoadOp::Load
+            };
+
+            let render_pass =
+                render_context
+                    .command_encoder
+                    .begin_render_pass(&RenderPassDescriptor {
+                        label: Some("light_falloff"),
+                        color_attachments: &[Some(RenderPassColorAttachment {
+                            view: &target.texture_view,
+                            resolve_target: None,
+                            ops: Operations { load, store: true },
+                        })],
+                        depth_stencil_attachment: None,
+                    });
+            let mut tracked_pass = TrackedRenderPass::new(render_pass);
+            tracked_pass.set_render_pipeline(pipeline);
+            tracked_pass.set_bind_group(0, bind_group, &[offset.offset]);
+            tracked_pass.set_bind_group(1, &light_bind_group.distance_field, &[]);
+            tracked_pass.draw(0..3, 0..1);
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,63 @@
+use bevy::asset::{AssetLoader, AssetPath, BoxedFuture, Error, LoadContext, LoadedAsset};
+use serde::Deserialize;
+
+use crate::OutlineStyle;
+
+/// Loader for [`OutlineStyle`] assets stored in `.outline.ron` files.
+///
+/// The file holds an `OutlineStyle` in RON notation. Omitted fields take their
+/// default values, and the fill texture is given as an asset path:
+///
+/// ```ron
+/// OutlineStyle(
+///     color: Rgba(red: 1.0, green: 0.6, blue: 0.1, alpha: 1.0),
+///     width: 4.0,
+///     falloff: Smoothstep,
+///     texture: Some("textures/hatching.png"),
+/// )
+/// ```
+///
+/// Styles are reloaded along with other assets when the `AssetServer` is
+/// watching for changes.
+#[derive(Default)]
+pub struct OutlineStyleLoader;
+
+// The fill texture path, which is read separately as it can't be deserialized
+// into a handle without the load context.
+#[derive(Deserialize)]
+#[serde(rename = "OutlineStyle")]
+struct OutlineStyleTexture {
+    #[serde(default)]
+    texture: Option<String>,
+}
+
+impl AssetLoader for OutlineStyleLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), Error>> {
+        Box::pin(async move {
+            let mut style: OutlineStyle = ron::de::from_bytes(bytes)?;
+            let texture: OutlineStyleTexture = ron::de::from_bytes(bytes)?;
+
+            let texture_path = texture
+                .texture
+                .map(|path| AssetPath::from(path.as_str()).to_owned());
+            if let Some(path) = &texture_path {
+                style.texture = Some(load_context.get_handle(path.get_id()));
+            }
+
+            let mut asset = LoadedAsset::new(style);
+            if let Some(path) = texture_path {
+                asset = asset.with_dependency(path);
+            }
+            load_context.set_default_asset(asset);
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["outline.ron"]
+    }
+}
@@ -0,0 +1,173 @@
+use bevy::{
+    prelude::*,
+    render::{primitives::Aabb, view::VisibilitySystems},
+    transform::TransformSystem,
+};
+
+use crate::Outline;
+
+/// Plugin for highlighting entities inside a selection rectangle, e.g. for
+/// RTS-style drag selection.
+///
+/// While the [`OutlineMarquee`] resource holds a rectangle, the [`Outline`]
+/// of each entity with a [`MarqueeSelectable`] component is enabled if the
+/// screen-space bounds of its `Aabb` intersect the rectangle, and disabled
+/// otherwise. An [`OutlineMarqueeBegin`] event is sent when a rectangle is
+/// set, and an [`OutlineMarqueeEnd`] event listing the selected entities when
+/// it is cleared. Outlines are left as they were at the end of the selection.
+#[derive(Default)]
+pub struct OutlineMarqueePlugin;
+
+impl Plugin for OutlineMarqueePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<OutlineMarquee>()
+            .add_event::<OutlineMarqueeBegin>()
+            .add_event::<OutlineMarqueeEnd>()
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                update_outline_marquee
+                    .after(TransformSystem::TransformPropagate)
+                    .after(VisibilitySystems::CalculateBounds),
+            );
+    }
+}
+
+/// Resource holding the selection rectangle of an [`OutlineMarqueePlugin`].
+#[derive(Clone, Debug, Default)]
+pub struct OutlineMarquee {
+    /// The camera whose view is being selected from.
+    pub camera: Option<Entity>,
+    /// Two opposite corners of the rectangle, in logical pixels from the
+    /// bottom left of the camera's target, as returned by
+    /// `Window::cursor_position`, or `None` when not selecting.
+    pub corners: Option<(Vec2, Vec2)>,
+    // The camera of the selection in progress in the previous frame, if any.
+    active: Option<Entity>,
+}
+
+/// Component for entities which can be selected by the [`OutlineMarquee`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Component)]
+pub struct MarqueeSelectable;
+
+/// Event sent when the [`OutlineMarquee`] starts selecting.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OutlineMarqueeBegin {
+    pub camera: Entity,
+}
+
+/// Event sent when the [`OutlineMarquee`] stops selecting.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OutlineMarqueeEnd {
+    pub camera: Entity,
+    /// The entities inside the rectangle in the last frame of the selection.
+    pub selected: Vec<Entity>,
+}
+
+fn update_outline_marquee(
+    mut marquee: ResMut<OutlineMarquee>,
+    mut begin_events: EventWriter<OutlineMarqueeBegin>,
+    mut end_events: EventWriter<OutlineMarqueeEnd>,
+    cameras: Query<(&Camera, &GlobalTransform)>,
+    mut selectables: Query<
+        (Entity, &GlobalTransform, &Aabb, &mut Outline),
+        With<MarqueeSelectable>,
+    >,
+) {
+    let selection = marquee.camera.zip(marquee.corners);
+
+    // Switching cameras ends the previous selection.
+    if let Some(active) = marquee.active {
+        if selection.map(|(camera, _)| camera) != Some(active) {
+            marquee.active = None;
+            let selected = selectables
+                .iter()
+                .filter(|(.., outline)| outline.enabled)
+                .map(|(entity, ..)| entity)
+                .collect();
+            end_events.send(OutlineMarqueeEnd {
+                camera: active,
+                selected,
+            });
+        }
+    }
+
+    let (camera_entity, rect) = match selection {
+        Some(s) => s,
+        None => return,
+    };
+
+    if marquee.active.is_none() {
+        marquee.active = Some(camera_entity);
+        begin_events.send(OutlineMarqueeBegin {
+            camera: camera_entity,
+        });
+    }
+
+    let (camera, camera_transform) = match cameras.get(camera_entity) {
+        Ok(c) => c,
+        Err(_) => return,
+    };
+    let (rect_min, rect_max) = match cursor_rect_to_ndc(camera, rect) {
+        Some(r) => r,
+        None => return,
+    };
+
+    let view_proj = camera.projection_matrix() * camera_transform.compute_matrix().inverse();
+    for (_, transform, aabb, mut outline) in selectables.iter_mut() {
+        let selected = ndc_bounds(view_proj * transform.compute_matrix(), aabb)
+            .is_some_and(|(min, max)| min.cmple(rect_max).all() && max.cmpge(rect_min).all());
+
+        // Only access the outline mutably when necessary, to avoid triggering
+        // change detection.
+        if outline.enabled != selected {
+            outline.enabled = selected;
+        }
+    }
+}
+
+/// Converts a rectangle in cursor coordinates into the normalized device
+/// coordinates of the camera's viewport.
+fn cursor_rect_to_ndc(camera: &Camera, (a, b): (Vec2, Vec2)) -> Option<(Vec2, Vec2)> {
+    let target_size = camera.logical_target_size()?;
+    let (viewport_min, viewport_max) = camera.logical_viewport_rect()?;
+    let viewport_size = viewport_max - viewport_min;
+
+    // The cursor's origin is at the bottom left of the target, and the
+    // viewport's at the top left.
+    let to_ndc = |pos: Vec2| {
+        let pos = Vec2::new(pos.x, target_size.y - pos.y) - viewport_min;
+        let screen = pos / viewport_size;
+        Vec2::new(screen.x * 2.0 - 1.0, 1.0 - screen.y * 2.0)
+    };
+
+    let (a, b) = (to_ndc(a), to_ndc(b));
+    Some((a.min(b), b.max(a)))
+}
+
+/// Returns the bounds of `aabb` in normalized device coordinates, given the
+/// transform from its local space to clip space, or `None` if it isn't
+/// entirely in front of the camera.
+pub(crate) fn ndc_bounds(local_to_clip: Mat4, aabb: &Aabb) -> Option<(Vec2, Vec2)> {
+    let center = Vec3::from(aabb.center);
+    let half_extents = Vec3::from(aabb.half_extents);
+
+    let mut min = Vec2::splat(f32::INFINITY);
+    let mut max = Vec2::splat(f32::NEG_INFINITY);
+    for i in 0..8 {
+        let sign = Vec3::new(
+            if i & 1 == 0 { -1.0 } else { 1.0 },
+            if i & 2 == 0 { -1.0 } else { 1.0 },
+            if i & 4 == 0 { -1.0 } else { 1.0 },
+        );
+        let clip = local_to_clip * (center + sign * half_extents).extend(1.0);
+        if clip.w <= 0.0 {
+            return None;
+        }
+
+        let ndc = Vec2::new(clip.x, clip.y) / clip.w;
+        min = min.min(ndc);
+        max = max.max(ndc);
+    }
+
+    Some((min, max))
+}
@@ -1,89 +1,860 @@
 use bevy::{
-    pbr::{MeshPipeline, MeshPipelineKey},
+    core_pipeline::core_3d::Opaque3d,
+    ecs::system::{
+        lifetimeless::{Read, SQuery, SRes},
+        SystemParamItem,
+    },
+    pbr::{AlphaMode, MeshPipeline, MeshPipelineKey},
     prelude::*,
     render::{
+        camera::ExtractedCamera,
+        extract_component::{ComponentUniforms, DynamicUniformIndex},
         mesh::InnerMeshVertexBufferLayout,
+        render_asset::RenderAssets,
         render_graph::{Node, RenderGraphContext, SlotInfo, SlotType},
-        render_phase::{DrawFunctions, PhaseItem, RenderPhase, TrackedRenderPass},
+        render_phase::{
+            DrawFunctions, EntityRenderCommand, PhaseItem, RenderCommandResult, RenderPhase,
+            TrackedRenderPass,
+        },
         render_resource::{
-            ColorTargetState, ColorWrites, FragmentState, LoadOp, MultisampleState, Operations,
-            RenderPassColorAttachment, RenderPassDescriptor, RenderPipelineDescriptor,
-            SpecializedMeshPipeline, SpecializedMeshPipelineError, TextureFormat,
+            BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
+            BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource, BindingType,
+            BufferBindingType, ColorTargetState, ColorWrites, Extent3d, Face, FragmentState,
+            FrontFace, LoadOp, MultisampleState, Operations, PolygonMode, PrimitiveState,
+            PrimitiveTopology, RenderPassColorAttachment, RenderPassDescriptor,
+            RenderPipelineDescriptor, SamplerBindingType, ShaderStages, ShaderType,
+            SpecializedMeshPipeline, SpecializedMeshPipelineError, TextureDescriptor,
+            TextureDimension, TextureFormat, TextureSampleType, TextureUsages,
+            TextureViewDimension, VertexState,
         },
-        renderer::RenderContext,
+        renderer::{RenderContext, RenderDevice},
+        texture::TextureCache,
+        view::{Msaa, ViewDepthTexture},
+        Extract,
+    },
+    utils::{FixedState, HashMap, HashSet, Hashed},
+};
+
+use crate::{
+    jfa,
+    outline::{
+        OutlineViewUniform, OutlineViewUniformOffset, OutlineViewUniforms, ResolvedViewStyles,
     },
-    utils::{FixedState, Hashed},
+    resources::{OutlineResources, ViewOutlineResources},
+    reuse, MeshMask, Outline, OutlineColorSource, OutlineDepthOcclusion, OutlineGroup,
+    OutlineSettings, MASK_SHADER_HANDLE,
 };
 
-use crate::{resources::OutlineResources, MeshMask, MASK_SHADER_HANDLE};
+/// The format of the mask texture of views with [`MaskColors`].
+///
+/// The RGB components hold the color of the covering entity, premultiplied by
+/// the coverage stored in the alpha component.
+pub(crate) const MASK_COLOR_TEXTURE_FORMAT: TextureFormat = TextureFormat::Rgba8Unorm;
+
+/// The format of the mask texture of other views, which only hold the
+/// coverage, in the R component.
+pub(crate) const MASK_COVERAGE_TEXTURE_FORMAT: TextureFormat = TextureFormat::R8Unorm;
+
+/// Returns the format of the mask texture of a view, depending on whether it
+/// has [`MaskColors`].
+pub(crate) fn mask_texture_format(colors: bool) -> TextureFormat {
+    if colors {
+        MASK_COLOR_TEXTURE_FORMAT
+    } else {
+        MASK_COVERAGE_TEXTURE_FORMAT
+    }
+}
+
+/// Returns the shader defs of pipelines drawing or sampling a mask, depending
+/// on whether it holds colors.
+pub(crate) fn mask_shader_defs(colors: bool) -> Vec<String> {
+    if colors {
+        vec![]
+    } else {
+        vec!["MASK_COVERAGE_ONLY".into()]
+    }
+}
+
+/// The format of the multisampled texture holding the `OutlineGroup` of each
+/// sample covered by the mask in the R component, and the distance of the
+/// covering entity from the camera, as a half-precision float, in the G
+/// component.
+///
+/// Bit 8 of the R component is set for samples where the covering entity is
+/// hidden behind other geometry. This is only tested for views with a
+/// [`MaskOcclusion`].
+pub(crate) const MASK_GROUP_TEXTURE_FORMAT: TextureFormat = TextureFormat::Rg16Uint;
+
+/// Per-entity data written to the mask.
+#[derive(Clone, Debug, Component, ShaderType)]
+pub struct MaskUniform {
+    // Colors used by styles which take their color from the entity's material.
+    base_color: Vec4,
+    emissive: Vec4,
+    // The entity's `OutlineGroup`.
+    group: u32,
+    // Base color alpha below which fragments are discarded, for entities with
+    // a `MaskAlphaTexture`.
+    alpha_cutoff: f32,
+}
+
+impl Default for MaskUniform {
+    fn default() -> Self {
+        MaskUniform {
+            base_color: Vec4::ONE,
+            emissive: Vec4::ONE,
+            group: 0,
+            alpha_cutoff: 0.0,
+        }
+    }
+}
 
+impl MaskUniform {
+    /// Returns the uniform of an entity in the given outline group, whose
+    /// base and emissive colors are both `color`.
+    #[cfg(feature = "sprite")]
+    pub(crate) fn from_color(color: Color, group: u32) -> MaskUniform {
+        let color = color.as_rgba_f32().into();
+        MaskUniform {
+            base_color: color,
+            emissive: color,
+            group,
+            ..default()
+        }
+    }
+}
+
+/// The base color texture of an entity with an alpha-masked material, whose
+/// cutout is applied to the mask.
+///
+/// Only extracted for meshes with UVs while [`OutlineSettings::alpha_mask`]
+/// is enabled.
+#[derive(Clone, Debug, Component)]
+pub struct MaskAlphaTexture(Option<Handle<Image>>);
+
+/// The pipeline drawing meshes into the mask.
+///
+/// Custom mask pipelines, such as for instanced meshes, can wrap a clone of
+/// this resource and adjust the descriptors it specializes, e.g. replacing the
+/// vertex shader and adding vertex buffers.
+#[derive(Clone)]
 pub struct MeshMaskPipeline {
-    mesh_pipeline: MeshPipeline,
+    pub(crate) mesh_pipeline: MeshPipeline,
+    // Layouts binding the dimensions, the entity's `MaskUniform` and the
+    // `OutlineViewUniform`, along with the view's depth texture if it tests
+    // occlusion. Indexed by `view_layout_index`.
+    view_layouts: [BindGroupLayout; 3],
+    // Layout binding the base color texture of alpha-masked entities.
+    alpha_mask_layout: BindGroupLayout,
+}
+
+// Returns the index of the view layout for views whose depth texture has the
+// given sample count, if they test occlusion.
+fn view_layout_index(occlusion_samples: Option<u32>) -> usize {
+    match occlusion_samples {
+        None => 0,
+        Some(1) => 1,
+        Some(_) => 2,
+    }
 }
 
 impl FromWorld for MeshMaskPipeline {
     fn from_world(world: &mut World) -> Self {
         let mesh_pipeline = world.get_resource::<MeshPipeline>().unwrap().clone();
 
-        MeshMaskPipeline { mesh_pipeline }
+        let device = world.resource::<RenderDevice>();
+        let view_layouts = [None, Some(false), Some(true)].map(|occlusion| {
+            let mut entries = vec![
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::VERTEX_FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: Some(jfa::Dimensions::min_size()),
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: true,
+                        min_binding_size: Some(MaskUniform::min_size()),
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::VERTEX_FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: true,
+                        min_binding_size: Some(OutlineViewUniform::min_size()),
+                    },
+                    count: None,
+                },
+            ];
+            if let Some(multisampled) = occlusion {
+                entries.push(BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Depth,
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled,
+                    },
+                    count: None,
+                });
+            }
+
+            device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+                label: Some("outline_mask_view_bind_group_layout"),
+                entries: &entries,
+            })
+        });
+
+        let alpha_mask_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("outline_mask_alpha_bind_group_layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: true },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+
+        MeshMaskPipeline {
+            mesh_pipeline,
+            view_layouts,
+            alpha_mask_layout,
+        }
+    }
+}
+
+impl MeshMaskPipeline {
+    /// Returns the layout of the [`MaskViewBindGroup`] of views which don't
+    /// test occlusion, such as 2D views.
+    #[cfg(feature = "sprite")]
+    pub(crate) fn view_layout(&self) -> &BindGroupLayout {
+        &self.view_layouts[view_layout_index(None)]
+    }
+}
+
+/// Key for specializing a [`MeshMaskPipeline`], usually obtained from
+/// [`MeshMaskViewKey::pipeline_key`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct MeshMaskPipelineKey {
+    pub mesh_key: MeshPipelineKey,
+    /// Whether to write the emissive color to the mask rather than the base
+    /// color.
+    pub emissive: bool,
+    /// The sample count of the view's depth texture, if samples hidden behind
+    /// it are flagged in the mask.
+    pub occlusion_samples: Option<u32>,
+    /// Whether fragments are discarded below the alpha cutoff of the entity's
+    /// material. The mesh must have UVs.
+    pub alpha_mask: bool,
+    /// Whether alpha-masked fragments cover the samples of the mask according
+    /// to their alpha rather than being discarded. This is only used with
+    /// `alpha_mask`, and requires a multisampled mask.
+    pub alpha_to_coverage: bool,
+    /// Whether the mask holds the color of the covering entity, rather than
+    /// only its coverage.
+    pub mask_colors: bool,
+}
+
+/// The parts of a [`MeshMaskPipelineKey`] shared by every mesh drawn into a
+/// view's mask.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct MeshMaskViewKey {
+    msaa_samples: u32,
+    emissive: bool,
+    occlusion_samples: Option<u32>,
+    alpha_to_coverage: bool,
+    mask_colors: bool,
+}
+
+impl MeshMaskViewKey {
+    /// Returns the key for drawing a mesh with the given topology into the
+    /// view's mask.
+    pub fn pipeline_key(&self, primitive_topology: PrimitiveTopology) -> MeshMaskPipelineKey {
+        MeshMaskPipelineKey {
+            mesh_key: MeshPipelineKey::from_msaa_samples(self.msaa_samples)
+                | MeshPipelineKey::from_primitive_topology(primitive_topology),
+            emissive: self.emissive,
+            occlusion_samples: self.occlusion_samples,
+            alpha_mask: false,
+            alpha_to_coverage: self.alpha_to_coverage,
+            mask_colors: self.mask_colors,
+        }
+    }
+}
+
+/// The [`MeshMaskViewKey`] of each view drawing a mesh mask, by view entity.
+///
+/// Filled in `RenderStage::Queue` by the
+/// [`OutlineRenderSystem::QueueMaskViewKeys`](crate::OutlineRenderSystem)
+/// system.
+#[derive(Default)]
+pub struct MeshMaskViewKeys {
+    keys: HashMap<Entity, MeshMaskViewKey>,
+}
+
+impl MeshMaskViewKeys {
+    /// Returns the key of the given view, if it draws a mesh mask.
+    pub fn get(&self, view: Entity) -> Option<MeshMaskViewKey> {
+        self.keys.get(&view).copied()
+    }
+}
+
+/// A view along with the components deciding its [`MeshMaskViewKey`].
+type MeshMaskView = (
+    Entity,
+    Option<&'static MaskOcclusion>,
+    Option<&'static MaskColors>,
+);
+
+pub(crate) fn queue_mesh_mask_view_keys(
+    mut view_keys: ResMut<MeshMaskViewKeys>,
+    resolved_styles: Res<ResolvedViewStyles>,
+    settings: Res<OutlineSettings>,
+    msaa: Res<Msaa>,
+    views: Query<MeshMaskView, With<RenderPhase<MeshMask>>>,
+) {
+    // Alpha to coverage is invalid for single-sampled targets.
+    let alpha_to_coverage = settings.alpha_to_coverage && msaa.samples > 1;
+
+    view_keys.keys.clear();
+    for (entity, occlusion, mask_colors) in views.iter() {
+        let emissive = resolved_styles
+            .get(entity)
+            .is_some_and(|style| style.params.color_source == OutlineColorSource::Emissive as u32);
+
+        view_keys.keys.insert(
+            entity,
+            MeshMaskViewKey {
+                msaa_samples: msaa.samples,
+                emissive,
+                occlusion_samples: occlusion.map(|occlusion| occlusion.samples),
+                alpha_to_coverage,
+                mask_colors: mask_colors.is_some(),
+            },
+        );
     }
 }
 
 impl SpecializedMeshPipeline for MeshMaskPipeline {
-    type Key = MeshPipelineKey;
+    type Key = MeshMaskPipelineKey;
 
     fn specialize(
         &self,
         key: Self::Key,
         layout: &Hashed<InnerMeshVertexBufferLayout, FixedState>,
     ) -> Result<RenderPipelineDescriptor, SpecializedMeshPipelineError> {
-        let mut desc = self.mesh_pipeline.specialize(key, layout)?;
+        // Only positions, and the attributes needed for alpha masking and
+        // skinning, are fetched, rather than the full layout of the main pass.
+        let mut vertex_attributes = vec![Mesh::ATTRIBUTE_POSITION.at_shader_location(0)];
+        let mut vertex_defs = vec![];
+        if key.alpha_mask {
+            vertex_attributes.push(Mesh::ATTRIBUTE_UV_0.at_shader_location(2));
+            vertex_defs.push("MASK_ALPHA_MASK".into());
+        }
+
+        let mut shader_defs = mask_shader_defs(key.mask_colors);
+        if key.emissive {
+            shader_defs.push("MASK_COLOR_EMISSIVE".into());
+        }
+
+        if let Some(samples) = key.occlusion_samples {
+            shader_defs.push("MASK_OCCLUSION".into());
+            if samples > 1 {
+                shader_defs.push("MASK_OCCLUSION_MULTISAMPLED".into());
+            }
+        }
+
+        let mesh_layout = if layout.contains(Mesh::ATTRIBUTE_JOINT_INDEX)
+            && layout.contains(Mesh::ATTRIBUTE_JOINT_WEIGHT)
+        {
+            vertex_attributes.push(Mesh::ATTRIBUTE_JOINT_INDEX.at_shader_location(5));
+            vertex_attributes.push(Mesh::ATTRIBUTE_JOINT_WEIGHT.at_shader_location(6));
+            vertex_defs.push("SKINNED".into());
+            shader_defs.push("SKINNED".into());
+            self.mesh_pipeline.skinned_mesh_layout.clone()
+        } else {
+            self.mesh_pipeline.mesh_layout.clone()
+        };
 
-        desc.layout = Some(vec![
+        let mut bind_group_layout = vec![
             self.mesh_pipeline.view_layout.clone(),
-            self.mesh_pipeline.mesh_layout.clone(),
-        ]);
-
-        desc.vertex.shader = MASK_SHADER_HANDLE.typed::<Shader>();
-
-        desc.fragment = Some(FragmentState {
-            shader: MASK_SHADER_HANDLE.typed::<Shader>(),
-            shader_defs: vec![],
-            entry_point: "fragment".into(),
-            targets: vec![Some(ColorTargetState {
-                format: TextureFormat::R8Unorm,
-                blend: None,
-                write_mask: ColorWrites::ALL,
-            })],
+            mesh_layout,
+            self.view_layouts[view_layout_index(key.occlusion_samples)].clone(),
+        ];
+        if key.alpha_mask {
+            shader_defs.push("MASK_ALPHA_MASK".into());
+            bind_group_layout.push(self.alpha_mask_layout.clone());
+        }
+
+        let alpha_to_coverage = key.alpha_mask && key.alpha_to_coverage;
+        if alpha_to_coverage {
+            shader_defs.push("MASK_ALPHA_TO_COVERAGE".into());
+        }
+
+        Ok(RenderPipelineDescriptor {
+            vertex: VertexState {
+                shader: MASK_SHADER_HANDLE.typed::<Shader>(),
+                entry_point: "vertex".into(),
+                shader_defs: vertex_defs,
+                buffers: vec![layout.get_layout(&vertex_attributes)?],
+            },
+            fragment: Some(FragmentState {
+                shader: MASK_SHADER_HANDLE.typed::<Shader>(),
+                shader_defs,
+                entry_point: "fragment".into(),
+                targets: vec![
+                    Some(ColorTargetState {
+                        format: mask_texture_format(key.mask_colors),
+                        blend: None,
+                        write_mask: ColorWrites::ALL,
+                    }),
+                    Some(ColorTargetState {
+                        format: MASK_GROUP_TEXTURE_FORMAT,
+                        blend: None,
+                        write_mask: ColorWrites::ALL,
+                    }),
+                ],
+            }),
+            layout: Some(bind_group_layout),
+            primitive: PrimitiveState {
+                front_face: FrontFace::Ccw,
+                cull_mode: Some(Face::Back),
+                unclipped_depth: false,
+                polygon_mode: PolygonMode::Fill,
+                conservative: false,
+                topology: key.mesh_key.primitive_topology(),
+                strip_index_format: None,
+            },
+            depth_stencil: None,
+            multisample: MultisampleState {
+                count: key.mesh_key.msaa_samples(),
+                mask: !0,
+                alpha_to_coverage_enabled: alpha_to_coverage,
+            },
+            label: Some("mesh_stencil_pipeline".into()),
+        })
+    }
+}
+
+/// Render command for binding the mask dimensions, used to fit the padded
+/// mask around the view, along with the entity's mask color, the view's
+/// outline parameters and its depth texture if it tests occlusion.
+pub struct SetMaskViewBindGroup<const I: usize>;
+
+impl<const I: usize> EntityRenderCommand for SetMaskViewBindGroup<I> {
+    type Param = (
+        SQuery<(Read<MaskViewBindGroup>, Read<OutlineViewUniformOffset>)>,
+        SQuery<Read<DynamicUniformIndex<MaskUniform>>>,
+    );
+
+    fn render<'w>(
+        view: Entity,
+        item: Entity,
+        (views, indices): SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        let (bind_group, view_offset) = match views.get_inner(view) {
+            Ok(v) => v,
+            Err(_) => return RenderCommandResult::Failure,
+        };
+        let index = match indices.get(item) {
+            Ok(i) => i.index(),
+            Err(_) => return RenderCommandResult::Failure,
+        };
+
+        pass.set_bind_group(I, &bind_group.bind_group, &[index, view_offset.offset]);
+
+        RenderCommandResult::Success
+    }
+}
+
+/// Marks views which draw a style depending on occlusion.
+#[derive(Clone, Copy, Debug, Component)]
+pub(crate) struct TestsOcclusion;
+
+/// Marks views whose mask holds the color of the covering entities, as one of
+/// their styles takes its colors from them or the mask is copied to an image.
+///
+/// The masks of other views only hold the coverage, in a single channel.
+#[derive(Clone, Copy, Debug, Component)]
+pub(crate) struct MaskColors;
+
+/// Marks views whose mask flags the samples hidden behind other geometry.
+#[derive(Clone, Copy, Debug, Component)]
+pub(crate) struct MaskOcclusion {
+    /// The sample count of the view's depth texture.
+    pub(crate) samples: u32,
+}
+
+/// Replaces the depth texture of each view which tests occlusion or has
+/// [`OutlineDepthOcclusion`] with one which can also be bound by the outline
+/// passes.
+///
+/// Views sharing a target share a depth texture, so the texture is replaced
+/// for all views with the same target. This must run after
+/// `prepare_core_3d_depth_textures`.
+pub(crate) fn prepare_outline_depth_textures(
+    mut commands: Commands,
+    mut texture_cache: ResMut<TextureCache>,
+    msaa: Res<Msaa>,
+    device: Res<RenderDevice>,
+    tests_occlusion: Query<(), (With<TestsOcclusion>, With<OutlineViewUniform>)>,
+    depth_occlusion: Query<(), (With<OutlineDepthOcclusion>, With<OutlineViewUniform>)>,
+    views: Query<(Entity, &ExtractedCamera), With<RenderPhase<Opaque3d>>>,
+) {
+    let tests_occlusion = |entity| tests_occlusion.contains(entity);
+
+    let targets: HashSet<_> = views
+        .iter()
+        .filter(|&(entity, _)| tests_occlusion(entity) || depth_occlusion.contains(entity))
+        .map(|(_, camera)| camera.target.clone())
+        .collect();
+    if targets.is_empty() {
+        return;
+    }
+
+    let mut textures = HashMap::default();
+    for (entity, camera) in views.iter() {
+        let size = match camera.physical_target_size {
+            Some(s) if targets.contains(&camera.target) => s,
+            _ => continue,
+        };
+
+        let cached_texture = textures
+            .entry(camera.target.clone())
+            .or_insert_with(|| {
+                texture_cache.get(
+                    &device,
+                    TextureDescriptor {
+                        label: Some("outline_view_depth_texture"),
+                        size: Extent3d {
+                            width: size.x,
+                            height: size.y,
+                            depth_or_array_layers: 1,
+                        },
+                        mip_level_count: 1,
+                        sample_count: msaa.samples,
+                        dimension: TextureDimension::D2,
+                        format: TextureFormat::Depth32Float,
+                        usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+                    },
+                )
+            })
+            .clone();
+
+        let mut entity_commands = commands.entity(entity);
+        entity_commands.insert(ViewDepthTexture {
+            texture: cached_texture.texture,
+            view: cached_texture.default_view,
+        });
+        if tests_occlusion(entity) {
+            entity_commands.insert(MaskOcclusion {
+                samples: msaa.samples,
+            });
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct MaskViewBindGroup {
+    bind_group: BindGroup,
+}
+
+/// The components of a view which add bindings to its mask view bind group.
+type MaskViewBindings = (
+    Entity,
+    &'static ViewOutlineResources,
+    Option<(&'static MaskOcclusion, &'static ViewDepthTexture)>,
+);
+
+pub(crate) fn queue_mask_view_bind_groups(
+    mut commands: Commands,
+    device: Res<RenderDevice>,
+    pipeline: Res<MeshMaskPipeline>,
+    uniforms: Res<ComponentUniforms<MaskUniform>>,
+    view_uniforms: Res<OutlineViewUniforms>,
+    views: Query<MaskViewBindings, With<OutlineViewUniformOffset>>,
+) {
+    let (uniform_binding, view_binding) = match (
+        uniforms.uniforms().binding(),
+        view_uniforms.uniforms.binding(),
+    ) {
+        (Some(u), Some(v)) => (u, v),
+        _ => return,
+    };
+
+    for (entity, view_res, occlusion) in views.iter() {
+        let mut entries = vec![
+            BindGroupEntry {
+                binding: 0,
+                resource: view_res.dimensions_buffer.binding().unwrap(),
+            },
+            BindGroupEntry {
+                binding: 1,
+                resource: uniform_binding.clone(),
+            },
+            BindGroupEntry {
+                binding: 2,
+                resource: view_binding.clone(),
+            },
+        ];
+        if let Some((_, depth)) = occlusion {
+            entries.push(BindGroupEntry {
+                binding: 3,
+                resource: BindingResource::TextureView(&depth.view),
+            });
+        }
+
+        let occlusion_samples = occlusion.map(|(occlusion, _)| occlusion.samples);
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("outline_mask_view_bind_group"),
+            layout: &pipeline.view_layouts[view_layout_index(occlusion_samples)],
+            entries: &entries,
         });
-        desc.depth_stencil = None;
 
-        desc.multisample = MultisampleState {
-            count: 4,
-            mask: !0,
-            alpha_to_coverage_enabled: false,
+        commands
+            .entity(entity)
+            .insert(MaskViewBindGroup { bind_group });
+    }
+}
+
+/// Component for mesh entities which are drawn into the mask by custom
+/// systems, rather than by the plugin.
+///
+/// As for other meshes, the entity must be visible and have an enabled
+/// [`Outline`] to receive a mask color and [`OutlineGroup`], which
+/// [`SetMaskViewBindGroup`] binds. This is used for geometry drawn with a
+/// custom draw command, such as instanced meshes, which queue their own
+/// [`MeshMask`](crate::MeshMask) items.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Component)]
+pub struct CustomMeshMask;
+
+pub(crate) fn extract_custom_mesh_masks(
+    mut commands: Commands,
+    mut previous_len: Local<usize>,
+    meshes: Extract<Query<Entity, With<CustomMeshMask>>>,
+) {
+    let mut batches = Vec::with_capacity(*previous_len);
+    batches.extend(meshes.iter().map(|entity| (entity, (CustomMeshMask,))));
+    *previous_len = batches.len();
+    commands.insert_or_spawn_batch(batches);
+}
+
+/// The components of an outlined mesh which determine its mask uniform.
+type OutlinedMeshUniformSource = (
+    Entity,
+    &'static ComputedVisibility,
+    &'static Outline,
+    &'static Handle<Mesh>,
+    Option<&'static Handle<StandardMaterial>>,
+    Option<&'static OutlineGroup>,
+);
+
+/// Extracts the material colors and outline groups of visible meshes with an
+/// enabled [`Outline`], along with the `Outline` itself, which marks the
+/// meshes drawn into the mask.
+///
+/// Meshes without a `StandardMaterial` are white.
+pub(crate) fn extract_mask_uniforms(
+    mut commands: Commands,
+    mut previous_len: Local<usize>,
+    mut previous_alpha_len: Local<usize>,
+    settings: Extract<Res<OutlineSettings>>,
+    materials: Extract<Res<Assets<StandardMaterial>>>,
+    mesh_assets: Extract<Res<Assets<Mesh>>>,
+    meshes: Extract<Query<OutlinedMeshUniformSource>>,
+) {
+    let mut batches = Vec::with_capacity(*previous_len);
+    let mut alpha_batches = Vec::with_capacity(*previous_alpha_len);
+    for (entity, visibility, outline, mesh, material, group) in meshes.iter() {
+        if !visibility.is_visible() || !outline.enabled {
+            continue;
+        }
+
+        let material = material.and_then(|handle| materials.get(handle));
+        let mut uniform = match material {
+            Some(material) => MaskUniform {
+                base_color: material.base_color.as_rgba_f32().into(),
+                emissive: material.emissive.as_rgba_f32().into(),
+                ..default()
+            },
+            None => MaskUniform::default(),
+        };
+        uniform.group = group.map_or(0, |group| group.0 as u32);
+
+        // The cutout is sampled using the mesh's UVs.
+        let has_uvs = || {
+            mesh_assets
+                .get(mesh)
+                .is_some_and(|mesh| mesh.attribute(Mesh::ATTRIBUTE_UV_0).is_some())
+        };
+        match material {
+            Some(material) if settings.alpha_mask && has_uvs() => match material.alpha_mode {
+                AlphaMode::Mask(cutoff) => {
+                    uniform.alpha_cutoff = cutoff;
+                    let texture = MaskAlphaTexture(material.base_color_texture.clone());
+                    alpha_batches.push((entity, (uniform, outline.clone(), texture)));
+                }
+                _ => batches.push((entity, (uniform, outline.clone()))),
+            },
+            _ => batches.push((entity, (uniform, outline.clone()))),
+        }
+    }
+    *previous_len = batches.len();
+    *previous_alpha_len = alpha_batches.len();
+    commands.insert_or_spawn_batch(batches);
+    commands.insert_or_spawn_batch(alpha_batches);
+}
+
+/// Bind groups for alpha-masked entities, by base color texture.
+pub struct MaskAlphaBindGroups {
+    bind_groups: HashMap<Option<Handle<Image>>, BindGroup>,
+}
+
+pub(crate) fn queue_mask_alpha_bind_groups(
+    mut commands: Commands,
+    device: Res<RenderDevice>,
+    res: Res<OutlineResources>,
+    pipeline: Res<MeshMaskPipeline>,
+    images: Res<RenderAssets<Image>>,
+    alpha_textures: Query<&MaskAlphaTexture>,
+) {
+    let mut bind_groups = HashMap::default();
+    for MaskAlphaTexture(texture) in alpha_textures.iter() {
+        if bind_groups.contains_key(texture) {
+            continue;
+        }
+
+        // Materials without a base color texture are cut out by their base
+        // color alpha alone.
+        let (view, sampler) = match texture {
+            Some(handle) => match images.get(handle) {
+                Some(image) => (&image.texture_view, &image.sampler),
+                None => continue,
+            },
+            None => (&res.white_texture, &res.fill_texture_sampler),
+        };
+
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("outline_mask_alpha_bind_group"),
+            layout: &pipeline.alpha_mask_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(view),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::Sampler(sampler),
+                },
+            ],
+        });
+        bind_groups.insert(texture.clone(), bind_group);
+    }
+
+    commands.insert_resource(MaskAlphaBindGroups { bind_groups });
+}
+
+/// Render command for binding the base color texture of alpha-masked
+/// entities. Other entities bind nothing.
+pub struct SetMaskAlphaBindGroup<const I: usize>;
+
+impl<const I: usize> EntityRenderCommand for SetMaskAlphaBindGroup<I> {
+    type Param = (SRes<MaskAlphaBindGroups>, SQuery<Read<MaskAlphaTexture>>);
+
+    fn render<'w>(
+        _view: Entity,
+        item: Entity,
+        (bind_groups, query): SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        let MaskAlphaTexture(texture) = match query.get_inner(item) {
+            Ok(t) => t,
+            Err(_) => return RenderCommandResult::Success,
         };
 
-        desc.label = Some("mesh_stencil_pipeline".into());
-        Ok(desc)
+        // Skipped until the texture is ready, as is the material itself.
+        match bind_groups.into_inner().bind_groups.get(texture) {
+            Some(bind_group) => pass.set_bind_group(I, bind_group, &[]),
+            None => return RenderCommandResult::Failure,
+        }
+
+        RenderCommandResult::Success
     }
 }
 
+/// Returns the color attachments of a pass drawing the mask, which clear the
+/// mask and the outline groups.
+pub(crate) fn mask_color_attachments(
+    res: &ViewOutlineResources,
+) -> [Option<RenderPassColorAttachment<'_>>; 2] {
+    // Without MSAA, the mask is drawn into the output directly.
+    let output = &*res.mask_output.default_view;
+    let (view, resolve_target) = match &res.mask_multisample {
+        Some(multisample) => (&*multisample.default_view, Some(output)),
+        None => (output, None),
+    };
+
+    [
+        Some(RenderPassColorAttachment {
+            view,
+            resolve_target,
+            ops: Operations {
+                load: LoadOp::Clear(Color::BLACK.into()),
+                store: true,
+            },
+        }),
+        Some(RenderPassColorAttachment {
+            view: &res.mask_group_multisample.default_view,
+            resolve_target: None,
+            ops: Operations {
+                load: LoadOp::Clear(Color::BLACK.into()),
+                store: true,
+            },
+        }),
+    ]
+}
+
 /// Render graph node for producing stencils from meshes.
 pub struct MeshMaskNode {
     query: QueryState<&'static RenderPhase<MeshMask>>,
 }
 
 impl MeshMaskNode {
+    /// The view entity.
     pub const IN_VIEW: &'static str = "view";
 
-    /// The produced stencil buffer.
+    /// The produced mask, resolved to a single sample.
     ///
-    /// This has format `TextureFormat::Depth24PlusStencil8`. Fragments covered
-    /// by a mesh are assigned a value of 255. All other fragments are assigned
-    /// a value of 0. The depth aspect is unused.
+    /// This has format `TextureFormat::Rgba8Unorm` if the view's styles take
+    /// their colors from the covering entities, and the alpha of each texel is
+    /// the fraction of its samples covered by an outlined entity. Otherwise,
+    /// it has format `TextureFormat::R8Unorm` and holds that fraction in R.
     pub const OUT_MASK: &'static str = "stencil";
 
+    /// Creates the node. `world` is the render world.
     pub fn new(world: &mut World) -> MeshMaskNode {
         MeshMaskNode {
             query: QueryState::new(world),
@@ -110,13 +881,18 @@ impl Node for MeshMaskNode {
         render_context: &mut RenderContext,
         world: &World,
     ) -> Result<(), bevy::render::render_graph::NodeRunError> {
-        let res = world.get_resource::<OutlineResources>().unwrap();
+        let view_entity = graph.get_input_entity(Self::IN_VIEW).unwrap();
+        let res = world.get::<ViewOutlineResources>(view_entity).unwrap();
 
         graph
-            .set_output(Self::OUT_MASK, res.mask_multisample.default_view.clone())
+            .set_output(Self::OUT_MASK, res.mask_output.default_view.clone())
             .unwrap();
 
-        let view_entity = graph.get_input_entity(Self::IN_VIEW).unwrap();
+        // The mask of the previous frame is still valid.
+        if reuse::is_reused(world, view_entity) {
+            return Ok(());
+        }
+
         let stencil_phase = match self.query.get_manual(world, view_entity) {
             Ok(q) => q,
             Err(_) => return Ok(()),
@@ -126,14 +902,7 @@ impl Node for MeshMaskNode {
             .command_encoder
             .begin_render_pass(&RenderPassDescriptor {
                 label: Some("outline_stencil_render_pass"),
-                color_attachments: &[Some(RenderPassColorAttachment {
-                    view: &res.mask_multisample.default_view,
-                    resolve_target: Some(&res.mask_output.default_view),
-                    ops: Operations {
-                        load: LoadOp::Clear(Color::BLACK.into()),
-                        store: true,
-                    },
-                })],
+                color_attachments: &mask_color_attachments(res),
                 depth_stencil_attachment: None,
             });
         let mut pass = TrackedRenderPass::new(pass_raw);
@@ -0,0 +1,324 @@
+use std::sync::{
+    atomic::{AtomicU8, Ordering},
+    Arc, Mutex,
+};
+
+use bevy::{
+    prelude::*,
+    render::{
+        render_graph::{Node, NodeRunError, RenderGraphContext, SlotInfo, SlotType},
+        render_resource::{
+            BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor,
+            BindGroupLayoutEntry, BindingResource, BindingType, Buffer, BufferBindingType,
+            BufferDescriptor, BufferUsages, CachedComputePipelineId, ComputePassDescriptor,
+            ComputePipelineDescriptor, MapMode, PipelineCache, ShaderStages, TextureSampleType,
+            TextureViewDimension,
+        },
+        renderer::{RenderContext, RenderDevice},
+    },
+    utils::HashMap,
+};
+
+use crate::{
+    jfa::Dimensions, outline::OutlineViewUniform, resources::ViewOutlineResources, reuse,
+    OutlineSettings, MASK_BOUNDS_SHADER_HANDLE,
+};
+
+// The reduced bounds of the mask, as four `u32`s.
+const BOUNDS_SIZE: u64 = (4 * std::mem::size_of::<u32>()) as u64;
+
+// Indexed by whether the mask is multisampled. Compute shaders are
+// unavailable on some backends, notably WebGL2, which have no pipelines.
+pub(crate) struct MaskBoundsPipeline {
+    layouts: [BindGroupLayout; 2],
+    pipeline_ids: Option<[CachedComputePipelineId; 2]>,
+}
+
+impl FromWorld for MaskBoundsPipeline {
+    fn from_world(world: &mut World) -> Self {
+        let device = world.resource::<RenderDevice>();
+        let layouts = [false, true].map(|multisampled| {
+            device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+                label: Some("outline_mask_bounds_bind_group_layout"),
+                entries: &[
+                    BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: ShaderStages::COMPUTE,
+                        ty: BindingType::Texture {
+                            sample_type: TextureSampleType::Uint,
+                            view_dimension: TextureViewDimension::D2,
+                            multisampled,
+                        },
+                        count: None,
+                    },
+                    BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: ShaderStages::COMPUTE,
+                        ty: BindingType::Buffer {
+                            ty: BufferBindingType::Storage { read_only: false },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+            })
+        });
+
+        let limits = device.limits();
+        let supported = limits.max_compute_invocations_per_workgroup >= 64
+            && limits.max_storage_buffers_per_shader_stage > 0;
+
+        let mut pipeline_cache = world.resource_mut::<PipelineCache>();
+        let pipeline_ids = supported.then(|| {
+            [false, true].map(|multisampled| {
+                let shader_defs = match multisampled {
+                    true => vec!["MASK_GROUP_MULTISAMPLED".into()],
+                    false => vec![],
+                };
+                pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
+                    label: Some("outline_mask_bounds_pipeline".into()),
+                    layout: Some(vec![layouts[multisampled as usize].clone()]),
+                    shader: MASK_BOUNDS_SHADER_HANDLE.typed::<Shader>(),
+                    shader_defs,
+                    entry_point: "reduce_bounds".into(),
+                })
+            })
+        });
+
+        MaskBoundsPipeline {
+            layouts,
+            pipeline_ids,
+        }
+    }
+}
+
+// Readback states of a view's bounds.
+const STATE_IDLE: u8 = 0;
+const STATE_COPIED: u8 = 1;
+const STATE_PENDING: u8 = 2;
+const STATE_MAPPED: u8 = 3;
+
+/// The buffers used to reduce a view's mask bounds and read them back.
+struct ViewBoundsBuffers {
+    bounds: Buffer,
+    readback: Buffer,
+    // One of the `STATE_*` constants. A new reduction is only started once
+    // the previous one has been read back, as the readback buffer can't be
+    // written while it is mapped.
+    state: Arc<AtomicU8>,
+    // The dimensions of the mask which was reduced.
+    dimensions: Mutex<Option<Dimensions>>,
+    // The bounds of the last reduction read back, in screen texcoords.
+    latest: Option<(Vec2, Vec2)>,
+}
+
+/// The bounds of the covered samples of each view's mask, reduced on the GPU
+/// while [`OutlineSettings::set_scissor`] is enabled.
+///
+/// The bounds are read back asynchronously, so they describe the mask of a
+/// frame a few frames ago.
+#[derive(Default)]
+pub(crate) struct MaskBoundsBuffers {
+    views: HashMap<Entity, ViewBoundsBuffers>,
+}
+
+impl MaskBoundsBuffers {
+    /// Returns the bounds of the covered samples of the last mask of `view`
+    /// to be read back, in screen texcoords, or `None` if none has been.
+    ///
+    /// An empty mask has a minimum greater than its maximum.
+    pub(crate) fn latest(&self, view: Entity) -> Option<(Vec2, Vec2)> {
+        self.views.get(&view)?.latest
+    }
+}
+
+/// Publishes the bounds which have been read back since the previous frame.
+///
+/// This runs in `RenderStage::Extract`, before the bounds are used to extract
+/// the silhouette bounds of each view.
+pub(crate) fn read_mask_bounds(mut buffers: ResMut<MaskBoundsBuffers>) {
+    // Mappings finish when a later frame's commands are submitted, which
+    // invokes their callbacks.
+    for view in buffers.views.values_mut() {
+        if view.state.load(Ordering::Acquire) != STATE_MAPPED {
+            continue;
+        }
+
+        let words: Vec<u32> = {
+            let data = view.readback.slice(..).get_mapped_range();
+            data.chunks_exact(4)
+                .map(|bytes| u32::from_ne_bytes(bytes.try_into().unwrap()))
+                .collect()
+        };
+        view.readback.unmap();
+        view.state.store(STATE_IDLE, Ordering::Release);
+
+        let dims = match *view.dimensions.lock().unwrap() {
+            Some(d) => d,
+            None => continue,
+        };
+        let screen_size = dims.screen_size().as_vec2();
+        let padding = (dims.size().as_vec2() - screen_size) / 2.0;
+        let min = UVec2::new(!words[0], !words[1]).as_vec2();
+        let max = UVec2::new(words[2], words[3]).as_vec2();
+        view.latest = Some(((min - padding) / screen_size, (max - padding) / screen_size));
+    }
+}
+
+/// Creates buffers for new views, and drops those of views which are no
+/// longer drawn.
+pub(crate) fn prepare_mask_bounds_buffers(
+    settings: Res<OutlineSettings>,
+    device: Res<RenderDevice>,
+    mut buffers: ResMut<MaskBoundsBuffers>,
+    views: Query<Entity, With<OutlineViewUniform>>,
+) {
+    if !settings.scissor {
+        buffers.views.clear();
+        return;
+    }
+
+    buffers.views.retain(|entity, _| views.get(*entity).is_ok());
+    for entity in views.iter() {
+        buffers
+            .views
+            .entry(entity)
+            .or_insert_with(|| ViewBoundsBuffers {
+                bounds: device.create_buffer(&BufferDescriptor {
+                    label: Some("outline_mask_bounds"),
+                    size: BOUNDS_SIZE,
+                    usage: BufferUsages::STORAGE | BufferUsages::COPY_SRC | BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                }),
+                readback: device.create_buffer(&BufferDescriptor {
+                    label: Some("outline_mask_bounds_readback"),
+                    size: BOUNDS_SIZE,
+                    usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                }),
+                state: Arc::new(AtomicU8::new(STATE_IDLE)),
+                dimensions: Mutex::new(None),
+                latest: None,
+            });
+    }
+}
+
+/// Starts mapping the readback buffers copied to this frame.
+///
+/// This runs after the frame's commands have been submitted.
+pub(crate) fn map_mask_bounds_buffers(buffers: Res<MaskBoundsBuffers>) {
+    for view in buffers.views.values() {
+        if view.state.load(Ordering::Acquire) != STATE_COPIED {
+            continue;
+        }
+
+        view.state.store(STATE_PENDING, Ordering::Release);
+        let state = view.state.clone();
+        view.readback
+            .slice(..)
+            .map_async(MapMode::Read, move |result| {
+                let new_state = match result {
+                    Ok(()) => STATE_MAPPED,
+                    Err(_) => STATE_IDLE,
+                };
+                state.store(new_state, Ordering::Release);
+            });
+    }
+}
+
+/// Render graph node which reduces the bounds of the covered samples of a
+/// view's mask, for use as its scissor rectangle in later frames.
+///
+/// This only runs while [`OutlineSettings::set_scissor`] is enabled, on
+/// backends supporting compute shaders.
+pub struct MaskBoundsNode;
+
+impl MaskBoundsNode {
+    pub const IN_VIEW: &'static str = "view";
+}
+
+impl Node for MaskBoundsNode {
+    fn input(&self) -> Vec<SlotInfo> {
+        vec![SlotInfo::new(Self::IN_VIEW, SlotType::Entity)]
+    }
+
+    fn run(
+        &self,
+        graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let view_ent = graph.get_input_entity(Self::IN_VIEW)?;
+        let res = match world.get::<ViewOutlineResources>(view_ent) {
+            Some(r) => r,
+            None => return Ok(()),
+        };
+
+        // A reused mask is unchanged, and so are its bounds.
+        if reuse::is_reused(world, view_ent) {
+            return Ok(());
+        }
+
+        let buffers = match world.get_resource::<MaskBoundsBuffers>() {
+            Some(b) => b,
+            None => return Ok(()),
+        };
+        let view = match buffers.views.get(&view_ent) {
+            Some(v) if v.state.load(Ordering::Acquire) == STATE_IDLE => v,
+            _ => return Ok(()),
+        };
+
+        let pipeline = world.resource::<MaskBoundsPipeline>();
+        let pipeline_ids = match pipeline.pipeline_ids {
+            Some(ids) => ids,
+            None => return Ok(()),
+        };
+        let pipeline_cache = world.resource::<PipelineCache>();
+        let multisampled = (res.mask_samples > 1) as usize;
+        let cached_pipeline = match pipeline_cache.get_compute_pipeline(pipeline_ids[multisampled])
+        {
+            Some(c) => c,
+            // Still queued.
+            None => return Ok(()),
+        };
+
+        let bind_group = render_context
+            .render_device
+            .create_bind_group(&BindGroupDescriptor {
+                label: Some("outline_mask_bounds_bind_group"),
+                layout: &pipeline.layouts[multisampled],
+                entries: &[
+                    BindGroupEntry {
+                        binding: 0,
+                        resource: BindingResource::TextureView(
+                            &res.mask_group_multisample.default_view,
+                        ),
+                    },
+                    BindGroupEntry {
+                        binding: 1,
+                        resource: view.bounds.as_entire_binding(),
+                    },
+                ],
+            });
+
+        let dims = *res.dimensions_buffer.get();
+        *view.dimensions.lock().unwrap() = Some(dims);
+
+        let size = dims.size();
+        let encoder = &mut render_context.command_encoder;
+        encoder.clear_buffer(&view.bounds, 0, None);
+        {
+            let mut pass = encoder.begin_compute_pass(&ComputePassDescriptor {
+                label: Some("outline_mask_bounds"),
+            });
+            pass.set_pipeline(cached_pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.dispatch_workgroups(size.x.div_ceil(8), size.y.div_ceil(8), 1);
+        }
+        encoder.copy_buffer_to_buffer(&view.bounds, 0, &view.readback, 0, BOUNDS_SIZE);
+        view.state.store(STATE_COPIED, Ordering::Release);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,85 @@
+use std::marker::PhantomData;
+
+use bevy::{ecs::query::ReadOnlyWorldQuery, prelude::*};
+
+use crate::Outline;
+
+/// Plugin for outlining every mesh entity which matches the query filter `F`.
+///
+/// Each frame, an [`Outline`] is added to mesh entities which match the filter
+/// and removed from those which no longer do. Entities which already have an
+/// `Outline` are left alone. As all outlined entities are drawn into the same
+/// mask, the matching entities are outlined as one merged group.
+///
+/// ```ignore
+/// // Outline everything with a collider.
+/// app.add_plugin(OutlineMatchingPlugin::<With<Collider>>::default());
+/// ```
+pub struct OutlineMatchingPlugin<F> {
+    marker: PhantomData<fn() -> F>,
+}
+
+impl<F> Default for OutlineMatchingPlugin<F> {
+    fn default() -> Self {
+        OutlineMatchingPlugin {
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<F: ReadOnlyWorldQuery + Send + Sync + 'static> Plugin for OutlineMatchingPlugin<F> {
+    fn build(&self, app: &mut App) {
+        app.add_system_to_stage(CoreStage::PostUpdate, outline_matching::<F>);
+    }
+}
+
+/// Plugin for outlining every mesh entity with the marker component `M`.
+///
+/// This keeps [`Outline`] in sync with the presence of an existing marker, such
+/// as a `Selected` or `Hovered` component maintained by a picking system, so no
+/// custom systems are needed to integrate with it. The marker must be on the
+/// mesh entity itself.
+///
+/// ```ignore
+/// app.add_plugin(OutlineForMarker::<Selected>::default());
+/// ```
+pub type OutlineForMarker<M> = OutlineMatchingPlugin<With<M>>;
+
+/// Marks an `Outline` added by an `OutlineMatchingPlugin<F>`.
+#[derive(Component)]
+struct MatchedOutline<F> {
+    marker: PhantomData<fn() -> F>,
+}
+
+/// Mesh entities matching `F` which haven't been outlined yet.
+type UnmatchedFilter<F> = (
+    With<Handle<Mesh>>,
+    Without<Outline>,
+    Without<MatchedOutline<F>>,
+    F,
+);
+
+fn outline_matching<F: ReadOnlyWorldQuery + Send + Sync + 'static>(
+    mut commands: Commands,
+    unmatched: Query<Entity, UnmatchedFilter<F>>,
+    matched: Query<Entity, With<MatchedOutline<F>>>,
+    filter: Query<(), F>,
+) {
+    for entity in matched.iter() {
+        if filter.get(entity).is_err() {
+            commands
+                .entity(entity)
+                .remove::<Outline>()
+                .remove::<MatchedOutline<F>>();
+        }
+    }
+
+    for entity in unmatched.iter() {
+        commands.entity(entity).insert_bundle((
+            Outline { enabled: true },
+            MatchedOutline::<F> {
+                marker: PhantomData,
+            },
+        ));
+    }
+}
@@ -0,0 +1,289 @@
+use std::marker::PhantomData;
+
+use bevy::{
+    pbr::{
+        DrawMesh, Material, MeshUniform, RenderMaterials, SetMaterialBindGroup, SetMeshBindGroup,
+        SetMeshViewBindGroup,
+    },
+    prelude::*,
+    render::{
+        mesh::InnerMeshVertexBufferLayout,
+        render_asset::RenderAssets,
+        render_phase::{AddRenderCommand, DrawFunctions, RenderPhase, SetItemPipeline},
+        render_resource::{
+            BindGroupLayout, PipelineCache, RenderPipelineDescriptor, ShaderRef,
+            SpecializedMeshPipeline, SpecializedMeshPipelineError, SpecializedMeshPipelines,
+        },
+        renderer::RenderDevice,
+        view::{ExtractedView, VisibleEntities},
+        Extract, RenderApp, RenderStage,
+    },
+    utils::{FixedState, Hashed},
+};
+
+use crate::{
+    mask::{
+        CustomMeshMask, MeshMaskPipeline, MeshMaskPipelineKey, MeshMaskViewKeys,
+        SetMaskViewBindGroup,
+    },
+    MeshMask, Outline, OutlineRenderSystem,
+};
+
+/// A [`Material`] which supplies its own vertex shader for drawing into the
+/// outline mask.
+///
+/// By default, outlined meshes are drawn into the mask with their undeformed
+/// vertices, so materials which displace vertices, such as for wind or waves,
+/// are outlined along the original mesh. Implementing this trait and adding an
+/// [`OutlineMaterialPlugin`] for the material draws its meshes into the mask
+/// with [`mask_vertex_shader`](OutlineMaterial::mask_vertex_shader) instead.
+///
+/// The shader is bound as follows:
+///
+/// - group 0 holds the view, as imported from `bevy_pbr::mesh_view_bindings`.
+/// - group 1 holds the mesh.
+/// - group 2 holds the mask dimensions and the view's outline parameters.
+/// - group 3 holds the material's bind group, as in the main pass.
+///
+/// The mesh's vertex attributes are at the same locations as in
+/// `bevy_pbr::mesh`, with the same shader defs, such as `VERTEX_UVS` and
+/// `SKINNED`.
+///
+/// The `outline::mask_vertex` shader import binds the mesh, dimensions and
+/// outline parameters. It also defines the `VertexOutput` struct which the
+/// shader must return and `mask_clip_position`, which projects a world space
+/// position into the mask, removing any [`OutlineJitter`](crate::OutlineJitter).
+/// It must be preceded by the `bevy_pbr::mesh_view_bindings` and
+/// `bevy_pbr::mesh_types` imports.
+///
+/// ```wgsl
+/// #import bevy_pbr::mesh_view_bindings
+/// #import bevy_pbr::mesh_types
+/// #import outline::mask_vertex
+///
+/// struct WaveMaterial {
+///     amplitude: f32,
+/// };
+///
+/// @group(3) @binding(0)
+/// var<uniform> material: WaveMaterial;
+///
+/// @vertex
+/// fn vertex(@location(0) position: vec3<f32>) -> VertexOutput {
+///     var world_position = mesh.model * vec4<f32>(position, 1.0);
+///     world_position.y += material.amplitude * sin(world_position.x);
+///
+///     var out: VertexOutput;
+///     out.clip_position = mask_clip_position(world_position);
+///     out.world_position = world_position.xyz;
+///     return out;
+/// }
+/// ```
+pub trait OutlineMaterial: Material {
+    /// Returns the vertex shader used to draw meshes with this material into
+    /// the outline mask.
+    ///
+    /// The shader's entry point must be named `vertex`. If
+    /// [`ShaderRef::Default`] is returned, the mask's own vertex shader is
+    /// used.
+    fn mask_vertex_shader() -> ShaderRef {
+        ShaderRef::Default
+    }
+}
+
+/// Plugin for drawing meshes with the material `M` into the outline mask with
+/// the material's [`OutlineMaterial::mask_vertex_shader`].
+///
+/// This must be added after the [`OutlinePlugin`](crate::OutlinePlugin) and
+/// the material's `MaterialPlugin`.
+pub struct OutlineMaterialPlugin<M> {
+    marker: PhantomData<fn() -> M>,
+}
+
+impl<M> Default for OutlineMaterialPlugin<M> {
+    fn default() -> Self {
+        OutlineMaterialPlugin {
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<M: OutlineMaterial> Plugin for OutlineMaterialPlugin<M> {
+    fn build(&self, app: &mut App) {
+        let render_app = match app.get_sub_app_mut(RenderApp) {
+            Ok(r) => r,
+            Err(_) => return,
+        };
+
+        render_app
+            .add_render_command::<MeshMask, DrawMaterialMask<M>>()
+            .init_resource::<MaterialMaskPipeline<M>>()
+            .init_resource::<SpecializedMeshPipelines<MaterialMaskPipeline<M>>>()
+            .add_system_to_stage(RenderStage::Extract, extract_material_masks::<M>)
+            .add_system_to_stage(
+                RenderStage::Queue,
+                queue_material_mesh_masks::<M>.after(OutlineRenderSystem::QueueMaskViewKeys),
+            );
+    }
+}
+
+fn extract_material_masks<M: OutlineMaterial>(
+    mut commands: Commands,
+    mut previous_len: Local<usize>,
+    meshes: Extract<Query<Entity, With<Handle<M>>>>,
+) {
+    let mut batches = Vec::with_capacity(*previous_len);
+    batches.extend(meshes.iter().map(|entity| (entity, (CustomMeshMask,))));
+    *previous_len = batches.len();
+    commands.insert_or_spawn_batch(batches);
+}
+
+/// The mask pipeline, with the vertex shader and bind group of the material
+/// `M`.
+pub struct MaterialMaskPipeline<M> {
+    mask_pipeline: MeshMaskPipeline,
+    material_layout: BindGroupLayout,
+    vertex_shader: Option<Handle<Shader>>,
+    marker: PhantomData<fn() -> M>,
+}
+
+impl<M: OutlineMaterial> FromWorld for MaterialMaskPipeline<M> {
+    fn from_world(world: &mut World) -> Self {
+        let asset_server = world.resource::<AssetServer>();
+        let device = world.resource::<RenderDevice>();
+
+        MaterialMaskPipeline {
+            mask_pipeline: world.resource::<MeshMaskPipeline>().clone(),
+            material_layout: M::bind_group_layout(device),
+            vertex_shader: match M::mask_vertex_shader() {
+                ShaderRef::Default => None,
+                ShaderRef::Handle(handle) => Some(handle),
+                ShaderRef::Path(path) => Some(asset_server.load(path)),
+            },
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<M: OutlineMaterial> SpecializedMeshPipeline for MaterialMaskPipeline<M> {
+    type Key = MeshMaskPipelineKey;
+
+    fn specialize(
+        &self,
+        key: Self::Key,
+        layout: &Hashed<InnerMeshVertexBufferLayout, FixedState>,
+    ) -> Result<RenderPipelineDescriptor, SpecializedMeshPipelineError> {
+        let mut desc = self.mask_pipeline.specialize(key, layout)?;
+        if let Some(vertex_shader) = &self.vertex_shader {
+            // The mask's own shader only reads positions, while custom shaders
+            // may read any attribute of the mesh pipeline.
+            let mesh_desc = self
+                .mask_pipeline
+                .mesh_pipeline
+                .specialize(key.mesh_key, layout)?;
+            desc.vertex.shader = vertex_shader.clone();
+            desc.vertex.buffers = mesh_desc.vertex.buffers;
+            for def in mesh_desc.vertex.shader_defs {
+                if !desc.vertex.shader_defs.contains(&def) {
+                    desc.vertex.shader_defs.push(def);
+                }
+            }
+        }
+
+        // The material's bind group replaces the alpha mask, which is only
+        // used with `StandardMaterial`.
+        desc.layout
+            .as_mut()
+            .unwrap()
+            .push(self.material_layout.clone());
+
+        Ok(desc)
+    }
+}
+
+type DrawMaterialMask<M> = (
+    SetItemPipeline,
+    SetMeshViewBindGroup<0>,
+    SetMeshBindGroup<1>,
+    SetMaskViewBindGroup<2>,
+    SetMaterialBindGroup<M, 3>,
+    DrawMesh,
+);
+
+/// An outlined mesh drawn into the mask with the material `M`.
+type MaterialMaskMesh<M> = (
+    &'static Handle<M>,
+    &'static Handle<Mesh>,
+    &'static MeshUniform,
+);
+
+#[allow(clippy::too_many_arguments)]
+fn queue_material_mesh_masks<M: OutlineMaterial>(
+    mesh_mask_draw_functions: Res<DrawFunctions<MeshMask>>,
+    material_mask_pipeline: Res<MaterialMaskPipeline<M>>,
+    mut pipelines: ResMut<SpecializedMeshPipelines<MaterialMaskPipeline<M>>>,
+    mut pipeline_cache: ResMut<PipelineCache>,
+    render_meshes: Res<RenderAssets<Mesh>>,
+    render_materials: Res<RenderMaterials<M>>,
+    view_keys: Res<MeshMaskViewKeys>,
+    material_meshes: Query<MaterialMaskMesh<M>, With<Outline>>,
+    mut views: Query<(
+        Entity,
+        &ExtractedView,
+        &VisibleEntities,
+        &mut RenderPhase<MeshMask>,
+    )>,
+) {
+    let draw_material_mask = mesh_mask_draw_functions
+        .read()
+        .get_id::<DrawMaterialMask<M>>()
+        .unwrap();
+
+    for (view_entity, view, visible_entities, mut mesh_mask_phase) in views.iter_mut() {
+        let view_key = match view_keys.get(view_entity) {
+            Some(k) => k,
+            None => continue,
+        };
+
+        let view_matrix = view.transform.compute_matrix();
+        let inv_view_row_2 = view_matrix.inverse().row(2);
+
+        for visible_entity in visible_entities.entities.iter().copied() {
+            let (material_handle, mesh_handle, mesh_uniform) =
+                match material_meshes.get(visible_entity) {
+                    Ok(m) => m,
+                    Err(_) => continue,
+                };
+
+            // Skipped until the material is ready, as in the main pass.
+            if !render_materials.contains_key(material_handle) {
+                continue;
+            }
+
+            let mesh = match render_meshes.get(mesh_handle) {
+                Some(m) => m,
+                None => continue,
+            };
+
+            let pipeline = match pipelines.specialize(
+                &mut pipeline_cache,
+                &material_mask_pipeline,
+                view_key.pipeline_key(mesh.primitive_topology),
+                &mesh.layout,
+            ) {
+                Ok(p) => p,
+                Err(e) => {
+                    error!("failed to specialize outline material mask pipeline: {}", e);
+                    continue;
+                }
+            };
+
+            mesh_mask_phase.add(MeshMask {
+                entity: visible_entity,
+                pipeline,
+                draw_function: draw_material_mask,
+                distance: inv_view_row_2.dot(mesh_uniform.transform.col(3)),
+            });
+        }
+    }
+}
@@ -0,0 +1,326 @@
+use bevy::{
+    prelude::*,
+    reflect::TypeUuid,
+    render::{
+        mesh::InnerMeshVertexBufferLayout,
+        render_asset::RenderAssets,
+        render_phase::{
+            sort_phase_system, AddRenderCommand, CachedRenderPipelinePhaseItem, DrawFunctionId,
+            DrawFunctions, EntityPhaseItem, PhaseItem, RenderPhase, SetItemPipeline,
+            TrackedRenderPass,
+        },
+        render_resource::{
+            CachedRenderPipelineId, ColorTargetState, ColorWrites, FragmentState, MultisampleState,
+            PipelineCache, PrimitiveState, RenderPipelineDescriptor, SpecializedMeshPipeline,
+            SpecializedMeshPipelineError, SpecializedMeshPipelines, VertexState,
+        },
+        view::{Msaa, VisibleEntities},
+        Extract, RenderApp, RenderStage,
+    },
+    sprite::{
+        ColorMaterial, DrawMesh2d, Mesh2dHandle, Mesh2dPipeline, Mesh2dPipelineKey, Mesh2dUniform,
+        SetMesh2dBindGroup, SetMesh2dViewBindGroup,
+    },
+    utils::{FixedState, FloatOrd, Hashed},
+};
+
+use crate::{
+    mask::{
+        self, MaskColors, MaskUniform, MeshMaskPipeline, SetMaskViewBindGroup,
+        MASK_GROUP_TEXTURE_FORMAT,
+    },
+    CameraOutline, Outline, OutlineFlash, OutlineGroup, OutlineStyle,
+};
+
+const MESH2D_MASK_SHADER_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 7612094453082261781);
+
+/// Adds outlines for 2D meshes to the core 2D graph.
+///
+/// Entities with a `Mesh2dHandle` and an enabled [`Outline`] are drawn into
+/// the mask of each outlined 2D camera by the sprite mask node, before any
+/// sprites. The mask color is taken from the entity's `ColorMaterial`, if it
+/// has one.
+pub(crate) fn add_mesh2d_outlines(app: &mut App) {
+    let mut shaders = app.world.resource_mut::<Assets<Shader>>();
+    shaders.set_untracked(
+        MESH2D_MASK_SHADER_HANDLE,
+        Shader::from_wgsl(include_str!("shaders/mesh2d_mask.wgsl")),
+    );
+
+    let render_app = match app.get_sub_app_mut(RenderApp) {
+        Ok(r) => r,
+        Err(_) => return,
+    };
+
+    render_app
+        .init_resource::<DrawFunctions<Mesh2dMask>>()
+        .add_render_command::<Mesh2dMask, DrawMesh2dMask>()
+        .init_resource::<Mesh2dMaskPipeline>()
+        .init_resource::<SpecializedMeshPipelines<Mesh2dMaskPipeline>>()
+        .add_system_to_stage(RenderStage::Extract, extract_mesh2d_mask_camera_phase)
+        .add_system_to_stage(RenderStage::Extract, extract_mesh2d_mask_uniforms)
+        .add_system_to_stage(RenderStage::Queue, queue_mesh2d_masks)
+        .add_system_to_stage(RenderStage::PhaseSort, sort_phase_system::<Mesh2dMask>);
+}
+
+/// Phase item for drawing a 2D mesh into the mask of a 2D view.
+pub struct Mesh2dMask {
+    /// The depth of the entity. Items are drawn in order of increasing depth,
+    /// as in the main 2D pass, so entities in front overwrite the colors and
+    /// groups of those behind them.
+    pub sort_key: FloatOrd,
+    pub pipeline: CachedRenderPipelineId,
+    pub entity: Entity,
+    pub draw_function: DrawFunctionId,
+}
+
+impl PhaseItem for Mesh2dMask {
+    type SortKey = (FloatOrd, Entity);
+
+    fn sort_key(&self) -> Self::SortKey {
+        // Ties are broken by entity, as for 3D meshes.
+        (self.sort_key, self.entity)
+    }
+
+    fn draw_function(&self) -> DrawFunctionId {
+        self.draw_function
+    }
+}
+
+impl EntityPhaseItem for Mesh2dMask {
+    fn entity(&self) -> Entity {
+        self.entity
+    }
+}
+
+impl CachedRenderPipelinePhaseItem for Mesh2dMask {
+    fn cached_pipeline(&self) -> CachedRenderPipelineId {
+        self.pipeline
+    }
+}
+
+type DrawMesh2dMask = (
+    SetItemPipeline,
+    SetMesh2dViewBindGroup<0>,
+    SetMesh2dBindGroup<1>,
+    SetMaskViewBindGroup<2>,
+    DrawMesh2d,
+);
+
+/// The pipeline drawing 2D meshes into the mask.
+pub struct Mesh2dMaskPipeline {
+    mesh2d_pipeline: Mesh2dPipeline,
+    mask_pipeline: MeshMaskPipeline,
+}
+
+impl FromWorld for Mesh2dMaskPipeline {
+    fn from_world(world: &mut World) -> Self {
+        Mesh2dMaskPipeline {
+            mesh2d_pipeline: world.resource::<Mesh2dPipeline>().clone(),
+            mask_pipeline: world.resource::<MeshMaskPipeline>().clone(),
+        }
+    }
+}
+
+/// Key for specializing the [`Mesh2dMaskPipeline`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Mesh2dMaskPipelineKey {
+    pub mesh_key: Mesh2dPipelineKey,
+    /// Whether the mask holds colors rather than only coverage.
+    pub mask_colors: bool,
+}
+
+impl SpecializedMeshPipeline for Mesh2dMaskPipeline {
+    type Key = Mesh2dMaskPipelineKey;
+
+    fn specialize(
+        &self,
+        key: Self::Key,
+        layout: &Hashed<InnerMeshVertexBufferLayout, FixedState>,
+    ) -> Result<RenderPipelineDescriptor, SpecializedMeshPipelineError> {
+        let vertex_buffer_layout =
+            layout.get_layout(&[Mesh::ATTRIBUTE_POSITION.at_shader_location(0)])?;
+
+        Ok(RenderPipelineDescriptor {
+            label: Some("outline_mesh2d_mask_pipeline".into()),
+            layout: Some(vec![
+                self.mesh2d_pipeline.view_layout.clone(),
+                self.mesh2d_pipeline.mesh_layout.clone(),
+                self.mask_pipeline.view_layout().clone(),
+            ]),
+            vertex: VertexState {
+                shader: MESH2D_MASK_SHADER_HANDLE.typed::<Shader>(),
+                shader_defs: vec![],
+                entry_point: "vertex".into(),
+                buffers: vec![vertex_buffer_layout],
+            },
+            fragment: Some(FragmentState {
+                shader: MESH2D_MASK_SHADER_HANDLE.typed::<Shader>(),
+                shader_defs: mask::mask_shader_defs(key.mask_colors),
+                entry_point: "fragment".into(),
+                targets: vec![
+                    Some(ColorTargetState {
+                        format: mask::mask_texture_format(key.mask_colors),
+                        blend: None,
+                        write_mask: ColorWrites::ALL,
+                    }),
+                    Some(ColorTargetState {
+                        format: MASK_GROUP_TEXTURE_FORMAT,
+                        blend: None,
+                        write_mask: ColorWrites::ALL,
+                    }),
+                ],
+            }),
+            primitive: PrimitiveState {
+                topology: key.mesh_key.primitive_topology(),
+                ..default()
+            },
+            depth_stencil: None,
+            multisample: MultisampleState {
+                count: key.mesh_key.msaa_samples(),
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+        })
+    }
+}
+
+fn extract_mesh2d_mask_camera_phase(
+    mut commands: Commands,
+    cameras: Extract<
+        Query<
+            Entity,
+            (
+                With<Camera2d>,
+                Or<(With<CameraOutline>, With<OutlineStyle>, With<OutlineFlash>)>,
+            ),
+        >,
+    >,
+) {
+    for entity in cameras.iter() {
+        commands
+            .get_or_spawn(entity)
+            .insert(RenderPhase::<Mesh2dMask>::default());
+    }
+}
+
+/// Extracts the colors and outline groups of visible 2D meshes with an enabled
+/// [`Outline`], along with the `Outline` itself.
+///
+/// Meshes without a `ColorMaterial` are white.
+fn extract_mesh2d_mask_uniforms(
+    mut commands: Commands,
+    mut previous_len: Local<usize>,
+    materials: Extract<Res<Assets<ColorMaterial>>>,
+    meshes: Extract<
+        Query<
+            (
+                Entity,
+                &ComputedVisibility,
+                &Outline,
+                Option<&Handle<ColorMaterial>>,
+                Option<&OutlineGroup>,
+            ),
+            With<Mesh2dHandle>,
+        >,
+    >,
+) {
+    let mut batches = Vec::with_capacity(*previous_len);
+    for (entity, visibility, outline, material, group) in meshes.iter() {
+        if !visibility.is_visible() || !outline.enabled {
+            continue;
+        }
+
+        let color = material
+            .and_then(|handle| materials.get(handle))
+            .map_or(Color::WHITE, |material| material.color);
+        let group = group.map_or(0, |group| group.0 as u32);
+        batches.push((
+            entity,
+            (MaskUniform::from_color(color, group), outline.clone()),
+        ));
+    }
+    *previous_len = batches.len();
+    commands.insert_or_spawn_batch(batches);
+}
+
+#[allow(clippy::too_many_arguments)]
+fn queue_mesh2d_masks(
+    mesh2d_mask_draw_functions: Res<DrawFunctions<Mesh2dMask>>,
+    mesh2d_mask_pipeline: Res<Mesh2dMaskPipeline>,
+    mut pipelines: ResMut<SpecializedMeshPipelines<Mesh2dMaskPipeline>>,
+    mut pipeline_cache: ResMut<PipelineCache>,
+    msaa: Res<Msaa>,
+    render_meshes: Res<RenderAssets<Mesh>>,
+    outline_meshes: Query<(&Mesh2dHandle, &Mesh2dUniform), With<Outline>>,
+    mut views: Query<(
+        &VisibleEntities,
+        &mut RenderPhase<Mesh2dMask>,
+        Option<&MaskColors>,
+    )>,
+) {
+    let draw_mesh2d_mask = mesh2d_mask_draw_functions
+        .read()
+        .get_id::<DrawMesh2dMask>()
+        .unwrap();
+    let msaa_key = Mesh2dPipelineKey::from_msaa_samples(msaa.samples);
+
+    for (visible_entities, mut mesh2d_mask_phase, mask_colors) in views.iter_mut() {
+        for visible_entity in visible_entities.entities.iter().copied() {
+            let (mesh_handle, mesh_uniform) = match outline_meshes.get(visible_entity) {
+                Ok(m) => m,
+                Err(_) => continue,
+            };
+
+            let mesh = match render_meshes.get(&mesh_handle.0) {
+                Some(m) => m,
+                None => continue,
+            };
+
+            let key = Mesh2dMaskPipelineKey {
+                mesh_key: msaa_key
+                    | Mesh2dPipelineKey::from_primitive_topology(mesh.primitive_topology),
+                mask_colors: mask_colors.is_some(),
+            };
+            let pipeline = match pipelines.specialize(
+                &mut pipeline_cache,
+                &mesh2d_mask_pipeline,
+                key,
+                &mesh.layout,
+            ) {
+                Ok(p) => p,
+                Err(e) => {
+                    error!("failed to specialize outline 2D mesh mask pipeline: {}", e);
+                    continue;
+                }
+            };
+
+            mesh2d_mask_phase.add(Mesh2dMask {
+                entity: visible_entity,
+                pipeline,
+                draw_function: draw_mesh2d_mask,
+                sort_key: FloatOrd(mesh_uniform.transform.w_axis.z),
+            });
+        }
+    }
+}
+
+/// Draws the 2D meshes queued for `view_entity` into `pass`.
+pub(crate) fn draw_mesh2d_masks<'w>(
+    pass: &mut TrackedRenderPass<'w>,
+    world: &'w World,
+    view_entity: Entity,
+) {
+    let phase = match world.get::<RenderPhase<Mesh2dMask>>(view_entity) {
+        Some(p) => p,
+        None => return,
+    };
+
+    let draw_functions = world.resource::<DrawFunctions<Mesh2dMask>>();
+    let mut draw_functions = draw_functions.write();
+    for item in phase.items.iter() {
+        let draw_function = draw_functions.get_mut(item.draw_function()).unwrap();
+        draw_function.draw(world, pass, view_entity, item);
+    }
+}
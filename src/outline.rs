@@ -1,68 +1,1451 @@
 use bevy::{
+    asset::HandleId,
     prelude::*,
     render::{
-        camera::ExtractedCamera,
-        render_asset::RenderAssets,
+        camera::{ExtractedCamera, RenderTarget},
+        primitives::Aabb,
+        render_asset::{RenderAsset, RenderAssets},
         render_graph::{Node, NodeRunError, RenderGraphContext, SlotInfo, SlotType},
         render_phase::TrackedRenderPass,
         render_resource::{
-            BindGroup, BindGroupLayout, BlendComponent, BlendFactor, BlendOperation, BlendState,
-            CachedRenderPipelineId, ColorTargetState, ColorWrites, FragmentState, LoadOp,
-            MultisampleState, Operations, PipelineCache, RenderPassColorAttachment,
-            RenderPassDescriptor, RenderPipelineDescriptor, ShaderType, SpecializedRenderPipeline,
-            SpecializedRenderPipelines, TextureFormat, TextureSampleType, TextureUsages,
-            UniformBuffer, VertexState,
+            encase, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
+            BindingResource, BlendComponent, BlendFactor, BlendOperation, BlendState, Buffer,
+            CachedRenderPipelineId, ColorTargetState, ColorWrites, DynamicUniformBuffer,
+            FragmentState, LoadOp, MultisampleState, Operations, PipelineCache,
+            RenderPassColorAttachment, RenderPassDescriptor, RenderPipelineDescriptor, ShaderType,
+            SpecializedRenderPipeline, SpecializedRenderPipelines, TextureFormat,
+            TextureSampleType, TextureUsages, TextureView, TextureViewId, UniformBuffer,
+            VertexState,
         },
-        renderer::RenderContext,
-        view::ExtractedWindows,
+        renderer::{RenderContext, RenderDevice, RenderQueue},
+        texture::BevyDefault,
+        view::{ExtractedWindows, Msaa, ViewDepthTexture, VisibleEntities},
+        Extract,
     },
+    utils::{HashMap, HashSet},
 };
 
 use crate::{
-    resources::{self, OutlineResources},
-    CameraOutline, OutlineStyle, FULLSCREEN_PRIMITIVE_STATE, OUTLINE_SHADER_HANDLE,
+    capture::{OutlineCapture, OutlineCapturePipeline},
+    coverage,
+    flash::OutlineFlash,
+    mask::{self, MaskColors, TestsOcclusion},
+    resources::{self, OutlineResources, ViewJfaFormat, ViewOutlineResources},
+    scissor,
+    tween::OutlineStyleTween,
+    CameraOutline, DistanceMetric, JfaNoSeed, JfaTextureFormat, Outline, OutlineColorSource,
+    OutlineDebugImages, OutlineDebugView, OutlineDepthOcclusion, OutlineFalloff, OutlineFill,
+    OutlineJfaFormat, OutlineJitter, OutlineOcclusion, OutlineOnly, OutlineOverlay,
+    OutlinePulseTarget, OutlineResolution, OutlineScreenMask, OutlineSettings, OutlineStyle,
+    OutlineTextures, OutlineUnits, OutlineUpsample, FULLSCREEN_PRIMITIVE_STATE,
+    OUTLINE_SHADER_HANDLE,
 };
 
-#[derive(Clone, Debug, Default, PartialEq, ShaderType)]
+bitflags::bitflags! {
+    /// Flags controlling the behavior of the outline shader.
+    ///
+    /// These must be kept in sync with the constants in `outline.wgsl`.
+    #[derive(Default)]
+    pub struct OutlineFlags: u32 {
+        /// Discard fragments covered by the mask.
+        const STENCIL_INTERIOR = 1 << 0;
+        /// Fill with a glow rather than a solid band.
+        const GLOW = 1 << 1;
+        /// Pulse the outline's width.
+        const PULSE_WIDTH = 1 << 2;
+        /// Pulse the outline's alpha.
+        const PULSE_ALPHA = 1 << 3;
+        /// Draw a band inside the silhouette.
+        const INNER = 1 << 4;
+        /// Modulate the outline with the fill texture.
+        const TEXTURED = 1 << 5;
+        /// Take the outline color from the mask rather than the style.
+        const MATERIAL_COLOR = 1 << 6;
+        /// Lengths are in world units rather than pixels.
+        const WORLD_UNITS = 1 << 7;
+        /// Lengths are in logical rather than physical pixels.
+        const LOGICAL_PIXELS = 1 << 8;
+    }
+}
+
+/// The smallest glow radius passed to the shader, in the style's units.
+const MIN_GLOW_RADIUS: f32 = 1e-4;
+
+#[derive(Clone, Debug, Default, PartialEq, Component, ShaderType)]
 pub struct OutlineParams {
     // Outline color.
     pub(crate) color: Vec4,
     // Outline weight in pixels.
     pub(crate) weight: f32,
+    // Bits of `OutlineFlags`.
+    pub(crate) flags: u32,
+    // Scale applied to the outline weight along each axis.
+    pub(crate) axis_scale: Vec2,
+    // Length of dashes in pixels, or zero for a solid outline.
+    pub(crate) dash_length: f32,
+    // Dash scrolling speed in pixels per second.
+    pub(crate) dash_speed: f32,
+    // Distance in pixels at which the glow fades out completely.
+    pub(crate) glow_radius: f32,
+    // Exponent of the glow falloff curve.
+    pub(crate) glow_exponent: f32,
+    // Fraction by which the pulsed properties are reduced at each trough.
+    pub(crate) pulse_amplitude: f32,
+    // Pulses per second.
+    pub(crate) pulse_frequency: f32,
+    // Offset of the pulses as a fraction of their period.
+    pub(crate) pulse_phase: f32,
+    // Color of the inner outline.
+    pub(crate) inner_color: Vec4,
+    // Width of the inner outline in pixels.
+    pub(crate) inner_width: f32,
+    // One of the `OUTLINE_FALLOFF_*` constants in `outline.wgsl`.
+    pub(crate) falloff: u32,
+    // Rate of exponential falloff.
+    pub(crate) falloff_k: f32,
+    // `DistanceMetric` used to measure distances.
+    pub(crate) metric: u32,
+    // `OutlineColorSource` of the outline color.
+    pub(crate) color_source: u32,
+    // Distance in pixels at which a band falloff starts fading.
+    pub(crate) band_start: f32,
+    // `OutlineEasing` of a band falloff.
+    pub(crate) band_easing: u32,
+    // Distances from the camera across which the outline fades out.
+    pub(crate) fade_start: f32,
+    pub(crate) fade_end: f32,
+    // Width in pixels over which the outer edge fades out.
+    pub(crate) edge_width: f32,
 }
 
 impl OutlineParams {
-    pub fn new(color: Color, weight: f32) -> OutlineParams {
+    pub fn new(color: Color, weight: f32, flags: OutlineFlags) -> OutlineParams {
         let color: Vec4 = color.as_rgba_f32().into();
 
-        OutlineParams { color, weight }
+        OutlineParams {
+            color,
+            weight,
+            flags: flags.bits(),
+            axis_scale: Vec2::ONE,
+            dash_length: 0.0,
+            dash_speed: 0.0,
+            glow_radius: 0.0,
+            glow_exponent: 1.0,
+            pulse_amplitude: 0.0,
+            pulse_frequency: 0.0,
+            pulse_phase: 0.0,
+            inner_color: Vec4::ZERO,
+            inner_width: 0.0,
+            falloff: 0,
+            falloff_k: 0.0,
+            metric: DistanceMetric::Euclidean as u32,
+            color_source: OutlineColorSource::Style as u32,
+            band_start: 0.0,
+            band_easing: 0,
+            fade_start: 0.0,
+            fade_end: 0.0,
+            edge_width: 1.0,
+        }
+    }
+
+    pub fn from_style(style: &OutlineStyle) -> OutlineParams {
+        let mut flags = OutlineFlags::empty();
+        flags.set(OutlineFlags::STENCIL_INTERIOR, style.stencil_interior);
+        flags.set(
+            OutlineFlags::PULSE_WIDTH,
+            style.pulse_target != OutlinePulseTarget::Alpha,
+        );
+        flags.set(
+            OutlineFlags::PULSE_ALPHA,
+            style.pulse_target != OutlinePulseTarget::Width,
+        );
+        flags.set(OutlineFlags::TEXTURED, style.texture.is_some());
+        flags.set(
+            OutlineFlags::MATERIAL_COLOR,
+            style.color_source != OutlineColorSource::Style,
+        );
+        flags.set(
+            OutlineFlags::WORLD_UNITS,
+            style.units == OutlineUnits::World,
+        );
+        flags.set(
+            OutlineFlags::LOGICAL_PIXELS,
+            style.units == OutlineUnits::LogicalPixels,
+        );
+
+        let (glow_radius, glow_exponent) = match style.fill {
+            OutlineFill::Solid => (0.0, 1.0),
+            OutlineFill::Glow { radius, exponent } => {
+                flags.insert(OutlineFlags::GLOW);
+                // The shader divides by the radius.
+                (radius.max(MIN_GLOW_RADIUS), exponent)
+            }
+        };
+
+        let (inner_color, inner_width) = match &style.inner {
+            Some(inner) => {
+                flags.insert(OutlineFlags::INNER);
+                (inner.color.as_rgba_f32().into(), inner.width)
+            }
+            None => (Vec4::ZERO, 0.0),
+        };
+
+        // Must be kept in sync with the constants in `outline.wgsl`.
+        let (falloff, falloff_k) = match style.falloff {
+            OutlineFalloff::Hard => (0, 0.0),
+            OutlineFalloff::Linear => (1, 0.0),
+            OutlineFalloff::Smoothstep => (2, 0.0),
+            OutlineFalloff::Exponential { k } => (3, k),
+            OutlineFalloff::Band { .. } => (4, 0.0),
+        };
+
+        // A band's end replaces the style's width.
+        let (width, band_start, band_easing) = match style.falloff {
+            OutlineFalloff::Band { start, end, easing } => {
+                (end, start.clamp(0.0, end), easing as u32)
+            }
+            _ => (style.width, 0.0, 0),
+        };
+
+        OutlineParams {
+            axis_scale: style.axis_scale,
+            falloff,
+            falloff_k,
+            metric: style.metric as u32,
+            color_source: style.color_source as u32,
+            dash_length: style.dash_length,
+            dash_speed: style.dash_speed,
+            glow_radius,
+            glow_exponent,
+            pulse_amplitude: style.pulse_amplitude,
+            pulse_frequency: style.pulse_frequency,
+            pulse_phase: style.pulse_phase,
+            inner_color,
+            inner_width,
+            band_start,
+            band_easing,
+            fade_start: style.fade_start,
+            fade_end: style.fade_end,
+            edge_width: style.edge_width,
+            ..OutlineParams::new(style.color, width, flags)
+        }
+    }
+
+    /// Returns the greatest distance from the silhouette covered by the outline.
+    ///
+    /// Pulses only ever shrink the outline, so this is its extent at the peak
+    /// of each pulse.
+    pub(crate) fn extent(&self) -> f32 {
+        let weight = if self.flags & OutlineFlags::GLOW.bits() != 0 {
+            self.glow_radius
+        } else {
+            self.weight
+        };
+
+        weight.max(self.inner_width) * self.axis_scale.max_element()
+    }
+
+    /// Returns the [`extent`](Self::extent) in pixels of a view with the given
+    /// number of pixels per world unit, scale factor and edge scale, including
+    /// the part of the antialiased edge past the outline weight.
+    pub(crate) fn pixel_extent(
+        &self,
+        pixels_per_unit: f32,
+        scale_factor: f32,
+        edge_scale: f32,
+    ) -> f32 {
+        let extent = if self.flags & OutlineFlags::WORLD_UNITS.bits() != 0 {
+            self.extent() * pixels_per_unit
+        } else if self.flags & OutlineFlags::LOGICAL_PIXELS.bits() != 0 {
+            self.extent() * scale_factor
+        } else {
+            self.extent()
+        };
+
+        // The edge of a solid outline is centered half a pixel inside the
+        // weight, so it only reaches past it when wider than a pixel.
+        if self.flags & OutlineFlags::GLOW.bits() != 0 {
+            extent
+        } else {
+            extent + (0.5 * self.edge_width * edge_scale - 0.5).max(0.0)
+        }
+    }
+}
+
+/// An outline style extracted to the render world.
+#[derive(Clone, Debug, PartialEq, Component)]
+pub struct ExtractedOutlineStyle {
+    pub(crate) params: OutlineParams,
+    pub(crate) texture: Option<Handle<Image>>,
+    pub(crate) order: i32,
+    pub(crate) occlusion: OutlineOcclusion,
+}
+
+impl ExtractedOutlineStyle {
+    pub fn from_style(style: &OutlineStyle) -> ExtractedOutlineStyle {
+        ExtractedOutlineStyle {
+            params: OutlineParams::from_style(style),
+            texture: style.texture.clone(),
+            order: style.order,
+            occlusion: style.occlusion,
+        }
+    }
+
+    /// Returns the view of the style's fill texture, or `None` if the style
+    /// has a texture which hasn't been prepared yet.
+    fn texture_view<'a>(
+        &self,
+        images: &'a RenderAssets<Image>,
+        res: &'a OutlineResources,
+    ) -> Option<&'a TextureView> {
+        match &self.texture {
+            Some(handle) => images.get(handle).map(|image| &image.texture_view),
+            None => Some(&res.white_texture),
+        }
     }
 }
 
+#[derive(Clone)]
 pub struct GpuOutlineParams {
     pub(crate) params: OutlineParams,
-    pub(crate) _buffer: UniformBuffer<OutlineParams>,
+    pub(crate) texture: Option<Handle<Image>>,
+    pub(crate) order: i32,
+    pub(crate) occlusion: OutlineOcclusion,
+    pub(crate) texture_view_id: TextureViewId,
+    pub(crate) buffer: Buffer,
     pub(crate) bind_group: BindGroup,
 }
 
+impl GpuOutlineParams {
+    pub(crate) fn new(
+        style: ExtractedOutlineStyle,
+        texture_view: &TextureView,
+        device: &RenderDevice,
+        queue: &RenderQueue,
+        res: &OutlineResources,
+    ) -> GpuOutlineParams {
+        let mut buffer = UniformBuffer::from(style.params.clone());
+        buffer.write_buffer(device, queue);
+
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: None,
+            layout: &res.outline_params_bind_group_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.buffer().unwrap().as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::TextureView(texture_view),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: BindingResource::Sampler(&res.fill_texture_sampler),
+                },
+            ],
+        });
+
+        GpuOutlineParams {
+            params: style.params,
+            texture: style.texture,
+            order: style.order,
+            occlusion: style.occlusion,
+            texture_view_id: texture_view.id(),
+            buffer: buffer.buffer().unwrap().clone(),
+            bind_group,
+        }
+    }
+
+    /// Updates the parameters of this style in place, keeping its bind group.
+    ///
+    /// The texture of `style` must be the one this was prepared with. As the
+    /// buffer is overwritten, this must only be used on styles which aren't
+    /// shared through the `PreparedStyleCache`.
+    fn update_params(&mut self, style: &ExtractedOutlineStyle, queue: &RenderQueue) {
+        self.params = style.params.clone();
+        self.order = style.order;
+        self.occlusion = style.occlusion;
+
+        let mut data = encase::UniformBuffer::new(Vec::new());
+        data.write(&self.params).unwrap();
+        queue.write_buffer(&self.buffer, 0, data.as_ref());
+    }
+
+    /// Returns whether this was prepared from `style` using `texture_view`.
+    fn matches(&self, style: &ExtractedOutlineStyle, texture_view: &TextureView) -> bool {
+        self.params == style.params
+            && self.texture == style.texture
+            && self.order == style.order
+            && self.occlusion == style.occlusion
+            && self.texture_view_id == texture_view.id()
+    }
+}
+
+/// The number of prepared styles kept by the `PreparedStyleCache`.
+pub(crate) const PREPARED_STYLE_CACHE_SIZE: usize = 16;
+
+/// Cache of recently prepared outline styles.
+///
+/// Styles are often swapped back and forth in quick succession (e.g. when
+/// hovering over an entity), and the unused style may be dropped in between.
+/// Keeping recently used styles around allows them to be reused immediately
+/// rather than uploading a new buffer and creating a new bind group.
+#[derive(Default)]
+pub struct PreparedStyleCache {
+    entries: Vec<CachedStyle>,
+    generation: u64,
+    // Number of styles prepared and evicted since the last call to
+    // `take_counts`.
+    prepared: usize,
+    evicted: usize,
+}
+
+struct CachedStyle {
+    gpu_params: GpuOutlineParams,
+    last_used: u64,
+}
+
+impl PreparedStyleCache {
+    /// Returns the number of prepared styles in the cache.
+    pub(crate) fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns the number of styles prepared and evicted since the last call,
+    /// and resets both counts.
+    pub(crate) fn take_counts(&mut self) -> (usize, usize) {
+        let counts = (self.prepared, self.evicted);
+        self.prepared = 0;
+        self.evicted = 0;
+        counts
+    }
+
+    /// Returns a prepared style for `style`, reusing a cached one if possible.
+    ///
+    /// Returns `None` if the style's fill texture hasn't been prepared yet.
+    pub(crate) fn get_or_prepare(
+        &mut self,
+        style: ExtractedOutlineStyle,
+        images: &RenderAssets<Image>,
+        device: &RenderDevice,
+        queue: &RenderQueue,
+        res: &OutlineResources,
+    ) -> Option<GpuOutlineParams> {
+        let texture_view = style.texture_view(images, res)?;
+
+        self.generation += 1;
+
+        if let Some(entry) = self
+            .entries
+            .iter_mut()
+            .find(|entry| entry.gpu_params.matches(&style, texture_view))
+        {
+            entry.last_used = self.generation;
+            return Some(entry.gpu_params.clone());
+        }
+
+        if self.entries.len() >= PREPARED_STYLE_CACHE_SIZE {
+            // Evict the least recently used style.
+            let (lru, _) = self
+                .entries
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .unwrap();
+            self.entries.swap_remove(lru);
+            self.evicted += 1;
+        }
+
+        self.prepared += 1;
+
+        let gpu_params = GpuOutlineParams::new(style, texture_view, device, queue, res);
+        self.entries.push(CachedStyle {
+            gpu_params: gpu_params.clone(),
+            last_used: self.generation,
+        });
+
+        Some(gpu_params)
+    }
+}
+
+/// `OutlineStyle` assets created, modified or removed in the main world and
+/// not yet prepared.
+///
+/// This takes the place of a `RenderAssetPlugin`, which can't update a
+/// prepared asset in place.
+#[derive(Default)]
+pub struct ExtractedStyleAssets {
+    // Styles waiting to be prepared, including those whose fill texture wasn't
+    // ready in previous frames.
+    pending: Vec<(Handle<OutlineStyle>, ExtractedOutlineStyle)>,
+    removed: Vec<Handle<OutlineStyle>>,
+    // Styles which were modified after being prepared, and were then prepared
+    // for their handle alone rather than taken from the `PreparedStyleCache`,
+    // and so can be updated in place.
+    owned: HashSet<Handle<OutlineStyle>>,
+}
+
+pub fn extract_outline_style_assets(
+    mut extracted: ResMut<ExtractedStyleAssets>,
+    mut events: Extract<EventReader<AssetEvent<OutlineStyle>>>,
+    assets: Extract<Res<Assets<OutlineStyle>>>,
+) {
+    let mut changed = HashSet::default();
+    for event in events.iter() {
+        match event {
+            AssetEvent::Created { handle } | AssetEvent::Modified { handle } => {
+                changed.insert(handle.clone_weak());
+            }
+            AssetEvent::Removed { handle } => {
+                changed.remove(handle);
+                extracted.pending.retain(|(pending, _)| pending != handle);
+                extracted.removed.push(handle.clone_weak());
+            }
+        }
+    }
+
+    for handle in changed {
+        if let Some(style) = assets.get(&handle) {
+            extracted.pending.push((handle, style.extract_asset()));
+        }
+    }
+}
+
+/// Prepares the extracted `OutlineStyle` assets.
+///
+/// New styles are taken from the `PreparedStyleCache`. Once a style is
+/// modified, it's prepared for its handle alone and then updated in place
+/// while its fill texture stays the same, so animating an asset doesn't create
+/// a buffer and bind group each frame.
+pub fn prepare_outline_style_assets(
+    device: Res<RenderDevice>,
+    queue: Res<RenderQueue>,
+    res: Res<OutlineResources>,
+    images: Res<RenderAssets<Image>>,
+    mut cache: ResMut<PreparedStyleCache>,
+    mut extracted: ResMut<ExtractedStyleAssets>,
+    mut render_assets: ResMut<RenderAssets<OutlineStyle>>,
+) {
+    let extracted = &mut *extracted;
+    for handle in extracted.removed.drain(..) {
+        render_assets.remove(&handle);
+        extracted.owned.remove(&handle);
+    }
+
+    for (handle, style) in std::mem::take(&mut extracted.pending) {
+        let texture_view = match style.texture_view(&images, &res) {
+            Some(view) => view,
+            None => {
+                extracted.pending.push((handle, style));
+                continue;
+            }
+        };
+
+        match render_assets.get_mut(&handle) {
+            Some(existing) if existing.matches(&style, texture_view) => {}
+            Some(existing)
+                if extracted.owned.contains(&handle)
+                    && existing.texture == style.texture
+                    && existing.texture_view_id == texture_view.id() =>
+            {
+                existing.update_params(&style, &queue);
+            }
+            Some(existing) => {
+                *existing = GpuOutlineParams::new(style, texture_view, &device, &queue, &res);
+                extracted.owned.insert(handle);
+            }
+            None => {
+                if let Some(gpu_params) =
+                    cache.get_or_prepare(style, &images, &device, &queue, &res)
+                {
+                    render_assets.insert(handle, gpu_params);
+                }
+            }
+        }
+    }
+}
+
+/// Prepared outline styles for cameras using an `OutlineStyle` component
+/// rather than a `CameraOutline`.
+#[derive(Default)]
+pub struct ViewOutlineStyles {
+    styles: HashMap<Entity, GpuOutlineParams>,
+    // Views whose styles were prepared for them alone rather than taken from
+    // the `PreparedStyleCache`, and so can be updated in place.
+    owned: HashSet<Entity>,
+}
+
+/// Marks views whose `OutlineStyle` component is being tweened or flashed.
+#[derive(Component)]
+pub struct AnimatedViewStyle;
+
+/// Marks views drawing an `OutlineFlash` in place of their primary style.
+#[derive(Component)]
+pub struct FlashedViewStyle;
+
+/// A camera's `OutlineFlash`, along with the styles it replaces.
+type FlashedCamera = (
+    Entity,
+    &'static OutlineFlash,
+    Option<&'static CameraOutline>,
+    Option<&'static OutlineStyle>,
+);
+
+/// Cameras whose `OutlineStyle` component is being tweened.
+type AnimatedCameraFilter = (With<Camera>, With<OutlineStyle>, With<OutlineStyleTween>);
+
+pub fn extract_camera_outline_styles(
+    mut commands: Commands,
+    mut previous_len: Local<usize>,
+    styles: Extract<Option<Res<Assets<OutlineStyle>>>>,
+    cam_style_query: Extract<Query<(Entity, &OutlineStyle), With<Camera>>>,
+    animated: Extract<Query<Entity, AnimatedCameraFilter>>,
+    flashes: Extract<Query<FlashedCamera, With<Camera>>>,
+) {
+    let mut batches = Vec::with_capacity(*previous_len);
+    batches.extend(
+        cam_style_query
+            .iter()
+            .map(|(entity, style)| (entity, (style.extract_asset(),))),
+    );
+    *previous_len = batches.len();
+    commands.insert_or_spawn_batch(batches);
+
+    for entity in animated.iter() {
+        commands.get_or_spawn(entity).insert(AnimatedViewStyle);
+    }
+
+    // The flashed style replaces the camera's primary style, taking the
+    // in-place update path for animated styles.
+    for (entity, flash, cam_outline, cam_style) in flashes.iter() {
+        if let Some(style) = flash.flashed_style(cam_outline, cam_style, styles.as_deref()) {
+            commands.get_or_spawn(entity).insert_bundle((
+                style.extract_asset(),
+                AnimatedViewStyle,
+                FlashedViewStyle,
+            ));
+        }
+    }
+}
+
+pub fn prepare_view_outline_styles(
+    device: Res<RenderDevice>,
+    queue: Res<RenderQueue>,
+    res: Res<OutlineResources>,
+    images: Res<RenderAssets<Image>>,
+    mut cache: ResMut<PreparedStyleCache>,
+    mut view_styles: ResMut<ViewOutlineStyles>,
+    views: Query<(Entity, &ExtractedOutlineStyle, Option<&AnimatedViewStyle>)>,
+) {
+    let view_styles = &mut *view_styles;
+    view_styles
+        .styles
+        .retain(|entity, _| views.get(*entity).is_ok());
+    view_styles
+        .owned
+        .retain(|entity| views.get(*entity).is_ok());
+
+    for (entity, style, animated) in views.iter() {
+        let texture_view = style.texture_view(&images, &res);
+
+        if let Some(existing) = view_styles.styles.get_mut(&entity) {
+            if texture_view.is_some_and(|view| existing.matches(style, view)) {
+                continue;
+            }
+
+            // Owned styles are updated in place if only their parameters
+            // changed, which avoids creating a buffer and bind group each
+            // frame while a style is animated.
+            let same_texture = texture_view.is_some_and(|view| {
+                existing.texture == style.texture && existing.texture_view_id == view.id()
+            });
+            if same_texture && view_styles.owned.contains(&entity) {
+                existing.update_params(style, &queue);
+                continue;
+            }
+        }
+
+        if animated.is_some() {
+            if let Some(view) = texture_view {
+                let gpu_params = GpuOutlineParams::new(style.clone(), view, &device, &queue, &res);
+                view_styles.styles.insert(entity, gpu_params);
+                view_styles.owned.insert(entity);
+            }
+            continue;
+        }
+
+        if let Some(gpu_params) =
+            cache.get_or_prepare(style.clone(), &images, &device, &queue, &res)
+        {
+            view_styles.styles.insert(entity, gpu_params);
+            view_styles.owned.remove(&entity);
+        }
+    }
+}
+
+/// Marks outline styles as changed when their fill texture changes, so that
+/// they are prepared again with the new texture.
+pub(crate) fn update_styles_with_modified_textures(
+    mut image_events: EventReader<AssetEvent<Image>>,
+    mut styles: ResMut<Assets<OutlineStyle>>,
+) {
+    let modified: Vec<Handle<Image>> = image_events
+        .iter()
+        .filter_map(|event| match event {
+            AssetEvent::Created { handle } | AssetEvent::Modified { handle } => {
+                Some(handle.clone_weak())
+            }
+            AssetEvent::Removed { .. } => None,
+        })
+        .collect();
+
+    if modified.is_empty() {
+        return;
+    }
+
+    let ids: Vec<HandleId> = styles
+        .iter()
+        .filter(|(_, style)| {
+            style
+                .texture
+                .as_ref()
+                .is_some_and(|texture| modified.contains(texture))
+        })
+        .map(|(id, _)| id)
+        .collect();
+
+    for id in ids {
+        // Accessing the style mutably marks it as modified.
+        let handle = styles.get_handle(id);
+        styles.get_mut(&handle);
+    }
+}
+
+/// Per-view parameters for the outline pass.
+#[derive(Clone, Debug, Default, PartialEq, Component, ShaderType)]
+pub struct OutlineViewUniform {
+    // Multiplier applied to the outline alpha.
+    pub(crate) alpha: f32,
+    // Alpha of the outline color drawn over outlined objects.
+    pub(crate) silhouette_alpha: f32,
+    // Multiplier applied to the edge width of the view's styles, widening
+    // their antialiased edges where the distance field is coarser.
+    pub(crate) edge_scale: f32,
+    // Time in seconds since startup, used to animate outlines.
+    pub(crate) time: f32,
+    // The camera's viewport as a fraction of its target: offset in XY, size
+    // in ZW. The mask covers only the viewport, so this is used to find the
+    // view's region of the target's depth texture.
+    pub(crate) viewport: Vec4,
+    // Bitset of the `OutlineGroup`s with their own style.
+    pub(crate) mapped_groups: [UVec4; 2],
+    // Inverse of the camera's projection, used to measure the distance of
+    // the geometry in the depth buffer.
+    pub(crate) inverse_projection: Mat4,
+    // Physical pixels per world unit, used by styles with lengths in world
+    // units.
+    pub(crate) pixels_per_unit: f32,
+    // The camera's `OutlineJitter` in normalized device coordinates, which is
+    // removed from the mask.
+    pub(crate) jitter: Vec2,
+    // Physical pixels per logical pixel of the camera's target, used by styles
+    // with lengths in logical pixels.
+    pub(crate) scale_factor: f32,
+}
+
+/// Converts the camera's jitter from pixels into normalized device coordinates.
+fn ndc_jitter(camera: &Camera, jitter: Option<&OutlineJitter>) -> Vec2 {
+    match (jitter, camera.physical_viewport_size()) {
+        (Some(jitter), Some(size)) if size.x > 0 && size.y > 0 => {
+            jitter.offset * Vec2::new(2.0, -2.0) / size.as_vec2()
+        }
+        _ => Vec2::ZERO,
+    }
+}
+
+/// Returns the camera's viewport as a fraction of its target, as stored in
+/// `OutlineViewUniform::viewport`.
+fn normalized_viewport(camera: &Camera) -> Vec4 {
+    let rect = camera
+        .physical_viewport_rect()
+        .zip(camera.physical_target_size())
+        .filter(|(_, target_size)| target_size.x > 0 && target_size.y > 0);
+
+    match rect {
+        Some(((min, max), target_size)) => {
+            let target_size = target_size.as_vec2();
+            let offset = min.as_vec2() / target_size;
+            let size = (max - min).as_vec2() / target_size;
+            Vec4::new(offset.x, offset.y, size.x, size.y)
+        }
+        None => Vec4::new(0.0, 0.0, 1.0, 1.0),
+    }
+}
+
+/// Returns the number of physical pixels per world unit of an orthographic
+/// camera, or one for other projections.
+fn pixels_per_unit(camera: &Camera) -> f32 {
+    let projection = camera.projection_matrix();
+    // Perspective projections divide by depth, and have no single scale.
+    if projection.w_axis.w != 1.0 {
+        return 1.0;
+    }
+
+    match camera.physical_viewport_size() {
+        // The projection maps a world unit to `y_axis.y` of the two units of
+        // clip space spanning the viewport's height.
+        Some(size) if size.y > 0 => 0.5 * projection.y_axis.y.abs() * size.y as f32,
+        _ => 1.0,
+    }
+}
+
+/// Returns the number of physical pixels per logical pixel of the camera's
+/// target, or one if the target's size is unknown.
+fn scale_factor(camera: &Camera) -> f32 {
+    match (camera.physical_target_size(), camera.logical_target_size()) {
+        (Some(physical), Some(logical)) if logical.x > 0.0 => physical.x as f32 / logical.x,
+        _ => 1.0,
+    }
+}
+
+/// Returns a bitset of the groups which have their own style in `outline`.
+fn mapped_groups(outline: Option<&CameraOutline>) -> [UVec4; 2] {
+    let mut bits = [UVec4::ZERO; 2];
+    for &group in outline
+        .into_iter()
+        .flat_map(|outline| outline.groups.keys())
+    {
+        let group = group as usize;
+        bits[group / 128][(group / 32) % 4] |= 1 << (group % 32);
+    }
+    bits
+}
+
+/// The app's elapsed time, extracted for animating outlines.
+pub struct ExtractedOutlineTime {
+    seconds: f32,
+}
+
+pub fn extract_outline_time(
+    mut commands: Commands,
+    time: Extract<Res<Time>>,
+    settings: Extract<Res<OutlineSettings>>,
+) {
+    let seconds = match settings.deterministic {
+        Some(_) => 0.0,
+        None => time.seconds_since_startup() as f32,
+    };
+
+    commands.insert_resource(ExtractedOutlineTime { seconds });
+}
+
+#[derive(Default)]
+pub struct OutlineViewUniforms {
+    pub(crate) uniforms: DynamicUniformBuffer<OutlineViewUniform>,
+}
+
+#[derive(Component)]
+pub struct OutlineViewUniformOffset {
+    pub(crate) offset: u32,
+}
+
+#[derive(Component)]
+pub struct OutlineViewBindGroup {
+    bind_group: BindGroup,
+}
+
+/// The view bind group of a view with [`OutlineDepthOcclusion`], which also
+/// binds the view's depth texture.
+#[derive(Component)]
+pub struct OutlineViewDepthBindGroup {
+    bind_group: BindGroup,
+}
+
+/// A camera along with every component affecting how its outline is drawn.
+type OutlineCamera = (
+    Entity,
+    &'static Camera,
+    &'static GlobalTransform,
+    &'static VisibleEntities,
+    Option<&'static CameraOutline>,
+    Option<&'static OutlineStyle>,
+    Option<&'static OutlineOnly>,
+    Option<&'static OutlineScreenMask>,
+    Option<&'static OutlineOverlay>,
+    Option<&'static OutlineFlash>,
+    Option<&'static OutlineDepthOcclusion>,
+    Option<&'static OutlineJitter>,
+    Option<&'static OutlineTextures>,
+    Option<&'static OutlineJfaFormat>,
+);
+
+pub fn extract_outline_view_uniforms(
+    mut commands: Commands,
+    settings: Extract<Res<OutlineSettings>>,
+    styles: Extract<Option<Res<Assets<OutlineStyle>>>>,
+    debug_images: Extract<Option<Res<OutlineDebugImages>>>,
+    cameras: Extract<Query<OutlineCamera>>,
+    outlined: Extract<Query<(&Aabb, &GlobalTransform, &Outline)>>,
+    res: Res<OutlineResources>,
+) {
+    // Without upsampling, the nearest seed is only known to within one JFA
+    // texel, so the edge is widened by that distance to hide the
+    // stair-stepping. The fade is centered on the same point in every mode to
+    // keep the outline's perceived weight consistent.
+    let edge_scale = match settings.active_upsample() {
+        OutlineUpsample::Nearest => settings.resolution.divisor() as f32,
+        _ => 1.0,
+    };
+
+    for (
+        entity,
+        camera,
+        camera_transform,
+        visible_entities,
+        cam_outline,
+        cam_style,
+        only,
+        screen_mask,
+        overlay,
+        flash,
+        depth_occlusion,
+        jitter,
+        textures,
+        camera_format,
+    ) in cameras.iter()
+    {
+        let flashed_style =
+            flash.and_then(|flash| flash.flashed_style(cam_outline, cam_style, styles.as_deref()));
+        let asset_style = cam_outline
+            .filter(|outline| outline.enabled)
+            .and_then(|outline| styles.as_ref()?.get(&outline.style));
+        let style = match flashed_style.as_ref().or(asset_style).or(cam_style) {
+            Some(s) => s,
+            None => continue,
+        };
+
+        let pixels_per_unit = pixels_per_unit(camera);
+        // Outlines are measured in pixels of the internal resolution in
+        // deterministic mode, independent of the display.
+        let scale_factor = match settings.deterministic {
+            Some(_) => 1.0,
+            None => scale_factor(camera),
+        };
+
+        let alpha = style
+            .max_coverage
+            .and_then(|max_coverage| {
+                let bounds = visible_entities
+                    .entities
+                    .iter()
+                    .filter_map(|&e| outlined.get(e).ok())
+                    .filter(|(_, _, outline)| outline.enabled)
+                    .map(|(aabb, transform, _)| (aabb, transform));
+                let extent = OutlineParams::from_style(style).pixel_extent(
+                    pixels_per_unit,
+                    scale_factor,
+                    edge_scale,
+                );
+                let coverage =
+                    coverage::estimate_coverage(camera, camera_transform, extent, bounds)?;
+
+                Some(coverage::coverage_alpha(coverage, max_coverage))
+            })
+            .unwrap_or(1.0);
+
+        let silhouette_alpha = match only {
+            Some(only) if only.fill_silhouettes => 1.0,
+            _ => 0.0,
+        };
+
+        let enabled_outline = cam_outline.filter(|outline| outline.enabled);
+        let style_assets = styles.as_deref();
+        let view_styles = || {
+            std::iter::once(style).chain(
+                enabled_outline
+                    .into_iter()
+                    .flat_map(|outline| {
+                        outline
+                            .layers
+                            .iter()
+                            .chain(outline.groups.values())
+                            .chain(&outline.occluded)
+                    })
+                    .filter_map(move |handle| style_assets?.get(handle)),
+            )
+        };
+
+        // Occlusion is only tested if one of the view's styles depends on it.
+        let tests_occlusion = enabled_outline.is_some_and(|outline| outline.occluded.is_some())
+            || view_styles().any(|style| style.occlusion != OutlineOcclusion::Always);
+
+        // The mask only holds colors if they're used, as a single channel is
+        // cheaper to draw and sample. Masks copied into images keep their
+        // colors, as the images have a fixed format.
+        let mask_colors = textures.is_some()
+            || debug_images.is_some()
+            || view_styles().any(|style| style.color_source != OutlineColorSource::Style);
+
+        // Likewise, the inner seeds are only flooded if an inner outline is
+        // drawn, or the distance field is copied into an image, unless the
+        // camera selects its own format.
+        let jfa_format = if let Some(&OutlineJfaFormat(format)) = camera_format {
+            format
+        } else if textures.is_some()
+            || debug_images.is_some()
+            || view_styles().any(|style| style.inner.is_some())
+        {
+            res.jfa_format
+        } else {
+            res.jfa_format.outer_seeds_only()
+        };
+
+        let mut entity_commands = commands.get_or_spawn(entity);
+        entity_commands.insert(OutlineViewUniform {
+            alpha,
+            silhouette_alpha,
+            edge_scale,
+            time: 0.0,
+            viewport: normalized_viewport(camera),
+            mapped_groups: mapped_groups(enabled_outline),
+            inverse_projection: camera.projection_matrix().inverse(),
+            pixels_per_unit,
+            jitter: ndc_jitter(camera, jitter),
+            scale_factor,
+        });
+        if tests_occlusion {
+            entity_commands.insert(TestsOcclusion);
+        }
+        if mask_colors {
+            entity_commands.insert(MaskColors);
+        }
+        entity_commands.insert(ViewJfaFormat(jfa_format));
+        if let Some(only) = only {
+            entity_commands.insert(only.clone());
+        }
+        if let Some(screen_mask) = screen_mask {
+            entity_commands.insert(screen_mask.clone());
+        }
+        if let Some(overlay) = overlay {
+            entity_commands.insert(*overlay);
+        }
+        if let Some(depth_occlusion) = depth_occlusion {
+            entity_commands.insert(*depth_occlusion);
+        }
+    }
+}
+
+pub fn prepare_outline_view_uniforms(
+    mut commands: Commands,
+    device: Res<RenderDevice>,
+    queue: Res<RenderQueue>,
+    time: Res<ExtractedOutlineTime>,
+    mut view_uniforms: ResMut<OutlineViewUniforms>,
+    views: Query<(Entity, &OutlineViewUniform)>,
+) {
+    view_uniforms.uniforms.clear();
+    for (entity, uniform) in views.iter() {
+        let offset = view_uniforms.uniforms.push(OutlineViewUniform {
+            time: time.seconds,
+            ..uniform.clone()
+        });
+        commands
+            .entity(entity)
+            .insert(OutlineViewUniformOffset { offset });
+    }
+    view_uniforms.uniforms.write_buffer(&device, &queue);
+}
+
+/// The components of a view which add bindings to its view bind group.
+type OutlineViewBindings = (
+    Entity,
+    Option<&'static OutlineScreenMask>,
+    Option<&'static ViewDepthTexture>,
+    Option<&'static OutlineDepthOcclusion>,
+);
+
+pub fn queue_outline_view_bind_groups(
+    mut commands: Commands,
+    device: Res<RenderDevice>,
+    res: Res<OutlineResources>,
+    msaa: Res<Msaa>,
+    images: Res<RenderAssets<Image>>,
+    view_uniforms: Res<OutlineViewUniforms>,
+    views: Query<OutlineViewBindings, With<OutlineViewUniformOffset>>,
+) {
+    let binding = match view_uniforms.uniforms.binding() {
+        Some(b) => b,
+        None => return,
+    };
+
+    for (entity, screen_mask, depth, depth_occlusion) in views.iter() {
+        let screen_mask = screen_mask
+            .and_then(|mask| images.get(&mask.image))
+            .map(|image| &image.texture_view)
+            .unwrap_or(&res.white_texture);
+
+        let entries = [
+            BindGroupEntry {
+                binding: 0,
+                resource: binding.clone(),
+            },
+            BindGroupEntry {
+                binding: 1,
+                resource: BindingResource::TextureView(screen_mask),
+            },
+            BindGroupEntry {
+                binding: 2,
+                resource: BindingResource::Sampler(&res.screen_mask_sampler),
+            },
+        ];
+
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("jfa_outline_view_bind_group"),
+            layout: &res.outline_view_bind_group_layout,
+            entries: &entries,
+        });
+
+        let mut entity_commands = commands.entity(entity);
+        entity_commands.insert(OutlineViewBindGroup { bind_group });
+
+        // The depth texture has been made bindable by
+        // `prepare_outline_depth_textures`.
+        if let Some((depth, _)) = depth.zip(depth_occlusion) {
+            let mut entries = entries.to_vec();
+            entries.push(BindGroupEntry {
+                binding: 3,
+                resource: BindingResource::TextureView(&depth.view),
+            });
+
+            let bind_group = device.create_bind_group(&BindGroupDescriptor {
+                label: Some("jfa_outline_view_depth_bind_group"),
+                layout: &res.outline_view_depth_bind_group_layouts[(msaa.samples > 1) as usize],
+                entries: &entries,
+            });
+
+            entity_commands.insert(OutlineViewDepthBindGroup { bind_group });
+        }
+    }
+}
+
+/// The outline pipeline specialized for a view's target format and depth
+/// occlusion.
+#[derive(Component)]
+pub struct OutlineViewPipeline {
+    pipeline_id: CachedRenderPipelineId,
+}
+
+/// The components of a view which determine the format of its outline pass.
+type OutlineViewTarget = (
+    Entity,
+    &'static ExtractedCamera,
+    Option<&'static OutlineOverlay>,
+    Option<&'static ViewDepthTexture>,
+    Option<&'static OutlineDepthOcclusion>,
+    &'static ViewJfaFormat,
+    Option<&'static MaskColors>,
+);
+
+/// Specializes the outline pipeline for the target of each outlined view.
+///
+/// Windows are assumed to use the default surface format, while image targets
+/// use the format of the image, so cameras can render outlines to textures.
+#[allow(clippy::too_many_arguments)]
+pub fn queue_outline_view_pipelines(
+    mut commands: Commands,
+    mut pipeline_cache: ResMut<PipelineCache>,
+    mut pipelines: ResMut<SpecializedRenderPipelines<OutlinePipeline>>,
+    outline_pipeline: Res<OutlinePipeline>,
+    settings: Res<OutlineSettings>,
+    msaa: Res<Msaa>,
+    images: Res<RenderAssets<Image>>,
+    cameras: Query<&ExtractedCamera>,
+    views: Query<OutlineViewTarget, With<OutlineViewUniformOffset>>,
+) {
+    for (
+        entity,
+        camera,
+        overlay,
+        depth,
+        depth_occlusion,
+        &ViewJfaFormat(jfa_format),
+        mask_colors,
+    ) in views.iter()
+    {
+        // Overlays are drawn onto the overlay camera's target, if it's active.
+        let target_camera = overlay
+            .and_then(|overlay| cameras.get(overlay.camera).ok())
+            .unwrap_or(camera);
+
+        let format = match &target_camera.target {
+            // TODO: BevyDefault for surface texture format is an anti-pattern;
+            // the target texture format should be queried from the window
+            // when Bevy exposes that functionality.
+            RenderTarget::Window(_) => TextureFormat::bevy_default(),
+            RenderTarget::Image(handle) => match images.get(handle) {
+                Some(image) => image.texture_format,
+                None => continue,
+            },
+        };
+
+        let mut key = match OutlinePipelineKey::new(format) {
+            Some(k) => k
+                .with_mask_samples(msaa.samples)
+                .with_mask_colors(mask_colors.is_some())
+                .with_jfa_format(jfa_format)
+                .with_upsample(settings.active_upsample(), settings.resolution)
+                .with_debug_view(settings.debug_view),
+            None => continue,
+        };
+        // Matches the condition for an `OutlineViewDepthBindGroup`.
+        if depth.is_some() && depth_occlusion.is_some() {
+            key = key.with_depth_occlusion(msaa.samples);
+        }
+
+        let pipeline_id = pipelines.specialize(&mut pipeline_cache, &outline_pipeline, key);
+        commands
+            .entity(entity)
+            .insert(OutlineViewPipeline { pipeline_id });
+    }
+}
+
+/// The prepared outline style used by each view this frame.
+///
+/// A view's style may be missing even though the view was extracted with one,
+/// e.g. if the style asset was removed in the same frame. Rather than skipping
+/// the outline, the view's style from the previous frame is reused for one
+/// more frame, which avoids flickering during rapid style changes.
+#[derive(Default)]
+pub struct ResolvedViewStyles {
+    styles: HashMap<Entity, ResolvedStyle>,
+}
+
+struct ResolvedStyle {
+    // The view's primary style.
+    gpu_params: GpuOutlineParams,
+    // All of the view's styles, including the primary one, in drawing order.
+    layers: Vec<ResolvedLayer>,
+    // Whether the style was found this frame, rather than being reused.
+    fresh: bool,
+}
+
+/// A view's style asset, unless it's replaced by a flash.
+type ViewStyleSource = (
+    Entity,
+    Option<&'static CameraOutline>,
+    Option<&'static FlashedViewStyle>,
+);
+
+pub fn resolve_view_styles(
+    mut resolved: ResMut<ResolvedViewStyles>,
+    assets: Option<Res<RenderAssets<OutlineStyle>>>,
+    view_styles: Res<ViewOutlineStyles>,
+    views: Query<ViewStyleSource, With<OutlineViewUniform>>,
+) {
+    resolved
+        .styles
+        .retain(|entity, _| views.get(*entity).is_ok());
+
+    for (entity, outline, flashed) in views.iter() {
+        let current = match outline {
+            Some(outline) if flashed.is_none() => {
+                assets.as_ref().and_then(|a| a.get(&outline.style))
+            }
+            _ => view_styles.styles.get(&entity),
+        };
+
+        match current {
+            Some(gpu_params) => {
+                let occluded = outline
+                    .and_then(|outline| outline.occluded.as_ref())
+                    .and_then(|occluded| assets.as_ref()?.get(occluded));
+
+                let mut layers = match occluded {
+                    // The primary style is split between the visible and
+                    // occluded regions.
+                    Some(occluded) => vec![
+                        ResolvedLayer {
+                            occlusion_filter: OutlineOcclusion::Visible,
+                            ..ResolvedLayer::ungrouped(gpu_params)
+                        },
+                        ResolvedLayer {
+                            occlusion_filter: OutlineOcclusion::Occluded,
+                            ..ResolvedLayer::ungrouped(occluded)
+                        },
+                    ],
+                    None => vec![ResolvedLayer::ungrouped(gpu_params)],
+                };
+                if let Some(outline) = outline {
+                    // Layers which aren't prepared yet are skipped for now.
+                    layers.extend(
+                        outline
+                            .layers
+                            .iter()
+                            .filter_map(|layer| assets.as_ref()?.get(layer))
+                            .map(ResolvedLayer::ungrouped),
+                    );
+                    layers.extend(outline.groups.iter().filter_map(|(&group, layer)| {
+                        Some(ResolvedLayer {
+                            group_filter: group as u32 + 1,
+                            ..ResolvedLayer::ungrouped(assets.as_ref()?.get(layer)?)
+                        })
+                    }));
+                }
+                layers.sort_by_key(|layer| {
+                    (
+                        layer.style.order,
+                        layer.group_filter,
+                        layer.occlusion_filter,
+                    )
+                });
+
+                resolved.styles.insert(
+                    entity,
+                    ResolvedStyle {
+                        gpu_params: gpu_params.clone(),
+                        layers,
+                        fresh: true,
+                    },
+                );
+            }
+            None => {
+                let reuse = match resolved.styles.get_mut(&entity) {
+                    Some(previous) => std::mem::replace(&mut previous.fresh, false),
+                    None => false,
+                };
+                if !reuse {
+                    resolved.styles.remove(&entity);
+                }
+            }
+        }
+    }
+}
+
+/// An outline style drawn by a view.
+pub(crate) struct ResolvedLayer {
+    pub(crate) style: GpuOutlineParams,
+    // The `OutlineGroup` to which the style applies, plus one, or zero if the
+    // style applies to all groups without their own style.
+    pub(crate) group_filter: u32,
+    // Whether the style applies where the nearest entity is visible, hidden
+    // behind other geometry, or either.
+    pub(crate) occlusion_filter: OutlineOcclusion,
+}
+
+impl ResolvedLayer {
+    fn ungrouped(style: &GpuOutlineParams) -> ResolvedLayer {
+        ResolvedLayer {
+            style: style.clone(),
+            group_filter: 0,
+            occlusion_filter: style.occlusion,
+        }
+    }
+
+    /// Returns the filters packed for the shader, which receives them as the
+    /// instance index.
+    pub(crate) fn filter(&self) -> u32 {
+        self.group_filter | (self.occlusion_filter as u32) << 16
+    }
+}
+
+impl ResolvedViewStyles {
+    /// Returns the prepared outline style for a view, if it has one.
+    pub(crate) fn get(&self, view: Entity) -> Option<&GpuOutlineParams> {
+        self.styles.get(&view).map(|resolved| &resolved.gpu_params)
+    }
+
+    /// Returns all of the prepared outline styles for a view, in drawing
+    /// order.
+    pub(crate) fn layers(&self, view: Entity) -> &[ResolvedLayer] {
+        self.styles
+            .get(&view)
+            .map_or(&[], |resolved| &resolved.layers)
+    }
+
+    /// Returns the greatest distance in pixels from the silhouette covered by
+    /// any of a view's styles, if it has one, given its `uniform`.
+    pub(crate) fn pixel_extent(
+        &self,
+        view: Entity,
+        uniform: Option<&OutlineViewUniform>,
+    ) -> Option<f32> {
+        let style = self.get(view)?;
+        let (pixels_per_unit, scale_factor, edge_scale) =
+            uniform.map_or((1.0, 1.0, 1.0), |uniform| {
+                (
+                    uniform.pixels_per_unit,
+                    uniform.scale_factor,
+                    uniform.edge_scale,
+                )
+            });
+
+        let extent = self
+            .layers(view)
+            .iter()
+            .map(|layer| {
+                layer
+                    .style
+                    .params
+                    .pixel_extent(pixels_per_unit, scale_factor, edge_scale)
+            })
+            .fold(
+                style
+                    .params
+                    .pixel_extent(pixels_per_unit, scale_factor, edge_scale),
+                f32::max,
+            );
+        Some(extent)
+    }
+}
+
+/// Returns the prepared outline style for a view, if it has one.
+pub(crate) fn view_style(world: &World, view: Entity) -> Option<&GpuOutlineParams> {
+    world.get_resource::<ResolvedViewStyles>()?.get(view)
+}
+
+/// Returns all of the prepared outline styles for a view, in drawing order.
+pub(crate) fn view_layers(world: &World, view: Entity) -> &[ResolvedLayer] {
+    world
+        .get_resource::<ResolvedViewStyles>()
+        .map_or(&[], |resolved| resolved.layers(view))
+}
+
+/// Returns the greatest distance in pixels from the silhouette covered by any
+/// of a view's styles, if it has one.
+pub(crate) fn view_pixel_extent(world: &World, view: Entity) -> Option<f32> {
+    world
+        .get_resource::<ResolvedViewStyles>()?
+        .pixel_extent(view, world.get::<OutlineViewUniform>(view))
+}
+
 #[derive(Clone, Debug)]
 pub struct OutlinePipeline {
     dimensions_layout: BindGroupLayout,
-    input_layout: BindGroupLayout,
+    input_layouts: [BindGroupLayout; 2],
     params_layout: BindGroupLayout,
+    view_layout: BindGroupLayout,
+    view_depth_layouts: [BindGroupLayout; 2],
+    jfa_no_seed: JfaNoSeed,
 }
 
 impl FromWorld for OutlinePipeline {
     fn from_world(world: &mut World) -> Self {
         let res = world.get_resource::<resources::OutlineResources>().unwrap();
         let dimensions_layout = res.dimensions_bind_group_layout.clone();
-        let input_layout = res.outline_src_bind_group_layout.clone();
+        let input_layouts = res.outline_src_bind_group_layouts.clone();
         let params_layout = res.outline_params_bind_group_layout.clone();
+        let view_layout = res.outline_view_bind_group_layout.clone();
+        let view_depth_layouts = res.outline_view_depth_bind_group_layouts.clone();
+        let jfa_no_seed = res.jfa_no_seed;
 
         OutlinePipeline {
             dimensions_layout,
-            input_layout,
+            input_layouts,
             params_layout,
+            view_layout,
+            view_depth_layouts,
+            jfa_no_seed,
         }
     }
 }
@@ -70,6 +1453,21 @@ impl FromWorld for OutlinePipeline {
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub struct OutlinePipelineKey {
     format: TextureFormat,
+    blend: bool,
+    matte: bool,
+    // The sample count of the view's depth texture, if outlines are occluded
+    // by it.
+    depth_samples: Option<u32>,
+    // The sample count of the view's mask.
+    mask_samples: u32,
+    // Whether the view's mask holds colors rather than only coverage.
+    mask_colors: bool,
+    // The format of the view's distance field.
+    jfa_format: JfaTextureFormat,
+    upsample: OutlineUpsample,
+    // The resolution of the distance field, if it's upsampled.
+    upsample_resolution: OutlineResolution,
+    debug_view: OutlineDebugView,
 }
 
 impl OutlinePipelineKey {
@@ -86,11 +1484,98 @@ impl OutlinePipelineKey {
             .allowed_usages
             .contains(TextureUsages::RENDER_ATTACHMENT)
         {
-            Some(OutlinePipelineKey { format })
+            Some(OutlinePipelineKey {
+                format,
+                blend: true,
+                matte: false,
+                depth_samples: None,
+                mask_samples: 1,
+                mask_colors: true,
+                jfa_format: JfaTextureFormat::default(),
+                upsample: OutlineUpsample::Nearest,
+                upsample_resolution: OutlineResolution::Full,
+                debug_view: OutlineDebugView::None,
+            })
         } else {
             None
         }
     }
+
+    /// Returns a key for a pipeline which reads a mask with `samples` samples.
+    pub(crate) fn with_mask_samples(self, samples: u32) -> OutlinePipelineKey {
+        OutlinePipelineKey {
+            mask_samples: samples,
+            ..self
+        }
+    }
+
+    /// Returns a key for a pipeline which reads a mask holding colors, or only
+    /// coverage if `colors` is false.
+    pub(crate) fn with_mask_colors(self, colors: bool) -> OutlinePipelineKey {
+        OutlinePipelineKey {
+            mask_colors: colors,
+            ..self
+        }
+    }
+
+    /// Returns a key for a pipeline which reads a distance field in
+    /// `jfa_format`.
+    pub(crate) fn with_jfa_format(self, jfa_format: JfaTextureFormat) -> OutlinePipelineKey {
+        OutlinePipelineKey { jfa_format, ..self }
+    }
+
+    /// Returns a key for a pipeline which overwrites the target rather than
+    /// blending with it.
+    pub(crate) fn without_blending(self) -> OutlinePipelineKey {
+        OutlinePipelineKey {
+            blend: false,
+            ..self
+        }
+    }
+
+    /// Returns a key for a pipeline which renders an opaque grayscale matte
+    /// rather than the outline's colors.
+    pub(crate) fn with_matte(self) -> OutlinePipelineKey {
+        OutlinePipelineKey {
+            matte: true,
+            ..self
+        }
+    }
+
+    /// Returns a key for a pipeline which upsamples a distance field of the
+    /// given resolution with `upsample`.
+    pub(crate) fn with_upsample(
+        self,
+        upsample: OutlineUpsample,
+        resolution: OutlineResolution,
+    ) -> OutlinePipelineKey {
+        // Keys differing only in the resolution of an unfiltered field share
+        // a pipeline.
+        let upsample_resolution = match upsample {
+            OutlineUpsample::Nearest => OutlineResolution::Full,
+            _ => resolution,
+        };
+        OutlinePipelineKey {
+            upsample,
+            upsample_resolution,
+            ..self
+        }
+    }
+
+    /// Returns a key for a pipeline which draws an intermediate buffer rather
+    /// than the outline.
+    pub(crate) fn with_debug_view(self, debug_view: OutlineDebugView) -> OutlinePipelineKey {
+        OutlinePipelineKey { debug_view, ..self }
+    }
+
+    /// Returns a key for a pipeline which discards outline fragments behind
+    /// nearer geometry in a depth texture with `samples` samples.
+    pub(crate) fn with_depth_occlusion(self, samples: u32) -> OutlinePipelineKey {
+        OutlinePipelineKey {
+            depth_samples: Some(samples),
+            ..self
+        }
+    }
 }
 
 impl SpecializedRenderPipeline for OutlinePipeline {
@@ -110,26 +1595,67 @@ impl SpecializedRenderPipeline for OutlinePipeline {
             },
         };
 
+        let mut shader_defs = key.jfa_format.shader_defs();
+        shader_defs.extend(self.jfa_no_seed.shader_defs());
+        shader_defs.extend(mask::mask_shader_defs(key.mask_colors));
+        if key.matte {
+            shader_defs.push("OUTLINE_MATTE".into());
+        }
+
+        if key.mask_samples > 1 {
+            shader_defs.push("MASK_GROUP_MULTISAMPLED".into());
+        }
+
+        match key.upsample {
+            OutlineUpsample::Nearest => {}
+            OutlineUpsample::Bilinear => shader_defs.push("OUTLINE_UPSAMPLE_BILINEAR".into()),
+            OutlineUpsample::EdgeAware => shader_defs.push("OUTLINE_UPSAMPLE_EDGE_AWARE".into()),
+        }
+        if key.upsample_resolution == OutlineResolution::Quarter {
+            shader_defs.push("OUTLINE_QUARTER_RESOLUTION".into());
+        }
+
+        // Debug views replace the outline with their own fragment shaders.
+        let fragment_entry_point = match key.debug_view {
+            OutlineDebugView::None => "fragment",
+            OutlineDebugView::Mask => "fragment_debug_mask",
+            OutlineDebugView::Seeds => "fragment_debug_seeds",
+            OutlineDebugView::DistanceHeatmap => "fragment_debug_distance",
+            OutlineDebugView::Voronoi => "fragment_debug_voronoi",
+        };
+
+        let view_layout = match key.depth_samples {
+            Some(samples) => {
+                shader_defs.push("OUTLINE_DEPTH_OCCLUSION".into());
+                if samples > 1 {
+                    shader_defs.push("OUTLINE_DEPTH_OCCLUSION_MULTISAMPLED".into());
+                }
+                self.view_depth_layouts[(samples > 1) as usize].clone()
+            }
+            None => self.view_layout.clone(),
+        };
+
         RenderPipelineDescriptor {
             label: Some("jfa_outline_pipeline".into()),
             layout: Some(vec![
                 self.dimensions_layout.clone(),
-                self.input_layout.clone(),
+                self.input_layouts[(key.mask_samples > 1) as usize].clone(),
                 self.params_layout.clone(),
+                view_layout,
             ]),
             vertex: VertexState {
                 shader: OUTLINE_SHADER_HANDLE.typed::<Shader>(),
                 shader_defs: vec![],
-                entry_point: "vertex".into(),
+                entry_point: "vertex_layer".into(),
                 buffers: vec![],
             },
             fragment: Some(FragmentState {
                 shader: OUTLINE_SHADER_HANDLE.typed::<Shader>(),
-                shader_defs: vec![],
-                entry_point: "fragment".into(),
+                shader_defs,
+                entry_point: fragment_entry_point.into(),
                 targets: vec![Some(ColorTargetState {
                     format: key.format,
-                    blend: Some(blend),
+                    blend: key.blend.then_some(blend),
                     write_mask: ColorWrites::ALL,
                 })],
             }),
@@ -144,30 +1670,47 @@ impl SpecializedRenderPipeline for OutlinePipeline {
     }
 }
 
+fn draw_outline<'w>(
+    pass: &mut TrackedRenderPass<'w>,
+    res: &'w ViewOutlineResources,
+    style: &'w GpuOutlineParams,
+    filter: u32,
+    view_bind_group: &'w BindGroup,
+    view_offset: &OutlineViewUniformOffset,
+) {
+    pass.set_bind_group(0, &res.dimensions_bind_group, &[]);
+    pass.set_bind_group(1, &res.outline_src_bind_group, &[]);
+    pass.set_bind_group(2, &style.bind_group, &[]);
+    pass.set_bind_group(3, view_bind_group, &[view_offset.offset]);
+    pass.draw(0..3, filter..filter + 1);
+}
+
+/// Render graph node for drawing outlines onto the camera target.
 pub struct OutlineNode {
-    pipeline_id: CachedRenderPipelineId,
-    query: QueryState<(&'static ExtractedCamera, &'static CameraOutline)>,
+    query: QueryState<(
+        &'static ExtractedCamera,
+        &'static OutlineViewUniformOffset,
+        &'static OutlineViewBindGroup,
+        &'static OutlineViewPipeline,
+    )>,
 }
 
 impl OutlineNode {
+    /// The view entity.
     pub const IN_VIEW: &'static str = "in_view";
-    pub const IN_JFA: &'static str = "in_jfa";
-    pub const OUT_VIEW: &'static str = "out_view";
 
-    pub fn new(world: &mut World, target_format: TextureFormat) -> OutlineNode {
-        let pipeline_id = world.resource_scope(|world, mut cache: Mut<PipelineCache>| {
-            let base = world.get_resource::<OutlinePipeline>().unwrap().clone();
-            let mut spec = world
-                .get_resource_mut::<SpecializedRenderPipelines<OutlinePipeline>>()
-                .unwrap();
-            let key =
-                OutlinePipelineKey::new(target_format).expect("invalid format for OutlineNode");
-            spec.specialize(&mut cache, &base, key)
-        });
+    /// The flooded JFA buffer, as produced by
+    /// [`JfaNode::OUT_JUMP`](crate::graph::JfaNode::OUT_JUMP).
+    pub const IN_JFA: &'static str = "in_jfa";
 
-        let query = QueryState::new(world);
+    /// The view entity, passed through for ordering later passes.
+    pub const OUT_VIEW: &'static str = "out_view";
 
-        OutlineNode { pipeline_id, query }
+    /// Creates the node. `world` is the render world.
+    pub fn new(world: &mut World) -> OutlineNode {
+        OutlineNode {
+            query: QueryState::new(world),
+        }
     }
 }
 
@@ -193,7 +1736,7 @@ impl Node for OutlineNode {
     }
 
     fn update(&mut self, world: &mut World) {
-        self.query.update_archetypes(world)
+        self.query.update_archetypes(world);
     }
 
     fn run(
@@ -205,48 +1748,145 @@ impl Node for OutlineNode {
         let view_ent = graph.get_input_entity(Self::IN_VIEW)?;
         graph.set_output(Self::OUT_VIEW, view_ent)?;
 
-        let (camera, outline) = &self.query.get_manual(world, view_ent).unwrap();
+        let (camera, view_offset, view_bind_group, view_pipeline) =
+            match self.query.get_manual(world, view_ent) {
+                Ok(q) => q,
+                Err(_) => return Ok(()),
+            };
+
+        // Overlays are drawn onto the overlay camera's target instead, if it's
+        // active.
+        let overlay_camera = world
+            .get::<OutlineOverlay>(view_ent)
+            .and_then(|overlay| world.get::<ExtractedCamera>(overlay.camera));
+        let target_camera = overlay_camera.unwrap_or(camera);
 
         let windows = world.resource::<ExtractedWindows>();
         let images = world.resource::<RenderAssets<Image>>();
-        let target_view = match camera.target.get_texture_view(windows, images) {
+        let target_view = match target_camera.target.get_texture_view(windows, images) {
             Some(v) => v,
             None => return Ok(()),
         };
 
-        let styles = world.resource::<RenderAssets<OutlineStyle>>();
-        let style = styles.get(&outline.style).unwrap();
+        let layers = view_layers(world, view_ent);
+        if layers.is_empty() {
+            return Ok(());
+        }
 
-        let res = world.get_resource::<OutlineResources>().unwrap();
+        let res = match world.get::<ViewOutlineResources>(view_ent) {
+            Some(r) => r,
+            None => return Ok(()),
+        };
 
         let pipelines = world.get_resource::<PipelineCache>().unwrap();
-        let pipeline = match pipelines.get_render_pipeline(self.pipeline_id) {
+
+        // Views with depth occlusion skip the outline until the depth pipeline
+        // is ready, rather than drawing it through walls.
+        let layer_bind_group = match world.get::<OutlineViewDepthBindGroup>(view_ent) {
+            Some(depth) => &depth.bind_group,
+            None => &view_bind_group.bind_group,
+        };
+        let pipeline = match pipelines.get_render_pipeline(view_pipeline.pipeline_id) {
             Some(p) => p,
             None => return Ok(()),
         };
 
-        let render_pass = render_context
-            .command_encoder
-            .begin_render_pass(&RenderPassDescriptor {
-                label: Some("jfa_outline"),
-                color_attachments: &[Some(RenderPassColorAttachment {
-                    view: target_view,
-                    resolve_target: None,
-                    ops: Operations {
-                        load: LoadOp::Load,
-                        store: true,
-                    },
-                })],
-                // TODO: support outlines being occluded by world geometry
-                depth_stencil_attachment: None,
-            });
+        let outline_only = world.get::<OutlineOnly>(view_ent);
+
+        {
+            let render_pass =
+                render_context
+                    .command_encoder
+                    .begin_render_pass(&RenderPassDescriptor {
+                        label: Some("jfa_outline"),
+                        color_attachments: &[Some(RenderPassColorAttachment {
+                            view: target_view,
+                            resolve_target: None,
+                            ops: Operations {
+                                load: match outline_only.and_then(|only| only.clear_color) {
+                                    Some(color) => LoadOp::Clear(color.into()),
+                                    None => LoadOp::Load,
+                                },
+                                store: true,
+                            },
+                        })],
+                        depth_stencil_attachment: None,
+                    });
 
-        let mut tracked_pass = TrackedRenderPass::new(render_pass);
-        tracked_pass.set_render_pipeline(pipeline);
-        tracked_pass.set_bind_group(0, &res.dimensions_bind_group, &[]);
-        tracked_pass.set_bind_group(1, &res.outline_src_bind_group, &[]);
-        tracked_pass.set_bind_group(2, &style.bind_group, &[]);
-        tracked_pass.draw(0..3, 0..1);
+            let mut tracked_pass = TrackedRenderPass::new(render_pass);
+            if let Some(viewport) = &target_camera.viewport {
+                tracked_pass.set_camera_viewport(viewport);
+            }
+            // Debug views draw over the whole viewport.
+            let scissor = scissor::view_outline_region(world, view_ent)
+                .filter(|_| {
+                    world.resource::<OutlineSettings>().debug_view == OutlineDebugView::None
+                })
+                .and_then(|region| scissor::viewport_scissor(target_camera, region));
+            if let Some(scissor) = scissor {
+                scissor.apply(&mut tracked_pass);
+            }
+            tracked_pass.set_render_pipeline(pipeline);
+            for layer in layers {
+                draw_outline(
+                    &mut tracked_pass,
+                    res,
+                    &layer.style,
+                    layer.filter(),
+                    layer_bind_group,
+                    view_offset,
+                );
+            }
+        }
+
+        let capture = world
+            .get::<OutlineCapture>(view_ent)
+            .zip(world.get::<OutlineCapturePipeline>(view_ent));
+        // Captures overwrite rather than blend, so only the primary style is
+        // captured.
+        let style = view_style(world, view_ent);
+        if let Some(((capture, capture_pipeline), style)) = capture.zip(style) {
+            let capture_image = images.get(&capture.image);
+            let pipeline = pipelines.get_render_pipeline(capture_pipeline.pipeline_id);
+
+            if let Some((capture_image, pipeline)) = capture_image.zip(pipeline) {
+                let render_pass =
+                    render_context
+                        .command_encoder
+                        .begin_render_pass(&RenderPassDescriptor {
+                            label: Some("jfa_outline_capture"),
+                            color_attachments: &[Some(RenderPassColorAttachment {
+                                view: &capture_image.texture_view,
+                                resolve_target: None,
+                                ops: Operations {
+                                    // Blending is disabled, so the region is
+                                    // overwritten even if it isn't cleared.
+                                    load: match (&capture.viewport, capture.matte) {
+                                        (Some(_), _) => LoadOp::Load,
+                                        (None, true) => LoadOp::Clear(Color::BLACK.into()),
+                                        (None, false) => LoadOp::Clear(Color::NONE.into()),
+                                    },
+                                    store: true,
+                                },
+                            })],
+                            depth_stencil_attachment: None,
+                        });
+
+                let mut tracked_pass = TrackedRenderPass::new(render_pass);
+                if let Some(viewport) = &capture.viewport {
+                    tracked_pass.set_camera_viewport(viewport);
+                }
+                tracked_pass.set_render_pipeline(pipeline);
+                draw_outline(
+                    &mut tracked_pass,
+                    res,
+                    style,
+                    0,
+                    &view_bind_group.bind_group,
+                    view_offset,
+                );
+            }
+        }
 
         Ok(())
     }
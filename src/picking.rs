@@ -0,0 +1,216 @@
+use bevy::{
+    prelude::*,
+    render::{camera::CameraUpdateSystem, primitives::Aabb, view::VisibleEntities},
+};
+
+use crate::{readback, Outline, OutlineReadback, OutlineReadbacks, OutlineSettings};
+
+/// Plugin for picking the outlined entity nearest to a point on screen.
+///
+/// Cameras with an [`OutlinePicking`] component read back the texel of their
+/// distance field under the picked point, which holds the nearest point on
+/// any outlined silhouette. The entity drawn there is then found by casting a
+/// ray through that point against the `Aabb`s of the outlined entities in
+/// view, so only entities with an `Aabb`, such as 3D meshes, can be picked.
+///
+/// This must be added after the
+/// [`OutlineReadbackPlugin`](crate::OutlineReadbackPlugin).
+#[derive(Default)]
+pub struct OutlinePickingPlugin;
+
+impl Plugin for OutlinePickingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_to_stage(
+            CoreStage::PreUpdate,
+            receive_outline_picks.after(readback::send_outline_readbacks),
+        )
+        .add_system_to_stage(
+            CoreStage::PostUpdate,
+            request_outline_picks.after(CameraUpdateSystem),
+        );
+    }
+}
+
+/// Component for picking the outlined entity nearest to a point in a
+/// camera's view, e.g. to snap selection to the closest highlighted unit.
+///
+/// The camera must also have an enabled
+/// [`CameraOutline`](crate::CameraOutline). Only silhouettes within the
+/// camera's flood distance, which covers the widest outline it draws, are
+/// found.
+#[derive(Clone, Debug, Default, Component)]
+pub struct OutlinePicking {
+    // The point to pick at in the next frame.
+    cursor: Option<Vec2>,
+    pending: Option<PendingPick>,
+    nearest: Option<(Entity, f32)>,
+}
+
+#[derive(Clone, Debug)]
+struct PendingPick {
+    // The id of the readback.
+    id: u64,
+    // The size of the camera's screen in the distance field, in texels.
+    screen_size: Vec2,
+    padding: f32,
+    // The number of logical pixels per texel of the distance field.
+    texel_scale: f32,
+    // The number of frames since the readback was requested.
+    age: u32,
+}
+
+// The number of frames after which a readback is assumed to have been
+// dropped, e.g. as the camera wasn't drawn.
+const MAX_PICK_AGE: u32 = 8;
+
+impl OutlinePicking {
+    /// Picks the outlined entity nearest to `cursor_pos`, given in logical
+    /// pixels from the bottom left of the camera's target, as returned by
+    /// `Window::cursor_position`.
+    ///
+    /// Picking reads from the GPU, so this returns the result of the most
+    /// recent pick to finish, which trails the cursor by a few frames. The
+    /// result is the entity and its distance from the picked point, in
+    /// logical pixels, or `None` if no outlined entity was found. Entities
+    /// covering the picked point are at a distance of zero.
+    pub fn nearest_at(&mut self, cursor_pos: Vec2) -> Option<(Entity, f32)> {
+        self.cursor = Some(cursor_pos);
+        self.nearest
+    }
+}
+
+/// Requests readbacks of the distance field texels under picked points.
+fn request_outline_picks(
+    settings: Res<OutlineSettings>,
+    readbacks: Res<OutlineReadbacks>,
+    mut cameras: Query<(Entity, &Camera, &mut OutlinePicking)>,
+) {
+    for (entity, camera, mut picking) in cameras.iter_mut() {
+        // Only one pick is in flight at a time, so that slow readbacks don't
+        // pile up.
+        if let Some(pending) = &mut picking.pending {
+            pending.age += 1;
+            if pending.age <= MAX_PICK_AGE {
+                continue;
+            }
+            picking.pending = None;
+        }
+        let cursor = match picking.cursor.take() {
+            Some(c) => c,
+            None => continue,
+        };
+
+        let (target_size, logical_target_size, (viewport_min, _), viewport_size) = match (
+            camera.physical_target_size(),
+            camera.logical_target_size(),
+            camera.physical_viewport_rect(),
+            camera.physical_viewport_size(),
+        ) {
+            (Some(t), Some(l), Some(r), Some(v)) => (t, l, r, v),
+            _ => continue,
+        };
+
+        // The cursor's origin is at the bottom left of the target, and the
+        // viewport's at the top left.
+        let scale_factor = target_size.x as f32 / logical_target_size.x;
+        let physical = cursor * scale_factor;
+        let physical = Vec2::new(physical.x, target_size.y as f32 - physical.y);
+        let screen = (physical - viewport_min.as_vec2()) / viewport_size.as_vec2();
+        if screen.cmplt(Vec2::ZERO).any() || screen.cmpge(Vec2::ONE).any() {
+            picking.nearest = None;
+            continue;
+        }
+
+        let screen_size = settings.internal_size(viewport_size).as_vec2();
+        let padding = settings.offscreen_padding as f32;
+        let texel = (screen * screen_size + padding).as_uvec2();
+        let id = readbacks.request_region(entity, texel, UVec2::ONE);
+        picking.pending = Some(PendingPick {
+            id,
+            screen_size,
+            padding,
+            texel_scale: viewport_size.x as f32 / screen_size.x / scale_factor,
+            age: 0,
+        });
+    }
+}
+
+/// Finds the entities nearest to picked points from finished readbacks.
+fn receive_outline_picks(
+    mut readbacks: EventReader<OutlineReadback>,
+    mut cameras: Query<(
+        &Camera,
+        &GlobalTransform,
+        &VisibleEntities,
+        &mut OutlinePicking,
+    )>,
+    outlines: Query<(&Outline, &GlobalTransform, &Aabb)>,
+) {
+    for readback in readbacks.iter() {
+        let (camera, camera_transform, visible, mut picking) =
+            match cameras.get_mut(readback.camera) {
+                Ok(c) => c,
+                Err(_) => continue,
+            };
+        let pick = match &picking.pending {
+            Some(p) if p.id == readback.id => p.clone(),
+            _ => continue,
+        };
+        picking.pending = None;
+
+        let (texel, distance) = match (
+            readback.texel(UVec2::ZERO),
+            readback.silhouette_distance(UVec2::ZERO),
+        ) {
+            (Some(t), Some(d)) => (t, d),
+            _ => {
+                picking.nearest = None;
+                continue;
+            }
+        };
+
+        // Cast a ray through the nearest silhouette texel. Two finite depths
+        // are used, as the far plane may be at infinity.
+        let seed = Vec2::new(texel.x, texel.y) * readback.texture_size.as_vec2();
+        let screen = (seed - pick.padding) / pick.screen_size;
+        let ndc = Vec2::new(screen.x * 2.0 - 1.0, 1.0 - screen.y * 2.0);
+        let view_proj = camera.projection_matrix() * camera_transform.compute_matrix().inverse();
+        let inverse_view_proj = view_proj.inverse();
+        let near = inverse_view_proj.project_point3(ndc.extend(1.0));
+        let far = inverse_view_proj.project_point3(ndc.extend(0.5));
+
+        let mut nearest: Option<(Entity, f32)> = None;
+        for &entity in visible.entities.iter() {
+            let (outline, transform, aabb) = match outlines.get(entity) {
+                Ok(o) => o,
+                Err(_) => continue,
+            };
+            if !outline.enabled {
+                continue;
+            }
+
+            let to_local = transform.compute_matrix().inverse();
+            let origin = to_local.transform_point3(near);
+            let dir = to_local.transform_point3(far) - origin;
+            let hit = ray_aabb(origin, dir, aabb);
+            if let Some(t) = hit.filter(|&t| nearest.is_none_or(|(_, n)| t < n)) {
+                nearest = Some((entity, t));
+            }
+        }
+
+        picking.nearest = nearest.map(|(entity, _)| (entity, distance * pick.texel_scale));
+    }
+}
+
+/// Returns the smallest non-negative `t` at which the ray `origin + t * dir`
+/// is inside `aabb`, if any.
+fn ray_aabb(origin: Vec3, dir: Vec3, aabb: &Aabb) -> Option<f32> {
+    let min = Vec3::from(aabb.center - aabb.half_extents);
+    let max = Vec3::from(aabb.center + aabb.half_extents);
+    let inv_dir = dir.recip();
+    let t0 = (min - origin) * inv_dir;
+    let t1 = (max - origin) * inv_dir;
+    let t_enter = t0.min(t1).max_element().max(0.0);
+    let t_exit = t0.max(t1).min_element();
+    (t_enter <= t_exit).then_some(t_enter)
+}
@@ -0,0 +1,464 @@
+use std::{
+    num::NonZeroU32,
+    sync::{
+        atomic::{AtomicU8, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use bevy::{
+    core_pipeline::{core_2d, core_3d},
+    prelude::*,
+    render::{
+        camera::ExtractedCamera,
+        render_graph::{Node, NodeRunError, RenderGraph, RenderGraphContext, SlotInfo, SlotType},
+        render_resource::{
+            Buffer, BufferDescriptor, BufferUsages, Extent3d, ImageCopyBuffer, ImageDataLayout,
+            MapMode, Origin3d,
+        },
+        renderer::{RenderContext, RenderDevice},
+        RenderApp, RenderStage,
+    },
+};
+
+use crate::{
+    graph,
+    outline::OutlineViewUniform,
+    resources::{ViewJfaFormat, ViewOutlineResources},
+    textures::intermediate_size,
+    JfaTextureFormat, OutlineSettings, JFA_NO_SEED,
+};
+
+/// Plugin for reading a camera's distance field back to the CPU.
+///
+/// This adds an [`OutlineReadbacks`] resource for requesting readbacks, and
+/// sends an [`OutlineReadback`] event for each finished one. Readbacks are
+/// asynchronous, so the events arrive a few frames after the request.
+///
+/// This must be added after the [`OutlinePlugin`](crate::OutlinePlugin) or
+/// [`MinimalOutlinePlugin`](crate::MinimalOutlinePlugin).
+#[derive(Default)]
+pub struct OutlineReadbackPlugin;
+
+impl Plugin for OutlineReadbackPlugin {
+    fn build(&self, app: &mut App) {
+        let readbacks = OutlineReadbacks::default();
+
+        app.insert_resource(readbacks.clone())
+            .add_event::<OutlineReadback>()
+            .add_system_to_stage(CoreStage::PreUpdate, send_outline_readbacks);
+
+        let render_app = match app.get_sub_app_mut(RenderApp) {
+            Ok(r) => r,
+            Err(_) => return,
+        };
+
+        render_app
+            .insert_resource(readbacks)
+            .init_resource::<ReadbackBuffers>()
+            .add_system_to_stage(RenderStage::Prepare, prepare_readback_buffers)
+            .add_system_to_stage(RenderStage::Cleanup, map_readback_buffers);
+
+        let mut root_graph = render_app.world.resource_mut::<RenderGraph>();
+        for core_graph in [core_3d::graph::NAME, core_2d::graph::NAME] {
+            let outline_graph = match root_graph
+                .get_sub_graph_mut(core_graph)
+                .and_then(|core| core.get_sub_graph_mut(graph::outline::NAME))
+            {
+                Some(g) => g,
+                // 2D outlines require the `sprite` feature.
+                None => continue,
+            };
+
+            let input_node_id = outline_graph.input_node().unwrap().id;
+            outline_graph.add_node(OutlineReadbackNode::NAME, OutlineReadbackNode);
+            outline_graph
+                .add_slot_edge(
+                    input_node_id,
+                    graph::outline::input::VIEW_ENTITY,
+                    OutlineReadbackNode::NAME,
+                    OutlineReadbackNode::IN_VIEW,
+                )
+                .unwrap();
+            outline_graph
+                .add_node_edge(graph::outline::node::JFA_PASS, OutlineReadbackNode::NAME)
+                .unwrap();
+        }
+    }
+}
+
+/// Resource for requesting readbacks of cameras' distance fields.
+///
+/// See [`OutlineReadbackPlugin`].
+#[derive(Clone, Default)]
+pub struct OutlineReadbacks(Arc<Mutex<ReadbackQueues>>);
+
+#[derive(Default)]
+struct ReadbackQueues {
+    next_id: u64,
+    requested: Vec<ReadbackRequest>,
+    finished: Vec<OutlineReadback>,
+}
+
+struct ReadbackRequest {
+    id: u64,
+    camera: Entity,
+    region: Option<(UVec2, UVec2)>,
+}
+
+impl OutlineReadbacks {
+    /// Requests a readback of the whole distance field of `camera`.
+    ///
+    /// Returns the id of the [`OutlineReadback`] event which delivers it.
+    pub fn request(&self, camera: Entity) -> u64 {
+        self.push(camera, None)
+    }
+
+    /// Requests a readback of the `size` texels of the distance field of
+    /// `camera` starting at `origin`.
+    ///
+    /// The distance field covers the camera's viewport along with the
+    /// offscreen padding, and is scaled by the render scale. The region is
+    /// clamped to the distance field. Returns the id of the [`OutlineReadback`]
+    /// event which delivers it.
+    pub fn request_region(&self, camera: Entity, origin: UVec2, size: UVec2) -> u64 {
+        self.push(camera, Some((origin, size)))
+    }
+
+    fn push(&self, camera: Entity, region: Option<(UVec2, UVec2)>) -> u64 {
+        let mut queues = self.0.lock().unwrap();
+        let id = queues.next_id;
+        queues.next_id += 1;
+        queues
+            .requested
+            .push(ReadbackRequest { id, camera, region });
+        id
+    }
+}
+
+/// Event carrying a finished readback of a camera's distance field.
+///
+/// Requests for cameras which don't draw outlines in the following frame are
+/// dropped without an event.
+#[derive(Clone, Debug)]
+pub struct OutlineReadback {
+    /// The id returned when the readback was requested.
+    pub id: u64,
+    pub camera: Entity,
+    /// The size of the whole distance field, in texels.
+    pub texture_size: UVec2,
+    /// The first texel of the region which was read back.
+    pub origin: UVec2,
+    /// The size of the region which was read back, in texels.
+    pub size: UVec2,
+    /// The texels of the region in row-major order.
+    ///
+    /// As in the JFA texture, the XY components of each texel hold the
+    /// texture coordinates of the nearest silhouette texel, and the ZW
+    /// components those of the nearest texel outside the silhouette. Texels
+    /// with no such seed hold [`JFA_NO_SEED`](crate::JFA_NO_SEED), as do the
+    /// ZW components of all texels if the camera's distance field has no inner
+    /// seeds, as with a two-channel [`JfaTextureFormat`] or a camera without
+    /// an [`InnerOutline`](crate::InnerOutline).
+    pub texels: Vec<Vec4>,
+}
+
+impl OutlineReadback {
+    /// Returns the texel at `pos`, relative to the region's origin.
+    pub fn texel(&self, pos: UVec2) -> Option<Vec4> {
+        if pos.x >= self.size.x || pos.y >= self.size.y {
+            return None;
+        }
+
+        Some(self.texels[(pos.y * self.size.x + pos.x) as usize])
+    }
+
+    /// Returns the distance in texels from the texel at `pos`, relative to the
+    /// region's origin, to the nearest silhouette, or `None` if no silhouette
+    /// was found within the flood distance.
+    ///
+    /// This matches `sdf_seed_distance` in the `outline::sdf` shader import, so
+    /// texels inside a silhouette have a distance of zero.
+    pub fn silhouette_distance(&self, pos: UVec2) -> Option<f32> {
+        let texel = self.texel(pos)?;
+        let seed = Vec2::new(texel.x, texel.y);
+        if seed.x < 0.0 {
+            return None;
+        }
+
+        let center = (self.origin + pos).as_vec2() + 0.5;
+        let seed = seed * self.texture_size.as_vec2();
+        Some((seed - center).length())
+    }
+}
+
+pub(crate) fn send_outline_readbacks(
+    readbacks: Res<OutlineReadbacks>,
+    mut events: EventWriter<OutlineReadback>,
+) {
+    let mut queues = readbacks.0.lock().unwrap();
+    events.send_batch(queues.finished.drain(..));
+}
+
+// Readback states of a request.
+const STATE_IDLE: u8 = 0;
+const STATE_COPIED: u8 = 1;
+const STATE_PENDING: u8 = 2;
+const STATE_MAPPED: u8 = 3;
+
+/// A readback which has been assigned a buffer.
+struct PendingReadback {
+    id: u64,
+    camera: Entity,
+    texture_size: UVec2,
+    origin: UVec2,
+    size: UVec2,
+    // The format of the camera's distance field.
+    format: JfaTextureFormat,
+    // Rows of the buffer are padded to the copy alignment.
+    padded_bytes_per_row: u32,
+    buffer: Buffer,
+    // One of the `STATE_*` constants.
+    state: Arc<AtomicU8>,
+}
+
+#[derive(Default)]
+struct ReadbackBuffers {
+    pending: Vec<PendingReadback>,
+}
+
+/// Publishes finished readbacks and creates buffers for new requests.
+fn prepare_readback_buffers(
+    settings: Res<OutlineSettings>,
+    device: Res<RenderDevice>,
+    readbacks: Res<OutlineReadbacks>,
+    mut buffers: ResMut<ReadbackBuffers>,
+    views: Query<(&ExtractedCamera, &ViewJfaFormat), With<OutlineViewUniform>>,
+) {
+    let mut queues = readbacks.0.lock().unwrap();
+    let ReadbackQueues {
+        requested,
+        finished,
+        ..
+    } = &mut *queues;
+
+    // Mappings finish when a later frame's commands are submitted, which
+    // invokes their callbacks. Requests which weren't copied in the previous
+    // frame, as their camera wasn't drawn, are dropped.
+    buffers.pending.retain(|readback| {
+        match readback.state.load(Ordering::Acquire) {
+            STATE_IDLE => return false,
+            STATE_MAPPED => {}
+            _ => return true,
+        }
+
+        let texel_bytes = texel_size(readback.format);
+        let texels = {
+            let data = readback.buffer.slice(..).get_mapped_range();
+            let row_len = (readback.size.x * texel_bytes) as usize;
+            data.chunks_exact(readback.padded_bytes_per_row as usize)
+                .flat_map(|row| row[..row_len].chunks_exact(texel_bytes as usize))
+                .map(|texel| decode_texel(texel, readback.format))
+                .collect()
+        };
+        readback.buffer.unmap();
+
+        finished.push(OutlineReadback {
+            id: readback.id,
+            camera: readback.camera,
+            texture_size: readback.texture_size,
+            origin: readback.origin,
+            size: readback.size,
+            texels,
+        });
+        false
+    });
+
+    for request in requested.drain(..) {
+        let (viewport_size, format) = match views.get(request.camera) {
+            Ok((camera, &ViewJfaFormat(format))) => match camera.physical_viewport_size {
+                Some(s) => (s, format),
+                None => continue,
+            },
+            Err(_) => continue,
+        };
+
+        let texture_size = intermediate_size(&settings, viewport_size);
+        let texture_size = UVec2::new(texture_size.width, texture_size.height);
+        let (origin, size) = request.region.unwrap_or((UVec2::ZERO, texture_size));
+        let origin = origin.min(texture_size);
+        let size = size.min(texture_size - origin);
+        if size.x == 0 || size.y == 0 {
+            continue;
+        }
+
+        let padded_bytes_per_row =
+            RenderDevice::align_copy_bytes_per_row((size.x * texel_size(format)) as usize) as u32;
+        buffers.pending.push(PendingReadback {
+            id: request.id,
+            camera: request.camera,
+            texture_size,
+            origin,
+            size,
+            format,
+            padded_bytes_per_row,
+            buffer: device.create_buffer(&BufferDescriptor {
+                label: Some("outline_readback"),
+                size: (padded_bytes_per_row * size.y) as u64,
+                usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }),
+            state: Arc::new(AtomicU8::new(STATE_IDLE)),
+        });
+    }
+}
+
+/// Returns the number of bytes per texel of JFA textures in `format`.
+pub(crate) fn texel_size(format: JfaTextureFormat) -> u32 {
+    format.texture_format().describe().block_size as u32
+}
+
+/// Decodes a texel of the JFA texture in `format`.
+///
+/// Formats without inner seeds decode to [`JFA_NO_SEED`] in the ZW
+/// components, and texels with no seed decode to it whichever
+/// [`JfaNoSeed`](crate::JfaNoSeed) the texture was drawn with.
+pub(crate) fn decode_texel(bytes: &[u8], format: JfaTextureFormat) -> Vec4 {
+    let mut components = [
+        JFA_NO_SEED[0],
+        JFA_NO_SEED[1],
+        JFA_NO_SEED[0],
+        JFA_NO_SEED[1],
+    ];
+    if format == JfaTextureFormat::Rgba8Packed {
+        // Each coordinate is split into its high and low bytes, and the
+        // largest value marks texels with no seed.
+        for (component, bytes) in components.iter_mut().zip(bytes.chunks_exact(2)) {
+            *component = match u16::from_be_bytes([bytes[0], bytes[1]]) {
+                u16::MAX => JFA_NO_SEED[0],
+                value => value as f32 / (u16::MAX - 1) as f32,
+            };
+        }
+        return Vec4::from(components);
+    }
+
+    for (component, bytes) in components.iter_mut().zip(bytes.chunks_exact(2)) {
+        let bits = u16::from_ne_bytes([bytes[0], bytes[1]]);
+        *component = match format {
+            JfaTextureFormat::Rgba16Snorm | JfaTextureFormat::Rg16Snorm => {
+                (bits as i16 as f32 / i16::MAX as f32).max(-1.0)
+            }
+            _ => f16_to_f32(bits),
+        };
+    }
+    // Seeds are within `[0, 1]`, so anything beyond is the far sentinel.
+    for seed in components.chunks_exact_mut(2) {
+        if seed[0] > 1.0 {
+            seed.copy_from_slice(&JFA_NO_SEED);
+        }
+    }
+    Vec4::from(components)
+}
+
+/// Converts the bits of a half-precision float into an `f32`.
+fn f16_to_f32(bits: u16) -> f32 {
+    let sign = if bits & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exp = ((bits >> 10) & 0x1f) as i32;
+    let mantissa = (bits & 0x3ff) as f32;
+    match exp {
+        0 => sign * mantissa * 2f32.powi(-24),
+        0x1f if mantissa == 0.0 => sign * f32::INFINITY,
+        0x1f => f32::NAN,
+        _ => sign * (1.0 + mantissa / 1024.0) * 2f32.powi(exp - 15),
+    }
+}
+
+/// Starts mapping the readback buffers copied to this frame.
+///
+/// This runs after the frame's commands have been submitted.
+fn map_readback_buffers(buffers: Res<ReadbackBuffers>) {
+    for readback in buffers.pending.iter() {
+        if readback.state.load(Ordering::Acquire) != STATE_COPIED {
+            continue;
+        }
+
+        readback.state.store(STATE_PENDING, Ordering::Release);
+        let state = readback.state.clone();
+        readback
+            .buffer
+            .slice(..)
+            .map_async(MapMode::Read, move |result| {
+                let new_state = match result {
+                    Ok(()) => STATE_MAPPED,
+                    // Dropped in the next frame.
+                    Err(_) => STATE_IDLE,
+                };
+                state.store(new_state, Ordering::Release);
+            });
+    }
+}
+
+/// Render graph node which copies the requested regions of a view's distance
+/// field into readback buffers.
+struct OutlineReadbackNode;
+
+impl OutlineReadbackNode {
+    const NAME: &'static str = "outline_readback";
+    const IN_VIEW: &'static str = "view";
+}
+
+impl Node for OutlineReadbackNode {
+    fn input(&self) -> Vec<SlotInfo> {
+        vec![SlotInfo::new(Self::IN_VIEW, SlotType::Entity)]
+    }
+
+    fn run(
+        &self,
+        graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let view_ent = graph.get_input_entity(Self::IN_VIEW)?;
+        let res = match world.get::<ViewOutlineResources>(view_ent) {
+            Some(r) => r,
+            None => return Ok(()),
+        };
+        let dims = res.dimensions_buffer.get().size();
+
+        let buffers = world.resource::<ReadbackBuffers>();
+        for readback in buffers.pending.iter() {
+            if readback.camera != view_ent
+                || readback.texture_size != dims
+                || readback.state.load(Ordering::Acquire) != STATE_IDLE
+            {
+                continue;
+            }
+
+            let mut source = res.jfa_final_output.texture.as_image_copy();
+            source.origin = Origin3d {
+                x: readback.origin.x,
+                y: readback.origin.y,
+                z: 0,
+            };
+            render_context.command_encoder.copy_texture_to_buffer(
+                source,
+                ImageCopyBuffer {
+                    buffer: &readback.buffer,
+                    layout: ImageDataLayout {
+                        offset: 0,
+                        bytes_per_row: NonZeroU32::new(readback.padded_bytes_per_row),
+                        rows_per_image: None,
+                    },
+                },
+                Extent3d {
+                    width: readback.size.x,
+                    height: readback.size.y,
+                    depth_or_array_layers: 1,
+                },
+            );
+            readback.state.store(STATE_COPIED, Ordering::Release);
+        }
+
+        Ok(())
+    }
+}
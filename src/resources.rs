@@ -1,73 +1,146 @@
 use bevy::{
     prelude::*,
     render::{
+        camera::ExtractedCamera,
         render_resource::{
             AddressMode, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
             BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource, BindingType,
             BufferBindingType, DynamicUniformBuffer, Extent3d, FilterMode, Sampler,
             SamplerBindingType, SamplerDescriptor, ShaderStages, ShaderType, TextureDescriptor,
             TextureDimension, TextureFormat, TextureSampleType, TextureUsages, TextureView,
-            TextureViewDimension, UniformBuffer,
+            TextureViewDescriptor, TextureViewDimension, UniformBuffer,
         },
         renderer::{RenderDevice, RenderQueue},
         texture::{CachedTexture, TextureCache},
-        view::ExtractedWindows,
+        view::Msaa,
     },
-    window::WindowId,
 };
 
-use crate::{jfa, outline, OutlineSettings, JFA_TEXTURE_FORMAT};
+use crate::{
+    jfa,
+    mask::{self, MaskColors, MASK_GROUP_TEXTURE_FORMAT},
+    outline::{self, OutlineViewUniform},
+    reuse::RetainedOutlineTextures,
+    DistanceMetric, JfaNoSeed, JfaTextureFormat, OutlineSettings,
+};
 
 const JFA_FROM_PRIMARY: &str = "jfa_from_primary_output_bind_group";
 const JFA_FROM_SECONDARY: &str = "jfa_from_secondary_output_bind_group";
 const JFA_OUTLINE_SRC: &str = "jfa_outline_src_bind_group";
 
+/// Bind group layouts, samplers and buffers shared by all outlined views.
 pub struct OutlineResources {
-    // Multisample target for initial mask pass.
-    pub mask_multisample: CachedTexture,
-    // Resolve target for the above.
-    pub mask_output: CachedTexture,
-
     pub dimensions_bind_group_layout: BindGroupLayout,
-    pub dimensions_buffer: UniformBuffer<jfa::Dimensions>,
-    pub dimensions_bind_group: BindGroup,
 
     // Non-filtering sampler for all sampling operations.
     pub sampler: Sampler,
 
-    // Bind group and layout for JFA init pass.
+    // Bind group layout for JFA init pass.
     pub jfa_init_bind_group_layout: BindGroupLayout,
-    pub jfa_init_bind_group: BindGroup,
 
     // Bind group layout for JFA iteration passes.
     pub jfa_bind_group_layout: BindGroupLayout,
-    // Dynamic uniform buffer containing power-of-two JFA distances from 1 to 32768.
+    // Dynamic uniform buffer containing power-of-two JFA distances from 1 to
+    // 32768, for each distance metric.
     // TODO: use instance ID instead?
     pub jfa_distance_buffer: DynamicUniformBuffer<jfa::JumpDist>,
     pub jfa_distance_offsets: Vec<u32>,
 
+    // The format of the jump flood textures, validated against the features
+    // of the device. Views may flood their own textures in its two-channel
+    // counterpart, as given by their `ViewJfaFormat`.
+    pub jfa_format: JfaTextureFormat,
+    // The sentinel stored in JFA texels with no seed.
+    pub jfa_no_seed: JfaNoSeed,
+    // Bind group layouts for sampling JFA results in the outline shader, for
+    // single and multisampled masks.
+    pub outline_src_bind_group_layouts: [BindGroupLayout; 2],
+    // Bind group layout for outline style parameters.
+    pub outline_params_bind_group_layout: BindGroupLayout,
+    // Bind group layout for per-view outline parameters.
+    pub outline_view_bind_group_layout: BindGroupLayout,
+    // `outline_view_bind_group_layout` with the view's depth texture, for
+    // single and multisampled depth.
+    pub outline_view_depth_bind_group_layouts: [BindGroupLayout; 2],
+    // Filtering sampler for screen masks.
+    pub screen_mask_sampler: Sampler,
+    // Repeating filtering sampler for outline fill textures.
+    pub fill_texture_sampler: Sampler,
+    // Opaque white texture, bound in place of missing screen masks and fill
+    // textures.
+    pub white_texture: TextureView,
+}
+
+/// The intermediate textures of an outlined view, and the bind groups using
+/// them.
+///
+/// This is prepared each frame for every view with outlines, sized to the
+/// view's render target, so that views drawn in the same frame don't overwrite
+/// each other's masks and distance fields.
+#[derive(Component)]
+pub struct ViewOutlineResources {
+    // Multisample target for initial mask pass, if MSAA is enabled.
+    pub mask_multisample: Option<CachedTexture>,
+    // Resolve target for the above, or the target of the mask pass itself
+    // without MSAA.
+    pub mask_output: CachedTexture,
+    // Target holding the `OutlineGroup` of each sample, multisampled if MSAA
+    // is enabled. Integer formats can't be resolved, so this is sampled
+    // directly.
+    pub mask_group_multisample: CachedTexture,
+    // The sample count of the mask, following the app's `Msaa` setting.
+    pub mask_samples: u32,
+    // The format of the jump flood textures, from the view's `ViewJfaFormat`.
+    pub jfa_format: JfaTextureFormat,
+
+    pub dimensions_buffer: UniformBuffer<jfa::Dimensions>,
+    pub dimensions_bind_group: BindGroup,
+
+    // Bind group for JFA init pass.
+    pub jfa_init_bind_group: BindGroup,
+
     // Bind group for jump flood passes targeting the primary output.
     pub jfa_from_secondary_bind_group: BindGroup,
     // Primary jump flood output.
     pub jfa_primary_output: CachedTexture,
+    // Size of the primary and secondary outputs, which is reduced by the
+    // `OutlineResolution`.
+    pub jfa_size: UVec2,
 
     // Bind group for jump flood passes targeting the secondary output.
     pub jfa_from_primary_bind_group: BindGroup,
     // Secondary jump flood output.
     pub jfa_secondary_output: CachedTexture,
 
-    // Bind groups for the final jump flood pass.
+    // Final jump flood output.
     pub jfa_final_output: CachedTexture,
 
-    // Bind group layout for sampling JFA results in the outline shader.
-    pub outline_src_bind_group_layout: BindGroupLayout,
-    // Bind group layout for outline style parameters.
-    pub outline_params_bind_group_layout: BindGroupLayout,
     pub outline_src_bind_group: BindGroup,
 }
 
+/// The format in which a view floods its JFA textures.
+///
+/// This is the camera's [`OutlineJfaFormat`](crate::OutlineJfaFormat) if it
+/// has one. Otherwise, it's the app's [`JfaTextureFormat`], or its two-channel
+/// counterpart if none of the view's styles has an inner outline and its
+/// distance field isn't copied into an image.
+#[derive(Clone, Copy, Debug, Component)]
+pub(crate) struct ViewJfaFormat(pub(crate) JfaTextureFormat);
+
+/// The number of jump distances stored per distance metric.
+const JFA_DISTANCE_COUNT: usize = 16;
+
+/// The furthest distance in pixels from a seed reached by the jump flood.
+pub(crate) const MAX_JFA_EXTENT: f32 = ((1 << JFA_DISTANCE_COUNT) - 1) as f32;
+
 impl OutlineResources {
-    fn create_jfa_bind_group(
+    /// Returns the offset of the jump distance `2^exp` for `metric` in the
+    /// distance buffer.
+    pub(crate) fn jfa_distance_offset(&self, metric: u32, exp: usize) -> u32 {
+        self.jfa_distance_offsets[metric as usize * JFA_DISTANCE_COUNT + exp]
+    }
+
+    pub(crate) fn create_jfa_bind_group(
         &self,
         device: &RenderDevice,
         label: &str,
@@ -118,6 +191,7 @@ fn create_outline_src_bind_group(
     label: &str,
     src: &TextureView,
     mask: &TextureView,
+    mask_group: &TextureView,
     sampler: &Sampler,
 ) -> BindGroup {
     device.create_bind_group(&BindGroupDescriptor {
@@ -136,41 +210,25 @@ fn create_outline_src_bind_group(
                 binding: 2,
                 resource: BindingResource::Sampler(sampler),
             },
+            BindGroupEntry {
+                binding: 3,
+                resource: BindingResource::TextureView(mask_group),
+            },
         ],
     })
 }
 
 impl FromWorld for OutlineResources {
     fn from_world(world: &mut World) -> Self {
-        let size = Extent3d {
-            width: 1,
-            height: 1,
-            depth_or_array_layers: 1,
-        };
-
         let device = world.get_resource::<RenderDevice>().unwrap().clone();
         let queue = world.get_resource::<RenderQueue>().unwrap().clone();
-        let mut textures = world.get_resource_mut::<TextureCache>().unwrap();
-
-        let mask_output_desc = tex_desc("outline_mask_output", size, TextureFormat::R8Unorm);
-        let mask_multisample_desc = TextureDescriptor {
-            label: Some("outline_mask_multisample"),
-            sample_count: 4,
-            ..mask_output_desc.clone()
-        };
-        let mask_multisample = textures.get(&device, mask_multisample_desc);
-        let mask_output = textures.get(&device, mask_output_desc);
-
-        let dims = jfa::Dimensions::new(size.width, size.height);
-        let mut dimensions_buffer = UniformBuffer::from(dims);
-        dimensions_buffer.write_buffer(&device, &queue);
 
         let dimensions_bind_group_layout =
             device.create_bind_group_layout(&BindGroupLayoutDescriptor {
                 label: Some("jfa_dimensions_bind_group_layout"),
                 entries: &[BindGroupLayoutEntry {
                     binding: 0,
-                    visibility: ShaderStages::FRAGMENT,
+                    visibility: ShaderStages::VERTEX_FRAGMENT,
                     ty: BindingType::Buffer {
                         ty: BufferBindingType::Uniform,
                         has_dynamic_offset: false,
@@ -180,15 +238,6 @@ impl FromWorld for OutlineResources {
                 }],
             });
 
-        let dimensions_bind_group = device.create_bind_group(&BindGroupDescriptor {
-            label: Some("jfa_dimensions_bind_group"),
-            layout: &dimensions_bind_group_layout,
-            entries: &[BindGroupEntry {
-                binding: 0,
-                resource: dimensions_buffer.binding().unwrap(),
-            }],
-        });
-
         let sampler = device.create_sampler(&SamplerDescriptor {
             label: Some("outline_jfa_sampler"),
             address_mode_u: AddressMode::ClampToEdge,
@@ -223,21 +272,6 @@ impl FromWorld for OutlineResources {
                     },
                 ],
             });
-        let jfa_init_bind_group = device.create_bind_group(&BindGroupDescriptor {
-            label: Some("outline_jfa_init_bind_group"),
-            layout: &jfa_init_bind_group_layout,
-            entries: &[
-                BindGroupEntry {
-                    binding: 0,
-                    resource: BindingResource::TextureView(&mask_output.default_view),
-                },
-                BindGroupEntry {
-                    binding: 1,
-                    resource: BindingResource::Sampler(&sampler),
-                },
-            ],
-        });
-
         let jfa_bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
             label: Some("outline_jfa_bind_group_layout"),
             entries: &[
@@ -271,49 +305,30 @@ impl FromWorld for OutlineResources {
         });
         let mut jfa_distance_buffer = DynamicUniformBuffer::default();
         let mut jfa_distance_offsets = Vec::new();
-        for exp in 0_u32..16 {
-            // TODO: this should be a DynamicUniformBuffer
-            let ofs = jfa_distance_buffer.push(jfa::JumpDist {
-                dist: 2_u32.pow(exp),
-            });
-
-            jfa_distance_offsets.push(ofs);
+        for metric in [
+            DistanceMetric::Euclidean,
+            DistanceMetric::Chebyshev,
+            DistanceMetric::Manhattan,
+        ] {
+            for exp in 0..JFA_DISTANCE_COUNT as u32 {
+                let ofs = jfa_distance_buffer.push(jfa::JumpDist {
+                    dist: 2_u32.pow(exp),
+                    metric: metric as u32,
+                });
+
+                jfa_distance_offsets.push(ofs);
+            }
         }
         jfa_distance_buffer.write_buffer(&device, &queue);
 
-        let jfa_primary_output_desc =
-            tex_desc("outline_jfa_primary_output", size, JFA_TEXTURE_FORMAT);
-        let jfa_primary_output = textures.get(&device, jfa_primary_output_desc);
-        let jfa_secondary_output_desc =
-            tex_desc("outline_jfa_secondary_output", size, JFA_TEXTURE_FORMAT);
-        let jfa_secondary_output = textures.get(&device, jfa_secondary_output_desc);
-        let jfa_final_output_desc = tex_desc("outline_jfa_final_output", size, JFA_TEXTURE_FORMAT);
-        let jfa_final_output = textures.get(&device, jfa_final_output_desc);
-
-        let jfa_from_secondary_bind_group = create_jfa_bind_group(
-            &device,
-            &jfa_bind_group_layout,
-            "outline_jfa_primary_bind_group",
-            jfa_distance_buffer.binding().unwrap(),
-            &jfa_secondary_output.default_view,
-            &sampler,
-        );
-        let jfa_from_primary_bind_group = create_jfa_bind_group(
-            &device,
-            &jfa_bind_group_layout,
-            "outline_jfa_secondary_bind_group",
-            jfa_distance_buffer.binding().unwrap(),
-            &jfa_primary_output.default_view,
-            &sampler,
-        );
-
         let mut outline_params_buffer = UniformBuffer::from(outline::OutlineParams::new(
             Color::hex("b4a2c8").unwrap(),
             32.0,
+            outline::OutlineFlags::empty(),
         ));
         outline_params_buffer.write_buffer(&device, &queue);
 
-        let outline_src_bind_group_layout =
+        let outline_src_bind_group_layouts = [false, true].map(|multisampled| {
             device.create_bind_group_layout(&BindGroupLayoutDescriptor {
                 label: Some("jfa_outline_bind_group_layout"),
                 entries: &[
@@ -346,8 +361,20 @@ impl FromWorld for OutlineResources {
                         ty: BindingType::Sampler(SamplerBindingType::NonFiltering),
                         count: None,
                     },
+                    // Mask groups
+                    BindGroupLayoutEntry {
+                        binding: 3,
+                        visibility: ShaderStages::FRAGMENT,
+                        ty: BindingType::Texture {
+                            sample_type: TextureSampleType::Uint,
+                            view_dimension: TextureViewDimension::D2,
+                            multisampled,
+                        },
+                        count: None,
+                    },
                 ],
-            });
+            })
+        });
 
         let outline_params_bind_group_layout =
             device.create_bind_group_layout(&BindGroupLayoutDescriptor {
@@ -364,98 +391,246 @@ impl FromWorld for OutlineResources {
                         },
                         count: None,
                     },
+                    // Fill texture
+                    BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: ShaderStages::FRAGMENT,
+                        ty: BindingType::Texture {
+                            sample_type: TextureSampleType::Float { filterable: true },
+                            view_dimension: TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: ShaderStages::FRAGMENT,
+                        ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                        count: None,
+                    },
                 ],
             });
 
-        let outline_src_bind_group = create_outline_src_bind_group(
-            &device,
-            &outline_src_bind_group_layout,
-            "jfa_outline_src_bind_group",
-            &jfa_final_output.default_view,
-            &mask_output.default_view,
-            &sampler,
-        );
+        let outline_view_entries = [
+            // OutlineViewUniform
+            BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: true,
+                    min_binding_size: Some(outline::OutlineViewUniform::min_size()),
+                },
+                count: None,
+            },
+            // Screen mask
+            BindGroupLayoutEntry {
+                binding: 1,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Texture {
+                    sample_type: TextureSampleType::Float { filterable: true },
+                    view_dimension: TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            },
+            BindGroupLayoutEntry {
+                binding: 2,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                count: None,
+            },
+        ];
+        let outline_view_bind_group_layout =
+            device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+                label: Some("jfa_outline_view_bind_group_layout"),
+                entries: &outline_view_entries,
+            });
+        // The view layout plus the view's depth texture, single and
+        // multisampled, for views with `OutlineDepthOcclusion`.
+        let outline_view_depth_bind_group_layouts = [false, true].map(|multisampled| {
+            let mut entries = outline_view_entries.to_vec();
+            entries.push(BindGroupLayoutEntry {
+                binding: 3,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Texture {
+                    sample_type: TextureSampleType::Depth,
+                    view_dimension: TextureViewDimension::D2,
+                    multisampled,
+                },
+                count: None,
+            });
+            device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+                label: Some("jfa_outline_view_depth_bind_group_layout"),
+                entries: &entries,
+            })
+        });
+
+        let screen_mask_sampler = device.create_sampler(&SamplerDescriptor {
+            label: Some("outline_screen_mask_sampler"),
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            address_mode_w: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            mipmap_filter: FilterMode::Nearest,
+            compare: None,
+            ..Default::default()
+        });
+
+        let fill_texture_sampler = device.create_sampler(&SamplerDescriptor {
+            label: Some("outline_fill_texture_sampler"),
+            address_mode_u: AddressMode::Repeat,
+            address_mode_v: AddressMode::Repeat,
+            address_mode_w: AddressMode::Repeat,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            mipmap_filter: FilterMode::Nearest,
+            compare: None,
+            ..Default::default()
+        });
+
+        let white_texture = device
+            .create_texture_with_data(
+                &queue,
+                &TextureDescriptor {
+                    usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+                    ..tex_desc(
+                        "outline_white_texture",
+                        Extent3d {
+                            width: 1,
+                            height: 1,
+                            depth_or_array_layers: 1,
+                        },
+                        TextureFormat::Rgba8Unorm,
+                    )
+                },
+                &[u8::MAX; 4],
+            )
+            .create_view(&TextureViewDescriptor::default());
 
         OutlineResources {
-            mask_multisample,
-            mask_output,
             dimensions_bind_group_layout,
-            dimensions_buffer,
-            dimensions_bind_group,
             jfa_init_bind_group_layout,
-            jfa_init_bind_group,
             jfa_bind_group_layout,
             sampler,
             jfa_distance_buffer,
             jfa_distance_offsets,
-            jfa_primary_output,
-            jfa_secondary_output,
-            jfa_final_output,
-            jfa_from_secondary_bind_group,
-            jfa_from_primary_bind_group,
-            outline_src_bind_group_layout,
+            jfa_format: *world.resource::<JfaTextureFormat>(),
+            jfa_no_seed: *world.resource::<JfaNoSeed>(),
+            outline_src_bind_group_layouts,
             outline_params_bind_group_layout,
-            outline_src_bind_group,
+            outline_view_bind_group_layout,
+            outline_view_depth_bind_group_layouts,
+            screen_mask_sampler,
+            fill_texture_sampler,
+            white_texture,
         }
     }
 }
 
-pub fn recreate_outline_resources(
+#[allow(clippy::too_many_arguments)]
+pub fn prepare_view_outline_resources(
+    mut commands: Commands,
     settings: Res<OutlineSettings>,
-    mut outline: ResMut<OutlineResources>,
+    outline: Res<OutlineResources>,
     device: Res<RenderDevice>,
     queue: Res<RenderQueue>,
     mut textures: ResMut<TextureCache>,
-    windows: Res<ExtractedWindows>,
+    mut retained: ResMut<RetainedOutlineTextures>,
+    msaa: Res<Msaa>,
+    views: Query<
+        (
+            Entity,
+            &ExtractedCamera,
+            &ViewJfaFormat,
+            Option<&MaskColors>,
+        ),
+        With<OutlineViewUniform>,
+    >,
 ) {
-    let primary = match windows.get(&WindowId::primary()) {
-        Some(w) => w,
-        None => return,
-    };
-
-    let half_size = Extent3d {
-        width: primary.physical_width / 2,
-        height: primary.physical_height / 2,
-        depth_or_array_layers: 1,
-    };
-
-    let size = Extent3d {
-        width: primary.physical_width,
-        height: primary.physical_height,
-        depth_or_array_layers: 1,
-    };
-
-    let half_resolution = settings.half_resolution;
-    let jfa_size = if half_resolution { half_size } else { size };
-
-    let new_dims = jfa::Dimensions::new(size.width, size.height);
-    let dims = outline.dimensions_buffer.get_mut();
-    if *dims != new_dims {
-        *dims = new_dims;
-        outline.dimensions_buffer.write_buffer(&device, &queue);
+    let mask_samples = msaa.samples;
+
+    // The mask and distance field are kept for the next frame while temporal
+    // reuse is enabled, so that static views can skip redrawing them.
+    if settings.temporal_reuse {
+        retained.retain_views(|view| views.get(view).is_ok());
+    } else {
+        retained.retain_views(|_| false);
     }
+    let mut retained = settings.temporal_reuse.then_some(&mut *retained);
+
+    for (entity, camera, &ViewJfaFormat(jfa_format), mask_colors) in views.iter() {
+        // The mask covers only the camera's viewport, so that cameras sharing
+        // a target each flood a texture of their own viewport's size.
+        let viewport_size = match camera.physical_viewport_size {
+            Some(s) => s,
+            None => continue,
+        };
+
+        let padding = settings.offscreen_padding;
+        let screen_size = settings.internal_size(viewport_size);
+
+        let size = Extent3d {
+            width: screen_size.x + 2 * padding,
+            height: screen_size.y + 2 * padding,
+            depth_or_array_layers: 1,
+        };
+
+        let divisor = settings.resolution.divisor();
+        let jfa_size = Extent3d {
+            width: (size.width / divisor).max(1),
+            height: (size.height / divisor).max(1),
+            depth_or_array_layers: 1,
+        };
+
+        let mut dimensions_buffer =
+            UniformBuffer::from(jfa::Dimensions::new(screen_size.x, screen_size.y, padding));
+        dimensions_buffer.write_buffer(&device, &queue);
+        let dimensions_bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("jfa_dimensions_bind_group"),
+            layout: &outline.dimensions_bind_group_layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: dimensions_buffer.binding().unwrap(),
+            }],
+        });
 
-    let old_mask = outline.mask_multisample.texture.id();
-    let mask_output_desc = tex_desc("outline_mask_output", size, TextureFormat::R8Unorm);
-    let mask_multisample_desc = TextureDescriptor {
-        label: Some("outline_mask_multisample"),
-        sample_count: 4,
-        ..mask_output_desc.clone()
-    };
-
-    // Recreate mask output targets.
-    outline.mask_output = textures.get(&device, mask_output_desc);
-    outline.mask_multisample = textures.get(&device, mask_multisample_desc);
-
-    if outline.mask_output.texture.id() != old_mask {
-        // Recreate JFA init pass bind group
-        outline.jfa_init_bind_group = device.create_bind_group(&BindGroupDescriptor {
+        let mask_output_desc = tex_desc(
+            "outline_mask_output",
+            size,
+            mask::mask_texture_format(mask_colors.is_some()),
+        );
+        let mask_multisample = (mask_samples > 1).then(|| {
+            let mask_multisample_desc = TextureDescriptor {
+                label: Some("outline_mask_multisample"),
+                sample_count: mask_samples,
+                ..mask_output_desc.clone()
+            };
+            textures.get(&device, mask_multisample_desc)
+        });
+        let mask_output = view_texture(
+            &device,
+            &mut textures,
+            retained.as_deref_mut(),
+            entity,
+            mask_output_desc,
+        );
+        let mask_group_multisample = view_texture(
+            &device,
+            &mut textures,
+            retained.as_deref_mut(),
+            entity,
+            mask_group_desc(size, mask_samples),
+        );
+
+        let jfa_init_bind_group = device.create_bind_group(&BindGroupDescriptor {
             label: Some("outline_jfa_init_bind_group"),
             layout: &outline.jfa_init_bind_group_layout,
             entries: &[
                 BindGroupEntry {
                     binding: 0,
-                    resource: BindingResource::TextureView(&outline.mask_output.default_view),
+                    resource: BindingResource::TextureView(&mask_output.default_view),
                 },
                 BindGroupEntry {
                     binding: 1,
@@ -463,45 +638,96 @@ pub fn recreate_outline_resources(
                 },
             ],
         });
-    }
 
-    let old_jfa_primary = outline.jfa_primary_output.texture.id();
-    let jfa_primary_desc = tex_desc("outline_jfa_primary_output", jfa_size, JFA_TEXTURE_FORMAT);
-    let jfa_primary_output = textures.get(&device, jfa_primary_desc);
-    if jfa_primary_output.texture.id() != old_jfa_primary {
-        outline.jfa_primary_output = jfa_primary_output;
-        outline.jfa_from_primary_bind_group = outline.create_jfa_bind_group(
+        let jfa_primary_desc = tex_desc(
+            "outline_jfa_primary_output",
+            jfa_size,
+            jfa_format.texture_format(),
+        );
+        let jfa_primary_output = textures.get(&device, jfa_primary_desc);
+        let jfa_from_primary_bind_group = outline.create_jfa_bind_group(
             &device,
             JFA_FROM_PRIMARY,
-            &outline.jfa_primary_output.default_view,
+            &jfa_primary_output.default_view,
         );
-    }
 
-    let old_jfa_secondary = outline.jfa_secondary_output.texture.id();
-    let jfa_secondary_desc = tex_desc("outline_jfa_secondary_output", jfa_size, JFA_TEXTURE_FORMAT);
-    let jfa_secondary_output = textures.get(&device, jfa_secondary_desc);
-    if jfa_secondary_output.texture.id() != old_jfa_secondary {
-        outline.jfa_secondary_output = jfa_secondary_output;
-        outline.jfa_from_secondary_bind_group = outline.create_jfa_bind_group(
+        let jfa_secondary_desc = tex_desc(
+            "outline_jfa_secondary_output",
+            jfa_size,
+            jfa_format.texture_format(),
+        );
+        let jfa_secondary_output = textures.get(&device, jfa_secondary_desc);
+        let jfa_from_secondary_bind_group = outline.create_jfa_bind_group(
             &device,
             JFA_FROM_SECONDARY,
-            &outline.jfa_secondary_output.default_view,
+            &jfa_secondary_output.default_view,
         );
-    }
 
-    let old_jfa_final = outline.jfa_final_output.texture.id();
-    let jfa_final_desc = tex_desc("outline_jfa_final_output", size, JFA_TEXTURE_FORMAT);
-    let jfa_final_output = textures.get(&device, jfa_final_desc);
-    if jfa_final_output.texture.id() != old_jfa_final {
-        outline.jfa_final_output = jfa_final_output;
-        outline.outline_src_bind_group = create_outline_src_bind_group(
+        let jfa_final_desc = tex_desc(
+            "outline_jfa_final_output",
+            size,
+            jfa_format.texture_format(),
+        );
+        let jfa_final_output = view_texture(
+            &device,
+            &mut textures,
+            retained.as_deref_mut(),
+            entity,
+            jfa_final_desc,
+        );
+        let outline_src_bind_group = create_outline_src_bind_group(
             &device,
-            &outline.outline_src_bind_group_layout,
+            &outline.outline_src_bind_group_layouts[(mask_samples > 1) as usize],
             JFA_OUTLINE_SRC,
-            &outline.jfa_final_output.default_view,
-            &outline.mask_output.default_view,
+            &jfa_final_output.default_view,
+            &mask_output.default_view,
+            &mask_group_multisample.default_view,
             &outline.sampler,
         );
+
+        commands.entity(entity).insert(ViewOutlineResources {
+            mask_multisample,
+            mask_output,
+            mask_group_multisample,
+            mask_samples,
+            jfa_format,
+            dimensions_buffer,
+            dimensions_bind_group,
+            jfa_init_bind_group,
+            jfa_from_secondary_bind_group,
+            jfa_primary_output,
+            jfa_size: UVec2::new(jfa_size.width, jfa_size.height),
+            jfa_from_primary_bind_group,
+            jfa_secondary_output,
+            jfa_final_output,
+            outline_src_bind_group,
+        });
+    }
+}
+
+/// Returns the texture of `view` matching `desc`, retained across frames if
+/// `retained` is given.
+fn view_texture(
+    device: &RenderDevice,
+    textures: &mut TextureCache,
+    retained: Option<&mut RetainedOutlineTextures>,
+    view: Entity,
+    desc: TextureDescriptor<'static>,
+) -> CachedTexture {
+    match retained {
+        Some(retained) => retained.get(device, view, desc),
+        None => textures.get(device, desc),
+    }
+}
+
+fn mask_group_desc(size: Extent3d, samples: u32) -> TextureDescriptor<'static> {
+    TextureDescriptor {
+        sample_count: samples,
+        ..tex_desc(
+            "outline_mask_group_multisample",
+            size,
+            MASK_GROUP_TEXTURE_FORMAT,
+        )
     }
 }
 
@@ -513,6 +739,9 @@ fn tex_desc(label: &'static str, size: Extent3d, format: TextureFormat) -> Textu
         sample_count: 1,
         dimension: TextureDimension::D2,
         format,
-        usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+        // Copied from by `OutlineDebugImagesPlugin`.
+        usage: TextureUsages::RENDER_ATTACHMENT
+            | TextureUsages::TEXTURE_BINDING
+            | TextureUsages::COPY_SRC,
     }
 }
@@ -0,0 +1,333 @@
+use bevy::{
+    asset::{Asset, HandleId},
+    ecs::event::{Events, ManualEventReader},
+    prelude::*,
+    render::{
+        mesh::skinning::SkinnedMesh,
+        render_phase::{DrawFunctions, RenderPhase},
+        render_resource::{PipelineCache, TextureDescriptor, TextureViewDescriptor},
+        renderer::RenderDevice,
+        texture::CachedTexture,
+        view::{Msaa, VisibleEntities},
+        Extract,
+    },
+    utils::{HashMap, HashSet},
+};
+
+use crate::{
+    debug_capture::OutlineDebug,
+    jfa::ViewJfaPipelines,
+    mask::{CustomMeshMask, MaskOcclusion, MeshMaskViewKey, MeshMaskViewKeys},
+    outline::{OutlineViewUniform, ResolvedViewStyles},
+    resources::ViewJfaFormat,
+    DrawMeshMask, JfaTextureFormat, MeshMask, Outline, OutlineGroup, OutlineJitter,
+    OutlineSettings,
+};
+
+/// Marks cameras whose mask is unchanged since the previous frame.
+///
+/// This is only extracted while [`OutlineSettings::set_temporal_reuse`] is
+/// enabled.
+#[derive(Clone, Copy, Debug, Component)]
+pub(crate) struct StaticOutlineView;
+
+/// Marks views whose mask and distance field are kept from the previous
+/// frame, so that the mask and jump flood passes are skipped.
+#[derive(Clone, Copy, Debug, Component)]
+pub(crate) struct ReusedOutline;
+
+/// Returns whether a view reuses the mask and distance field of the previous
+/// frame.
+pub(crate) fn is_reused(world: &World, view: Entity) -> bool {
+    world.get::<ReusedOutline>(view).is_some()
+}
+
+/// The parameters of the jump flood held by a retained distance field.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct FloodKey {
+    extent: f32,
+    metric: u32,
+    mask: Option<MeshMaskViewKey>,
+    format: JfaTextureFormat,
+}
+
+/// The mask and distance field textures of each view, which are kept from one
+/// frame to the next while [`OutlineSettings::set_temporal_reuse`] is enabled.
+#[derive(Default)]
+pub(crate) struct RetainedOutlineTextures {
+    views: HashMap<Entity, RetainedViewTextures>,
+}
+
+#[derive(Default)]
+struct RetainedViewTextures {
+    textures: Vec<(TextureDescriptor<'static>, CachedTexture)>,
+    // The flood held by the distance field, or `None` if it may be incomplete,
+    // e.g. as it was drawn before all pipelines were ready.
+    flooded: Option<FloodKey>,
+}
+
+impl RetainedOutlineTextures {
+    /// Returns the retained texture of `view` matching `desc`, creating it if
+    /// there is none.
+    ///
+    /// Textures are told apart by their labels, and replacing one discards the
+    /// view's distance field.
+    pub(crate) fn get(
+        &mut self,
+        device: &RenderDevice,
+        view: Entity,
+        desc: TextureDescriptor<'static>,
+    ) -> CachedTexture {
+        let retained = self.views.entry(view).or_default();
+        if let Some((_, texture)) = retained.textures.iter().find(|(d, _)| *d == desc) {
+            return texture.clone();
+        }
+
+        retained.flooded = None;
+        retained.textures.retain(|(d, _)| d.label != desc.label);
+
+        let texture = device.create_texture(&desc);
+        let cached = CachedTexture {
+            default_view: texture.create_view(&TextureViewDescriptor::default()),
+            texture,
+        };
+        retained.textures.push((desc, cached.clone()));
+        cached
+    }
+
+    /// Drops the textures of views for which `keep` returns false.
+    pub(crate) fn retain_views(&mut self, mut keep: impl FnMut(Entity) -> bool) {
+        self.views.retain(|&view, _| keep(view));
+    }
+}
+
+/// Adds the IDs of the assets created, modified or removed in `events` since
+/// `reader` last read them to `modified`.
+fn read_asset_events<T: Asset>(
+    reader: &mut ManualEventReader<AssetEvent<T>>,
+    events: &Events<AssetEvent<T>>,
+    modified: &mut HashSet<HandleId>,
+) {
+    for event in reader.iter(events) {
+        let (AssetEvent::Created { handle }
+        | AssetEvent::Modified { handle }
+        | AssetEvent::Removed { handle }) = event;
+        modified.insert(handle.id);
+    }
+}
+
+/// Readers of the events of the asset types drawn into masks.
+type AssetEventReaders = (
+    ManualEventReader<AssetEvent<Mesh>>,
+    ManualEventReader<AssetEvent<StandardMaterial>>,
+    ManualEventReader<AssetEvent<Image>>,
+);
+
+/// The events of the asset types drawn into masks.
+type AssetEventQueues = (
+    Res<'static, Events<AssetEvent<Mesh>>>,
+    Res<'static, Events<AssetEvent<StandardMaterial>>>,
+    Res<'static, Events<AssetEvent<Image>>>,
+);
+
+/// Cameras whose view of their outlined entities changed.
+type ChangedCameraFilter = (
+    With<Camera>,
+    Or<(
+        Changed<Camera>,
+        Changed<GlobalTransform>,
+        Changed<OutlineJitter>,
+    )>,
+);
+
+/// The components of an outlined entity which determine how its mask is
+/// drawn.
+type OutlineMaskSource = (
+    &'static Outline,
+    Option<&'static Handle<Mesh>>,
+    Option<&'static Handle<StandardMaterial>>,
+    Option<&'static SkinnedMesh>,
+    Option<&'static CustomMeshMask>,
+);
+
+/// Outlined entities whose mask may have changed.
+type ChangedOutlineFilter = (
+    With<Outline>,
+    Or<(
+        Changed<Outline>,
+        Changed<OutlineGroup>,
+        Changed<GlobalTransform>,
+        Changed<Handle<Mesh>>,
+        Changed<Handle<StandardMaterial>>,
+    )>,
+);
+
+/// Extracts a [`StaticOutlineView`] for each camera for which neither the
+/// camera nor any of its visible outlined entities changed since the previous
+/// frame.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn extract_static_outline_views(
+    mut commands: Commands,
+    mut previous_len: Local<usize>,
+    mut previous_visible: Local<HashMap<Entity, Vec<Entity>>>,
+    mut readers: Local<AssetEventReaders>,
+    settings: Extract<Res<OutlineSettings>>,
+    msaa: Extract<Res<Msaa>>,
+    debug: Extract<Option<Res<OutlineDebug>>>,
+    asset_events: Extract<AssetEventQueues>,
+    materials: Extract<Res<Assets<StandardMaterial>>>,
+    cameras: Extract<Query<(Entity, &Camera, &VisibleEntities)>>,
+    changed_cameras: Extract<Query<(), ChangedCameraFilter>>,
+    outlines: Extract<Query<OutlineMaskSource>>,
+    changed_outlines: Extract<Query<(), ChangedOutlineFilter>>,
+    #[cfg(feature = "ui")] ui_outlines: Extract<
+        Query<(&ComputedVisibility, &Outline), With<bevy::ui::Node>>,
+    >,
+) {
+    // Events are read every frame, so that old events aren't picked up once
+    // reuse is enabled.
+    let mut modified = HashSet::default();
+    let (mesh_events, material_events, image_events) = &*asset_events;
+    read_asset_events(&mut readers.0, mesh_events, &mut modified);
+    read_asset_events(&mut readers.1, material_events, &mut modified);
+    read_asset_events(&mut readers.2, image_events, &mut modified);
+
+    // UI nodes are drawn by every view, and captures need the textures of the
+    // passes which are skipped.
+    let redraw_all = debug
+        .as_ref()
+        .is_some_and(|debug| debug.is_capture_requested());
+    #[cfg(feature = "ui")]
+    let redraw_all = redraw_all
+        || ui_outlines
+            .iter()
+            .any(|(visibility, outline)| visibility.is_visible() && outline.enabled);
+    if !settings.temporal_reuse || redraw_all {
+        previous_visible.clear();
+        return;
+    }
+
+    let all_changed = settings.is_changed() || msaa.is_changed();
+    let mut visible_outlines = HashMap::with_capacity(previous_visible.len());
+    let mut batches = Vec::with_capacity(*previous_len);
+    for (camera_entity, camera, visible) in cameras.iter() {
+        if !camera.is_active {
+            continue;
+        }
+
+        let mut is_static = !all_changed && changed_cameras.get(camera_entity).is_err();
+        let mut entities = Vec::new();
+        for &entity in visible.entities.iter() {
+            let (outline, mesh, material, skinned, custom) = match outlines.get(entity) {
+                Ok(o) => o,
+                Err(_) => continue,
+            };
+            if !outline.enabled {
+                continue;
+            }
+            entities.push(entity);
+
+            let texture = material
+                .and_then(|handle| materials.get(handle))
+                .and_then(|material| material.base_color_texture.as_ref());
+            let modified_asset = [
+                mesh.map(|handle| handle.id),
+                material.map(|handle| handle.id),
+                texture.map(|handle| handle.id),
+            ]
+            .iter()
+            .flatten()
+            .any(|id| modified.contains(id));
+
+            is_static &= mesh.is_some()
+                && skinned.is_none()
+                && custom.is_none()
+                && !modified_asset
+                && changed_outlines.get(entity).is_err();
+        }
+
+        // Entities may have been hidden or removed.
+        entities.sort_unstable();
+        is_static &= previous_visible.get(&camera_entity) == Some(&entities);
+        visible_outlines.insert(camera_entity, entities);
+
+        if is_static {
+            batches.push((camera_entity, (StaticOutlineView,)));
+        }
+    }
+
+    *previous_visible = visible_outlines;
+    *previous_len = batches.len();
+    commands.insert_or_spawn_batch(batches);
+}
+
+/// A view along with the components deciding whether its distance field can
+/// be reused.
+type ReusableView = (
+    Entity,
+    &'static OutlineViewUniform,
+    Option<&'static RenderPhase<MeshMask>>,
+    Option<&'static MaskOcclusion>,
+    Option<&'static StaticOutlineView>,
+    &'static ViewJfaFormat,
+    Option<&'static ViewJfaPipelines>,
+);
+
+/// Marks the static views whose retained distance field matches their styles
+/// with [`ReusedOutline`], and records the flood drawn by every other view.
+///
+/// This runs in `RenderStage::PhaseSort`, once the mask items of every view
+/// are queued.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn resolve_outline_reuse(
+    mut commands: Commands,
+    mut retained: ResMut<RetainedOutlineTextures>,
+    resolved_styles: Res<ResolvedViewStyles>,
+    view_keys: Res<MeshMaskViewKeys>,
+    pipeline_cache: Res<PipelineCache>,
+    draw_functions: Res<DrawFunctions<MeshMask>>,
+    views: Query<ReusableView>,
+) {
+    let draw_mesh_mask = draw_functions.read().get_id::<DrawMeshMask>().unwrap();
+    for (entity, uniform, phase, occlusion, is_static, &ViewJfaFormat(format), jfa_pipelines) in
+        views.iter()
+    {
+        let retained = match retained.views.get_mut(&entity) {
+            Some(r) => r,
+            None => continue,
+        };
+
+        let key = resolved_styles
+            .get(entity)
+            .zip(resolved_styles.pixel_extent(entity, Some(uniform)))
+            .map(|(style, extent)| FloodKey {
+                extent,
+                metric: style.params.metric,
+                mask: view_keys.get(entity),
+                format,
+            });
+
+        // The occlusion of the mask depends on everything else in the view.
+        let reusable = occlusion.is_none() && key.is_some();
+        if reusable && is_static.is_some() && retained.flooded == key {
+            commands.entity(entity).insert(ReusedOutline);
+            continue;
+        }
+
+        // Items queued by other systems, such as for materials with their own
+        // vertex shaders, may change without being tracked.
+        let mask_ready = phase.iter().flat_map(|phase| &phase.items).all(|item| {
+            item.draw_function == draw_mesh_mask
+                && pipeline_cache.get_render_pipeline(item.pipeline).is_some()
+        });
+        let jfa_ready = jfa_pipelines.is_some_and(|pipelines| {
+            pipeline_cache.get_render_pipeline(pipelines.init).is_some()
+                && pipeline_cache.get_render_pipeline(pipelines.jfa).is_some()
+        });
+        retained.flooded = if reusable && jfa_ready && mask_ready {
+            key
+        } else {
+            None
+        };
+    }
+}
@@ -0,0 +1,44 @@
+use bevy::{
+    prelude::*,
+    scene::{SceneInstance, SceneSpawner},
+};
+
+use crate::Outline;
+
+/// Component for scene roots whose meshes should be outlined once spawned.
+///
+/// Scenes (such as those loaded from glTF files) are spawned asynchronously,
+/// and the meshes they contain are descendants of the scene root rather than
+/// the root itself. Adding an `Outline` to the root therefore has no effect.
+/// Instead, add an `OutlineScene` alongside the root's `SceneInstance`; once
+/// the instance is ready, `outline` is applied to every mesh in the scene and
+/// this component is removed.
+#[derive(Clone, Debug, PartialEq, Component)]
+pub struct OutlineScene {
+    pub outline: Outline,
+}
+
+pub(crate) fn outline_scene_instances(
+    mut commands: Commands,
+    scene_spawner: Res<SceneSpawner>,
+    roots: Query<(Entity, &SceneInstance, &OutlineScene)>,
+    meshes: Query<(), With<Handle<Mesh>>>,
+) {
+    for (root, instance, outline_scene) in roots.iter() {
+        if !scene_spawner.instance_is_ready(**instance) {
+            continue;
+        }
+
+        if let Some(entities) = scene_spawner.iter_instance_entities(**instance) {
+            for entity in entities {
+                if meshes.get(entity).is_ok() {
+                    commands
+                        .entity(entity)
+                        .insert(outline_scene.outline.clone());
+                }
+            }
+        }
+
+        commands.entity(root).remove::<OutlineScene>();
+    }
+}
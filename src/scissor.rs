@@ -0,0 +1,190 @@
+use bevy::{
+    prelude::*,
+    render::{
+        camera::ExtractedCamera, primitives::Aabb, render_phase::TrackedRenderPass,
+        view::VisibleEntities, Extract,
+    },
+};
+
+use crate::{
+    marquee::ndc_bounds,
+    mask_bounds::MaskBoundsBuffers,
+    outline::{view_pixel_extent, OutlineViewUniform},
+    resources::ViewOutlineResources,
+    Outline, OutlineSettings,
+};
+
+/// The distance in pixels by which scissor rectangles are extended past the
+/// outline, covering jitter and the coarser distance field at reduced
+/// resolutions.
+const SCISSOR_MARGIN: f32 = 4.0;
+
+/// The screen-space bounds of the silhouettes in a view's mask, in screen
+/// texcoords.
+///
+/// This is only extracted while [`OutlineSettings::set_scissor`] is enabled,
+/// and views without it are drawn in full.
+#[derive(Clone, Copy, Debug, Component)]
+pub(crate) struct SilhouetteBounds {
+    min: Vec2,
+    max: Vec2,
+}
+
+/// A rectangle of a render target in pixels.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct ScissorRect {
+    origin: UVec2,
+    size: UVec2,
+}
+
+impl ScissorRect {
+    /// Returns the rectangle of a `target_size` target covering the region
+    /// from `min` to `max`, in pixels.
+    ///
+    /// Empty scissor rectangles are invalid, so the rectangle covers at least
+    /// one pixel.
+    pub(crate) fn covering(min: Vec2, max: Vec2, target_size: UVec2) -> ScissorRect {
+        let last = target_size.max(UVec2::ONE) - UVec2::ONE;
+        let origin = min.floor().max(Vec2::ZERO).as_uvec2().min(last);
+        let end = max.ceil().max(Vec2::ZERO).as_uvec2().min(target_size);
+        ScissorRect {
+            origin,
+            size: end.max(origin + UVec2::ONE) - origin,
+        }
+    }
+
+    pub(crate) fn apply(&self, pass: &mut TrackedRenderPass) {
+        pass.set_scissor_rect(self.origin.x, self.origin.y, self.size.x, self.size.y);
+    }
+}
+
+/// Returns the region of a view's screen outside of which its outline is
+/// empty, in screen texcoords, or `None` if it isn't known.
+pub(crate) fn view_outline_region(world: &World, view: Entity) -> Option<(Vec2, Vec2)> {
+    let bounds = world.get::<SilhouetteBounds>(view)?;
+    let res = world.get::<ViewOutlineResources>(view)?;
+    let extent = view_pixel_extent(world, view)?;
+    let edge_scale = world
+        .get::<OutlineViewUniform>(view)
+        .map_or(1.0, |uniform| uniform.edge_scale);
+
+    let screen_size = res.dimensions_buffer.get().screen_size().as_vec2();
+    let margin = Vec2::splat(extent + edge_scale + SCISSOR_MARGIN) / screen_size;
+    Some((bounds.min - margin, bounds.max + margin))
+}
+
+/// Returns the scissor rectangle covering the region of a view's screen,
+/// given in screen texcoords, in a `target_size` texture covering the view's
+/// framebuffer, including the offscreen padding.
+pub(crate) fn framebuffer_scissor(
+    res: &ViewOutlineResources,
+    (min, max): (Vec2, Vec2),
+    target_size: UVec2,
+) -> ScissorRect {
+    let dims = res.dimensions_buffer.get();
+    let fb_size = dims.size().as_vec2();
+    let screen_size = dims.screen_size().as_vec2();
+    let padding = (fb_size - screen_size) / 2.0;
+
+    let scale = target_size.as_vec2() / fb_size;
+    let to_target = |texcoord: Vec2| (padding + texcoord * screen_size) * scale;
+    ScissorRect::covering(to_target(min), to_target(max), target_size)
+}
+
+/// Returns the scissor rectangle covering the region of a view's screen,
+/// given in screen texcoords, in the viewport of `camera`.
+pub(crate) fn viewport_scissor(
+    camera: &ExtractedCamera,
+    (min, max): (Vec2, Vec2),
+) -> Option<ScissorRect> {
+    let target_size = camera.physical_target_size?;
+    let (origin, size) = match &camera.viewport {
+        Some(viewport) => (viewport.physical_position, viewport.physical_size),
+        None => (UVec2::ZERO, target_size),
+    };
+
+    let to_target = |texcoord: Vec2| origin.as_vec2() + texcoord * size.as_vec2();
+    Some(ScissorRect::covering(
+        to_target(min),
+        to_target(max),
+        target_size,
+    ))
+}
+
+/// Extracts the [`SilhouetteBounds`] of each camera from the `Aabb`s of its
+/// visible outlined entities, along with the bounds of its mask reduced on
+/// the GPU in a recent frame.
+pub(crate) fn extract_silhouette_bounds(
+    mut commands: Commands,
+    mut previous_len: Local<usize>,
+    mask_bounds: Res<MaskBoundsBuffers>,
+    settings: Extract<Res<OutlineSettings>>,
+    cameras: Extract<Query<(Entity, &Camera, &GlobalTransform, &VisibleEntities)>>,
+    outlines: Extract<Query<(&Outline, &GlobalTransform, Option<&Aabb>)>>,
+    #[cfg(feature = "ui")] ui_outlines: Extract<Query<&Outline, With<bevy::ui::Node>>>,
+) {
+    if !settings.scissor {
+        return;
+    }
+
+    // UI nodes are drawn by every view, and have no `Aabb`.
+    #[cfg(feature = "ui")]
+    if ui_outlines.iter().any(|outline| outline.enabled) {
+        return;
+    }
+
+    let mut batches = Vec::with_capacity(*previous_len);
+    'cameras: for (camera_entity, camera, camera_transform, visible) in cameras.iter() {
+        if !camera.is_active {
+            continue;
+        }
+
+        // The mask covers geometry drawn outside of its `Aabb`, such as skinned
+        // meshes and displaced vertices, as of the frame it was reduced in.
+        // Entities without usable bounds still fall back to the full view.
+        let latest = mask_bounds.latest(camera_entity);
+
+        let view_proj = camera.projection_matrix() * camera_transform.compute_matrix().inverse();
+        let mut min = Vec2::splat(f32::INFINITY);
+        let mut max = Vec2::splat(f32::NEG_INFINITY);
+        for &entity in visible.entities.iter() {
+            let (outline, transform, aabb) = match outlines.get(entity) {
+                Ok(o) => o,
+                Err(_) => continue,
+            };
+            if !outline.enabled {
+                continue;
+            }
+
+            let ndc =
+                aabb.and_then(|aabb| ndc_bounds(view_proj * transform.compute_matrix(), aabb));
+            let (ndc_min, ndc_max) = match ndc {
+                Some(b) => b,
+                None => continue 'cameras,
+            };
+
+            // The Y axis of NDC points up, and of screen texcoords down.
+            min = min.min(Vec2::new(ndc_min.x, -ndc_max.y) * 0.5 + 0.5);
+            max = max.max(Vec2::new(ndc_max.x, -ndc_min.y) * 0.5 + 0.5);
+        }
+
+        // The mask only enlarges the bounds of entities drawn this frame, as
+        // it may be a few frames stale.
+        if let Some((mask_min, mask_max)) = latest.filter(|_| min.cmple(max).all()) {
+            min = min.min(mask_min);
+            max = max.max(mask_max);
+        }
+
+        // Views without outlined entities have nothing to draw, so they're
+        // scissored to a corner.
+        if min.cmpgt(max).any() {
+            min = Vec2::ZERO;
+            max = Vec2::ZERO;
+        }
+
+        batches.push((camera_entity, (SilhouetteBounds { min, max },)));
+    }
+
+    *previous_len = batches.len();
+    commands.insert_or_spawn_batch(batches);
+}
@@ -1,3 +1,9 @@
+// Framebuffer dimensions uniform.
+//
+// Public shader import: `Dimensions`, `dims` and `screen_to_framebuffer` are
+// part of bevy_jfa's stable API. Must be kept in sync with `Dimensions` in
+// jfa.rs.
+
 struct Dimensions {
     // Framebuffer width in pixels.
     width: f32,
@@ -7,7 +13,17 @@ struct Dimensions {
     inv_width: f32,
     // Reciprocal of height.
     inv_height: f32,
+    // Number of pixels by which the framebuffer extends past each edge of the
+    // screen.
+    padding: f32,
 };
 
 @group(0) @binding(0)
 var<uniform> dims: Dimensions;
+
+// Converts a screen texcoord to the corresponding framebuffer texcoord.
+fn screen_to_framebuffer(texcoord: vec2<f32>) -> vec2<f32> {
+    let fb_size = vec2<f32>(dims.width, dims.height);
+    let screen_size = fb_size - 2.0 * dims.padding;
+    return (texcoord * screen_size + dims.padding) / fb_size;
+}
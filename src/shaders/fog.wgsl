@@ -0,0 +1,60 @@
+#import outline::fullscreen
+#import outline::sdf
+
+// Must match `FogUniform`.
+struct FogUniform {
+    inverse_view_proj: mat4x4<f32>,
+    world_to_map: mat4x4<f32>,
+    color: vec4<f32>,
+    // The distance over which the fog thickens, in distance field texels.
+    fade: f32,
+};
+
+@group(0) @binding(0)
+var<uniform> fog: FogUniform;
+
+@group(1) @binding(0)
+var distance_field: texture_2d<f32>;
+@group(1) @binding(1)
+var distance_field_sampler: sampler;
+
+struct FragmentIn {
+    @location(0) texcoord: vec2<f32>,
+};
+
+// Returns the map space position of the NDC point `ndc`.
+fn ndc_to_map(ndc: vec3<f32>) -> vec3<f32> {
+    let world = fog.inverse_view_proj * vec4<f32>(ndc, 1.0);
+    let map = fog.world_to_map * vec4<f32>(world.xyz / world.w, 1.0);
+    return map.xyz;
+}
+
+@fragment
+fn fragment(in: FragmentIn) -> @location(0) vec4<f32> {
+    let fogged = fog.color;
+    let ndc = vec2<f32>(in.texcoord.x * 2.0 - 1.0, 1.0 - in.texcoord.y * 2.0);
+
+    // Cast a ray through the fragment onto the map plane, at z = 0 in map
+    // space. Two finite depths are used, as the far plane may be at infinity.
+    let near = ndc_to_map(vec3<f32>(ndc, 1.0));
+    let far = ndc_to_map(vec3<f32>(ndc, 0.5));
+    let dz = near.z - far.z;
+    if (abs(dz) < 0.00001) {
+        return fogged;
+    }
+
+    let t = near.z / dz;
+    if (t < 0.0) {
+        return fogged;
+    }
+
+    let uv = mix(near.xy, far.xy, t);
+    if (any(uv < vec2<f32>(0.0)) || any(uv > vec2<f32>(1.0))) {
+        return fogged;
+    }
+
+    // Revealed texels are their own nearest seeds, at a distance of zero.
+    let distance = sdf_sample_distance(distance_field, distance_field_sampler, uv);
+    let fog_amount = clamp(distance / max(fog.fade, 0.0001), 0.0, 1.0);
+    return vec4<f32>(fogged.rgb, fogged.a * smoothstep(0.0, 1.0, fog_amount));
+}
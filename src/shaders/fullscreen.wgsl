@@ -1,3 +1,8 @@
+// Fullscreen triangle vertex shader.
+//
+// Public shader import: the `vertex` entry point, the `fullscreen_vertex`
+// function it wraps and their `VertexOut` are part of bevy_jfa's stable API.
+
 struct Vertex {
     pos: vec2<f32>,
     texcoord: vec2<f32>,
@@ -42,8 +47,9 @@ struct VertexOut {
     @location(0) texcoord: vec2<f32>,
 };
 
-@vertex
-fn vertex(@builtin(vertex_index) idx: u32) -> VertexOut {
+// Returns the output of the fullscreen triangle's vertex `idx`, for use in
+// entry points which need additional outputs.
+fn fullscreen_vertex(idx: u32) -> VertexOut {
     var v: Vertex;
     switch (idx % 3u) {
         case 0u: {
@@ -67,3 +73,8 @@ fn vertex(@builtin(vertex_index) idx: u32) -> VertexOut {
     out.texcoord = v.texcoord;
     return out;
 }
+
+@vertex
+fn vertex(@builtin(vertex_index) idx: u32) -> VertexOut {
+    return fullscreen_vertex(idx);
+}
@@ -0,0 +1,53 @@
+// Counts the mask samples covered by each outline group.
+
+// Outline group of each mask sample in the low byte of R, and nonzero G where
+// covered.
+@group(0) @binding(0)
+#ifdef MASK_GROUP_MULTISAMPLED
+var mask_group: texture_multisampled_2d<u32>;
+#else
+var mask_group: texture_2d<u32>;
+#endif
+@group(0) @binding(1)
+var<storage, read_write> counts: array<atomic<u32>, 256>;
+
+// Counts for this workgroup, to avoid contention on the global counts.
+var<workgroup> local_counts: array<atomic<u32>, 256>;
+
+@compute @workgroup_size(8, 8, 1)
+fn count_groups(
+    @builtin(global_invocation_id) global_id: vec3<u32>,
+    @builtin(local_invocation_index) local_index: u32,
+) {
+    // Each of the 64 invocations owns four of the 256 counters.
+    for (var i = 0u; i < 4u; i += 1u) {
+        atomicStore(&local_counts[local_index * 4u + i], 0u);
+    }
+    workgroupBarrier();
+
+#ifdef MASK_GROUP_MULTISAMPLED
+    let samples = i32(textureNumSamples(mask_group));
+#else
+    let samples = 1;
+#endif
+
+    let size = vec2<u32>(textureDimensions(mask_group));
+    if (global_id.x < size.x && global_id.y < size.y) {
+        for (var i = 0; i < samples; i += 1) {
+            let texel = textureLoad(mask_group, vec2<i32>(global_id.xy), i);
+            // Uncovered samples hold zero.
+            if (texel.g != 0u) {
+                atomicAdd(&local_counts[texel.r & 0xffu], 1u);
+            }
+        }
+    }
+    workgroupBarrier();
+
+    for (var i = 0u; i < 4u; i += 1u) {
+        let group = local_index * 4u + i;
+        let count = atomicLoad(&local_counts[group]);
+        if (count != 0u) {
+            atomicAdd(&counts[group], count);
+        }
+    }
+}
@@ -1,10 +1,13 @@
 #import outline::fullscreen
 #import outline::dimensions
+#import outline::jfa_utils
 
 // Bind group 0 imported from outline::dimensions
 
 struct JumpDist {
     dist: u32,
+    // One of the JFA_METRIC_* constants.
+    metric: u32,
 };
 
 @group(1) @binding(0)
@@ -32,38 +35,47 @@ fn fragment(in: FragmentIn) -> @location(0) vec4<f32> {
     // TODO: this is actually the largest finite f32. WGSL doesn't seem to have
     // a way to write an infinity float literal.
     let infinity = 0x1.FFFFFp127;
-    // Minimum pixel-space distance between this fragment and one of the initial fragments.
-    var min_dist2: f32 = infinity;
-    // The framebuffer-space position of the closest initial fragment.
-    var min_dist2_pos: vec2<f32> = vec2<f32>(-1.0, -1.0);
+    // Minimum pixel-space distances between this fragment and one of the
+    // initial fragments outside and inside the silhouette.
+    var min_dist: vec2<f32> = vec2<f32>(infinity, infinity);
+    // The framebuffer-space positions of the closest initial fragments
+    // outside (xy) and inside (zw) the silhouette.
+    var min_dist_pos: vec4<f32> = vec4<f32>(JFA_NO_SEED, JFA_NO_SEED);
 
     // Fetch 9 samples in a 3x3 grid, jump_dist pixels apart.
-    var samples: array<vec2<f32>, 9>;
-    samples[0] = textureSample(src_buffer, src_sampler, in.texcoord + vec2<f32>(-dx, -dy)).xy;
-    samples[1] = textureSample(src_buffer, src_sampler, in.texcoord + vec2<f32>(-dx, 0.0)).xy;
-    samples[2] = textureSample(src_buffer, src_sampler, in.texcoord + vec2<f32>(-dx, dy)).xy;
-    samples[3] = textureSample(src_buffer, src_sampler, in.texcoord + vec2<f32>(0.0, -dy)).xy;
-    samples[4] = textureSample(src_buffer, src_sampler, in.texcoord).xy;
-    samples[5] = textureSample(src_buffer, src_sampler, in.texcoord + vec2<f32>(0.0, dy)).xy;
-    samples[6] = textureSample(src_buffer, src_sampler, in.texcoord + vec2<f32>(dx, -dy)).xy;
-    samples[7] = textureSample(src_buffer, src_sampler, in.texcoord + vec2<f32>(dx, 0.0)).xy;
-    samples[8] = textureSample(src_buffer, src_sampler, in.texcoord + vec2<f32>(dx, dy)).xy;
+    var samples: array<vec4<f32>, 9>;
+    samples[0] = jfa_decode(textureSample(src_buffer, src_sampler, in.texcoord + vec2<f32>(-dx, -dy)));
+    samples[1] = jfa_decode(textureSample(src_buffer, src_sampler, in.texcoord + vec2<f32>(-dx, 0.0)));
+    samples[2] = jfa_decode(textureSample(src_buffer, src_sampler, in.texcoord + vec2<f32>(-dx, dy)));
+    samples[3] = jfa_decode(textureSample(src_buffer, src_sampler, in.texcoord + vec2<f32>(0.0, -dy)));
+    samples[4] = jfa_decode(textureSample(src_buffer, src_sampler, in.texcoord));
+    samples[5] = jfa_decode(textureSample(src_buffer, src_sampler, in.texcoord + vec2<f32>(0.0, dy)));
+    samples[6] = jfa_decode(textureSample(src_buffer, src_sampler, in.texcoord + vec2<f32>(dx, -dy)));
+    samples[7] = jfa_decode(textureSample(src_buffer, src_sampler, in.texcoord + vec2<f32>(dx, 0.0)));
+    samples[8] = jfa_decode(textureSample(src_buffer, src_sampler, in.texcoord + vec2<f32>(dx, dy)));
 
     for (var i: i32 = 0; i < 9; i = i + 1) {
-        let fb_sample = samples[i];
-        let valid = fb_sample.x != -1.0;
+        let outer_sample = samples[i].xy;
+        let inner_sample = samples[i].zw;
 
-        // Convert sample to pixel coordinates when computing distance.
-        let pix_sample = fb_sample * fb_to_pix;
-        let delta = pix_coord - pix_sample;
-        let dist2 = dot(delta, delta);
+        // Convert samples to pixel coordinates when computing distance.
+        let outer_delta = pix_coord - outer_sample * fb_to_pix;
+        let inner_delta = pix_coord - inner_sample * fb_to_pix;
+        let dist = vec2<f32>(
+            jfa_distance(outer_delta, jump_dist.metric),
+            jfa_distance(inner_delta, jump_dist.metric),
+        );
 
-        // It doesn't seem as though there's a way to avoid this branch :(
-        if (valid && dist2 < min_dist2) {
-            min_dist2 = dist2;
-            min_dist2_pos = fb_sample;
+        // It doesn't seem as though there's a way to avoid these branches :(
+        if (jfa_has_seed(outer_sample) && dist.x < min_dist.x) {
+            min_dist.x = dist.x;
+            min_dist_pos = vec4<f32>(outer_sample, min_dist_pos.zw);
+        }
+        if (jfa_has_seed(inner_sample) && dist.y < min_dist.y) {
+            min_dist.y = dist.y;
+            min_dist_pos = vec4<f32>(min_dist_pos.xy, inner_sample);
         }
     }
 
-    return vec4<f32>(min_dist2_pos, 0.0, 1.0);
+    return jfa_encode(min_dist_pos);
 }
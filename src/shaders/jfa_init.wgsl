@@ -1,5 +1,6 @@
 #import outline::fullscreen
 #import outline::dimensions
+#import outline::jfa_utils
 
 // Jump flood initialization pass.
 @group(1) @binding(0)
@@ -7,14 +8,23 @@ var mask_buffer: texture_2d<f32>;
 @group(1) @binding(1)
 var mask_sampler: sampler;
 
+// Returns the coverage of the mask at `texcoord`. Masks without colors hold
+// it in the R component rather than in alpha.
+fn mask_coverage(texcoord: vec2<f32>) -> f32 {
+    let texel = textureSample(mask_buffer, mask_sampler, texcoord);
+#ifdef MASK_COVERAGE_ONLY
+    return texel.r;
+#else
+    return texel.a;
+#endif
+}
+
 struct FragmentIn {
     @location(0) texcoord: vec2<f32>,
 };
 
 @fragment
 fn fragment(in: FragmentIn) -> @location(0) vec4<f32> {
-    let out_position = vec4<f32>(in.texcoord, 0.0, 1.0);
-
     // Scaling factor to convert framebuffer to pixel coordinates.
     let fb_to_pix = vec2<f32>(dims.width, dims.height);
     // Pixel coordinates of this fragment.
@@ -26,34 +36,39 @@ fn fragment(in: FragmentIn) -> @location(0) vec4<f32> {
 
     // Fetch 9 samples in a 3x3 grid, jump_dist pixels apart.
     var samples: mat3x3<f32>;
-    samples[0][0] = textureSample(mask_buffer, mask_sampler, in.texcoord + vec2<f32>(-dx, -dy)).x;
-    samples[0][1] = textureSample(mask_buffer, mask_sampler, in.texcoord + vec2<f32>(-dx, 0.0)).x;
-    samples[0][2] = textureSample(mask_buffer, mask_sampler, in.texcoord + vec2<f32>(-dx, dy)).x;
-    samples[1][0] = textureSample(mask_buffer, mask_sampler, in.texcoord + vec2<f32>(0.0, -dy)).x;
-    samples[1][1] = textureSample(mask_buffer, mask_sampler, in.texcoord).x;
-    samples[1][2] = textureSample(mask_buffer, mask_sampler, in.texcoord + vec2<f32>(0.0, dy)).x;
-    samples[2][0] = textureSample(mask_buffer, mask_sampler, in.texcoord + vec2<f32>(dx, -dy)).x;
-    samples[2][1] = textureSample(mask_buffer, mask_sampler, in.texcoord + vec2<f32>(dx, 0.0)).x;
-    samples[2][2] = textureSample(mask_buffer, mask_sampler, in.texcoord + vec2<f32>(dx, dy)).x;
+    samples[0][0] = mask_coverage(in.texcoord + vec2<f32>(-dx, -dy));
+    samples[0][1] = mask_coverage(in.texcoord + vec2<f32>(-dx, 0.0));
+    samples[0][2] = mask_coverage(in.texcoord + vec2<f32>(-dx, dy));
+    samples[1][0] = mask_coverage(in.texcoord + vec2<f32>(0.0, -dy));
+    samples[1][1] = mask_coverage(in.texcoord);
+    samples[1][2] = mask_coverage(in.texcoord + vec2<f32>(0.0, dy));
+    samples[2][0] = mask_coverage(in.texcoord + vec2<f32>(dx, -dy));
+    samples[2][1] = mask_coverage(in.texcoord + vec2<f32>(dx, 0.0));
+    samples[2][2] = mask_coverage(in.texcoord + vec2<f32>(dx, dy));
 
+    // Fragments fully inside the silhouette seed the exterior flood, and
+    // fragments fully outside it seed the interior flood.
     if (samples[1][1] > 0.99) {
-        return out_position;
+        return jfa_encode(vec4<f32>(in.texcoord, JFA_NO_SEED));
     }
 
     if (samples[1][1] < 0.01) {
-        return vec4<f32>(-1.0, -1.0, 0.0, 1.0);
+        return jfa_encode(vec4<f32>(JFA_NO_SEED, in.texcoord));
     }
 
+    // Edge fragments seed both floods.
+
     let sobel_x = samples[0][0] + 2.0 * samples[0][1] + samples[0][2] - samples[2][0] - 2.0 * samples[2][1] - samples[2][2];
     let sobel_y = samples[0][0] + 2.0 * samples[1][0] + samples[2][0] - samples[0][2] - 2.0 * samples[1][2] - samples[2][2];
     let dir = -vec2<f32>(sobel_x, sobel_y);
 
     if (abs(dir.x) < 0.005 && abs(dir.y) < 0.005) {
-        return out_position;
+        return jfa_encode(vec4<f32>(in.texcoord, in.texcoord));
     }
 
     let dir = normalize(dir);
     let offset = dir * (1.0 - samples[1][1]) * vec2<f32>(dx, dy);
+    let edge = in.texcoord + offset;
 
-    return out_position + vec4<f32>(offset, 0.0, 1.0);
+    return jfa_encode(vec4<f32>(edge, edge));
 }
@@ -0,0 +1,95 @@
+// Utilities for interpreting the contents of JFA textures.
+//
+// Each texel of a JFA texture holds the framebuffer-space coordinates of the
+// nearest seed, or JFA_NO_SEED if no seed has been found for that texel. The
+// RG channels hold the nearest seed outside the silhouette, and the BA
+// channels hold the nearest seed inside it.
+//
+// Shaders read and write texels through `jfa_decode` and `jfa_encode`, which
+// convert from and to the storage format selected by the shader defs of
+// `JfaTextureFormat`:
+//
+// - JFA_OUTER_SEEDS_ONLY: only the nearest seed outside the silhouette is
+//   stored, and the inner seed always decodes to JFA_NO_SEED.
+// - JFA_PACKED: each coordinate of the outer seed is stored as a 16-bit
+//   integer split across two 8-bit channels, with the largest value reserved
+//   for JFA_NO_SEED.
+
+// The sentinel selected by the shader defs of `JfaNoSeed`. Must be kept in
+// sync with `JFA_NO_SEED` and `JFA_FAR_NO_SEED`.
+#ifdef JFA_NO_SEED_FAR
+let JFA_NO_SEED: vec2<f32> = vec2<f32>(1024.0, 1024.0);
+#else
+let JFA_NO_SEED: vec2<f32> = vec2<f32>(-1.0, -1.0);
+#endif
+
+// Distance metrics. Must be kept in sync with `DistanceMetric`.
+let JFA_METRIC_EUCLIDEAN: u32 = 0u;
+let JFA_METRIC_CHEBYSHEV: u32 = 1u;
+let JFA_METRIC_MANHATTAN: u32 = 2u;
+
+// Returns the length of `delta` under the given metric.
+fn jfa_distance(delta: vec2<f32>, metric: u32) -> f32 {
+    let d = abs(delta);
+
+    if (metric == JFA_METRIC_CHEBYSHEV) {
+        return max(d.x, d.y);
+    } else if (metric == JFA_METRIC_MANHATTAN) {
+        return d.x + d.y;
+    }
+
+    // JFA_METRIC_EUCLIDEAN
+    return sqrt(dot(delta, delta));
+}
+
+// Returns whether a JFA texel refers to a seed.
+fn jfa_has_seed(texel: vec2<f32>) -> bool {
+    return texel.x != JFA_NO_SEED.x;
+}
+
+#ifdef JFA_PACKED
+// Packed coordinates are multiples of 1 / JFA_PACKED_SCALE.
+let JFA_PACKED_SCALE: f32 = 65534.0;
+
+// Returns the coordinate packed in the high and low bytes `bytes`.
+fn jfa_unpack(bytes: vec2<f32>) -> f32 {
+    let value = dot(round(bytes * 255.0), vec2<f32>(256.0, 1.0));
+    return select(value / JFA_PACKED_SCALE, JFA_NO_SEED.x, value > JFA_PACKED_SCALE);
+}
+
+// Returns the high and low bytes of the packed coordinate `coord`.
+fn jfa_pack(coord: f32) -> vec2<f32> {
+    var value = JFA_PACKED_SCALE + 1.0;
+    if (coord != JFA_NO_SEED.x) {
+        value = round(clamp(coord, 0.0, 1.0) * JFA_PACKED_SCALE);
+    }
+    let high = floor(value / 256.0);
+    return vec2<f32>(high, value - high * 256.0) / 255.0;
+}
+#endif
+
+// Returns the seeds held by a texel of a JFA texture, outer in XY and inner in
+// ZW.
+fn jfa_decode(texel: vec4<f32>) -> vec4<f32> {
+#ifdef JFA_PACKED
+    let outer = vec2<f32>(jfa_unpack(texel.rg), jfa_unpack(texel.ba));
+#else
+    let outer = texel.xy;
+#endif
+
+#ifdef JFA_OUTER_SEEDS_ONLY
+    return vec4<f32>(outer, JFA_NO_SEED);
+#else
+    return vec4<f32>(outer, texel.zw);
+#endif
+}
+
+// Returns the texel of a JFA texture holding the seeds `seeds`, outer in XY
+// and inner in ZW.
+fn jfa_encode(seeds: vec4<f32>) -> vec4<f32> {
+#ifdef JFA_PACKED
+    return vec4<f32>(jfa_pack(seeds.x), jfa_pack(seeds.y));
+#else
+    return seeds;
+#endif
+}
@@ -0,0 +1,33 @@
+#import outline::fullscreen
+#import outline::sdf
+
+// Must match `LightFalloffUniform`.
+struct LightFalloff {
+    color: vec4<f32>,
+    // The distance at which the light reaches zero, in texels.
+    radius: f32,
+};
+
+@group(0) @binding(0)
+var<uniform> light: LightFalloff;
+
+@group(1) @binding(0)
+var distance_field: texture_2d<f32>;
+@group(1) @binding(1)
+var distance_field_sampler: sampler;
+
+struct FragmentIn {
+    @location(0) texcoord: vec2<f32>,
+};
+
+@fragment
+fn fragment(in: FragmentIn) -> @location(0) vec4<f32> {
+    // Texels covered by the seed are their own nearest seeds, at a distance
+    // of zero, and are fully lit.
+    let distance = sdf_sample_distance(distance_field, distance_field_sampler, in.texcoord);
+    let x = clamp(distance / max(light.radius, 0.0001), 0.0, 1.0);
+
+    // A windowed falloff, which eases out to reach zero exactly at `radius`.
+    let window = 1.0 - x * x;
+    return light.color * window * window;
+}
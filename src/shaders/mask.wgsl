@@ -2,26 +2,132 @@
 
 #import bevy_pbr::mesh_view_bindings
 #import bevy_pbr::mesh_types
+#import outline::mask_vertex
 
-@group(1) @binding(0)
-var<uniform> mesh: Mesh;
+// Must match `MaskUniform`.
+struct MaskUniform {
+    base_color: vec4<f32>,
+    emissive: vec4<f32>,
+    group: u32,
+    alpha_cutoff: f32,
+};
+
+@group(2) @binding(1)
+var<uniform> mask_uniform: MaskUniform;
+
+#ifdef MASK_OCCLUSION
+// The main pass depth of the view's target.
+@group(2) @binding(3)
+#ifdef MASK_OCCLUSION_MULTISAMPLED
+var scene_depth: texture_depth_multisampled_2d;
+#else
+var scene_depth: texture_depth_2d;
+#endif
+
+// Set in the group output of samples hidden behind other geometry.
+let MASK_GROUP_OCCLUDED: u32 = 256u;
+
+// Returns whether a point is hidden behind the geometry in the depth buffer.
+fn is_occluded(world_position: vec3<f32>) -> bool {
+    let clip = view.view_proj * vec4<f32>(world_position, 1.0);
+    let ndc = clip.xyz / clip.w;
+    let uv = ndc.xy * vec2<f32>(0.5, -0.5) + 0.5;
+    let target_size = vec2<f32>(textureDimensions(scene_depth));
+    let pixel = (view_params.viewport.xy + uv * view_params.viewport.zw) * target_size;
+    let coord = clamp(vec2<i32>(pixel), vec2<i32>(0), vec2<i32>(target_size) - 1);
+
+    let depth = textureLoad(scene_depth, coord, 0);
+    // Depth is reversed, so nearer geometry has greater depth. The tolerance
+    // keeps the entity from occluding itself where the depth buffer was
+    // rasterized at a different point within the pixel.
+    return depth > ndc.z * 1.005;
+}
+#endif
+
+#ifdef MASK_ALPHA_MASK
+@group(3) @binding(0)
+var base_color_texture: texture_2d<f32>;
+@group(3) @binding(1)
+var base_color_sampler: sampler;
+#endif
 
 struct Vertex {
     @location(0) position: vec3<f32>,
-};
-
-struct VertexOutput {
-    @builtin(position) clip_position: vec4<f32>,
+#ifdef MASK_ALPHA_MASK
+    @location(2) uv: vec2<f32>,
+#endif
+#ifdef SKINNED
+    @location(5) joint_indices: vec4<u32>,
+    @location(6) joint_weights: vec4<f32>,
+#endif
 };
 
 @vertex
 fn vertex(vertex: Vertex) -> VertexOutput {
+#ifdef SKINNED
+    let model = skin_model(vertex.joint_indices, vertex.joint_weights);
+#else
+    let model = mesh.model;
+#endif
+
     var out: VertexOutput;
-    out.clip_position = view.view_proj * mesh.model * vec4<f32>(vertex.position, 1.0);
+    let world_position = model * vec4<f32>(vertex.position, 1.0);
+    out.clip_position = mask_clip_position(world_position);
+    out.world_position = world_position.xyz;
+#ifdef MASK_ALPHA_MASK
+    out.uv = vertex.uv;
+#endif
     return out;
 }
 
+struct FragmentOut {
+    // The mask stores the entity's color in RGB and coverage in alpha. After
+    // the multisample resolve, the color is premultiplied by the coverage.
+    // Masks without colors store only the coverage, in R.
+    @location(0) mask: vec4<f32>,
+    // The entity's outline group and, as a half-precision float, its distance
+    // from the camera, per sample.
+    @location(1) group: vec2<u32>,
+};
+
 @fragment
-fn fragment() -> @location(0) vec4<f32> {
-    return vec4<f32>(1.0, 1.0, 1.0, 1.0);
+fn fragment(in: VertexOutput) -> FragmentOut {
+    var coverage = 1.0;
+#ifdef MASK_ALPHA_MASK
+    let alpha = mask_uniform.base_color.a
+        * textureSample(base_color_texture, base_color_sampler, in.uv).a;
+#ifdef MASK_ALPHA_TO_COVERAGE
+    // The alpha is sharpened around the cutoff, so that coverage only ramps
+    // up across about a pixel rather than over the whole texture gradient.
+    let ramp = max(fwidth(alpha), 0.0001);
+    coverage = clamp((alpha - mask_uniform.alpha_cutoff) / ramp + 0.5, 0.0, 1.0);
+#else
+    if (alpha < mask_uniform.alpha_cutoff) {
+        discard;
+    }
+#endif
+#endif
+
+    var out: FragmentOut;
+    // With alpha to coverage, the alpha of the mask selects the samples
+    // written by every output.
+#ifdef MASK_COVERAGE_ONLY
+    out.mask = vec4<f32>(coverage, 0.0, 0.0, coverage);
+#else
+#ifdef MASK_COLOR_EMISSIVE
+    out.mask = vec4<f32>(mask_uniform.emissive.rgb, coverage);
+#else
+    out.mask = vec4<f32>(mask_uniform.base_color.rgb, coverage);
+#endif
+#endif
+    // Uncovered samples hold zero, so the stored distance must be nonzero.
+    let distance = max(length(in.world_position - view.world_position), 0.001);
+    let packed_distance = pack2x16float(vec2<f32>(distance, 0.0)) & 0xffffu;
+    out.group = vec2<u32>(mask_uniform.group, packed_distance);
+#ifdef MASK_OCCLUSION
+    if (is_occluded(in.world_position)) {
+        out.group.x = out.group.x | MASK_GROUP_OCCLUDED;
+    }
+#endif
+    return out;
 }
@@ -0,0 +1,61 @@
+// Finds the bounding box of the covered samples of the mask.
+
+// Outline group of each mask sample in the low byte of R, and nonzero G where
+// covered.
+@group(0) @binding(0)
+#ifdef MASK_GROUP_MULTISAMPLED
+var mask_group: texture_multisampled_2d<u32>;
+#else
+var mask_group: texture_2d<u32>;
+#endif
+
+// The bitwise complement of the minimum X and Y of the covered pixels,
+// followed by the maximum X and Y plus one, so that every component is
+// reduced with atomicMax and a zeroed buffer holds an empty box.
+@group(0) @binding(1)
+var<storage, read_write> bounds: array<atomic<u32>, 4>;
+
+// Bounds of this workgroup, to avoid contention on the global bounds.
+var<workgroup> local_bounds: array<atomic<u32>, 4>;
+
+@compute @workgroup_size(8, 8, 1)
+fn reduce_bounds(
+    @builtin(global_invocation_id) global_id: vec3<u32>,
+    @builtin(local_invocation_index) local_index: u32,
+) {
+    if (local_index < 4u) {
+        atomicStore(&local_bounds[local_index], 0u);
+    }
+    workgroupBarrier();
+
+#ifdef MASK_GROUP_MULTISAMPLED
+    let samples = i32(textureNumSamples(mask_group));
+#else
+    let samples = 1;
+#endif
+
+    let size = vec2<u32>(textureDimensions(mask_group));
+    if (global_id.x < size.x && global_id.y < size.y) {
+        var covered = false;
+        for (var i = 0; i < samples; i += 1) {
+            let texel = textureLoad(mask_group, vec2<i32>(global_id.xy), i);
+            // Uncovered samples hold zero.
+            covered = covered || texel.g != 0u;
+        }
+
+        if (covered) {
+            atomicMax(&local_bounds[0], ~global_id.x);
+            atomicMax(&local_bounds[1], ~global_id.y);
+            atomicMax(&local_bounds[2], global_id.x + 1u);
+            atomicMax(&local_bounds[3], global_id.y + 1u);
+        }
+    }
+    workgroupBarrier();
+
+    if (local_index < 4u) {
+        let value = atomicLoad(&local_bounds[local_index]);
+        if (value != 0u) {
+            atomicMax(&bounds[local_index], value);
+        }
+    }
+}
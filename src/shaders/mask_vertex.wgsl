@@ -0,0 +1,74 @@
+// Bindings and helpers for vertex shaders drawing into the mask.
+//
+// Shaders importing this must first import `bevy_pbr::mesh_view_bindings` and
+// `bevy_pbr::mesh_types`. The mesh is bound to group 1 rather than group 2, so
+// `bevy_pbr::mesh_bindings` must not be imported. With `SKINNED`, the joint
+// matrices are bound alongside it and `skin_model` is imported from
+// `bevy_pbr::skinning`.
+
+#define_import_path outline::mask_vertex
+
+@group(1) @binding(0)
+var<uniform> mesh: Mesh;
+#ifdef SKINNED
+@group(1) @binding(1)
+var<uniform> joint_matrices: SkinnedMesh;
+#import bevy_pbr::skinning
+#endif
+
+// Must match `Dimensions` in outline::dimensions, which can't be imported
+// here as it is bound to group 0.
+struct Dimensions {
+    width: f32,
+    height: f32,
+    inv_width: f32,
+    inv_height: f32,
+    padding: f32,
+};
+
+@group(2) @binding(0)
+var<uniform> dims: Dimensions;
+
+// Must match `OutlineViewUniform`.
+struct ViewParams {
+    alpha: f32,
+    silhouette_alpha: f32,
+    edge_scale: f32,
+    time: f32,
+    // The camera's viewport as a fraction of its target: offset in XY, size
+    // in ZW.
+    viewport: vec4<f32>,
+    mapped_groups: array<vec4<u32>, 2>,
+    inverse_projection: mat4x4<f32>,
+    pixels_per_unit: f32,
+    // The offset of the view's jittered projection in normalized device
+    // coordinates.
+    jitter: vec2<f32>,
+    scale_factor: f32,
+};
+
+@group(2) @binding(2)
+var<uniform> view_params: ViewParams;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) world_position: vec3<f32>,
+#ifdef MASK_ALPHA_MASK
+    @location(1) uv: vec2<f32>,
+#endif
+};
+
+// Projects a world space position into the mask, shrinking the view to fit
+// inside the padding around it. Any jitter of the view's projection is
+// removed, so the mask stays put from frame to frame.
+fn mask_clip_position(world_position: vec4<f32>) -> vec4<f32> {
+    var clip_position = view.view_proj * world_position;
+    clip_position = vec4<f32>(
+        clip_position.xy - view_params.jitter * clip_position.w,
+        clip_position.zw,
+    );
+
+    let fb_size = vec2<f32>(dims.width, dims.height);
+    let screen_size = fb_size - 2.0 * dims.padding;
+    return vec4<f32>(clip_position.xy * screen_size / fb_size, clip_position.zw);
+}
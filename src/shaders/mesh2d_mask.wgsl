@@ -0,0 +1,77 @@
+// Mask generation shader for 2D meshes.
+
+#import bevy_sprite::mesh2d_view_bindings
+#import bevy_sprite::mesh2d_types
+
+// Bound to group 1 rather than group 2, as in `bevy_sprite::mesh2d_bindings`,
+// which must not be imported.
+@group(1) @binding(0)
+var<uniform> mesh: Mesh2d;
+
+// Must match `Dimensions` in outline::dimensions, which can't be imported
+// here as it is bound to group 0.
+struct Dimensions {
+    width: f32,
+    height: f32,
+    inv_width: f32,
+    inv_height: f32,
+    padding: f32,
+};
+
+@group(2) @binding(0)
+var<uniform> dims: Dimensions;
+
+// Must match `MaskUniform`.
+struct MaskUniform {
+    base_color: vec4<f32>,
+    emissive: vec4<f32>,
+    group: u32,
+    alpha_cutoff: f32,
+};
+
+@group(2) @binding(1)
+var<uniform> mask_uniform: MaskUniform;
+
+struct Vertex {
+    @location(0) position: vec3<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) world_position: vec3<f32>,
+};
+
+@vertex
+fn vertex(vertex: Vertex) -> VertexOutput {
+    var out: VertexOutput;
+    let world_position = mesh.model * vec4<f32>(vertex.position, 1.0);
+    let clip_position = view.view_proj * world_position;
+
+    // Shrink the view to fit inside the padding around the mask.
+    let fb_size = vec2<f32>(dims.width, dims.height);
+    let screen_size = fb_size - 2.0 * dims.padding;
+    out.clip_position = vec4<f32>(clip_position.xy * screen_size / fb_size, clip_position.zw);
+    out.world_position = world_position.xyz;
+    return out;
+}
+
+// Must match `FragmentOut` in the mesh mask shader.
+struct FragmentOut {
+    @location(0) mask: vec4<f32>,
+    @location(1) group: vec2<u32>,
+};
+
+@fragment
+fn fragment(in: VertexOutput) -> FragmentOut {
+    var out: FragmentOut;
+#ifdef MASK_COVERAGE_ONLY
+    out.mask = vec4<f32>(1.0, 0.0, 0.0, 1.0);
+#else
+    out.mask = vec4<f32>(mask_uniform.base_color.rgb, 1.0);
+#endif
+    // Uncovered samples hold zero, so the stored distance must be nonzero.
+    let distance = max(length(in.world_position - view.world_position), 0.001);
+    let packed_distance = pack2x16float(vec2<f32>(distance, 0.0)) & 0xffffu;
+    out.group = vec2<u32>(mask_uniform.group, packed_distance);
+    return out;
+}
@@ -1,52 +1,747 @@
 #import outline::fullscreen
 #import outline::dimensions
+#import outline::jfa_utils
 
 struct Params {
     color: vec4<f32>,
     // Outline weight in pixels.
     weight: f32,
+    // Bitwise combination of the OUTLINE_FLAG_* constants.
+    flags: u32,
+    // Scale applied to the outline weight along each axis.
+    axis_scale: vec2<f32>,
+    // Length of dashes in pixels, or zero for a solid outline.
+    dash_length: f32,
+    // Dash scrolling speed in pixels per second.
+    dash_speed: f32,
+    // Distance in pixels at which the glow fades out completely.
+    glow_radius: f32,
+    // Exponent of the glow falloff curve.
+    glow_exponent: f32,
+    // Fraction by which the pulsed properties are reduced at each trough.
+    pulse_amplitude: f32,
+    // Pulses per second.
+    pulse_frequency: f32,
+    // Offset of the pulses as a fraction of their period.
+    pulse_phase: f32,
+    // Color of the inner outline.
+    inner_color: vec4<f32>,
+    // Width of the inner outline in pixels.
+    inner_width: f32,
+    // One of the OUTLINE_FALLOFF_* constants.
+    falloff: u32,
+    // Rate of exponential falloff.
+    falloff_k: f32,
+    // One of the JFA_METRIC_* constants.
+    metric: u32,
+    // `OutlineColorSource` of the outline color. Only used on the CPU.
+    color_source: u32,
+    // Distance in pixels at which a band falloff starts fading.
+    band_start: f32,
+    // One of the OUTLINE_EASING_* constants.
+    band_easing: u32,
+    // Distances from the camera across which the outline fades out. Disabled
+    // unless `fade_end` is greater than `fade_start`.
+    fade_start: f32,
+    fade_end: f32,
+    // Width in pixels over which the outer edge fades out.
+    edge_width: f32,
 };
 
+// Must be kept in sync with `OutlineFlags`.
+let OUTLINE_FLAG_STENCIL_INTERIOR: u32 = 1u;
+let OUTLINE_FLAG_GLOW: u32 = 2u;
+let OUTLINE_FLAG_PULSE_WIDTH: u32 = 4u;
+let OUTLINE_FLAG_PULSE_ALPHA: u32 = 8u;
+let OUTLINE_FLAG_INNER: u32 = 16u;
+let OUTLINE_FLAG_TEXTURED: u32 = 32u;
+let OUTLINE_FLAG_MATERIAL_COLOR: u32 = 64u;
+let OUTLINE_FLAG_WORLD_UNITS: u32 = 128u;
+let OUTLINE_FLAG_LOGICAL_PIXELS: u32 = 256u;
+
+// Must be kept in sync with `OutlineFalloff`.
+let OUTLINE_FALLOFF_HARD: u32 = 0u;
+let OUTLINE_FALLOFF_LINEAR: u32 = 1u;
+let OUTLINE_FALLOFF_SMOOTHSTEP: u32 = 2u;
+let OUTLINE_FALLOFF_EXPONENTIAL: u32 = 3u;
+let OUTLINE_FALLOFF_BAND: u32 = 4u;
+
+// Must be kept in sync with `OutlineEasing`.
+let OUTLINE_EASING_LINEAR: u32 = 0u;
+let OUTLINE_EASING_EASE_IN: u32 = 1u;
+let OUTLINE_EASING_EASE_OUT: u32 = 2u;
+let OUTLINE_EASING_EASE_IN_OUT: u32 = 3u;
+let OUTLINE_EASING_BOUNCE: u32 = 4u;
+
 @group(1) @binding(0)
 var jfa_buffer: texture_2d<f32>;
 @group(1) @binding(1)
 var mask_buffer: texture_2d<f32>;
 @group(1) @binding(2)
 var nearest_sampler: sampler;
+// Outline group of each mask sample.
+@group(1) @binding(3)
+#ifdef MASK_GROUP_MULTISAMPLED
+var mask_group: texture_multisampled_2d<u32>;
+#else
+var mask_group: texture_2d<u32>;
+#endif
 
 @group(2) @binding(0)
 var<uniform> params: Params;
+@group(2) @binding(1)
+var fill_texture: texture_2d<f32>;
+@group(2) @binding(2)
+var fill_sampler: sampler;
+
+struct ViewParams {
+    // Multiplier applied to the outline alpha.
+    alpha: f32,
+    // Alpha of the outline color drawn over outlined objects.
+    silhouette_alpha: f32,
+    // Multiplier applied to the edge width of the style.
+    edge_scale: f32,
+    // Time in seconds since startup.
+    time: f32,
+    // The camera's viewport as a fraction of its target: offset in XY, size
+    // in ZW.
+    viewport: vec4<f32>,
+    // Bitset of the outline groups with their own style.
+    mapped_groups: array<vec4<u32>, 2>,
+    // Inverse of the camera's projection.
+    inverse_projection: mat4x4<f32>,
+    // Physical pixels per world unit.
+    pixels_per_unit: f32,
+    // The offset of the view's jittered projection, unused here.
+    jitter: vec2<f32>,
+    // Physical pixels per logical pixel of the target.
+    scale_factor: f32,
+};
+
+@group(3) @binding(0)
+var<uniform> view_params: ViewParams;
+// Red channel multiplies the outline alpha.
+@group(3) @binding(1)
+var screen_mask: texture_2d<f32>;
+@group(3) @binding(2)
+var screen_mask_sampler: sampler;
+#ifdef OUTLINE_DEPTH_OCCLUSION
+// The main pass depth of the view's target.
+@group(3) @binding(3)
+#ifdef OUTLINE_DEPTH_OCCLUSION_MULTISAMPLED
+var scene_depth: texture_depth_multisampled_2d;
+#else
+var scene_depth: texture_depth_2d;
+#endif
+#endif
+
+// Must match `OutlineOcclusion`.
+let OUTLINE_OCCLUSION_ALWAYS: u32 = 0u;
+let OUTLINE_OCCLUSION_VISIBLE: u32 = 1u;
+let OUTLINE_OCCLUSION_OCCLUDED: u32 = 2u;
+
+// Set in the mask group of samples hidden behind other geometry.
+let MASK_GROUP_OCCLUDED: u32 = 256u;
+
+// Whether a matte is drawn, in which case `outline_color` returns the outline
+// alone, leaving the silhouette to `fragment`.
+#ifdef OUTLINE_MATTE
+let OUTLINE_IS_MATTE: bool = true;
+#else
+let OUTLINE_IS_MATTE: bool = false;
+#endif
 
 struct FragmentIn {
     @location(0) texcoord: vec2<f32>,
+    // The outline group drawn by this style plus one, or zero for all groups
+    // without their own style.
+    @location(1) @interpolate(flat) group_filter: u32,
+    // Whether the style is drawn where the nearest entity is visible,
+    // occluded or either.
+    @location(2) @interpolate(flat) occlusion_filter: u32,
 };
 
-@fragment
-fn fragment(in: FragmentIn) -> @location(0) vec4<f32> {
-    let fb_jfa_pos = textureSample(jfa_buffer, nearest_sampler, in.texcoord).xy;
+struct LayerVertexOut {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) texcoord: vec2<f32>,
+    @location(1) @interpolate(flat) group_filter: u32,
+    @location(2) @interpolate(flat) occlusion_filter: u32,
+};
+
+// Fullscreen vertex shader which passes the group filter, given in the low 16
+// bits of the instance index, and the occlusion filter, given in the high
+// bits, to the fragment shader.
+@vertex
+fn vertex_layer(
+    @builtin(vertex_index) idx: u32,
+    @builtin(instance_index) layer_filter: u32,
+) -> LayerVertexOut {
+    let v = fullscreen_vertex(idx);
+
+    var out: LayerVertexOut;
+    out.pos = v.pos;
+    out.texcoord = v.texcoord;
+    out.group_filter = layer_filter & 0xffffu;
+    out.occlusion_filter = layer_filter >> 16u;
+    return out;
+}
+
+// Returns the coverage held by a texel of the mask. Masks without colors hold
+// it in the R component rather than in alpha.
+fn mask_coverage(texel: vec4<f32>) -> f32 {
+#ifdef MASK_COVERAGE_ONLY
+    return texel.r;
+#else
+    return texel.a;
+#endif
+}
+
+// Returns the number of samples in each texel of the mask.
+fn mask_samples() -> i32 {
+#ifdef MASK_GROUP_MULTISAMPLED
+    return i32(textureNumSamples(mask_group));
+#else
+    return 1;
+#endif
+}
+
+// Returns the outline group of the entity covering the mask at `texcoord`.
+fn mask_group_at(texcoord: vec2<f32>) -> u32 {
+    let coord = vec2<i32>(texcoord * vec2<f32>(dims.width, dims.height));
+
+    // Uncovered samples hold zero, so this finds a covering entity's group
+    // unless the texel is covered by several groups.
+    var group = 0u;
+    for (var i = 0; i < mask_samples(); i += 1) {
+        group = max(group, textureLoad(mask_group, coord, i).r & 0xffu);
+    }
+    return group;
+}
+
+// Returns whether the entities covering the mask at `texcoord` are hidden
+// behind other geometry, i.e. whether every covered sample is occluded.
+fn mask_occluded_at(texcoord: vec2<f32>) -> bool {
+    let coord = vec2<i32>(texcoord * vec2<f32>(dims.width, dims.height));
+
+    var covered = false;
+    for (var i = 0; i < mask_samples(); i += 1) {
+        let texel = textureLoad(mask_group, coord, i);
+        // Uncovered samples hold zero.
+        if (texel.g != 0u) {
+            if ((texel.r & MASK_GROUP_OCCLUDED) == 0u) {
+                return false;
+            }
+            covered = true;
+        }
+    }
+    return covered;
+}
+
+fn group_is_mapped(group: u32) -> bool {
+    let word = view_params.mapped_groups[group / 128u][(group / 32u) % 4u];
+    return ((word >> (group % 32u)) & 1u) != 0u;
+}
+
+// Returns the distance from the camera of the entities covering the mask at
+// `texcoord`, taking the nearest if there are several.
+fn mask_distance_at(texcoord: vec2<f32>) -> f32 {
+    let coord = vec2<i32>(texcoord * vec2<f32>(dims.width, dims.height));
+
+    var distance = 0.0;
+    for (var i = 0; i < mask_samples(); i += 1) {
+        let packed = textureLoad(mask_group, coord, i).g;
+        // Uncovered samples hold zero.
+        if (packed != 0u) {
+            let sample_distance = unpack2x16float(packed).x;
+            if (distance == 0.0 || sample_distance < distance) {
+                distance = sample_distance;
+            }
+        }
+    }
+    return distance;
+}
+
+// Returns the framebuffer texcoord of the outlined entity nearest to the
+// fragment at screen texcoord `texcoord`: either the one covering it or the
+// one at its nearest seed.
+fn nearest_entity_texcoord(texcoord: vec2<f32>) -> vec2<f32> {
+    let fb_texcoord = screen_to_framebuffer(texcoord);
+    if (mask_coverage(textureSampleLevel(mask_buffer, nearest_sampler, fb_texcoord, 0.0)) <= 0.0) {
+        return jfa_decode(textureSampleLevel(jfa_buffer, nearest_sampler, fb_texcoord, 0.0)).xy;
+    }
+    return fb_texcoord;
+}
+
+// Returns whether the style being drawn applies to the fragment at
+// `texcoord`.
+fn group_filter_passes(texcoord: vec2<f32>, group_filter: u32) -> bool {
+    let any_mapped = any(view_params.mapped_groups[0] != vec4<u32>(0u))
+        || any(view_params.mapped_groups[1] != vec4<u32>(0u));
+    if (group_filter == 0u && !any_mapped) {
+        return true;
+    }
+
+    let group = mask_group_at(nearest_entity_texcoord(texcoord));
+    if (group_filter == 0u) {
+        return !group_is_mapped(group);
+    }
+    return group == group_filter - 1u;
+}
+
+// Returns whether the style being drawn applies to the fragment at `texcoord`
+// given the visibility of its nearest outlined entity.
+fn occlusion_filter_passes(texcoord: vec2<f32>, occlusion_filter: u32) -> bool {
+    if (occlusion_filter == OUTLINE_OCCLUSION_ALWAYS) {
+        return true;
+    }
+
+    let occluded = mask_occluded_at(nearest_entity_texcoord(texcoord));
+    return occluded == (occlusion_filter == OUTLINE_OCCLUSION_OCCLUDED);
+}
+
+#ifdef OUTLINE_DEPTH_OCCLUSION
+// Returns whether the geometry in the depth buffer at `texcoord` is nearer to
+// the camera than the fragment's nearest outlined entity.
+fn depth_occluded(texcoord: vec2<f32>) -> bool {
+    let target_size = vec2<f32>(textureDimensions(scene_depth));
+    let pixel = (view_params.viewport.xy + texcoord * view_params.viewport.zw) * target_size;
+    let coord = clamp(vec2<i32>(pixel), vec2<i32>(0), vec2<i32>(target_size) - 1);
+
+    // Depth is reversed, so zero is the far plane, where nothing was drawn.
+    let depth = textureLoad(scene_depth, coord, 0);
+    if (depth <= 0.0) {
+        return false;
+    }
+
+    let ndc = vec2<f32>(texcoord.x * 2.0 - 1.0, 1.0 - texcoord.y * 2.0);
+    let view_position = view_params.inverse_projection * vec4<f32>(ndc, depth, 1.0);
+    let scene_distance = length(view_position.xyz / view_position.w);
+
+    // The mask stores distances at half precision, so a small tolerance keeps
+    // entities from occluding their own outlines.
+    let entity_distance = mask_distance_at(nearest_entity_texcoord(texcoord));
+    return entity_distance > 0.0 && scene_distance < entity_distance * 0.99;
+}
+#endif
+
+// Returns the alpha multiplier for the fragment at `texcoord` from the
+// distance of its nearest outlined entity to the camera.
+fn distance_fade(texcoord: vec2<f32>) -> f32 {
+    if (params.fade_end <= params.fade_start) {
+        return 1.0;
+    }
+
+    let distance = mask_distance_at(nearest_entity_texcoord(texcoord));
+    return 1.0 - smoothstep(params.fade_start, params.fade_end, distance);
+}
+
+// Returns the scale applied to the properties pulsed by `flag` at the current
+// time, between `1 - pulse_amplitude` at each trough and one at each peak.
+fn pulse_scale(flag: u32) -> f32 {
+    if ((params.flags & flag) == 0u) {
+        return 1.0;
+    }
+
+    let cycles = view_params.time * params.pulse_frequency + params.pulse_phase;
+    return 1.0 - params.pulse_amplitude * (0.5 - 0.5 * cos(6.2831855 * fract(cycles)));
+}
+
+// Returns the alpha of the dash pattern at `pix_coord`, with `unit` pixels per
+// unit of the dash length.
+fn dash_alpha(pix_coord: vec2<f32>, unit: f32) -> f32 {
+    if (params.dash_length <= 0.0) {
+        return 1.0;
+    }
+
+    // Diagonal stripes, scrolling over time.
+    let period = 2.0 * params.dash_length;
+    let offset = (pix_coord.x + pix_coord.y) / unit - view_params.time * params.dash_speed;
+
+    // Signed distance to the nearest end of a dash, positive inside the dash.
+    let phase = fract((offset + 0.5 * params.dash_length) / period) * period - params.dash_length;
+    let dist = 0.5 * params.dash_length - abs(phase);
+
+    return clamp(dist * unit + 0.5, 0.0, 1.0);
+}
+
+// Returns the number of pixels per unit of the style's lengths.
+fn style_unit() -> f32 {
+    if ((params.flags & OUTLINE_FLAG_WORLD_UNITS) != 0u) {
+        return view_params.pixels_per_unit;
+    }
+    if ((params.flags & OUTLINE_FLAG_LOGICAL_PIXELS) != 0u) {
+        return view_params.scale_factor;
+    }
+    return 1.0;
+}
+
+// Returns the color of the entity covering `texcoord` in the mask, or the style
+// color if the style doesn't use material colors or the texel is uncovered.
+fn mask_color(texcoord: vec2<f32>, style_color: vec3<f32>) -> vec3<f32> {
+    if ((params.flags & OUTLINE_FLAG_MATERIAL_COLOR) == 0u) {
+        return style_color;
+    }
+
+#ifdef MASK_COVERAGE_ONLY
+    return style_color;
+#else
+    let texel = textureSampleLevel(mask_buffer, nearest_sampler, texcoord, 0.0);
+    if (texel.a <= 0.0) {
+        return style_color;
+    }
+
+    // The mask color is premultiplied by coverage.
+    return texel.rgb / texel.a;
+#endif
+}
+
+// Applies one of the OUTLINE_EASING_* curves to `t`. Must be kept in sync with
+// `OutlineEasing::ease`.
+fn ease(t: f32, easing: u32) -> f32 {
+    if (easing == OUTLINE_EASING_EASE_IN) {
+        return t * t;
+    } else if (easing == OUTLINE_EASING_EASE_OUT) {
+        return t * (2.0 - t);
+    } else if (easing == OUTLINE_EASING_EASE_IN_OUT) {
+        return t * t * (3.0 - 2.0 * t);
+    } else if (easing == OUTLINE_EASING_BOUNCE) {
+        let n = 7.5625;
+        let d = 2.75;
+        if (t < 1.0 / d) {
+            return n * t * t;
+        } else if (t < 2.0 / d) {
+            let u = t - 1.5 / d;
+            return n * u * u + 0.75;
+        } else if (t < 2.5 / d) {
+            let u = t - 2.25 / d;
+            return n * u * u + 0.9375;
+        }
+        let u = t - 2.625 / d;
+        return n * u * u + 0.984375;
+    }
+
+    // OUTLINE_EASING_LINEAR
+    return t;
+}
+
+// The size in pixels of a texel of the reduced-resolution distance field.
+#ifdef OUTLINE_QUARTER_RESOLUTION
+let JFA_TEXEL_SIZE: f32 = 4.0;
+#else
+let JFA_TEXEL_SIZE: f32 = 2.0;
+#endif
+
+#ifdef OUTLINE_UPSAMPLE_EDGE_AWARE
+// Returns the framebuffer texcoord of the mask texel nearest to `pix_coord`
+// among the block of two by two JFA texels around the reduced-resolution seed
+// `seed`, considering covered texels if `covered` is true and uncovered ones
+// otherwise, or `seed` if there are none.
+fn refine_seed(pix_coord: vec2<f32>, seed: vec2<f32>, covered: bool) -> vec2<f32> {
     let fb_to_pix = vec2<f32>(dims.width, dims.height);
+    let base = vec2<i32>(floor(seed * fb_to_pix - (JFA_TEXEL_SIZE - 0.5)));
+    let max_coord = vec2<i32>(fb_to_pix) - 1;
+    let window = 2 * i32(JFA_TEXEL_SIZE);
+
+    var nearest = seed;
+    var nearest_dist = 0x1.FFFFFp127;
+    for (var y = 0; y < window; y += 1) {
+        for (var x = 0; x < window; x += 1) {
+            let coord = clamp(base + vec2<i32>(x, y), vec2<i32>(0), max_coord);
+            if ((mask_coverage(textureLoad(mask_buffer, coord, 0)) >= 0.5) == covered) {
+                let center = vec2<f32>(coord) + 0.5;
+                let delta = (pix_coord - center) / params.axis_scale;
+                let dist = jfa_distance(delta, params.metric);
+                if (dist < nearest_dist) {
+                    nearest = center / fb_to_pix;
+                    nearest_dist = dist;
+                }
+            }
+        }
+    }
+    return nearest;
+}
+#endif
+
+#ifdef OUTLINE_UPSAMPLE_BILINEAR
+// Returns the distance in pixels from `pix_coord` to the nearest seed,
+// interpolated bilinearly between the texel centers of the reduced-resolution
+// distance field, or `fallback` if any of them has no seed. Seeds inside the
+// silhouette are used if `inner` is true, and outside it otherwise.
+fn bilinear_seed_distance(pix_coord: vec2<f32>, inner: bool, fallback: f32) -> f32 {
+    let fb_to_pix = vec2<f32>(dims.width, dims.height);
+
+    // Texels of the field are centered halfway across each block of pixels.
+    let half_texel = JFA_TEXEL_SIZE / 2.0;
+    let base = floor((pix_coord - half_texel) / JFA_TEXEL_SIZE) * JFA_TEXEL_SIZE + half_texel;
+    let t = (pix_coord - base) / JFA_TEXEL_SIZE;
+
+    var dists = vec4<f32>(0.0);
+    for (var i = 0; i < 4; i += 1) {
+        let corner = base + JFA_TEXEL_SIZE * vec2<f32>(f32(i & 1), f32(i >> 1u));
+        let texel = jfa_decode(textureSampleLevel(jfa_buffer, nearest_sampler, corner / fb_to_pix, 0.0));
+        let seed = select(texel.xy, texel.zw, inner);
+        if (!jfa_has_seed(seed)) {
+            return fallback;
+        }
+        dists[i] = jfa_distance((corner - seed * fb_to_pix) / params.axis_scale, params.metric);
+    }
+    return mix(mix(dists[0], dists[1], t.x), mix(dists[2], dists[3], t.x), t.y);
+}
+#endif
+
+// Returns the distance in pixels from `pix_coord` to its nearest seed `seed`,
+// which is outside the silhouette if `inner` is true and inside it otherwise,
+// upsampled with the view's filter at reduced resolutions.
+fn seed_distance(pix_coord: vec2<f32>, seed: vec2<f32>, inner: bool) -> f32 {
+    let fb_to_pix = vec2<f32>(dims.width, dims.height);
+
+    var nearest = seed;
+#ifdef OUTLINE_UPSAMPLE_EDGE_AWARE
+    if (jfa_has_seed(seed)) {
+        nearest = refine_seed(pix_coord, seed, !inner);
+    }
+#endif
+
+    let dist = jfa_distance((pix_coord - nearest * fb_to_pix) / params.axis_scale, params.metric);
+#ifdef OUTLINE_UPSAMPLE_BILINEAR
+    return bilinear_seed_distance(pix_coord, inner, dist);
+#else
+    return dist;
+#endif
+}
 
-    let mask_value = textureSample(mask_buffer, nearest_sampler, in.texcoord).r;
+// Returns the alpha of a solid outline of the given weight at `mag` pixels
+// from the silhouette, excluding antialiasing. Band distances are scaled by
+// `weight_scale`, the pulse scale of the weight.
+fn falloff_alpha(mag: f32, weight: f32, weight_scale: f32) -> f32 {
+    let t = clamp(mag / max(weight, 0.0001), 0.0, 1.0);
+
+    if (params.falloff == OUTLINE_FALLOFF_LINEAR) {
+        return 1.0 - t;
+    } else if (params.falloff == OUTLINE_FALLOFF_SMOOTHSTEP) {
+        return 1.0 - smoothstep(0.0, 1.0, t);
+    } else if (params.falloff == OUTLINE_FALLOFF_EXPONENTIAL) {
+        return exp(-params.falloff_k * t);
+    } else if (params.falloff == OUTLINE_FALLOFF_BAND) {
+        let band_start = params.band_start * weight_scale;
+        let band = max(weight - band_start, 0.0001);
+        let s = clamp((mag - band_start) / band, 0.0, 1.0);
+        return 1.0 - ease(s, params.band_easing);
+    }
+
+    // OUTLINE_FALLOFF_HARD
+    return 1.0;
+}
+
+fn outline_color(in: FragmentIn) -> vec4<f32> {
+    let texcoord = screen_to_framebuffer(in.texcoord);
+    let jfa_texel = jfa_decode(textureSample(jfa_buffer, nearest_sampler, texcoord));
+    let fb_jfa_pos = jfa_texel.xy;
+    let fb_to_pix = vec2<f32>(dims.width, dims.height);
+
+    let mask_value = mask_coverage(textureSample(mask_buffer, nearest_sampler, texcoord));
 
     // Fragment position in pixel space.
-    let pix_coord = in.texcoord * fb_to_pix;
-    // Closest initial fragment in pixel space.
-    let pix_jfa_pos = fb_jfa_pos * fb_to_pix;
+    let pix_coord = texcoord * fb_to_pix;
+
+    // Distances are measured in pixels and converted to the style's units,
+    // while the antialiased edge is always `edge_pixels` pixels wide.
+    let unit = style_unit();
+    let edge_pixels = params.edge_width * view_params.edge_scale;
+    let edge_width = edge_pixels / unit;
+    let edge_offset = (0.5 * edge_pixels - 0.5) / unit;
+
+    // Pulses scale every width, but never the antialiased edge.
+    let width_scale = pulse_scale(OUTLINE_FLAG_PULSE_WIDTH);
+    let weight = params.weight * width_scale;
+
+    if ((params.flags & OUTLINE_FLAG_INNER) != 0u && mask_value > 0.0 && !OUTLINE_IS_MATTE) {
+        // Distance to the closest initial fragment outside the silhouette.
+        let inner_mag = seed_distance(pix_coord, jfa_texel.zw, true) / unit;
+        let inner_edge = params.inner_width * width_scale + edge_offset;
+        let inner_fade = clamp((inner_edge - inner_mag) / edge_width, 0.0, 1.0);
 
-    let delta = pix_coord - pix_jfa_pos;
-    let mag = sqrt(dot(delta, delta));
+        if (jfa_has_seed(jfa_texel.zw) && inner_fade > 0.0) {
+            // Partially covered fragments show the outer outline over the
+            // uncovered part.
+            let inner_alpha = mask_value * inner_fade;
+            let outer_alpha = 1.0 - mask_value;
+            let alpha = inner_alpha + outer_alpha;
+            let inner_color = mask_color(texcoord, params.inner_color.rgb);
+            let outer_color = mask_color(texcoord, params.color.rgb);
+            let color = (inner_color * inner_alpha + outer_color * outer_alpha) / alpha;
+            return vec4<f32>(color, alpha);
+        }
+    }
+
+    if ((params.flags & OUTLINE_FLAG_STENCIL_INTERIOR) != 0u && mask_value > 0.0
+        && !OUTLINE_IS_MATTE) {
+        discard;
+    }
+
+    let mag = seed_distance(pix_coord, fb_jfa_pos, false) / unit;
+
+    // Color of the outline, taken from the closest covered fragment when
+    // using material colors.
+    var color = params.color.rgb;
+    if (mask_value > 0.0) {
+        color = mask_color(texcoord, color);
+    } else if (jfa_has_seed(fb_jfa_pos)) {
+        color = mask_color(fb_jfa_pos, color);
+    }
 
     // Computed texcoord and stored texcoord are likely to differ even if they
     // represent the same position due to storage as fp16, so an epsilon is
     // needed.
     if (mask_value < 1.0) {
-        if (mask_value > 0.0) {
-            return vec4<f32>(params.color.rgb, 1.0 - mask_value);
+        if (mask_value > 0.0 && !OUTLINE_IS_MATTE) {
+            let alpha = 1.0 - mask_value + mask_value * view_params.silhouette_alpha;
+            return vec4<f32>(color, alpha);
+        } else if (!jfa_has_seed(fb_jfa_pos)) {
+            return vec4<f32>(0.0, 0.0, 0.0, 0.0);
+        } else if ((params.flags & OUTLINE_FLAG_GLOW) != 0u) {
+            let radius = max(params.glow_radius * width_scale, 0.0001);
+            let glow = pow(clamp(1.0 - mag / radius, 0.0, 1.0), params.glow_exponent);
+            return vec4<f32>(color, glow * dash_alpha(pix_coord, unit));
         } else {
-            let fade = clamp(params.weight - mag, 0.0, 1.0);
-            return vec4<f32>(params.color.rgb, fade);
+            // Fade out over `edge_pixels` pixels, centered half a pixel inside
+            // the outline weight.
+            let edge = weight + edge_offset;
+            let fade = clamp((edge - mag) / edge_width, 0.0, 1.0);
+            let alpha = fade * falloff_alpha(mag, weight, width_scale);
+            return vec4<f32>(color, alpha * dash_alpha(pix_coord, unit));
         }
     } else {
-        return vec4<f32>(0.0, 0.0, 0.0, 0.0);
+        return vec4<f32>(color, view_params.silhouette_alpha);
     }
 }
+
+@fragment
+fn fragment(in: FragmentIn) -> @location(0) vec4<f32> {
+    // Sampled first, as `outline_color` may discard.
+    let mask = textureSample(screen_mask, screen_mask_sampler, in.texcoord).r;
+
+#ifdef OUTLINE_MATTE
+    // The silhouette is always part of the matte, even if the style stencils
+    // it out or the view draws it translucent.
+    let mask_value = mask_coverage(
+        textureSample(mask_buffer, nearest_sampler, screen_to_framebuffer(in.texcoord)),
+    );
+    if (mask_value >= 1.0) {
+        let matte = mask * view_params.alpha;
+        return vec4<f32>(matte, matte, matte, 1.0);
+    }
+#endif
+
+    var color = outline_color(in);
+
+    if (!group_filter_passes(in.texcoord, in.group_filter)
+        || !occlusion_filter_passes(in.texcoord, in.occlusion_filter)) {
+        discard;
+    }
+
+#ifdef OUTLINE_DEPTH_OCCLUSION
+    if (depth_occluded(in.texcoord)) {
+        discard;
+    }
+#endif
+
+    color.a = color.a * distance_fade(in.texcoord) * pulse_scale(OUTLINE_FLAG_PULSE_ALPHA);
+
+    if ((params.flags & OUTLINE_FLAG_TEXTURED) != 0u) {
+        // Map the texture to the screen at its native resolution.
+        let fill_size = vec2<f32>(textureDimensions(fill_texture));
+        let screen_size = vec2<f32>(dims.width, dims.height) - 2.0 * dims.padding;
+        let fill_uv = in.texcoord * screen_size / fill_size;
+        color = color * textureSampleLevel(fill_texture, fill_sampler, fill_uv, 0.0);
+    }
+
+#ifdef OUTLINE_MATTE
+    // Partially covered texels blend the silhouette's coverage over the
+    // outline, antialiasing the edges of the matte.
+    let coverage = mask_value + (1.0 - mask_value) * color.a;
+    let matte = coverage * mask * view_params.alpha;
+    return vec4<f32>(matte, matte, matte, 1.0);
+#else
+    return vec4<f32>(color.rgb, color.a * mask * view_params.alpha);
+#endif
+}
+
+// Debug views, drawn opaquely in place of the outline. Must be kept in sync
+// with `OutlineDebugView`.
+
+@fragment
+fn fragment_debug_mask(in: FragmentIn) -> @location(0) vec4<f32> {
+    let texcoord = screen_to_framebuffer(in.texcoord);
+    let mask_value = mask_coverage(textureSampleLevel(mask_buffer, nearest_sampler, texcoord, 0.0));
+    return vec4<f32>(vec3<f32>(mask_value), 1.0);
+}
+
+@fragment
+fn fragment_debug_seeds(in: FragmentIn) -> @location(0) vec4<f32> {
+    let texcoord = screen_to_framebuffer(in.texcoord);
+    let seed = jfa_decode(textureSampleLevel(jfa_buffer, nearest_sampler, texcoord, 0.0)).xy;
+    if (!jfa_has_seed(seed)) {
+        return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+    }
+
+    // A texel is a seed if its nearest seed lies within it.
+    let fb_to_pix = vec2<f32>(dims.width, dims.height);
+    if (all(abs(seed - texcoord) * fb_to_pix < vec2<f32>(0.5))) {
+        return vec4<f32>(1.0, 1.0, 1.0, 1.0);
+    }
+    return vec4<f32>(seed, 0.0, 1.0);
+}
+
+// Maps `t` in [0, 1] to a color from blue through cyan, green and yellow to
+// red.
+fn heatmap(t: f32) -> vec3<f32> {
+    let x = 4.0 * t;
+    return clamp(vec3<f32>(x - 2.0, 2.0 - abs(x - 2.0), 2.0 - x), vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+@fragment
+fn fragment_debug_distance(in: FragmentIn) -> @location(0) vec4<f32> {
+    let texcoord = screen_to_framebuffer(in.texcoord);
+    let seed = jfa_decode(textureSampleLevel(jfa_buffer, nearest_sampler, texcoord, 0.0)).xy;
+    if (!jfa_has_seed(seed)) {
+        return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+    }
+
+    // Measured as in `outline_color`, so the contour matches the outline.
+    let fb_to_pix = vec2<f32>(dims.width, dims.height);
+    let delta = (texcoord - seed) * fb_to_pix / params.axis_scale;
+    let unit = style_unit();
+    let mag = jfa_distance(delta, params.metric) / unit;
+
+    let weight = max(params.weight, 1.0 / unit);
+    var color = heatmap(clamp(mag / (2.0 * weight), 0.0, 1.0));
+
+    // One pixel wide contour at the outline width.
+    let contour = 1.0 - clamp(abs(mag - params.weight) * unit, 0.0, 1.0);
+    color = mix(color, vec3<f32>(1.0), contour);
+    return vec4<f32>(color, 1.0);
+}
+
+// Returns an arbitrary color for the texel at integer coordinates `coord`.
+fn hash_color(coord: vec2<u32>) -> vec3<f32> {
+    var h = (coord.x * 73856093u) ^ (coord.y * 19349663u);
+    h = (h ^ (h >> 16u)) * 0x45d9f3bu;
+    h = h ^ (h >> 16u);
+    let bytes = vec3<u32>(h, h >> 8u, h >> 16u) & vec3<u32>(0xffu);
+    return vec3<f32>(bytes) / 255.0;
+}
+
+@fragment
+fn fragment_debug_voronoi(in: FragmentIn) -> @location(0) vec4<f32> {
+    let texcoord = screen_to_framebuffer(in.texcoord);
+    let seed = jfa_decode(textureSampleLevel(jfa_buffer, nearest_sampler, texcoord, 0.0)).xy;
+    if (!jfa_has_seed(seed)) {
+        return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+    }
+
+    // Seeds are stored at half precision, so they're identified by the texel
+    // containing them.
+    let seed_texel = vec2<u32>(max(seed * vec2<f32>(dims.width, dims.height), vec2<f32>(0.0)));
+    return vec4<f32>(hash_color(seed_texel), 1.0);
+}
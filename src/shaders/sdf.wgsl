@@ -0,0 +1,33 @@
+// Helpers for sampling JFA distance fields from user shaders.
+//
+// A JFA texture stores, for each texel, the texture-space coordinates of the
+// nearest seed. These helpers convert that into a distance in texels. Shaders
+// using them must be compiled with the shader defs of the app's
+// `JfaTextureFormat` and `JfaNoSeed`.
+
+#import outline::jfa_utils
+
+// Distance returned for texels with no nearest seed.
+let SDF_NO_SEED_DISTANCE: f32 = 0x1.FFFFFp127;
+
+// Returns the distance in texels between `texcoord` and `seed`, the value of
+// a JFA texel, in a texture of the given size.
+fn sdf_seed_distance(seed: vec2<f32>, texcoord: vec2<f32>, size: vec2<f32>) -> f32 {
+    if (!jfa_has_seed(seed)) {
+        return SDF_NO_SEED_DISTANCE;
+    }
+
+    let delta = (texcoord - seed) * size;
+    return sqrt(dot(delta, delta));
+}
+
+// Samples a JFA texture and returns the distance in texels between `texcoord`
+// and the nearest seed.
+//
+// `jfa_sampler` should be a non-filtering sampler, as interpolating between
+// seed coordinates is meaningless.
+fn sdf_sample_distance(jfa: texture_2d<f32>, jfa_sampler: sampler, texcoord: vec2<f32>) -> f32 {
+    let size = vec2<f32>(textureDimensions(jfa));
+    let seed = jfa_decode(textureSampleLevel(jfa, jfa_sampler, texcoord, 0.0)).xy;
+    return sdf_seed_distance(seed, texcoord, size);
+}
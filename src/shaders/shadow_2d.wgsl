@@ -0,0 +1,59 @@
+#import outline::fullscreen
+#import outline::sdf
+
+// Must match `ShadowLightUniform`.
+struct ShadowLight {
+    // The position of the light in map texture coordinates.
+    position: vec2<f32>,
+    // The reach of the light, in texels.
+    radius: f32,
+    softness: f32,
+};
+
+@group(0) @binding(0)
+var<uniform> light: ShadowLight;
+
+@group(1) @binding(0)
+var distance_field: texture_2d<f32>;
+@group(1) @binding(1)
+var distance_field_sampler: sampler;
+
+struct FragmentIn {
+    @location(0) texcoord: vec2<f32>,
+};
+
+let MAX_STEPS: i32 = 64;
+
+@fragment
+fn fragment(in: FragmentIn) -> @location(0) vec4<f32> {
+    let size = vec2<f32>(textureDimensions(distance_field));
+    let to_light = (light.position - in.texcoord) * size;
+    let light_dist = length(to_light);
+    if (light_dist > light.radius) {
+        return vec4<f32>(0.0);
+    }
+
+    // March towards the light through the distance field, stepping by the
+    // distance to the nearest occluder. The closer the ray passes to an
+    // occluder relative to its distance travelled, the deeper the penumbra.
+    let dir = to_light / max(light_dist, 0.0001);
+    var visibility = 1.0;
+    var t = 0.0;
+    for (var i: i32 = 0; i < MAX_STEPS; i = i + 1) {
+        if (t >= light_dist) {
+            break;
+        }
+
+        let uv = in.texcoord + dir * t / size;
+        let d = sdf_sample_distance(distance_field, distance_field_sampler, uv);
+        if (d < 0.5) {
+            visibility = 0.0;
+            break;
+        }
+
+        visibility = min(visibility, d / max(light.softness * t, 0.0001));
+        t = t + max(d, 1.0);
+    }
+
+    return vec4<f32>(clamp(visibility, 0.0, 1.0));
+}
@@ -0,0 +1,97 @@
+// Sprite mask generation shader.
+
+// Must match `ViewUniform` in bevy_render.
+struct View {
+    view_proj: mat4x4<f32>,
+    inverse_view_proj: mat4x4<f32>,
+    view: mat4x4<f32>,
+    inverse_view: mat4x4<f32>,
+    projection: mat4x4<f32>,
+    inverse_projection: mat4x4<f32>,
+    world_position: vec3<f32>,
+    width: f32,
+    height: f32,
+};
+
+@group(0) @binding(0)
+var<uniform> view: View;
+
+// Must match `Dimensions` in outline::dimensions, which can't be imported
+// here as it is bound to group 0.
+struct Dimensions {
+    width: f32,
+    height: f32,
+    inv_width: f32,
+    inv_height: f32,
+    padding: f32,
+};
+
+@group(1) @binding(0)
+var<uniform> dims: Dimensions;
+
+@group(2) @binding(0)
+var sprite_texture: texture_2d<f32>;
+@group(2) @binding(1)
+var sprite_sampler: sampler;
+
+// Sprite texels with a lower alpha are outside the silhouette.
+let SPRITE_ALPHA_CUTOFF: f32 = 0.5;
+
+struct Vertex {
+    @location(0) position: vec3<f32>,
+    @location(1) uv: vec2<f32>,
+    @location(2) color: vec4<f32>,
+    @location(3) group: u32,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) world_position: vec3<f32>,
+    @location(1) uv: vec2<f32>,
+    @location(2) color: vec4<f32>,
+    @location(3) @interpolate(flat) group: u32,
+};
+
+@vertex
+fn vertex(vertex: Vertex) -> VertexOutput {
+    var out: VertexOutput;
+    var clip_position = view.view_proj * vec4<f32>(vertex.position, 1.0);
+
+    // Shrink the view to fit inside the padding around the mask.
+    let fb_size = vec2<f32>(dims.width, dims.height);
+    let screen_size = fb_size - 2.0 * dims.padding;
+    clip_position = vec4<f32>(clip_position.xy * screen_size / fb_size, clip_position.zw);
+
+    out.clip_position = clip_position;
+    out.world_position = vertex.position;
+    out.uv = vertex.uv;
+    out.color = vertex.color;
+    out.group = vertex.group;
+    return out;
+}
+
+// Must match `FragmentOut` in the mesh mask shader.
+struct FragmentOut {
+    @location(0) mask: vec4<f32>,
+    @location(1) group: vec2<u32>,
+};
+
+@fragment
+fn fragment(in: VertexOutput) -> FragmentOut {
+    let color = textureSample(sprite_texture, sprite_sampler, in.uv) * in.color;
+    if (color.a < SPRITE_ALPHA_CUTOFF) {
+        discard;
+    }
+
+    var out: FragmentOut;
+#ifdef MASK_COVERAGE_ONLY
+    out.mask = vec4<f32>(1.0, 0.0, 0.0, 1.0);
+#else
+    out.mask = vec4<f32>(color.rgb, 1.0);
+#endif
+    // Uncovered samples hold zero, so the stored distance must be nonzero.
+    let distance = max(length(in.world_position - view.world_position), 0.001);
+    let packed_distance = pack2x16float(vec2<f32>(distance, 0.0)) & 0xffffu;
+    out.group = vec2<u32>(in.group, packed_distance);
+    return out;
+}
@@ -0,0 +1,584 @@
+use bevy::{
+    prelude::*,
+    reflect::TypeUuid,
+    render::{
+        main_graph,
+        render_asset::RenderAssets,
+        render_graph::{Node, NodeRunError, RenderGraph, RenderGraphContext},
+        render_phase::TrackedRenderPass,
+        render_resource::{
+            BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
+            BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource, BindingType,
+            BufferBindingType, CachedRenderPipelineId, ColorTargetState, ColorWrites,
+            DynamicUniformBuffer, Extent3d, FragmentState, LoadOp, MultisampleState, Operations,
+            PipelineCache, RenderPassColorAttachment, RenderPassDescriptor,
+            RenderPipelineDescriptor, SamplerBindingType, ShaderStages, ShaderType, TextureFormat,
+            TextureSampleType, TextureUsages, TextureViewDimension, VertexState,
+        },
+        renderer::{RenderContext, RenderDevice, RenderQueue},
+        Extract, RenderApp, RenderStage,
+    },
+    transform::TransformSystem,
+};
+
+use crate::{
+    jfa_request::JfaRequestNode, resources::OutlineResources, textures::placeholder_image,
+    JfaNoSeed, JfaRequest, JfaTextureFormat, FULLSCREEN_PRIMITIVE_STATE, FULLSCREEN_SHADER_HANDLE,
+};
+
+const SHADOW_2D_SHADER_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 14253609717294815034);
+
+const SHADOW_TEXTURE_FORMAT: TextureFormat = TextureFormat::R8Unorm;
+
+/// Plugin for 2D soft shadows cast by [`ShadowOccluder2d`]s.
+///
+/// Each frame, the occluders are drawn into the seed mask of the
+/// [`ShadowMap2d`], which is flooded by a [`JfaRequest`] to find the distance
+/// from each texel of the map to the nearest occluder. Each
+/// [`ShadowLight2d`] then has its shadows traced through the distance field
+/// into its shadow image, before any camera is rendered.
+///
+/// This must be added after the [`JfaPlugin`](crate::JfaPlugin), which runs
+/// the jump flood passes.
+#[derive(Default)]
+pub struct Shadow2dPlugin;
+
+impl Plugin for Shadow2dPlugin {
+    fn build(&self, app: &mut App) {
+        crate::init_jfa_texture_format(app);
+
+        let mut shaders = app.world.resource_mut::<Assets<Shader>>();
+        shaders.set_untracked(
+            SHADOW_2D_SHADER_HANDLE,
+            Shader::from_wgsl(include_str!("shaders/shadow_2d.wgsl")),
+        );
+
+        app.init_resource::<ShadowMap2d>()
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                draw_occluder_mask.after(TransformSystem::TransformPropagate),
+            )
+            .add_system_to_stage(CoreStage::PostUpdate, resize_shadow_images);
+
+        let render_app = match app.get_sub_app_mut(RenderApp) {
+            Ok(r) => r,
+            Err(_) => return,
+        };
+
+        render_app
+            .init_resource::<ShadowPipeline>()
+            .init_resource::<ShadowUniforms>()
+            .add_system_to_stage(RenderStage::Extract, extract_shadow_lights)
+            .add_system_to_stage(RenderStage::Prepare, prepare_shadow_uniforms)
+            .add_system_to_stage(RenderStage::Queue, queue_shadow_bind_groups);
+
+        let shadow_node = ShadowNode::new(&mut render_app.world);
+        let mut root_graph = render_app.world.resource_mut::<RenderGraph>();
+        root_graph.add_node(ShadowNode::NAME, shadow_node);
+        root_graph
+            .add_node_edge(JfaRequestNode::NAME, ShadowNode::NAME)
+            .unwrap();
+        root_graph
+            .add_node_edge(ShadowNode::NAME, main_graph::node::CAMERA_DRIVER)
+            .unwrap();
+    }
+}
+
+/// Resource describing the map in which 2D shadows are traced.
+///
+/// The map covers a rectangle of `size` world units centered on `center` in
+/// the XY plane. Row zero of its textures lies at the bottom of the map, so
+/// the texture coordinates of a world position are `(pos - min) / size`,
+/// where `min` is the bottom left corner.
+#[derive(Clone, Debug)]
+pub struct ShadowMap2d {
+    pub center: Vec2,
+    pub size: Vec2,
+    /// The size of the map's textures, in texels.
+    pub resolution: UVec2,
+    mask: Handle<Image>,
+    distance_field: Handle<Image>,
+    // The entity holding the `JfaRequest` for the map.
+    request: Entity,
+}
+
+impl FromWorld for ShadowMap2d {
+    fn from_world(world: &mut World) -> Self {
+        let jfa_format = world.resource::<JfaTextureFormat>().texture_format();
+        let mut images = world.resource_mut::<Assets<Image>>();
+        let mask = images.add(placeholder_image(TextureFormat::Rgba8Unorm));
+        let distance_field = images.add(placeholder_image(jfa_format));
+        let request = world
+            .spawn()
+            .insert(JfaRequest::new(mask.clone(), distance_field.clone(), 1))
+            .id();
+
+        ShadowMap2d {
+            center: Vec2::ZERO,
+            size: Vec2::splat(1000.0),
+            resolution: UVec2::splat(512),
+            mask,
+            distance_field,
+            request,
+        }
+    }
+}
+
+impl ShadowMap2d {
+    /// The seed mask, in `TextureFormat::Rgba8Unorm`, whose alpha holds the
+    /// coverage of the occluders.
+    pub fn mask(&self) -> &Handle<Image> {
+        &self.mask
+    }
+
+    /// The distance field of the mask, as described in [`JfaRequest`].
+    pub fn distance_field(&self) -> &Handle<Image> {
+        &self.distance_field
+    }
+
+    fn texels_per_unit(&self) -> Vec2 {
+        self.resolution.as_vec2() / self.size
+    }
+
+    /// Returns the texture coordinates of the world position `pos`.
+    fn texcoord(&self, pos: Vec2) -> Vec2 {
+        (pos - (self.center - 0.5 * self.size)) / self.size
+    }
+}
+
+/// The shape of a [`ShadowOccluder2d`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OccluderShape {
+    /// A rectangle of the given size, centered on the entity.
+    Rect(Vec2),
+    /// A circle of the given radius, centered on the entity.
+    Circle(f32),
+}
+
+/// Component for entities which cast 2D shadows.
+///
+/// The occluder is drawn into the map as its shape, transformed by the
+/// entity's `GlobalTransform`, rather than by the contents of any sprite or
+/// mesh on the entity.
+#[derive(Clone, Copy, Debug, PartialEq, Component)]
+pub struct ShadowOccluder2d {
+    pub shape: OccluderShape,
+}
+
+/// Component for 2D lights with soft shadows.
+///
+/// Each frame, `shadow` is filled with the visibility of the light from each
+/// texel of the [`ShadowMap2d`], in `TextureFormat::R8Unorm`: `1.0` where the
+/// light is unobstructed, `0.0` in full shadow or beyond `radius`, and values
+/// in between in the penumbra. Attenuation with distance is left to the
+/// shaders sampling the image.
+#[derive(Clone, Debug, Component)]
+pub struct ShadowLight2d {
+    /// The reach of the light, in world units.
+    pub radius: f32,
+    /// The width of the penumbra relative to the distance from the occluder.
+    /// Zero gives hard shadows.
+    pub softness: f32,
+    pub shadow: Handle<Image>,
+}
+
+impl ShadowLight2d {
+    /// Creates a light reaching `radius` world units, with a new shadow image
+    /// which is sized to match the shadow map.
+    pub fn new(images: &mut Assets<Image>, radius: f32) -> Self {
+        ShadowLight2d {
+            radius,
+            softness: 0.1,
+            shadow: images.add(placeholder_image(SHADOW_TEXTURE_FORMAT)),
+        }
+    }
+}
+
+/// Draws the occluders into the seed mask, and updates the map's `JfaRequest`.
+fn draw_occluder_mask(
+    map: Res<ShadowMap2d>,
+    mut images: ResMut<Assets<Image>>,
+    mut requests: Query<&mut JfaRequest>,
+    occluders: Query<(&GlobalTransform, &ShadowOccluder2d)>,
+    lights: Query<&ShadowLight2d>,
+) {
+    let resolution = map.resolution.max(UVec2::ONE);
+    let texels_per_unit = map.texels_per_unit();
+
+    // The flood must reach as far as the widest light, as shadows are traced
+    // by stepping through it.
+    if let Ok(mut request) = requests.get_mut(map.request) {
+        let radius = lights.iter().map(|light| light.radius).fold(0.0, f32::max);
+        let max_dist = (radius * texels_per_unit.max_element()).ceil() as u32;
+        if request.max_dist != max_dist {
+            request.max_dist = max_dist;
+        }
+    }
+
+    let mut data = vec![0; (resolution.x * resolution.y * 4) as usize];
+    for (transform, occluder) in occluders.iter() {
+        let (scale, rotation, translation) = transform.to_scale_rotation_translation();
+        let inverse_rotation = rotation.inverse();
+        let scale = Vec2::new(scale.x, scale.y);
+        let (half_extent, radius) = match occluder.shape {
+            OccluderShape::Rect(size) => (0.5 * size * scale, 0.0),
+            OccluderShape::Circle(radius) => (Vec2::ZERO, radius * scale.max_element()),
+        };
+
+        // The bounding circle of the occluder, in texels.
+        let center = map.texcoord(Vec2::new(translation.x, translation.y)) * resolution.as_vec2();
+        let bound = (half_extent.length() + radius) * texels_per_unit + 1.0;
+        let min = (center - bound).floor().max(Vec2::ZERO).as_uvec2();
+        let max = (center + bound).ceil().as_uvec2().min(resolution);
+
+        for y in min.y..max.y {
+            for x in min.x..max.x {
+                let texel_pos = (Vec2::new(x as f32, y as f32) + 0.5 - center) / texels_per_unit;
+                let local = inverse_rotation * texel_pos.extend(0.0);
+                let local = Vec2::new(local.x, local.y);
+
+                // The signed distance from the texel to the occluder's edge,
+                // in texels.
+                let q = local.abs() - half_extent;
+                let distance = q.max(Vec2::ZERO).length() + q.max_element().min(0.0) - radius;
+                let distance = distance * texels_per_unit.min_element();
+                let coverage = (0.5 - distance).clamp(0.0, 1.0);
+
+                let texel = ((y * resolution.x + x) * 4) as usize;
+                let alpha = (coverage * u8::MAX as f32).round() as u8;
+                data[texel..texel + 3].fill(u8::MAX);
+                data[texel + 3] = data[texel + 3].max(alpha);
+            }
+        }
+    }
+
+    // Only access the image mutably when necessary, as doing so causes it to
+    // be uploaded to the GPU again.
+    let size = Extent3d {
+        width: resolution.x,
+        height: resolution.y,
+        depth_or_array_layers: 1,
+    };
+    let up_to_date = images
+        .get(&map.mask)
+        .is_some_and(|image| image.texture_descriptor.size == size && image.data == data);
+    if !up_to_date {
+        if let Some(image) = images.get_mut(&map.mask) {
+            image.texture_descriptor.size = size;
+            image.data = data;
+        }
+    }
+}
+
+/// Resizes the shadow images of lights to match the map.
+fn resize_shadow_images(
+    map: Res<ShadowMap2d>,
+    mut images: ResMut<Assets<Image>>,
+    lights: Query<&ShadowLight2d>,
+) {
+    let resolution = map.resolution.max(UVec2::ONE);
+    let size = Extent3d {
+        width: resolution.x,
+        height: resolution.y,
+        depth_or_array_layers: 1,
+    };
+    let usage = TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING;
+
+    for light in lights.iter() {
+        let up_to_date = images.get(&light.shadow).is_some_and(|image| {
+            let desc = &image.texture_descriptor;
+            desc.size == size && desc.format == SHADOW_TEXTURE_FORMAT && desc.usage.contains(usage)
+        });
+
+        // Only access the image mutably when necessary, as doing so causes it
+        // to be uploaded to the GPU again.
+        if !up_to_date {
+            if let Some(image) = images.get_mut(&light.shadow) {
+                image.texture_descriptor.format = SHADOW_TEXTURE_FORMAT;
+                image.texture_descriptor.usage |= usage;
+                image.data.clear();
+                image.resize(size);
+            }
+        }
+    }
+}
+
+/// The distance field of the shadow map, extracted into the render world.
+struct ExtractedShadowMap {
+    distance_field: Handle<Image>,
+}
+
+#[derive(Component)]
+struct ExtractedShadowLight {
+    uniform: ShadowLightUniform,
+    shadow: Handle<Image>,
+}
+
+fn extract_shadow_lights(
+    mut commands: Commands,
+    mut previous_len: Local<usize>,
+    map: Extract<Res<ShadowMap2d>>,
+    lights: Extract<Query<(Entity, &GlobalTransform, &ShadowLight2d)>>,
+) {
+    commands.insert_resource(ExtractedShadowMap {
+        distance_field: map.distance_field.clone(),
+    });
+
+    let texels_per_unit = map.texels_per_unit();
+    let mut batches = Vec::with_capacity(*previous_len);
+    for (entity, transform, light) in lights.iter() {
+        let position = transform.translation();
+        let uniform = ShadowLightUniform {
+            position: map.texcoord(Vec2::new(position.x, position.y)),
+            radius: light.radius * texels_per_unit.max_element(),
+            softness: light.softness,
+        };
+        batches.push((
+            entity,
+            (ExtractedShadowLight {
+                uniform,
+                shadow: light.shadow.clone(),
+            },),
+        ));
+    }
+    *previous_len = batches.len();
+    commands.insert_or_spawn_batch(batches);
+}
+
+#[derive(Clone, ShaderType)]
+struct ShadowLightUniform {
+    position: Vec2,
+    radius: f32,
+    softness: f32,
+}
+
+#[derive(Default)]
+struct ShadowUniforms {
+    uniforms: DynamicUniformBuffer<ShadowLightUniform>,
+    bind_group: Option<BindGroup>,
+    distance_field_bind_group: Option<BindGroup>,
+}
+
+#[derive(Component)]
+struct ShadowLightUniformOffset {
+    offset: u32,
+}
+
+fn prepare_shadow_uniforms(
+    mut commands: Commands,
+    device: Res<RenderDevice>,
+    queue: Res<RenderQueue>,
+    mut shadow_uniforms: ResMut<ShadowUniforms>,
+    lights: Query<(Entity, &ExtractedShadowLight)>,
+) {
+    shadow_uniforms.uniforms.clear();
+    for (entity, light) in lights.iter() {
+        let offset = shadow_uniforms.uniforms.push(light.uniform.clone());
+        commands
+            .entity(entity)
+            .insert(ShadowLightUniformOffset { offset });
+    }
+    shadow_uniforms.uniforms.write_buffer(&device, &queue);
+}
+
+fn queue_shadow_bind_groups(
+    device: Res<RenderDevice>,
+    res: Res<OutlineResources>,
+    pipeline: Res<ShadowPipeline>,
+    images: Res<RenderAssets<Image>>,
+    map: Res<ExtractedShadowMap>,
+    mut shadow_uniforms: ResMut<ShadowUniforms>,
+) {
+    let shadow_uniforms = &mut *shadow_uniforms;
+    shadow_uniforms.bind_group = shadow_uniforms.uniforms.binding().map(|binding| {
+        device.create_bind_group(&BindGroupDescriptor {
+            label: Some("shadow_2d_uniform_bind_group"),
+            layout: &pipeline.uniform_layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: binding,
+            }],
+        })
+    });
+
+    let distance_field = images.get(&map.distance_field);
+    shadow_uniforms.distance_field_bind_group = distance_field.map(|distance_field| {
+        device.create_bind_group(&BindGroupDescriptor {
+            label: Some("shadow_2d_distance_field_bind_group"),
+            layout: &pipeline.distance_field_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(&distance_field.texture_view),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::Sampler(&res.sampler),
+                },
+            ],
+        })
+    });
+}
+
+struct ShadowPipeline {
+    uniform_layout: BindGroupLayout,
+    distance_field_layout: BindGroupLayout,
+    pipeline_id: CachedRenderPipelineId,
+}
+
+impl FromWorld for ShadowPipeline {
+    fn from_world(world: &mut World) -> Self {
+        let device = world.resource::<RenderDevice>();
+
+        let uniform_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("shadow_2d_uniform_bind_group_layout"),
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: true,
+                    min_binding_size: Some(ShadowLightUniform::min_size()),
+                },
+                count: None,
+            }],
+        });
+
+        let distance_field_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("shadow_2d_distance_field_bind_group_layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: false },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(SamplerBindingType::NonFiltering),
+                    count: None,
+                },
+            ],
+        });
+
+        let jfa_format = *world.resource::<JfaTextureFormat>();
+        let jfa_no_seed = *world.resource::<JfaNoSeed>();
+        let mut pipeline_cache = world.resource_mut::<PipelineCache>();
+        let pipeline_id = pipeline_cache.queue_render_pipeline(RenderPipelineDescriptor {
+            label: Some("shadow_2d_pipeline".into()),
+            layout: Some(vec![uniform_layout.clone(), distance_field_layout.clone()]),
+            vertex: VertexState {
+                shader: FULLSCREEN_SHADER_HANDLE.typed::<Shader>(),
+                shader_defs: vec![],
+                entry_point: "vertex".into(),
+                buffers: vec![],
+            },
+            fragment: Some(FragmentState {
+                shader: SHADOW_2D_SHADER_HANDLE.typed::<Shader>(),
+                shader_defs: [jfa_format.shader_defs(), jfa_no_seed.shader_defs()].concat(),
+                entry_point: "fragment".into(),
+                targets: vec![Some(ColorTargetState {
+                    format: SHADOW_TEXTURE_FORMAT,
+                    blend: None,
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+            primitive: FULLSCREEN_PRIMITIVE_STATE,
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+        });
+
+        ShadowPipeline {
+            uniform_layout,
+            distance_field_layout,
+            pipeline_id,
+        }
+    }
+}
+
+/// Render graph node which traces the shadows of all [`ShadowLight2d`]s.
+///
+/// This runs in the main render graph, after the jump flood requests and
+/// before the camera driver.
+struct ShadowNode {
+    query: QueryState<(
+        &'static ExtractedShadowLight,
+        &'static ShadowLightUniformOffset,
+    )>,
+}
+
+impl ShadowNode {
+    const NAME: &'static str = "shadow_2d";
+
+    fn new(world: &mut World) -> Self {
+        ShadowNode {
+            query: QueryState::new(world),
+        }
+    }
+}
+
+impl Node for ShadowNode {
+    fn update(&mut self, world: &mut World) {
+        self.query.update_archetypes(world);
+    }
+
+    fn run(
+        &self,
+        _graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let shadow_uniforms = world.resource::<ShadowUniforms>();
+        let (bind_group, distance_field_bind_group) = match (
+            &shadow_uniforms.bind_group,
+            &shadow_uniforms.distance_field_bind_group,
+        ) {
+            (Some(b), Some(d)) => (b, d),
+            _ => return Ok(()),
+        };
+
+        let pipeline_cache = world.resource::<PipelineCache>();
+        let pipeline_id = world.resource::<ShadowPipeline>().pipeline_id;
+        let pipeline = match pipeline_cache.get_render_pipeline(pipeline_id) {
+            Some(p) => p,
+            None => return Ok(()),
+        };
+
+        let images = world.resource::<RenderAssets<Image>>();
+        for (light, offset) in self.query.iter_manual(world) {
+            let shadow = match images.get(&light.shadow) {
+                Some(s) if s.texture_format == SHADOW_TEXTURE_FORMAT => s,
+                _ => continue,
+            };
+
+            let render_pass =
+                render_context
+                    .command_encoder
+                    .begin_render_pass(&RenderPassDescriptor {
+                        label: Some("shadow_2d"),
+                        color_attachments: &[Some(RenderPassColorAttachment {
+                            view: &shadow.texture_view,
+                            resolve_target: None,
+                            ops: Operations {
+                                load: LoadOp::Load,
+                                store: true,
+                            },
+                        })],
+                        depth_stencil_attachment: None,
+                    });
+            let mut tracked_pass = TrackedRenderPass::new(render_pass);
+            tracked_pass.set_render_pipeline(pipeline);
+            tracked_pass.set_bind_group(0, bind_group, &[offset.offset]);
+            tracked_pass.set_bind_group(1, distance_field_bind_group, &[]);
+            tracked_pass.draw(0..3, 0..1);
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,711 @@
+use std::ops::Range;
+
+use bevy::{
+    core::{Pod, Zeroable},
+    core_pipeline::core_2d,
+    prelude::*,
+    reflect::TypeUuid,
+    render::{
+        render_asset::RenderAssets,
+        render_graph::{Node, NodeRunError, RenderGraph, RenderGraphContext, SlotInfo, SlotType},
+        render_phase::TrackedRenderPass,
+        render_resource::{
+            BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
+            BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource, BindingType,
+            BlendState, BufferBindingType, BufferUsages, BufferVec, CachedRenderPipelineId,
+            ColorTargetState, ColorWrites, FragmentState, MultisampleState, PipelineCache,
+            PrimitiveState, RenderPassDescriptor, RenderPipelineDescriptor, SamplerBindingType,
+            ShaderStages, ShaderType, SpecializedRenderPipeline, SpecializedRenderPipelines,
+            TextureSampleType, TextureViewDimension, VertexBufferLayout, VertexFormat, VertexState,
+            VertexStepMode,
+        },
+        renderer::{RenderContext, RenderDevice, RenderQueue},
+        view::{Msaa, ViewUniform, ViewUniformOffset, ViewUniforms, VisibleEntities},
+        Extract, RenderApp, RenderStage,
+    },
+    sprite::{Rect, Sprite, TextureAtlas, TextureAtlasSprite},
+    utils::{HashMap, HashSet},
+};
+
+use crate::{
+    graph::{self, OutlineDriverNode},
+    mask::{self, MaskColors, MeshMaskNode, MASK_GROUP_TEXTURE_FORMAT},
+    mesh2d,
+    resources::{OutlineResources, ViewOutlineResources},
+    reuse, Outline, OutlineGroup,
+};
+
+const SPRITE_MASK_SHADER_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 2815902468338512243);
+
+/// Adds outlines for sprites to the core 2D graph.
+///
+/// Entities with a `Sprite` or `TextureAtlasSprite` and an enabled [`Outline`]
+/// are drawn into the mask of each outlined 2D camera. Texels with an alpha
+/// below one half are outside the sprite's silhouette, so the outline follows
+/// the sprite's visible shape rather than its quad.
+pub(crate) fn add_sprite_outlines(app: &mut App) {
+    let mut shaders = app.world.resource_mut::<Assets<Shader>>();
+    shaders.set_untracked(
+        SPRITE_MASK_SHADER_HANDLE,
+        Shader::from_wgsl(include_str!("shaders/sprite_mask.wgsl")),
+    );
+
+    let render_app = match app.get_sub_app_mut(RenderApp) {
+        Ok(r) => r,
+        Err(_) => return,
+    };
+
+    render_app
+        .init_resource::<SpriteMaskPipeline>()
+        .init_resource::<SpecializedRenderPipelines<SpriteMaskPipeline>>()
+        .init_resource::<ExtractedSpriteMasks>()
+        .init_resource::<SpriteMaskBatches>()
+        .add_system_to_stage(RenderStage::Extract, extract_sprite_masks)
+        .add_system_to_stage(RenderStage::Queue, queue_sprite_masks);
+
+    let mask_node = SpriteMaskNode::new(&mut render_app.world);
+    let outline_graph =
+        graph::build_outline_graph_with_mask(&mut render_app.world, mask_node).unwrap();
+
+    let mut root_graph = render_app.world.resource_mut::<RenderGraph>();
+    let draw_2d_graph = match root_graph.get_sub_graph_mut(core_2d::graph::NAME) {
+        Some(g) => g,
+        None => return,
+    };
+    let draw_2d_input = draw_2d_graph.input_node().unwrap().id;
+
+    draw_2d_graph.add_sub_graph(graph::outline::NAME, outline_graph);
+    let outline_driver = draw_2d_graph.add_node(OutlineDriverNode::NAME, OutlineDriverNode);
+    draw_2d_graph
+        .add_slot_edge(
+            draw_2d_input,
+            core_2d::graph::input::VIEW_ENTITY,
+            outline_driver,
+            OutlineDriverNode::INPUT_VIEW,
+        )
+        .unwrap();
+    draw_2d_graph
+        .add_node_edge(core_2d::graph::node::MAIN_PASS, outline_driver)
+        .unwrap();
+}
+
+pub struct SpriteMaskPipeline {
+    view_layout: BindGroupLayout,
+    dimensions_layout: BindGroupLayout,
+    texture_layout: BindGroupLayout,
+}
+
+impl FromWorld for SpriteMaskPipeline {
+    fn from_world(world: &mut World) -> Self {
+        let dimensions_layout = world
+            .resource::<OutlineResources>()
+            .dimensions_bind_group_layout
+            .clone();
+
+        let device = world.resource::<RenderDevice>();
+        let view_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("outline_sprite_mask_view_bind_group_layout"),
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::VERTEX_FRAGMENT,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: true,
+                    min_binding_size: Some(ViewUniform::min_size()),
+                },
+                count: None,
+            }],
+        });
+        let texture_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("outline_sprite_mask_texture_bind_group_layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: true },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+
+        SpriteMaskPipeline {
+            view_layout,
+            dimensions_layout,
+            texture_layout,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SpriteMaskPipelineKey {
+    /// The sample count of the mask.
+    pub samples: u32,
+    /// Whether the mask holds colors rather than only coverage.
+    pub mask_colors: bool,
+}
+
+impl SpecializedRenderPipeline for SpriteMaskPipeline {
+    type Key = SpriteMaskPipelineKey;
+
+    fn specialize(&self, key: Self::Key) -> RenderPipelineDescriptor {
+        RenderPipelineDescriptor {
+            label: Some("outline_sprite_mask_pipeline".into()),
+            layout: Some(vec![
+                self.view_layout.clone(),
+                self.dimensions_layout.clone(),
+                self.texture_layout.clone(),
+            ]),
+            vertex: VertexState {
+                shader: SPRITE_MASK_SHADER_HANDLE.typed::<Shader>(),
+                shader_defs: vec![],
+                entry_point: "vertex".into(),
+                buffers: vec![VertexBufferLayout::from_vertex_formats(
+                    VertexStepMode::Vertex,
+                    [
+                        VertexFormat::Float32x3,
+                        VertexFormat::Float32x2,
+                        VertexFormat::Float32x4,
+                        VertexFormat::Uint32,
+                    ],
+                )],
+            },
+            fragment: Some(FragmentState {
+                shader: SPRITE_MASK_SHADER_HANDLE.typed::<Shader>(),
+                shader_defs: mask::mask_shader_defs(key.mask_colors),
+                entry_point: "fragment".into(),
+                targets: vec![
+                    Some(ColorTargetState {
+                        format: mask::mask_texture_format(key.mask_colors),
+                        blend: Some(BlendState::REPLACE),
+                        write_mask: ColorWrites::ALL,
+                    }),
+                    Some(ColorTargetState {
+                        format: MASK_GROUP_TEXTURE_FORMAT,
+                        blend: None,
+                        write_mask: ColorWrites::ALL,
+                    }),
+                ],
+            }),
+            primitive: PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: MultisampleState {
+                count: key.samples,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+        }
+    }
+}
+
+/// A textured quad to draw into the mask.
+pub(crate) struct ExtractedSpriteMask {
+    pub(crate) entity: Entity,
+    pub(crate) transform: GlobalTransform,
+    pub(crate) color: Color,
+    // The region of the image to draw, or `None` for the whole image.
+    pub(crate) rect: Option<Rect>,
+    pub(crate) custom_size: Option<Vec2>,
+    pub(crate) anchor: Vec2,
+    pub(crate) flip_x: bool,
+    pub(crate) flip_y: bool,
+    // The world-space rectangle the quad is clipped to, if any.
+    pub(crate) clip: Option<Rect>,
+    pub(crate) image: Handle<Image>,
+    pub(crate) group: u32,
+}
+
+/// The outlined sprites extracted this frame.
+#[derive(Default)]
+pub struct ExtractedSpriteMasks {
+    sprites: Vec<ExtractedSpriteMask>,
+}
+
+fn extract_sprite_masks(
+    mut extracted: ResMut<ExtractedSpriteMasks>,
+    atlases: Extract<Res<Assets<TextureAtlas>>>,
+    sprites: Extract<
+        Query<(
+            Entity,
+            &ComputedVisibility,
+            &Outline,
+            &Sprite,
+            &GlobalTransform,
+            &Handle<Image>,
+            Option<&OutlineGroup>,
+        )>,
+    >,
+    atlas_sprites: Extract<
+        Query<(
+            Entity,
+            &ComputedVisibility,
+            &Outline,
+            &TextureAtlasSprite,
+            &GlobalTransform,
+            &Handle<TextureAtlas>,
+            Option<&OutlineGroup>,
+        )>,
+    >,
+) {
+    extracted.sprites.clear();
+
+    for (entity, visibility, outline, sprite, transform, image, group) in sprites.iter() {
+        if !visibility.is_visible() || !outline.enabled {
+            continue;
+        }
+
+        extracted.sprites.push(ExtractedSpriteMask {
+            entity,
+            transform: *transform,
+            color: sprite.color,
+            rect: None,
+            custom_size: sprite.custom_size,
+            anchor: sprite.anchor.as_vec(),
+            flip_x: sprite.flip_x,
+            flip_y: sprite.flip_y,
+            clip: None,
+            image: image.clone_weak(),
+            group: group.map_or(0, |group| group.0 as u32),
+        });
+    }
+
+    for (entity, visibility, outline, sprite, transform, atlas, group) in atlas_sprites.iter() {
+        if !visibility.is_visible() || !outline.enabled {
+            continue;
+        }
+
+        let atlas = match atlases.get(atlas) {
+            Some(a) => a,
+            None => continue,
+        };
+        let rect = match atlas.textures.get(sprite.index) {
+            Some(r) => *r,
+            None => continue,
+        };
+
+        extracted.sprites.push(ExtractedSpriteMask {
+            entity,
+            transform: *transform,
+            color: sprite.color,
+            rect: Some(rect),
+            custom_size: sprite.custom_size,
+            anchor: sprite.anchor.as_vec(),
+            flip_x: sprite.flip_x,
+            flip_y: sprite.flip_y,
+            clip: None,
+            image: atlas.texture.clone_weak(),
+            group: group.map_or(0, |group| group.0 as u32),
+        });
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub(crate) struct SpriteMaskVertex {
+    position: [f32; 3],
+    uv: [f32; 2],
+    color: [f32; 4],
+    group: u32,
+}
+
+// SAFETY: all fields are 4-byte scalars or arrays of them, so the struct has
+// no padding, and any bit pattern is valid.
+unsafe impl Zeroable for SpriteMaskVertex {}
+unsafe impl Pod for SpriteMaskVertex {}
+
+/// Corners of a sprite's quad, counterclockwise from the bottom left.
+const QUAD_CORNERS: [Vec2; 4] = [
+    Vec2::new(-0.5, -0.5),
+    Vec2::new(0.5, -0.5),
+    Vec2::new(0.5, 0.5),
+    Vec2::new(-0.5, 0.5),
+];
+
+/// Corners of the two triangles making up a quad.
+const QUAD_INDICES: [usize; 6] = [0, 2, 3, 0, 1, 2];
+
+/// The vertices of this frame's outlined quads, in runs sharing an image.
+pub struct SpriteMaskBatches {
+    // The mask pipelines for masks without and with colors, specialized for
+    // the current MSAA sample count if any view uses them.
+    pipeline_ids: [Option<CachedRenderPipelineId>; 2],
+    vertices: BufferVec<SpriteMaskVertex>,
+    view_bind_group: Option<BindGroup>,
+    // The runs drawn by each view.
+    views: HashMap<Entity, Vec<(BindGroup, Range<u32>)>>,
+}
+
+impl Default for SpriteMaskBatches {
+    fn default() -> Self {
+        SpriteMaskBatches {
+            pipeline_ids: [None; 2],
+            vertices: BufferVec::new(BufferUsages::VERTEX),
+            view_bind_group: None,
+            views: HashMap::default(),
+        }
+    }
+}
+
+impl SpriteMaskBatches {
+    /// Specializes the mask pipeline for views whose masks hold colors, as
+    /// given by `mask_colors`, with `samples` samples.
+    pub(crate) fn specialize_pipelines(
+        &mut self,
+        pipelines: &mut SpecializedRenderPipelines<SpriteMaskPipeline>,
+        pipeline_cache: &mut PipelineCache,
+        pipeline: &SpriteMaskPipeline,
+        samples: u32,
+        mask_colors: impl IntoIterator<Item = bool>,
+    ) {
+        self.pipeline_ids = [None; 2];
+        for mask_colors in mask_colors {
+            let key = SpriteMaskPipelineKey {
+                samples,
+                mask_colors,
+            };
+            self.pipeline_ids[mask_colors as usize] =
+                Some(pipelines.specialize(pipeline_cache, pipeline, key));
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn queue_sprite_masks(
+    device: Res<RenderDevice>,
+    queue: Res<RenderQueue>,
+    pipeline: Res<SpriteMaskPipeline>,
+    mut pipelines: ResMut<SpecializedRenderPipelines<SpriteMaskPipeline>>,
+    mut pipeline_cache: ResMut<PipelineCache>,
+    msaa: Res<Msaa>,
+    view_uniforms: Res<ViewUniforms>,
+    images: Res<RenderAssets<Image>>,
+    mut extracted: ResMut<ExtractedSpriteMasks>,
+    mut batches: ResMut<SpriteMaskBatches>,
+    views: Query<
+        (Entity, &VisibleEntities, Option<&MaskColors>),
+        (With<Camera2d>, With<ViewOutlineResources>),
+    >,
+) {
+    batches.specialize_pipelines(
+        &mut pipelines,
+        &mut pipeline_cache,
+        &pipeline,
+        msaa.samples,
+        views
+            .iter()
+            .map(|(_, _, mask_colors)| mask_colors.is_some()),
+    );
+
+    batch_mask_quads(
+        &device,
+        &queue,
+        &pipeline,
+        &view_uniforms,
+        &images,
+        &mut extracted.sprites,
+        views
+            .iter()
+            .map(|(entity, visible_entities, _)| (entity, Some(visible_entities))),
+        &mut batches,
+    );
+}
+
+/// Writes the vertices of `quads` to `batches` for each of `views`, drawing
+/// them back to front.
+///
+/// Views with [`VisibleEntities`] only draw the quads of their visible
+/// entities, while other views draw every quad.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn batch_mask_quads<'a>(
+    device: &RenderDevice,
+    queue: &RenderQueue,
+    pipeline: &SpriteMaskPipeline,
+    view_uniforms: &ViewUniforms,
+    images: &RenderAssets<Image>,
+    quads: &mut [ExtractedSpriteMask],
+    views: impl IntoIterator<Item = (Entity, Option<&'a VisibleEntities>)>,
+    batches: &mut SpriteMaskBatches,
+) {
+    batches.vertices.clear();
+    batches.views.clear();
+
+    batches.view_bind_group = view_uniforms.uniforms.binding().map(|binding| {
+        device.create_bind_group(&BindGroupDescriptor {
+            label: Some("outline_sprite_mask_view_bind_group"),
+            layout: &pipeline.view_layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: binding,
+            }],
+        })
+    });
+
+    // Quads are drawn back to front, so that nearer quads overwrite the
+    // colors and groups of those behind them. Ties are broken by image to
+    // keep batches long.
+    quads.sort_by(|a, b| {
+        let a_z = a.transform.translation().z;
+        let b_z = b.transform.translation().z;
+        a_z.total_cmp(&b_z)
+            .then_with(|| a.image.id.cmp(&b.image.id))
+    });
+
+    let mut visible = HashSet::default();
+    for (view, visible_entities) in views {
+        // Quads are only drawn by views which can see their entity, so that
+        // `RenderLayers` apply to the mask as they do to the main pass.
+        if let Some(visible_entities) = visible_entities {
+            visible.clear();
+            visible.extend(visible_entities.entities.iter().copied());
+        }
+
+        let mut view_batches = Vec::new();
+        let mut current: Option<(&Handle<Image>, u32)> = None;
+        for quad in quads.iter() {
+            if visible_entities.is_some() && !visible.contains(&quad.entity) {
+                continue;
+            }
+
+            // Quads are skipped until their image has been prepared.
+            let image = match images.get(&quad.image) {
+                Some(i) => i,
+                None => continue,
+            };
+
+            let (uv_min, uv_max) = match quad.rect {
+                Some(rect) => (rect.min / image.size, rect.max / image.size),
+                None => (Vec2::ZERO, Vec2::ONE),
+            };
+            let size = quad
+                .custom_size
+                .or_else(|| quad.rect.map(|rect| rect.max - rect.min))
+                .unwrap_or(image.size);
+            if size.x == 0.0 || size.y == 0.0 {
+                continue;
+            }
+
+            let start = batches.vertices.len() as u32;
+            match current {
+                Some((handle, _)) if *handle == quad.image => (),
+                Some((handle, batch_start)) => {
+                    push_batch(
+                        &mut view_batches,
+                        device,
+                        pipeline,
+                        images,
+                        handle,
+                        batch_start..start,
+                    );
+                    current = Some((&quad.image, start));
+                }
+                None => current = Some((&quad.image, start)),
+            }
+
+            let matrix = quad.transform.compute_matrix();
+            let color = quad.color.as_linear_rgba_f32();
+            let vertices = QUAD_CORNERS.map(|mut corner| {
+                let mut position =
+                    matrix.transform_point3(((corner - quad.anchor) * size).extend(0.0));
+
+                // Clipping assumes the quad is axis-aligned, as UI nodes are.
+                if let Some(clip) = quad.clip {
+                    position = position
+                        .truncate()
+                        .clamp(clip.min, clip.max)
+                        .extend(position.z);
+                    let local = matrix.inverse().transform_point3(position).truncate();
+                    corner = local / size + quad.anchor;
+                }
+
+                // Texture coordinates increase downwards.
+                let mut t = Vec2::new(corner.x + 0.5, 0.5 - corner.y);
+                if quad.flip_x {
+                    t.x = 1.0 - t.x;
+                }
+                if quad.flip_y {
+                    t.y = 1.0 - t.y;
+                }
+                let uv = uv_min + t * (uv_max - uv_min);
+
+                SpriteMaskVertex {
+                    position: position.to_array(),
+                    uv: uv.to_array(),
+                    color,
+                    group: quad.group,
+                }
+            });
+            for index in QUAD_INDICES {
+                batches.vertices.push(vertices[index]);
+            }
+        }
+
+        if let Some((handle, batch_start)) = current {
+            let end = batches.vertices.len() as u32;
+            push_batch(
+                &mut view_batches,
+                device,
+                pipeline,
+                images,
+                handle,
+                batch_start..end,
+            );
+        }
+
+        batches.views.insert(view, view_batches);
+    }
+
+    batches.vertices.write_buffer(device, queue);
+}
+
+fn push_batch(
+    batches: &mut Vec<(BindGroup, Range<u32>)>,
+    device: &RenderDevice,
+    pipeline: &SpriteMaskPipeline,
+    images: &RenderAssets<Image>,
+    image: &Handle<Image>,
+    vertices: Range<u32>,
+) {
+    let image = match images.get(image) {
+        Some(i) => i,
+        None => return,
+    };
+
+    let bind_group = device.create_bind_group(&BindGroupDescriptor {
+        label: Some("outline_sprite_mask_texture_bind_group"),
+        layout: &pipeline.texture_layout,
+        entries: &[
+            BindGroupEntry {
+                binding: 0,
+                resource: BindingResource::TextureView(&image.texture_view),
+            },
+            BindGroupEntry {
+                binding: 1,
+                resource: BindingResource::Sampler(&image.sampler),
+            },
+        ],
+    });
+    batches.push((bind_group, vertices));
+}
+
+/// Render graph node for drawing outlined sprites into the mask.
+///
+/// This has the same slots as the [`MeshMaskNode`].
+pub struct SpriteMaskNode {
+    query: QueryState<&'static ViewUniformOffset>,
+}
+
+impl SpriteMaskNode {
+    pub fn new(world: &mut World) -> SpriteMaskNode {
+        SpriteMaskNode {
+            query: QueryState::new(world),
+        }
+    }
+}
+
+impl Node for SpriteMaskNode {
+    fn input(&self) -> Vec<SlotInfo> {
+        vec![SlotInfo::new(MeshMaskNode::IN_VIEW, SlotType::Entity)]
+    }
+
+    fn output(&self) -> Vec<SlotInfo> {
+        vec![SlotInfo::new(MeshMaskNode::OUT_MASK, SlotType::TextureView)]
+    }
+
+    fn update(&mut self, world: &mut World) {
+        self.query.update_archetypes(world);
+    }
+
+    fn run(
+        &self,
+        graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let view_entity = graph.get_input_entity(MeshMaskNode::IN_VIEW)?;
+        let res = world.get::<ViewOutlineResources>(view_entity).unwrap();
+
+        graph.set_output(MeshMaskNode::OUT_MASK, res.mask_output.default_view.clone())?;
+
+        if reuse::is_reused(world, view_entity) {
+            return Ok(());
+        }
+
+        let view_offset = self.query.get_manual(world, view_entity).ok();
+
+        // Meshes are drawn first, so sprites overlapping them take precedence.
+        let mut pass = begin_mask_pass(render_context, res);
+        mesh2d::draw_mesh2d_masks(&mut pass, world, view_entity);
+        draw_mask_quads(
+            &mut pass,
+            world,
+            view_entity,
+            view_offset,
+            world.resource::<SpriteMaskBatches>(),
+        );
+
+        Ok(())
+    }
+}
+
+/// Begins a pass drawing into the mask of a view with the given resources.
+///
+/// The mask is cleared even if nothing is drawn, so that the outline pass
+/// doesn't pick up a mask left over from another view or graph.
+pub(crate) fn begin_mask_pass<'a>(
+    render_context: &'a mut RenderContext,
+    res: &'a ViewOutlineResources,
+) -> TrackedRenderPass<'a> {
+    let pass_raw = render_context
+        .command_encoder
+        .begin_render_pass(&RenderPassDescriptor {
+            label: Some("outline_sprite_mask_render_pass"),
+            color_attachments: &mask::mask_color_attachments(res),
+            depth_stencil_attachment: None,
+        });
+    TrackedRenderPass::new(pass_raw)
+}
+
+/// Draws `batches` into the mask of `view_entity` with `pass`, projecting them
+/// with the view uniform at `view_offset`.
+pub(crate) fn draw_mask_quads<'w>(
+    pass: &mut TrackedRenderPass<'w>,
+    world: &'w World,
+    view_entity: Entity,
+    view_offset: Option<&ViewUniformOffset>,
+    batches: &'w SpriteMaskBatches,
+) {
+    let res = world.get::<ViewOutlineResources>(view_entity).unwrap();
+
+    let mask_colors = world.get::<MaskColors>(view_entity).is_some();
+    let pipeline = batches.pipeline_ids[mask_colors as usize]
+        .and_then(|id| world.resource::<PipelineCache>().get_render_pipeline(id));
+    let (pipeline, view_offset, view_bind_group, vertices, view_batches) = match (
+        pipeline,
+        view_offset,
+        &batches.view_bind_group,
+        batches.vertices.buffer(),
+        batches.views.get(&view_entity),
+    ) {
+        (Some(p), Some(o), Some(b), Some(v), Some(vb)) => (p, o, b, v, vb),
+        _ => return,
+    };
+
+    pass.set_render_pipeline(pipeline);
+    pass.set_bind_group(0, view_bind_group, &[view_offset.offset]);
+    pass.set_bind_group(1, &res.dimensions_bind_group, &[]);
+    pass.set_vertex_buffer(0, vertices.slice(..));
+    for (bind_group, range) in view_batches.iter() {
+        pass.set_bind_group(2, bind_group, &[]);
+        pass.draw(range.clone(), 0..1);
+    }
+}
@@ -0,0 +1,231 @@
+use bevy::{
+    prelude::*,
+    render::{
+        render_asset::RenderAssets,
+        render_graph::{Node, NodeRunError, RenderGraphContext, SlotInfo, SlotType},
+        render_resource::{Extent3d, TextureDimension, TextureFormat, TextureUsages},
+        renderer::RenderContext,
+        Extract,
+    },
+};
+
+use crate::{
+    mask::MASK_COLOR_TEXTURE_FORMAT, resources::ViewOutlineResources, JfaTextureFormat,
+    OutlineJfaFormat, OutlineSettings,
+};
+
+/// Component for publishing a camera's mask and distance field as images.
+///
+/// While this component is present on a camera with an outline, the camera's
+/// intermediate textures are copied into these images each frame, once the
+/// distance field is complete. As the images are ordinary assets, they can be
+/// bound by materials and by other plugins' shaders, e.g. to add heat haze or
+/// a shimmer around selected entities.
+///
+/// The images are resized to match the intermediate textures, which cover the
+/// camera's viewport along with the offscreen padding. Copies are skipped
+/// while the images don't match, so they may briefly hold stale contents after
+/// the viewport is resized.
+#[derive(Clone, Debug, Component)]
+pub struct OutlineTextures {
+    /// Copy of the mask, in `TextureFormat::Rgba8Unorm`.
+    ///
+    /// The RGB components hold the color of the covering entity, premultiplied
+    /// by the coverage stored in the alpha component.
+    pub mask: Handle<Image>,
+    /// Copy of the final jump flood output.
+    ///
+    /// The RG components hold the texture coordinates of the nearest seed
+    /// outside the silhouette, and the BA components those of the nearest
+    /// seed inside it. Texels without a seed hold `-1.0`. The texels are
+    /// stored in the camera's [`OutlineJfaFormat`] if it has one, and the
+    /// app's [`JfaTextureFormat`] otherwise, so shaders sampling this image
+    /// must decode them with the `outline::jfa_utils` import.
+    pub distance_field: Handle<Image>,
+}
+
+impl OutlineTextures {
+    /// Creates the component with new images, which are sized once the
+    /// camera's viewport is known.
+    pub fn new(images: &mut Assets<Image>) -> Self {
+        OutlineTextures {
+            mask: images.add(placeholder_image(MASK_COLOR_TEXTURE_FORMAT)),
+            distance_field: images.add(placeholder_image(MASK_COLOR_TEXTURE_FORMAT)),
+        }
+    }
+}
+
+/// Returns a one-pixel image of the given format, which can be copied into.
+pub(crate) fn placeholder_image(format: TextureFormat) -> Image {
+    let pixel = vec![0; format.describe().block_size as usize];
+    let mut image = Image::new_fill(
+        Extent3d {
+            width: 1,
+            height: 1,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        &pixel,
+        format,
+    );
+    image.texture_descriptor.usage |= TextureUsages::COPY_DST;
+    image
+}
+
+/// Returns the size of the intermediate textures of a view with the given
+/// viewport size, including the offscreen padding.
+pub(crate) fn intermediate_size(settings: &OutlineSettings, viewport_size: UVec2) -> Extent3d {
+    let padding = settings.offscreen_padding;
+    let screen_size = settings.internal_size(viewport_size);
+    Extent3d {
+        width: screen_size.x + 2 * padding,
+        height: screen_size.y + 2 * padding,
+        depth_or_array_layers: 1,
+    }
+}
+
+/// Resizes the images `mask` and `distance_field` to hold copies of the
+/// intermediate textures of the given size.
+pub(crate) fn fit_images(
+    images: &mut Assets<Image>,
+    jfa_format: JfaTextureFormat,
+    mask: &Handle<Image>,
+    distance_field: &Handle<Image>,
+    size: Extent3d,
+) {
+    for (handle, format) in [
+        (mask, MASK_COLOR_TEXTURE_FORMAT),
+        (distance_field, jfa_format.texture_format()),
+    ] {
+        let up_to_date = images.get(handle).is_some_and(|image| {
+            image.texture_descriptor.size == size && image.texture_descriptor.format == format
+        });
+
+        // Only access the image mutably when necessary, as doing so causes it
+        // to be uploaded to the GPU again.
+        if !up_to_date {
+            if let Some(image) = images.get_mut(handle) {
+                image.texture_descriptor.format = format;
+                image.data.clear();
+                image.resize(size);
+            }
+        }
+    }
+}
+
+pub(crate) fn resize_outline_textures(
+    settings: Res<OutlineSettings>,
+    jfa_format: Res<JfaTextureFormat>,
+    mut images: ResMut<Assets<Image>>,
+    cameras: Query<(&Camera, &OutlineTextures, Option<&OutlineJfaFormat>)>,
+) {
+    for (camera, textures, camera_format) in cameras.iter() {
+        if let Some(viewport_size) = camera.physical_viewport_size() {
+            let size = intermediate_size(&settings, viewport_size);
+            fit_images(
+                &mut images,
+                camera_format.map_or(*jfa_format, |format| format.0),
+                &textures.mask,
+                &textures.distance_field,
+                size,
+            );
+        }
+    }
+}
+
+pub(crate) fn extract_outline_textures(
+    mut commands: Commands,
+    mut previous_len: Local<usize>,
+    cameras: Extract<Query<(Entity, &OutlineTextures), With<Camera>>>,
+) {
+    let mut batches = Vec::with_capacity(*previous_len);
+    batches.extend(
+        cameras
+            .iter()
+            .map(|(entity, textures)| (entity, (textures.clone(),))),
+    );
+    *previous_len = batches.len();
+    commands.insert_or_spawn_batch(batches);
+}
+
+/// Copies the mask and final jump flood output in `res` into the images `mask`
+/// and `distance_field`.
+///
+/// Images which don't yet match the size of the intermediate textures, such as
+/// after a resize, are skipped.
+pub(crate) fn copy_outline_textures(
+    render_context: &mut RenderContext,
+    gpu_images: &RenderAssets<Image>,
+    res: &ViewOutlineResources,
+    mask: &Handle<Image>,
+    distance_field: &Handle<Image>,
+) {
+    let dims = res.dimensions_buffer.get().size();
+    let size = Extent3d {
+        width: dims.x,
+        height: dims.y,
+        depth_or_array_layers: 1,
+    };
+
+    for (handle, source, format) in [
+        (mask, &res.mask_output, MASK_COLOR_TEXTURE_FORMAT),
+        (
+            distance_field,
+            &res.jfa_final_output,
+            res.jfa_format.texture_format(),
+        ),
+    ] {
+        // Images of another format are left for views which match it.
+        let image = match gpu_images.get(handle) {
+            Some(i) if i.size == dims.as_vec2() && i.texture_format == format => i,
+            _ => continue,
+        };
+
+        render_context.command_encoder.copy_texture_to_texture(
+            source.texture.as_image_copy(),
+            image.texture.as_image_copy(),
+            size,
+        );
+    }
+}
+
+/// Render graph node which copies the intermediate outline textures of a view
+/// into its [`OutlineTextures`].
+pub(crate) struct OutlineTexturesCopyNode;
+
+impl OutlineTexturesCopyNode {
+    pub(crate) const IN_VIEW: &'static str = "view";
+}
+
+impl Node for OutlineTexturesCopyNode {
+    fn input(&self) -> Vec<SlotInfo> {
+        vec![SlotInfo::new(Self::IN_VIEW, SlotType::Entity)]
+    }
+
+    fn run(
+        &self,
+        graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let view_ent = graph.get_input_entity(Self::IN_VIEW)?;
+        let (res, textures) = match (
+            world.get::<ViewOutlineResources>(view_ent),
+            world.get::<OutlineTextures>(view_ent),
+        ) {
+            (Some(r), Some(t)) => (r, t),
+            _ => return Ok(()),
+        };
+
+        let gpu_images = world.resource::<RenderAssets<Image>>();
+        copy_outline_textures(
+            render_context,
+            gpu_images,
+            res,
+            &textures.mask,
+            &textures.distance_field,
+        );
+
+        Ok(())
+    }
+}
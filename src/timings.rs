@@ -0,0 +1,405 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use bevy::{
+    core_pipeline::{core_2d, core_3d},
+    diagnostic::{Diagnostic, DiagnosticId, Diagnostics},
+    prelude::*,
+    render::{
+        render_graph::{Node, NodeRunError, RenderGraph, RenderGraphContext, SlotInfo, SlotType},
+        render_resource::{CommandEncoder, WgpuFeatures},
+        renderer::{RenderContext, RenderDevice, RenderQueue},
+        RenderApp, RenderStage,
+    },
+    utils::HashMap,
+};
+use wgpu_profiler::{GpuProfiler, GpuTimerScopeResult};
+
+use crate::graph;
+
+/// Plugin for measuring the GPU time spent on outline passes.
+///
+/// Timestamps are written around the mask, JFA initialization and outline
+/// passes of each view, and around each jump flood pass, and the times of the
+/// latest finished frame are published in the [`OutlineTimings`] resource and
+/// as diagnostics summed over all views. Results lag a few frames behind, as
+/// they are read back once the GPU has finished the frame.
+///
+/// Timestamp queries are an optional feature of the device, which must be
+/// requested through the `WgpuSettings` resource before the `RenderPlugin` is
+/// built, e.g. `WgpuSettings { features: WgpuFeatures::TIMESTAMP_QUERY,
+/// ..default() }`. Without it, a warning is logged and no times are measured.
+///
+/// This requires the `wgpu-profiler` feature, and must be added after the
+/// [`OutlinePlugin`](crate::OutlinePlugin) or
+/// [`MinimalOutlinePlugin`](crate::MinimalOutlinePlugin).
+#[derive(Default)]
+pub struct OutlineTimingsPlugin;
+
+impl OutlineTimingsPlugin {
+    pub const MASK_TIME: DiagnosticId =
+        DiagnosticId::from_u128(137491250385237705916468823360717826531);
+    pub const JFA_INIT_TIME: DiagnosticId =
+        DiagnosticId::from_u128(259112683040553722480516235934961187229);
+    pub const JFA_TIME: DiagnosticId =
+        DiagnosticId::from_u128(80215583648711035683318346541260964127);
+    pub const OUTLINE_TIME: DiagnosticId =
+        DiagnosticId::from_u128(301826746107913275584101632457928613094);
+}
+
+impl Plugin for OutlineTimingsPlugin {
+    fn build(&self, app: &mut App) {
+        let finished = FinishedTimings::default();
+
+        app.init_resource::<OutlineTimings>()
+            .insert_resource(finished.clone())
+            .add_startup_system(setup_timing_diagnostics)
+            .add_system(update_outline_timings);
+
+        let render_app = match app.get_sub_app_mut(RenderApp) {
+            Ok(r) => r,
+            Err(_) => return,
+        };
+
+        let device = render_app.world.resource::<RenderDevice>().clone();
+        if !device.features().contains(WgpuFeatures::TIMESTAMP_QUERY) {
+            warn!(
+                "Outline timings require the `TIMESTAMP_QUERY` feature, which is not enabled \
+                 on the render device. Request it in `WgpuSettings` to measure outline passes."
+            );
+            return;
+        }
+        let timestamp_period = render_app
+            .world
+            .resource::<RenderQueue>()
+            .get_timestamp_period();
+
+        render_app
+            .insert_resource(OutlineProfiler {
+                state: Mutex::new(ProfilerState {
+                    profiler: GpuProfiler::new(
+                        MAX_PENDING_FRAMES,
+                        timestamp_period,
+                        device.features(),
+                    ),
+                    open_scopes: 0,
+                }),
+                device,
+            })
+            .insert_resource(finished)
+            .add_system_to_stage(RenderStage::Cleanup, finish_outline_timings);
+
+        // Each node ends the scope of the pass before it and begins the scope
+        // of the pass after it. Jump flood passes are timed by the `JfaNode`.
+        let nodes = [
+            (
+                OutlineTimestampNode::MASK_NAME,
+                None,
+                Some(graph::outline::node::MASK_PASS),
+                false,
+                Some(MASK_SCOPE),
+            ),
+            (
+                OutlineTimestampNode::JFA_INIT_NAME,
+                Some(graph::outline::node::MASK_PASS),
+                Some(graph::outline::node::JFA_INIT_PASS),
+                true,
+                Some(JFA_INIT_SCOPE),
+            ),
+            (
+                OutlineTimestampNode::JFA_INIT_END_NAME,
+                Some(graph::outline::node::JFA_INIT_PASS),
+                Some(graph::outline::node::JFA_PASS),
+                true,
+                None,
+            ),
+            (
+                OutlineTimestampNode::OUTLINE_NAME,
+                Some(graph::outline::node::JFA_PASS),
+                Some(graph::outline::node::OUTLINE_PASS),
+                false,
+                Some(OUTLINE_SCOPE),
+            ),
+            (
+                OutlineTimestampNode::OUTLINE_END_NAME,
+                Some(graph::outline::node::OUTLINE_PASS),
+                None,
+                true,
+                None,
+            ),
+        ];
+
+        let mut root_graph = render_app.world.resource_mut::<RenderGraph>();
+        for core_graph in [core_3d::graph::NAME, core_2d::graph::NAME] {
+            let outline_graph = match root_graph
+                .get_sub_graph_mut(core_graph)
+                .and_then(|core| core.get_sub_graph_mut(graph::outline::NAME))
+            {
+                Some(g) => g,
+                // 2D outlines require the `sprite` feature.
+                None => continue,
+            };
+
+            let input_node_id = outline_graph.input_node().unwrap().id;
+            for (name, after, before, end, begin) in nodes {
+                outline_graph.add_node(name, OutlineTimestampNode { end, begin });
+                outline_graph
+                    .add_slot_edge(
+                        input_node_id,
+                        graph::outline::input::VIEW_ENTITY,
+                        name,
+                        OutlineTimestampNode::IN_VIEW,
+                    )
+                    .unwrap();
+                if let Some(after) = after {
+                    outline_graph.add_node_edge(after, name).unwrap();
+                }
+                if let Some(before) = before {
+                    outline_graph.add_node_edge(name, before).unwrap();
+                }
+            }
+        }
+    }
+}
+
+/// The number of frames whose timestamps may be in flight at once.
+const MAX_PENDING_FRAMES: usize = 4;
+
+// Scope labels, which are followed by the bits of the view entity.
+const MASK_SCOPE: &str = "outline_mask";
+const JFA_INIT_SCOPE: &str = "outline_jfa_init";
+const JFA_SCOPE: &str = "outline_jfa";
+const OUTLINE_SCOPE: &str = "outline_pass";
+
+/// The GPU time spent on outline passes in a recent frame, as measured by the
+/// [`OutlineTimingsPlugin`].
+#[derive(Clone, Debug, Default)]
+pub struct OutlineTimings {
+    views: HashMap<Entity, OutlineViewTimings>,
+}
+
+impl OutlineTimings {
+    /// Returns the times of the passes drawing the outline of `camera`, if it
+    /// was drawn.
+    pub fn view(&self, camera: Entity) -> Option<&OutlineViewTimings> {
+        self.views.get(&camera)
+    }
+
+    /// Returns the times of the passes of each outlined camera.
+    pub fn iter(&self) -> impl Iterator<Item = (Entity, &OutlineViewTimings)> {
+        self.views
+            .iter()
+            .map(|(&camera, timings)| (camera, timings))
+    }
+
+    /// Returns the time spent on the outlines of all cameras.
+    pub fn total(&self) -> Duration {
+        self.views.values().map(OutlineViewTimings::total).sum()
+    }
+}
+
+/// The GPU time spent on each outline pass of a camera.
+#[derive(Clone, Debug, Default)]
+pub struct OutlineViewTimings {
+    pub mask: Duration,
+    pub jfa_init: Duration,
+    /// The time of each jump flood pass, from the widest jump to the
+    /// narrowest. This is empty when the view reuses its distance field.
+    pub jfa: Vec<Duration>,
+    pub outline: Duration,
+}
+
+impl OutlineViewTimings {
+    /// Returns the time spent on all of the jump flood passes.
+    pub fn jfa_total(&self) -> Duration {
+        self.jfa.iter().sum()
+    }
+
+    /// Returns the time spent on all of the passes.
+    pub fn total(&self) -> Duration {
+        self.mask + self.jfa_init + self.jfa_total() + self.outline
+    }
+}
+
+/// The timings of the latest finished frame, shared between the main and
+/// render worlds.
+#[derive(Clone, Default)]
+struct FinishedTimings(Arc<Mutex<Option<OutlineTimings>>>);
+
+/// Writes the timestamps of outline passes, once the [`OutlineTimingsPlugin`]
+/// has been added.
+pub(crate) struct OutlineProfiler {
+    state: Mutex<ProfilerState>,
+    device: RenderDevice,
+}
+
+struct ProfilerState {
+    profiler: GpuProfiler,
+    // The number of scopes which have begun but not ended.
+    open_scopes: usize,
+}
+
+impl OutlineProfiler {
+    /// Begins timing a pass of `view`, which may nest within another.
+    fn begin_scope(&self, label: &str, view: Entity, encoder: &mut CommandEncoder) {
+        let mut state = self.state.lock().unwrap();
+        state.profiler.begin_scope(
+            &format!("{}#{}", label, view.to_bits()),
+            encoder,
+            self.device.wgpu_device(),
+        );
+        state.open_scopes += 1;
+    }
+
+    /// Ends the innermost scope.
+    ///
+    /// Queries are resolved once all scopes have ended, as the end timestamps
+    /// of open scopes haven't been written yet.
+    fn end_scope(&self, encoder: &mut CommandEncoder) {
+        let mut state = self.state.lock().unwrap();
+        state.profiler.end_scope(encoder);
+        state.open_scopes -= 1;
+        if state.open_scopes == 0 {
+            state.profiler.resolve_queries(encoder);
+        }
+    }
+
+    /// Begins timing a jump flood pass of `view`.
+    pub(crate) fn begin_jfa_pass(&self, view: Entity, encoder: &mut CommandEncoder) {
+        self.begin_scope(JFA_SCOPE, view, encoder);
+    }
+
+    /// Ends timing a jump flood pass.
+    pub(crate) fn end_jfa_pass(&self, encoder: &mut CommandEncoder) {
+        self.end_scope(encoder);
+    }
+}
+
+/// Ends the profiler frame once its commands have been submitted, and
+/// publishes the timings of the latest frame read back from the GPU.
+fn finish_outline_timings(profiler: Res<OutlineProfiler>, finished: Res<FinishedTimings>) {
+    let mut state = profiler.state.lock().unwrap();
+    if state.profiler.end_frame().is_err() {
+        error!("outline timing scopes were left open at the end of the frame");
+    }
+
+    let mut latest = None;
+    while let Some(results) = state.profiler.process_finished_frame() {
+        latest = Some(results);
+    }
+    if let Some(results) = latest {
+        *finished.0.lock().unwrap() = Some(collect_timings(&results));
+    }
+}
+
+fn collect_timings(results: &[GpuTimerScopeResult]) -> OutlineTimings {
+    let mut timings = OutlineTimings::default();
+    for result in results {
+        let (label, view) = match result.label.rsplit_once('#') {
+            Some((label, bits)) => match bits.parse() {
+                Ok(bits) => (label, Entity::from_bits(bits)),
+                Err(_) => continue,
+            },
+            None => continue,
+        };
+
+        let time = Duration::from_secs_f64((result.time.end - result.time.start).max(0.0));
+        let view = timings.views.entry(view).or_default();
+        match label {
+            MASK_SCOPE => view.mask += time,
+            JFA_INIT_SCOPE => view.jfa_init += time,
+            JFA_SCOPE => view.jfa.push(time),
+            OUTLINE_SCOPE => view.outline += time,
+            _ => {}
+        }
+    }
+    timings
+}
+
+fn setup_timing_diagnostics(mut diagnostics: ResMut<Diagnostics>) {
+    let diagnostics_ids = [
+        (OutlineTimingsPlugin::MASK_TIME, "outline_mask_time"),
+        (OutlineTimingsPlugin::JFA_INIT_TIME, "outline_jfa_init_time"),
+        (OutlineTimingsPlugin::JFA_TIME, "outline_jfa_time"),
+        (OutlineTimingsPlugin::OUTLINE_TIME, "outline_pass_time"),
+    ];
+    for (id, name) in diagnostics_ids {
+        diagnostics.add(Diagnostic::new(id, name, 20).with_suffix("ms"));
+    }
+}
+
+fn update_outline_timings(
+    mut diagnostics: ResMut<Diagnostics>,
+    mut timings: ResMut<OutlineTimings>,
+    finished: Res<FinishedTimings>,
+) {
+    let latest = match finished.0.lock().unwrap().take() {
+        Some(t) => t,
+        None => return,
+    };
+    *timings = latest;
+
+    let millis = |time: fn(&OutlineViewTimings) -> Duration| {
+        timings
+            .views
+            .values()
+            .map(time)
+            .sum::<Duration>()
+            .as_secs_f64()
+            * 1000.0
+    };
+    diagnostics.add_measurement(OutlineTimingsPlugin::MASK_TIME, || millis(|v| v.mask));
+    diagnostics.add_measurement(OutlineTimingsPlugin::JFA_INIT_TIME, || {
+        millis(|v| v.jfa_init)
+    });
+    diagnostics.add_measurement(OutlineTimingsPlugin::JFA_TIME, || {
+        millis(OutlineViewTimings::jfa_total)
+    });
+    diagnostics.add_measurement(OutlineTimingsPlugin::OUTLINE_TIME, || millis(|v| v.outline));
+}
+
+/// Render graph node which writes timestamps between the passes of the outline
+/// graph.
+struct OutlineTimestampNode {
+    // Whether this node ends the scope of the previous pass.
+    end: bool,
+    // The scope begun by this node.
+    begin: Option<&'static str>,
+}
+
+impl OutlineTimestampNode {
+    const MASK_NAME: &'static str = "outline_timestamp_mask";
+    const JFA_INIT_NAME: &'static str = "outline_timestamp_jfa_init";
+    const JFA_INIT_END_NAME: &'static str = "outline_timestamp_jfa_init_end";
+    const OUTLINE_NAME: &'static str = "outline_timestamp_outline";
+    const OUTLINE_END_NAME: &'static str = "outline_timestamp_outline_end";
+    const IN_VIEW: &'static str = "view";
+}
+
+impl Node for OutlineTimestampNode {
+    fn input(&self) -> Vec<SlotInfo> {
+        vec![SlotInfo::new(Self::IN_VIEW, SlotType::Entity)]
+    }
+
+    fn run(
+        &self,
+        graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let view_ent = graph.get_input_entity(Self::IN_VIEW)?;
+        let profiler = world.resource::<OutlineProfiler>();
+
+        let encoder = &mut render_context.command_encoder;
+        if self.end {
+            profiler.end_scope(encoder);
+        }
+        if let Some(label) = self.begin {
+            profiler.begin_scope(label, view_ent, encoder);
+        }
+
+        Ok(())
+    }
+}
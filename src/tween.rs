@@ -0,0 +1,274 @@
+use bevy::{prelude::*, reflect::FromReflect};
+
+use crate::{InnerOutline, OutlineFalloff, OutlineFill, OutlineStyle};
+
+/// Component for animating a camera's [`OutlineStyle`] component from one
+/// style to another.
+///
+/// Each frame, the camera's `OutlineStyle` is set to an interpolation of
+/// `from` and `to`. Numeric fields and colors are interpolated, colors in
+/// linear space; other fields, such as the fill texture or distance metric,
+/// switch from `from` to `to` halfway through. Once `duration` seconds have
+/// passed, the style is set to `to` and this component is removed.
+///
+/// Animated styles are updated in place on the GPU rather than prepared anew
+/// each frame, so tweening doesn't allocate new assets or bind groups. Styles
+/// referenced by a [`CameraOutline`](crate::CameraOutline) can't be tweened
+/// with this component, but modified `OutlineStyle` assets are updated in
+/// place as well, so they can be animated by other systems.
+#[derive(Clone, Debug, Default, Component, Reflect, FromReflect)]
+#[reflect(Component, Default)]
+pub struct OutlineStyleTween {
+    pub from: OutlineStyle,
+    pub to: OutlineStyle,
+    /// Duration of the tween in seconds.
+    pub duration: f32,
+    pub easing: OutlineEasing,
+    elapsed: f32,
+}
+
+impl OutlineStyleTween {
+    pub fn new(
+        from: OutlineStyle,
+        to: OutlineStyle,
+        duration: f32,
+        easing: OutlineEasing,
+    ) -> OutlineStyleTween {
+        OutlineStyleTween {
+            from,
+            to,
+            duration,
+            easing,
+            elapsed: 0.0,
+        }
+    }
+
+    /// Returns the fraction of the tween which has elapsed, before easing.
+    pub fn progress(&self) -> f32 {
+        if self.duration <= 0.0 {
+            return 1.0;
+        }
+
+        (self.elapsed / self.duration).clamp(0.0, 1.0)
+    }
+}
+
+/// Easing curve applied to the progress of an [`OutlineStyleTween`] or
+/// across an [`OutlineFalloff::Band`].
+///
+/// The discriminants must be kept in sync with the `OUTLINE_EASING_*`
+/// constants in `outline.wgsl`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Reflect, FromReflect)]
+#[cfg_attr(feature = "ron", derive(serde::Serialize, serde::Deserialize))]
+#[reflect_value(Debug, PartialEq, Default)]
+#[cfg_attr(
+    feature = "ron",
+    reflect_value(Debug, PartialEq, Default, Serialize, Deserialize)
+)]
+pub enum OutlineEasing {
+    #[default]
+    Linear = 0,
+    /// Starts slowly and accelerates.
+    EaseIn = 1,
+    /// Starts quickly and decelerates.
+    EaseOut = 2,
+    /// Starts and ends slowly, along a smoothstep curve.
+    EaseInOut = 3,
+    /// Overshoots and bounces back several times before settling.
+    Bounce = 4,
+}
+
+impl OutlineEasing {
+    /// Applies the easing curve to `t`, which should be between `0.0` and
+    /// `1.0`.
+    pub fn ease(self, t: f32) -> f32 {
+        match self {
+            OutlineEasing::Linear => t,
+            OutlineEasing::EaseIn => t * t,
+            OutlineEasing::EaseOut => t * (2.0 - t),
+            OutlineEasing::EaseInOut => t * t * (3.0 - 2.0 * t),
+            OutlineEasing::Bounce => bounce(t),
+        }
+    }
+}
+
+/// The standard "ease out bounce" curve.
+fn bounce(t: f32) -> f32 {
+    const N: f32 = 7.5625;
+    const D: f32 = 2.75;
+
+    if t < 1.0 / D {
+        N * t * t
+    } else if t < 2.0 / D {
+        let t = t - 1.5 / D;
+        N * t * t + 0.75
+    } else if t < 2.5 / D {
+        let t = t - 2.25 / D;
+        N * t * t + 0.9375
+    } else {
+        let t = t - 2.625 / D;
+        N * t * t + 0.984375
+    }
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+/// Interpolates between two colors in linear space.
+fn lerp_color(a: Color, b: Color, t: f32) -> Color {
+    let a = Vec4::from(a.as_linear_rgba_f32());
+    let b = Vec4::from(b.as_linear_rgba_f32());
+    let c = a.lerp(b, t);
+    Color::rgba_linear(c.x, c.y, c.z, c.w)
+}
+
+/// Interpolates between two outline styles.
+fn lerp_style(from: &OutlineStyle, to: &OutlineStyle, t: f32) -> OutlineStyle {
+    // Fields which can't be interpolated switch halfway through.
+    let nearest = if t < 0.5 { from } else { to };
+
+    let fill = match (from.fill, to.fill) {
+        (
+            OutlineFill::Glow {
+                radius: r0,
+                exponent: e0,
+            },
+            OutlineFill::Glow {
+                radius: r1,
+                exponent: e1,
+            },
+        ) => OutlineFill::Glow {
+            radius: lerp(r0, r1, t),
+            exponent: lerp(e0, e1, t),
+        },
+        _ => nearest.fill,
+    };
+
+    let falloff = match (from.falloff, to.falloff) {
+        (OutlineFalloff::Exponential { k: k0 }, OutlineFalloff::Exponential { k: k1 }) => {
+            OutlineFalloff::Exponential { k: lerp(k0, k1, t) }
+        }
+        (
+            OutlineFalloff::Band {
+                start: s0,
+                end: e0,
+                easing: easing0,
+            },
+            OutlineFalloff::Band {
+                start: s1,
+                end: e1,
+                easing: easing1,
+            },
+        ) => OutlineFalloff::Band {
+            start: lerp(s0, s1, t),
+            end: lerp(e0, e1, t),
+            easing: if t < 0.5 { easing0 } else { easing1 },
+        },
+        _ => nearest.falloff,
+    };
+
+    let inner = match (&from.inner, &to.inner) {
+        (Some(a), Some(b)) => Some(InnerOutline {
+            color: lerp_color(a.color, b.color, t),
+            width: lerp(a.width, b.width, t),
+        }),
+        _ => nearest.inner.clone(),
+    };
+
+    let max_coverage = match (from.max_coverage, to.max_coverage) {
+        (Some(a), Some(b)) => Some(lerp(a, b, t)),
+        _ => nearest.max_coverage,
+    };
+
+    OutlineStyle {
+        color: lerp_color(from.color, to.color, t),
+        width: lerp(from.width, to.width, t),
+        max_coverage,
+        fade_start: lerp(from.fade_start, to.fade_start, t),
+        fade_end: lerp(from.fade_end, to.fade_end, t),
+        axis_scale: from.axis_scale.lerp(to.axis_scale, t),
+        dash_length: lerp(from.dash_length, to.dash_length, t),
+        dash_speed: lerp(from.dash_speed, to.dash_speed, t),
+        pulse_amplitude: lerp(from.pulse_amplitude, to.pulse_amplitude, t),
+        pulse_frequency: lerp(from.pulse_frequency, to.pulse_frequency, t),
+        pulse_phase: lerp(from.pulse_phase, to.pulse_phase, t),
+        fill,
+        falloff,
+        edge_width: lerp(from.edge_width, to.edge_width, t),
+        inner,
+        ..nearest.clone()
+    }
+}
+
+/// Advances each `OutlineStyleTween` and updates its camera's style.
+pub(crate) fn tween_outline_styles(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut tweens: Query<(Entity, &mut OutlineStyleTween, Option<&mut OutlineStyle>)>,
+) {
+    for (entity, mut tween, style) in tweens.iter_mut() {
+        tween.elapsed += time.delta_seconds();
+
+        let progress = tween.progress();
+        let new_style = lerp_style(&tween.from, &tween.to, tween.easing.ease(progress));
+
+        match style {
+            Some(mut style) => *style = new_style,
+            None => {
+                commands.entity(entity).insert(new_style);
+            }
+        }
+
+        if progress >= 1.0 {
+            commands.entity(entity).remove::<OutlineStyleTween>();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EASINGS: [OutlineEasing; 5] = [
+        OutlineEasing::Linear,
+        OutlineEasing::EaseIn,
+        OutlineEasing::EaseOut,
+        OutlineEasing::EaseInOut,
+        OutlineEasing::Bounce,
+    ];
+
+    #[test]
+    fn easing_endpoints() {
+        for easing in EASINGS {
+            assert!(easing.ease(0.0).abs() < 1e-6, "{easing:?} at 0");
+            assert!((easing.ease(1.0) - 1.0).abs() < 1e-6, "{easing:?} at 1");
+        }
+    }
+
+    #[test]
+    fn easing_stays_in_range() {
+        for easing in EASINGS {
+            for i in 0..=100 {
+                let eased = easing.ease(i as f32 / 100.0);
+                assert!((0.0..=1.0 + 1e-6).contains(&eased), "{easing:?} at {i}%");
+            }
+        }
+    }
+
+    #[test]
+    fn lerp_style_endpoints() {
+        let from = OutlineStyle::default();
+        let to = OutlineStyle {
+            width: 8.0,
+            pulse_amplitude: 0.5,
+            ..default()
+        };
+        // Colors are interpolated in linear space, so only compare lengths.
+        for (t, width, pulse_amplitude) in [(0.0, 32.0, 0.0), (0.5, 20.0, 0.25), (1.0, 8.0, 0.5)] {
+            let style = lerp_style(&from, &to, t);
+            assert_eq!(style.width, width);
+            assert_eq!(style.pulse_amplitude, pulse_amplitude);
+        }
+    }
+}
@@ -0,0 +1,257 @@
+use bevy::{
+    core_pipeline::{core_2d, core_3d},
+    prelude::*,
+    render::{
+        render_asset::RenderAssets,
+        render_graph::{Node, NodeRunError, RenderGraph, RenderGraphContext, SlotInfo, SlotType},
+        render_resource::{PipelineCache, SpecializedRenderPipelines},
+        renderer::{RenderContext, RenderDevice, RenderQueue},
+        view::{Msaa, ViewUniformOffset, ViewUniforms},
+        Extract, RenderApp, RenderStage,
+    },
+    ui::{draw_ui_graph, CalculatedClip, DefaultCameraView, Node as UiNode, UiColor, UiImage},
+};
+
+use crate::{
+    graph::{self, OutlineDriverNode},
+    mask::{MaskColors, MeshMaskNode},
+    resources::ViewOutlineResources,
+    sprite::{self, ExtractedSpriteMask, SpriteMaskBatches, SpriteMaskPipeline},
+    Outline, OutlineGroup, OutlineSettings,
+};
+
+/// Labels for the UI outline sub-graph, which is run once per outlined view
+/// after the UI has been drawn.
+pub mod outline_ui {
+    pub const NAME: &str = "outline_ui_graph";
+
+    pub mod node {
+        pub const DRIVER: &str = "outline_ui_driver";
+    }
+}
+
+/// Adds outlines for UI nodes to the core 2D and 3D graphs.
+///
+/// UI entities with an enabled [`Outline`] are drawn into a separate mask,
+/// which is outlined on top of the UI pass. As with sprites, texels with an
+/// alpha below one half are outside the node's silhouette, so nodes with a
+/// transparent `UiColor` are not outlined.
+pub(crate) fn add_ui_outlines(app: &mut App) {
+    let render_app = match app.get_sub_app_mut(RenderApp) {
+        Ok(r) => r,
+        Err(_) => return,
+    };
+
+    render_app
+        .init_resource::<ExtractedUiMasks>()
+        .init_resource::<UiMaskBatches>()
+        .add_system_to_stage(RenderStage::Extract, extract_ui_masks)
+        .add_system_to_stage(RenderStage::Queue, queue_ui_masks);
+
+    let core_graphs = [
+        (core_2d::graph::NAME, core_2d::graph::input::VIEW_ENTITY),
+        (core_3d::graph::NAME, core_3d::graph::input::VIEW_ENTITY),
+    ];
+    for (core_graph, input_view) in core_graphs {
+        let mask_node = UiMaskNode::new(&mut render_app.world);
+        let outline_graph =
+            graph::build_outline_graph_with_mask(&mut render_app.world, mask_node).unwrap();
+
+        let mut root_graph = render_app.world.resource_mut::<RenderGraph>();
+        let draw_graph = match root_graph.get_sub_graph_mut(core_graph) {
+            Some(g) => g,
+            None => continue,
+        };
+        let input_node = draw_graph.input_node().unwrap().id;
+
+        draw_graph.add_sub_graph(outline_ui::NAME, outline_graph);
+        let driver = draw_graph.add_node(outline_ui::node::DRIVER, UiOutlineDriverNode);
+        draw_graph
+            .add_slot_edge(input_node, input_view, driver, UiOutlineDriverNode::IN_VIEW)
+            .unwrap();
+        draw_graph
+            .add_node_edge(draw_ui_graph::node::UI_PASS, driver)
+            .unwrap();
+
+        // Outlines of meshes and sprites are drawn beneath the UI.
+        draw_graph
+            .add_node_edge(OutlineDriverNode::NAME, draw_ui_graph::node::UI_PASS)
+            .unwrap();
+    }
+}
+
+/// Render graph node which draws the outlines of UI nodes for a view.
+struct UiOutlineDriverNode;
+
+impl UiOutlineDriverNode {
+    const IN_VIEW: &'static str = "view";
+}
+
+impl Node for UiOutlineDriverNode {
+    fn input(&self) -> Vec<SlotInfo> {
+        vec![SlotInfo::new(Self::IN_VIEW, SlotType::Entity)]
+    }
+
+    fn run(
+        &self,
+        graph: &mut RenderGraphContext,
+        _render_context: &mut RenderContext,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        // Without outlined UI nodes, drawing the empty UI mask would overwrite
+        // the mask and distance field retained for temporal reuse.
+        let reuse = world.resource::<OutlineSettings>().temporal_reuse();
+        if reuse && world.resource::<ExtractedUiMasks>().nodes.is_empty() {
+            return Ok(());
+        }
+
+        graph::run_outline_graph(graph, world, Self::IN_VIEW, outline_ui::NAME)
+    }
+}
+
+/// The outlined UI nodes extracted this frame.
+#[derive(Default)]
+pub struct ExtractedUiMasks {
+    nodes: Vec<ExtractedSpriteMask>,
+}
+
+#[derive(Default)]
+pub struct UiMaskBatches(SpriteMaskBatches);
+
+fn extract_ui_masks(
+    mut extracted: ResMut<ExtractedUiMasks>,
+    nodes: Extract<
+        Query<(
+            Entity,
+            &ComputedVisibility,
+            &Outline,
+            &UiNode,
+            &GlobalTransform,
+            &UiColor,
+            &UiImage,
+            Option<&CalculatedClip>,
+            Option<&OutlineGroup>,
+        )>,
+    >,
+) {
+    extracted.nodes.clear();
+
+    for (entity, visibility, outline, node, transform, color, image, clip, group) in nodes.iter() {
+        if !visibility.is_visible() || !outline.enabled {
+            continue;
+        }
+
+        extracted.nodes.push(ExtractedSpriteMask {
+            entity,
+            transform: *transform,
+            color: color.0,
+            rect: None,
+            custom_size: Some(node.size),
+            anchor: Vec2::ZERO,
+            flip_x: false,
+            flip_y: false,
+            clip: clip.map(|clip| clip.clip),
+            image: image.0.clone_weak(),
+            group: group.map_or(0, |group| group.0 as u32),
+        });
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn queue_ui_masks(
+    device: Res<RenderDevice>,
+    queue: Res<RenderQueue>,
+    pipeline: Res<SpriteMaskPipeline>,
+    mut pipelines: ResMut<SpecializedRenderPipelines<SpriteMaskPipeline>>,
+    mut pipeline_cache: ResMut<PipelineCache>,
+    msaa: Res<Msaa>,
+    view_uniforms: Res<ViewUniforms>,
+    images: Res<RenderAssets<Image>>,
+    mut extracted: ResMut<ExtractedUiMasks>,
+    mut batches: ResMut<UiMaskBatches>,
+    views: Query<(Entity, Option<&MaskColors>), With<ViewOutlineResources>>,
+) {
+    batches.0.specialize_pipelines(
+        &mut pipelines,
+        &mut pipeline_cache,
+        &pipeline,
+        msaa.samples,
+        views.iter().map(|(_, mask_colors)| mask_colors.is_some()),
+    );
+
+    sprite::batch_mask_quads(
+        &device,
+        &queue,
+        &pipeline,
+        &view_uniforms,
+        &images,
+        &mut extracted.nodes,
+        // UI nodes have no `RenderLayers`, and are drawn by every view.
+        views.iter().map(|(entity, _)| (entity, None)),
+        &mut batches.0,
+    );
+}
+
+/// Render graph node for drawing outlined UI nodes into the mask.
+///
+/// This has the same slots as the [`MeshMaskNode`]. UI nodes are projected
+/// with the camera's default UI view rather than the camera itself.
+pub struct UiMaskNode {
+    camera_query: QueryState<&'static DefaultCameraView>,
+    view_query: QueryState<&'static ViewUniformOffset>,
+}
+
+impl UiMaskNode {
+    pub fn new(world: &mut World) -> UiMaskNode {
+        UiMaskNode {
+            camera_query: QueryState::new(world),
+            view_query: QueryState::new(world),
+        }
+    }
+}
+
+impl Node for UiMaskNode {
+    fn input(&self) -> Vec<SlotInfo> {
+        vec![SlotInfo::new(MeshMaskNode::IN_VIEW, SlotType::Entity)]
+    }
+
+    fn output(&self) -> Vec<SlotInfo> {
+        vec![SlotInfo::new(MeshMaskNode::OUT_MASK, SlotType::TextureView)]
+    }
+
+    fn update(&mut self, world: &mut World) {
+        self.camera_query.update_archetypes(world);
+        self.view_query.update_archetypes(world);
+    }
+
+    fn run(
+        &self,
+        graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let view_entity = graph.get_input_entity(MeshMaskNode::IN_VIEW)?;
+        let res = world.get::<ViewOutlineResources>(view_entity).unwrap();
+
+        graph.set_output(MeshMaskNode::OUT_MASK, res.mask_output.default_view.clone())?;
+
+        // Cameras with the UI disabled have no default UI view, and get an
+        // empty mask.
+        let view_offset = self
+            .camera_query
+            .get_manual(world, view_entity)
+            .ok()
+            .and_then(|ui_view| self.view_query.get_manual(world, ui_view.0).ok());
+
+        let mut pass = sprite::begin_mask_pass(render_context, res);
+        sprite::draw_mask_quads(
+            &mut pass,
+            world,
+            view_entity,
+            view_offset,
+            &world.resource::<UiMaskBatches>().0,
+        );
+
+        Ok(())
+    }
+}
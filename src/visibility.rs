@@ -0,0 +1,71 @@
+use bevy::{prelude::*, render::view::VisibleEntities, utils::HashSet};
+
+use crate::{CameraOutline, Outline, OutlineFlash, OutlineStyle};
+
+/// Event sent when an outlined entity starts contributing to the mask of a
+/// camera.
+///
+/// An entity contributes to the mask of an active, outlined camera while its
+/// [`Outline`] is enabled and it is in the camera's `VisibleEntities`, i.e.
+/// it is visible, on one of the camera's render layers and within its
+/// frustum.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OutlineShown {
+    pub entity: Entity,
+    pub camera: Entity,
+}
+
+/// Event sent when an outlined entity stops contributing to the mask of a
+/// camera, including when the entity or camera is despawned.
+///
+/// See [`OutlineShown`] for when an entity contributes to the mask.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OutlineHidden {
+    pub entity: Entity,
+    pub camera: Entity,
+}
+
+/// A camera along with the components which make it draw outlines.
+type OutlinedCamera = (
+    Entity,
+    &'static Camera,
+    &'static VisibleEntities,
+    Option<&'static CameraOutline>,
+    Option<&'static OutlineStyle>,
+    Option<&'static OutlineFlash>,
+);
+
+/// Sends [`OutlineShown`] and [`OutlineHidden`] events for changes in the
+/// entities contributing to each camera's mask.
+pub(crate) fn send_outline_visibility_events(
+    mut shown: Local<HashSet<(Entity, Entity)>>,
+    mut shown_events: EventWriter<OutlineShown>,
+    mut hidden_events: EventWriter<OutlineHidden>,
+    cameras: Query<OutlinedCamera>,
+    outlines: Query<&Outline>,
+) {
+    let mut now_shown = HashSet::default();
+    for (camera, cam, visible, cam_outline, cam_style, flash) in cameras.iter() {
+        let outlined = cam_outline.is_some_and(|outline| outline.enabled)
+            || cam_style.is_some()
+            || flash.is_some();
+        if !cam.is_active || !outlined {
+            continue;
+        }
+
+        for &entity in visible.entities.iter() {
+            if outlines.get(entity).is_ok_and(|outline| outline.enabled) {
+                now_shown.insert((camera, entity));
+            }
+        }
+    }
+
+    for &(camera, entity) in now_shown.difference(&shown) {
+        shown_events.send(OutlineShown { entity, camera });
+    }
+    for &(camera, entity) in shown.difference(&now_shown) {
+        hidden_events.send(OutlineHidden { entity, camera });
+    }
+
+    *shown = now_shown;
+}